@@ -1,15 +1,28 @@
 mod routes;
 
 use log::info;
+use odin::files::settings::Settings;
+use odin::utils::instances::instance_port;
 use odin::{logger::initialize_logger, server::ServerInfo, utils::environment::fetch_var};
-use std::net::SocketAddrV4;
-use std::str::FromStr;
+use std::net::{Ipv4Addr, SocketAddrV4};
 use warp::Filter;
 
 fn fetch_info() -> ServerInfo {
-  let port: u16 = fetch_var("PORT", "2457").parse().unwrap();
-  let address = fetch_var("ADDRESS", format!("127.0.0.1:{}", port + 1).as_str());
-  ServerInfo::from(SocketAddrV4::from_str(&address).unwrap())
+  let settings = Settings::current();
+  let address = settings
+    .http
+    .address
+    .unwrap_or_else(|| SocketAddrV4::new(Ipv4Addr::LOCALHOST, settings.server.port + 1));
+  ServerInfo::from(address)
+}
+
+/// Queries an instance on its configured port, `None` if the instance is not configured.
+fn fetch_instance_info(instance: &str) -> Option<ServerInfo> {
+  let port = instance_port(instance)?;
+  Some(ServerInfo::from(SocketAddrV4::new(
+    Ipv4Addr::LOCALHOST,
+    port + 1,
+  )))
 }
 
 #[tokio::main]
@@ -22,10 +35,20 @@ async fn main() {
   let root = warp::path::end().map(routes::invoke);
   let status = warp::path!("status").map(routes::status::invoke);
   let metrics = warp::path!("metrics").map(routes::metrics::invoke);
-  let routes = warp::any().and(root.or(status).or(metrics));
+  let instance_status = warp::path!("status" / String).map(routes::instances::status);
+  let instances = warp::path!("instances").map(routes::instances::invoke);
+  let modpack = warp::path!("modpack").and_then(routes::modpack::invoke);
+  let routes = warp::any().and(
+    root
+      .or(status)
+      .or(instance_status)
+      .or(instances)
+      .or(metrics)
+      .or(modpack),
+  );
 
   // HTTP Server
-  let http_port: u16 = fetch_var("HTTP_PORT", "3000").parse().unwrap();
+  let http_port = Settings::current().http.port.unwrap_or(3000);
 
   // Start server
   info!("Starting web server....");
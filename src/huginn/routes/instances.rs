@@ -0,0 +1,35 @@
+use crate::fetch_instance_info;
+use odin::server::ServerInfo;
+use odin::utils::instances::{instance_port, list_instances};
+use serde::Serialize;
+use warp::http::StatusCode;
+use warp::reply::{json, with_status, Json, WithStatus};
+
+#[derive(Serialize)]
+struct InstanceStatus {
+  name: String,
+  port: Option<u16>,
+  status: Option<ServerInfo>,
+}
+
+pub fn invoke() -> Json {
+  let instances: Vec<InstanceStatus> = list_instances()
+    .into_iter()
+    .map(|name| InstanceStatus {
+      port: instance_port(&name),
+      status: fetch_instance_info(&name),
+      name,
+    })
+    .collect();
+  json(&instances)
+}
+
+pub fn status(name: String) -> WithStatus<Json> {
+  match fetch_instance_info(&name) {
+    Some(info) => with_status(json(&info), StatusCode::OK),
+    None => with_status(
+      json(&format!("Instance {} is not configured", name)),
+      StatusCode::NOT_FOUND,
+    ),
+  }
+}
@@ -1,4 +1,63 @@
 use crate::fetch_info;
+use odin::worlds::db::{WorldStats, BOSSES, KNOWN_PREFABS};
+use odin::worlds::{active_world, find_world, world_stats};
+use std::sync::Mutex;
+
+/// The last parsed .db with the name and save time of its world, worlds save every 30 minutes
+/// while a scrape happens every few seconds.
+static WORLD_STATS: Mutex<Option<(String, Option<i64>, WorldStats)>> = Mutex::new(None);
+
+fn cached_world_stats(name: &str) -> Option<WorldStats> {
+  let modified = find_world(name)?.modified;
+  let mut cache = WORLD_STATS.lock().unwrap();
+  if let Some((cached_name, cached_modified, stats)) = cache.as_ref() {
+    if cached_name == name && *cached_modified == modified {
+      return Some(stats.clone());
+    }
+  }
+  let stats = world_stats(name).ok()?;
+  *cache = Some((name.to_string(), modified, stats.clone()));
+  Some(stats)
+}
+
+fn world_metrics(name: &str, stats: &WorldStats) -> Vec<String> {
+  let labels = format!("world=\"{}\"", name);
+  let mut content = vec![
+    format!("valheim_world_day{{{}}} {}", labels, stats.day),
+    format!(
+      "valheim_world_net_time_seconds{{{}}} {}",
+      labels, stats.net_time
+    ),
+    format!("valheim_world_objects{{{}}} {}", labels, stats.zdos),
+  ];
+  if let Some(built) = stats.player_built {
+    content.push(format!(
+      "valheim_world_player_built_objects{{{}}} {}",
+      labels, built
+    ));
+  }
+  for (_, boss) in BOSSES {
+    content.push(format!(
+      "valheim_world_boss_defeated{{{}, boss=\"{}\"}} {}",
+      labels,
+      boss,
+      stats
+        .defeated_bosses
+        .iter()
+        .any(|defeated| defeated == boss) as i32
+    ));
+  }
+  // Only named prefabs, a label per hash would grow with every mod.
+  for prefab in KNOWN_PREFABS {
+    if let Some(count) = stats.prefabs.get(prefab) {
+      content.push(format!(
+        "valheim_world_prefab_objects{{{}, prefab=\"{}\"}} {}",
+        labels, prefab, count
+      ));
+    }
+  }
+  content
+}
 
 pub fn invoke() -> String {
   let info = fetch_info();
@@ -8,7 +67,7 @@ pub fn invoke() -> String {
     version = &info.version,
     map = &info.map
   );
-  let content = [
+  let mut content = vec![
     format!(
       "valheim_online{labels} {online}",
       labels = &labels,
@@ -30,5 +89,9 @@ pub fn invoke() -> String {
       bepinex_installed = info.bepinex.enabled as i32
     ),
   ];
+  let world = active_world();
+  if let Some(stats) = cached_world_stats(&world) {
+    content.extend(world_metrics(&world, &stats));
+  }
   format!("{}\n", content.join("\n"))
 }
@@ -1,6 +1,8 @@
 use crate::fetch_info;
 
+pub(crate) mod instances;
 pub(crate) mod metrics;
+pub(crate) mod modpack;
 pub(crate) mod status;
 
 pub fn invoke() -> String {
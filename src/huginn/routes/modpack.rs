@@ -0,0 +1,37 @@
+use log::error;
+use odin::mods::modpack::client_modpack;
+use std::convert::Infallible;
+use warp::http::{header, Response, StatusCode};
+use warp::hyper::Body;
+
+pub async fn invoke() -> Result<Response<Body>, Infallible> {
+  let modpack = tokio::task::spawn_blocking(|| {
+    client_modpack().and_then(|path| {
+      std::fs::read(path).map_err(|e| odin::errors::ValheimModError::FileOpenError(e.to_string()))
+    })
+  })
+  .await;
+
+  let response = match modpack {
+    Ok(Ok(content)) => Response::builder()
+      .header(header::CONTENT_TYPE, "application/zip")
+      .header(
+        header::CONTENT_DISPOSITION,
+        "attachment; filename=\"modpack.zip\"",
+      )
+      .body(Body::from(content)),
+    Ok(Err(e)) => {
+      error!("Failed to serve the modpack: {}", e);
+      Response::builder()
+        .status(StatusCode::NOT_FOUND)
+        .body(Body::from(e.to_string()))
+    }
+    Err(e) => {
+      error!("Failed to build the modpack: {}", e);
+      Response::builder()
+        .status(StatusCode::INTERNAL_SERVER_ERROR)
+        .body(Body::empty())
+    }
+  };
+  Ok(response.unwrap())
+}
@@ -0,0 +1,290 @@
+use std::sync::Arc;
+
+use log::{info, warn};
+use reqwest::Method;
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use crate::bot::{BotConfig, Operations};
+use crate::errors::BotError;
+
+const APPLICATION_COMMAND: u8 = 2;
+/// Interaction callback types.
+const CHANNEL_MESSAGE: u8 = 4;
+const DEFERRED_CHANNEL_MESSAGE: u8 = 5;
+/// Only the member who ran the command sees the reply.
+const EPHEMERAL: u16 = 1 << 6;
+const MAX_MESSAGE_LENGTH: usize = 2000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SlashCommand {
+  Status,
+  Players,
+  Restart,
+  Backup,
+}
+
+impl SlashCommand {
+  pub const ALL: [SlashCommand; 4] = [
+    SlashCommand::Status,
+    SlashCommand::Players,
+    SlashCommand::Restart,
+    SlashCommand::Backup,
+  ];
+
+  pub fn name(&self) -> &'static str {
+    match self {
+      SlashCommand::Status => "status",
+      SlashCommand::Players => "players",
+      SlashCommand::Restart => "restart",
+      SlashCommand::Backup => "backup",
+    }
+  }
+
+  fn description(&self) -> &'static str {
+    match self {
+      SlashCommand::Status => "Shows whether the server is online and what it runs",
+      SlashCommand::Players => "Lists the players who are online",
+      SlashCommand::Restart => "Restarts the server",
+      SlashCommand::Backup => "Backs up the active world",
+    }
+  }
+
+  /// Commands which change the server need one of the admin roles.
+  pub fn requires_admin(&self) -> bool {
+    matches!(self, SlashCommand::Restart | SlashCommand::Backup)
+  }
+
+  fn from_name(name: &str) -> Option<SlashCommand> {
+    SlashCommand::ALL
+      .into_iter()
+      .find(|command| command.name() == name)
+  }
+
+  fn run(&self, operations: &dyn Operations) -> String {
+    let result = match self {
+      SlashCommand::Status => Ok(operations.status()),
+      SlashCommand::Players => Ok(operations.players()),
+      SlashCommand::Restart => operations.restart(),
+      SlashCommand::Backup => operations.backup(),
+    };
+    result.unwrap_or_else(|e| format!("/{} failed: {}", self.name(), e))
+  }
+}
+
+/// Whether a member with `roles` may run `command`.
+fn is_allowed(command: SlashCommand, roles: &[String], admin_roles: &[String]) -> bool {
+  !command.requires_admin() || roles.iter().any(|role| admin_roles.contains(role))
+}
+
+fn truncate(content: String) -> String {
+  if content.chars().count() <= MAX_MESSAGE_LENGTH {
+    return content;
+  }
+  let mut content: String = content.chars().take(MAX_MESSAGE_LENGTH - 1).collect();
+  content.push('…');
+  content
+}
+
+#[derive(Debug, Deserialize)]
+struct User {
+  username: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct Member {
+  #[serde(default)]
+  roles: Vec<String>,
+  user: Option<User>,
+}
+
+#[derive(Debug, Deserialize)]
+struct InteractionData {
+  name: String,
+}
+
+/// The part of an interaction the bot needs, see
+/// https://discord.com/developers/docs/interactions/receiving-and-responding
+#[derive(Debug, Deserialize)]
+pub(crate) struct Interaction {
+  id: String,
+  application_id: String,
+  #[serde(rename = "type")]
+  kind: u8,
+  token: String,
+  data: Option<InteractionData>,
+  /// Missing for commands run in a direct message, which never pass the role check.
+  member: Option<Member>,
+}
+
+/// Calls Discord's REST API as the bot.
+#[derive(Clone)]
+pub(crate) struct Rest {
+  client: reqwest::Client,
+  api_url: String,
+  token: String,
+}
+
+impl Rest {
+  pub(crate) fn new(config: &BotConfig) -> Rest {
+    Rest {
+      client: reqwest::Client::new(),
+      api_url: config.api_url.trim_end_matches('/').to_string(),
+      token: config.token.clone(),
+    }
+  }
+
+  async fn request(&self, method: Method, path: &str, body: Value) -> Result<(), BotError> {
+    let response = self
+      .client
+      .request(method, format!("{}{}", self.api_url, path))
+      .header("Authorization", format!("Bot {}", self.token))
+      .json(&body)
+      .send()
+      .await?;
+    let status = response.status();
+    if !status.is_success() {
+      return Err(BotError::Api(
+        status.as_u16(),
+        response.text().await.unwrap_or_default(),
+      ));
+    }
+    Ok(())
+  }
+
+  /// Replaces the registered commands with the ones odin has, in one guild if `guild_id` is set.
+  pub(crate) async fn register(
+    &self,
+    application_id: &str,
+    guild_id: Option<&str>,
+  ) -> Result<(), BotError> {
+    let path = match guild_id {
+      Some(guild_id) => format!(
+        "/applications/{}/guilds/{}/commands",
+        application_id, guild_id
+      ),
+      None => format!("/applications/{}/commands", application_id),
+    };
+    let commands: Vec<Value> = SlashCommand::ALL
+      .iter()
+      .map(|command| {
+        json!({
+          "name": command.name(),
+          "description": command.description(),
+          "type": 1,
+          "dm_permission": false,
+        })
+      })
+      .collect();
+    self
+      .request(Method::PUT, &path, Value::from(commands))
+      .await
+  }
+
+  async fn respond(&self, interaction: &Interaction, body: Value) -> Result<(), BotError> {
+    let path = format!(
+      "/interactions/{}/{}/callback",
+      interaction.id, interaction.token
+    );
+    self.request(Method::POST, &path, body).await
+  }
+
+  async fn edit_response(
+    &self,
+    interaction: &Interaction,
+    content: String,
+  ) -> Result<(), BotError> {
+    let path = format!(
+      "/webhooks/{}/{}/messages/@original",
+      interaction.application_id, interaction.token
+    );
+    let body = json!({ "content": truncate(content) });
+    self.request(Method::PATCH, &path, body).await
+  }
+
+  async fn reply_privately(
+    &self,
+    interaction: &Interaction,
+    content: String,
+  ) -> Result<(), BotError> {
+    let body = json!({
+      "type": CHANNEL_MESSAGE,
+      "data": { "content": content, "flags": EPHEMERAL },
+    });
+    self.respond(interaction, body).await
+  }
+}
+
+/// Answers a slash command. The reply is deferred first, Discord only waits three seconds for
+/// one and a restart takes a lot longer.
+pub(crate) async fn handle_interaction(
+  rest: Rest,
+  admin_roles: Arc<Vec<String>>,
+  operations: Arc<dyn Operations>,
+  interaction: Interaction,
+) -> Result<(), BotError> {
+  if interaction.kind != APPLICATION_COMMAND {
+    return Ok(());
+  }
+  let name = interaction
+    .data
+    .as_ref()
+    .map_or("", |data| data.name.as_str());
+  let Some(command) = SlashCommand::from_name(name) else {
+    return rest
+      .reply_privately(&interaction, format!("odin doesn't know /{}", name))
+      .await;
+  };
+  let user = interaction
+    .member
+    .as_ref()
+    .and_then(|member| member.user.as_ref())
+    .map_or("Someone", |user| user.username.as_str())
+    .to_string();
+  let roles = interaction
+    .member
+    .as_ref()
+    .map_or(&[][..], |member| member.roles.as_slice());
+  if !is_allowed(command, roles, &admin_roles) {
+    warn!("{} isn't allowed to run /{}", user, command.name());
+    return rest
+      .reply_privately(
+        &interaction,
+        format!("You need an admin role to run /{}", command.name()),
+      )
+      .await;
+  }
+
+  info!("{} ran /{}", user, command.name());
+  rest
+    .respond(&interaction, json!({ "type": DEFERRED_CHANNEL_MESSAGE }))
+    .await?;
+  let content = tokio::task::spawn_blocking(move || command.run(&*operations))
+    .await
+    .unwrap_or_else(|e| format!("/{} failed: {}", command.name(), e));
+  rest.edit_response(&interaction, content).await
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_admin_commands_need_a_role() {
+    let admins = vec![String::from("1001")];
+    let member = vec![String::from("2002")];
+    let admin = vec![String::from("2002"), String::from("1001")];
+    assert!(is_allowed(SlashCommand::Status, &[], &admins));
+    assert!(!is_allowed(SlashCommand::Restart, &member, &admins));
+    assert!(is_allowed(SlashCommand::Restart, &admin, &admins));
+    assert!(!is_allowed(SlashCommand::Backup, &admin, &[]));
+  }
+
+  #[test]
+  fn test_truncate_long_replies() {
+    assert_eq!(truncate(String::from("short")), "short");
+    let long = truncate("a".repeat(3000));
+    assert_eq!(long.chars().count(), MAX_MESSAGE_LENGTH);
+    assert!(long.ends_with('…'));
+  }
+}
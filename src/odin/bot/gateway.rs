@@ -0,0 +1,392 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use futures_util::{SinkExt, StreamExt};
+use log::{debug, error, info, warn};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::net::TcpStream;
+use tokio_rustls::rustls::pki_types::ServerName;
+use tokio_rustls::rustls::{crypto, ClientConfig, RootCertStore};
+use tokio_rustls::TlsConnector;
+use tokio_tungstenite::tungstenite::http::Uri;
+use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::WebSocketStream;
+
+use crate::bot::commands::{handle_interaction, Interaction, Rest};
+use crate::bot::{BotConfig, Operations};
+use crate::errors::BotError;
+
+/// Gateway opcodes, see https://discord.com/developers/docs/topics/opcodes-and-status-codes
+const DISPATCH: u8 = 0;
+const HEARTBEAT: u8 = 1;
+const IDENTIFY: u8 = 2;
+const RECONNECT: u8 = 7;
+const INVALID_SESSION: u8 = 9;
+const HELLO: u8 = 10;
+const HEARTBEAT_ACK: u8 = 11;
+
+/// Close codes after which reconnecting with the same settings can't work, e.g. a bad token.
+const FATAL_CLOSE_CODES: [u16; 6] = [4004, 4010, 4011, 4012, 4013, 4014];
+
+/// Slash commands arrive without any gateway intents.
+const INTENTS: u64 = 0;
+
+#[derive(Debug, Deserialize, Serialize)]
+pub(crate) struct Payload {
+  pub(crate) op: u8,
+  #[serde(default)]
+  pub(crate) d: Value,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub(crate) s: Option<u64>,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub(crate) t: Option<String>,
+}
+
+impl Payload {
+  fn new(op: u8, d: Value) -> Payload {
+    Payload {
+      op,
+      d,
+      s: None,
+      t: None,
+    }
+  }
+}
+
+trait Stream: AsyncRead + AsyncWrite + Unpin + Send {}
+impl<T: AsyncRead + AsyncWrite + Unpin + Send> Stream for T {}
+
+type Socket = WebSocketStream<Box<dyn Stream>>;
+
+/// Connects to the gateway, over TLS for `wss://` urls.
+async fn connect(url: &str) -> Result<Socket, BotError> {
+  let uri: Uri = url
+    .parse()
+    .map_err(|_| BotError::Gateway(format!("invalid gateway url {}", url)))?;
+  let host = uri
+    .host()
+    .ok_or_else(|| BotError::Gateway(format!("invalid gateway url {}", url)))?
+    .to_string();
+  let secure = uri.scheme_str() == Some("wss");
+  let port = uri.port_u16().unwrap_or(if secure { 443 } else { 80 });
+  let tcp = TcpStream::connect((host.as_str(), port)).await?;
+  let stream: Box<dyn Stream> = if secure {
+    let mut roots = RootCertStore::empty();
+    roots.extend(webpki_roots::TLS_SERVER_ROOTS.iter().cloned());
+    let config = ClientConfig::builder_with_provider(Arc::new(crypto::ring::default_provider()))
+      .with_safe_default_protocol_versions()
+      .map_err(|e| BotError::Gateway(e.to_string()))?
+      .with_root_certificates(roots)
+      .with_no_client_auth();
+    let name = ServerName::try_from(host).map_err(|e| BotError::Gateway(e.to_string()))?;
+    Box::new(
+      TlsConnector::from(Arc::new(config))
+        .connect(name, tcp)
+        .await?,
+    )
+  } else {
+    Box::new(tcp)
+  };
+  let (socket, _) = tokio_tungstenite::client_async(url, stream).await?;
+  Ok(socket)
+}
+
+async fn send(socket: &mut Socket, payload: Payload) -> Result<(), BotError> {
+  socket
+    .send(Message::Text(serde_json::to_string(&payload)?))
+    .await?;
+  Ok(())
+}
+
+/// Waits for the next payload, `None` once the gateway closed the connection.
+async fn receive(socket: &mut Socket) -> Result<Option<Payload>, BotError> {
+  while let Some(message) = socket.next().await {
+    match message? {
+      Message::Text(text) => return Ok(Some(serde_json::from_str(&text)?)),
+      Message::Close(Some(frame)) => {
+        let code = u16::from(frame.code);
+        if FATAL_CLOSE_CODES.contains(&code) {
+          return Err(BotError::Rejected(code, frame.reason.to_string()));
+        }
+        debug!("The gateway closed the connection with {}", code);
+        return Ok(None);
+      }
+      Message::Close(None) => return Ok(None),
+      _ => {}
+    }
+  }
+  Ok(None)
+}
+
+fn identify(config: &BotConfig) -> Payload {
+  Payload::new(
+    IDENTIFY,
+    json!({
+      "token": config.token,
+      "intents": INTENTS,
+      "properties": { "os": std::env::consts::OS, "browser": "odin", "device": "odin" },
+    }),
+  )
+}
+
+/// Handles one gateway connection until Discord closes it or asks for a reconnect, which
+/// returns `Ok` so the caller can connect again.
+pub(crate) async fn session(
+  config: &BotConfig,
+  operations: Arc<dyn Operations>,
+) -> Result<(), BotError> {
+  let mut socket = connect(&config.gateway_url).await?;
+  let hello = match receive(&mut socket).await? {
+    Some(payload) if payload.op == HELLO => payload,
+    _ => return Err(BotError::Gateway(String::from("expected a hello"))),
+  };
+  let interval = hello.d["heartbeat_interval"]
+    .as_u64()
+    .ok_or_else(|| BotError::Gateway(String::from("hello without a heartbeat interval")))?;
+  send(&mut socket, identify(config)).await?;
+
+  let rest = Rest::new(config);
+  let admin_roles = Arc::new(config.admin_roles.clone());
+  let mut heartbeat = tokio::time::interval(Duration::from_millis(interval));
+  // The first tick completes right away.
+  heartbeat.tick().await;
+  let mut sequence: Option<u64> = None;
+  let mut acknowledged = true;
+  loop {
+    let payload = tokio::select! {
+      _ = heartbeat.tick() => {
+        // Without an ack since the last heartbeat the connection is dead without being closed.
+        if !acknowledged {
+          return Err(BotError::Gateway(String::from("heartbeat was not acknowledged")));
+        }
+        acknowledged = false;
+        send(&mut socket, Payload::new(HEARTBEAT, json!(sequence))).await?;
+        continue;
+      }
+      payload = receive(&mut socket) => match payload? {
+        Some(payload) => payload,
+        None => return Ok(()),
+      },
+    };
+    if payload.s.is_some() {
+      sequence = payload.s;
+    }
+    match payload.op {
+      HEARTBEAT => send(&mut socket, Payload::new(HEARTBEAT, json!(sequence))).await?,
+      HEARTBEAT_ACK => acknowledged = true,
+      RECONNECT | INVALID_SESSION => return Ok(()),
+      DISPATCH => match payload.t.as_deref() {
+        Some("READY") => {
+          let application_id = payload.d["application"]["id"].as_str().unwrap_or_default();
+          match rest
+            .register(application_id, config.guild_id.as_deref())
+            .await
+          {
+            Ok(()) => info!("Connected to Discord, slash commands are registered"),
+            Err(e) => error!("Failed to register the slash commands: {}", e),
+          }
+        }
+        Some("INTERACTION_CREATE") => {
+          let interaction: Interaction = match serde_json::from_value(payload.d) {
+            Ok(interaction) => interaction,
+            Err(e) => {
+              warn!("Ignoring an interaction odin can't read: {}", e);
+              continue;
+            }
+          };
+          let (rest, admin_roles, operations) =
+            (rest.clone(), admin_roles.clone(), operations.clone());
+          // Commands run on their own so the heartbeat keeps going during a restart.
+          tokio::spawn(async move {
+            if let Err(e) = handle_interaction(rest, admin_roles, operations, interaction).await {
+              error!("Failed to answer a slash command: {}", e);
+            }
+          });
+        }
+        _ => {}
+      },
+      _ => {}
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use mockito::Matcher;
+  use tokio::net::TcpListener;
+  use tokio::sync::oneshot;
+  use tokio_tungstenite::tungstenite::protocol::frame::coding::CloseCode;
+  use tokio_tungstenite::tungstenite::protocol::CloseFrame;
+
+  struct FakeOperations;
+
+  impl Operations for FakeOperations {
+    fn status(&self) -> String {
+      String::from("online")
+    }
+    fn players(&self) -> String {
+      String::from("nobody")
+    }
+    fn restart(&self) -> Result<String, String> {
+      Err(String::from("not in a test"))
+    }
+    fn backup(&self) -> Result<String, String> {
+      Ok(String::from("Backed up Dedicated"))
+    }
+  }
+
+  fn dispatch(sequence: u64, event: &str, d: Value) -> Message {
+    let payload = Payload {
+      op: DISPATCH,
+      d,
+      s: Some(sequence),
+      t: Some(event.to_string()),
+    };
+    Message::Text(serde_json::to_string(&payload).unwrap())
+  }
+
+  fn interaction(id: &str, token: &str, name: &str, role: &str) -> Value {
+    json!({
+      "id": id,
+      "application_id": "42",
+      "type": 2,
+      "token": token,
+      "data": { "name": name },
+      "member": { "roles": [role], "user": { "username": "ragnar" } },
+    })
+  }
+
+  fn config(api_url: String, gateway: std::net::SocketAddr) -> BotConfig {
+    BotConfig {
+      token: String::from("secret"),
+      guild_id: Some(String::from("7")),
+      admin_roles: vec![String::from("1001")],
+      api_url,
+      gateway_url: format!("ws://{}", gateway),
+    }
+  }
+
+  #[tokio::test]
+  async fn test_session_answers_slash_commands() {
+    let mut api = mockito::Server::new_async().await;
+    let register = api
+      .mock("PUT", "/applications/42/guilds/7/commands")
+      .match_header("authorization", "Bot secret")
+      .match_body(Matcher::PartialJson(json!([{ "name": "status" }])))
+      .with_body("[]")
+      .create_async()
+      .await;
+    let deferred = api
+      .mock("POST", "/interactions/1/abc/callback")
+      .match_body(Matcher::Json(json!({ "type": 5 })))
+      .with_status(204)
+      .create_async()
+      .await;
+    let answered = api
+      .mock("PATCH", "/webhooks/42/abc/messages/@original")
+      .match_body(Matcher::Json(json!({ "content": "Backed up Dedicated" })))
+      .create_async()
+      .await;
+    let denied = api
+      .mock("POST", "/interactions/2/def/callback")
+      .match_body(Matcher::PartialJson(
+        json!({ "type": 4, "data": { "flags": 64 } }),
+      ))
+      .with_status(204)
+      .create_async()
+      .await;
+
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let config = config(api.url(), listener.local_addr().unwrap());
+    let (close, closed) = oneshot::channel::<()>();
+    let gateway = tokio::spawn(async move {
+      let (stream, _) = listener.accept().await.unwrap();
+      let mut socket = tokio_tungstenite::accept_async(stream).await.unwrap();
+      let hello = Payload::new(HELLO, json!({ "heartbeat_interval": 45000 }));
+      socket
+        .send(Message::Text(serde_json::to_string(&hello).unwrap()))
+        .await
+        .unwrap();
+      let identify: Payload = match socket.next().await.unwrap().unwrap() {
+        Message::Text(text) => serde_json::from_str(&text).unwrap(),
+        message => panic!("expected identify, got {:?}", message),
+      };
+      socket
+        .send(dispatch(
+          1,
+          "READY",
+          json!({ "application": { "id": "42" } }),
+        ))
+        .await
+        .unwrap();
+      socket
+        .send(dispatch(
+          2,
+          "INTERACTION_CREATE",
+          interaction("1", "abc", "backup", "1001"),
+        ))
+        .await
+        .unwrap();
+      socket
+        .send(dispatch(
+          3,
+          "INTERACTION_CREATE",
+          interaction("2", "def", "restart", "2002"),
+        ))
+        .await
+        .unwrap();
+      closed.await.unwrap();
+      socket.close(None).await.unwrap();
+      identify
+    });
+    let session = tokio::spawn(async move { session(&config, Arc::new(FakeOperations)).await });
+
+    for _ in 0..100 {
+      if answered.matched_async().await && denied.matched_async().await {
+        break;
+      }
+      tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+    close.send(()).unwrap();
+    let identify = gateway.await.unwrap();
+    assert!(session.await.unwrap().is_ok());
+    assert_eq!(identify.op, IDENTIFY);
+    assert_eq!(identify.d["token"], "secret");
+    register.assert_async().await;
+    deferred.assert_async().await;
+    answered.assert_async().await;
+    denied.assert_async().await;
+  }
+
+  #[tokio::test]
+  async fn test_session_stops_on_a_rejected_token() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let config = config(
+      String::from("http://127.0.0.1:9"),
+      listener.local_addr().unwrap(),
+    );
+    tokio::spawn(async move {
+      let (stream, _) = listener.accept().await.unwrap();
+      let mut socket = tokio_tungstenite::accept_async(stream).await.unwrap();
+      let hello = Payload::new(HELLO, json!({ "heartbeat_interval": 45000 }));
+      socket
+        .send(Message::Text(serde_json::to_string(&hello).unwrap()))
+        .await
+        .unwrap();
+      socket.next().await;
+      let frame = CloseFrame {
+        code: CloseCode::from(4004),
+        reason: "Authentication failed.".into(),
+      };
+      socket.close(Some(frame)).await.unwrap();
+    });
+    let error = session(&config, Arc::new(FakeOperations))
+      .await
+      .unwrap_err();
+    assert!(matches!(error, BotError::Rejected(4004, _)));
+    assert!(error.is_fatal());
+  }
+}
@@ -0,0 +1,189 @@
+mod commands;
+mod gateway;
+
+pub use commands::SlashCommand;
+
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+use std::sync::Arc;
+use std::time::Duration;
+
+use a2s::A2SClient;
+use chrono::Local;
+use log::{info, warn};
+
+use crate::commands::backup::create_backup;
+use crate::commands::status::local_address;
+use crate::errors::BotError;
+use crate::files::settings::DiscordSettings;
+use crate::server::{self, ServerInfo};
+use crate::utils::common_paths::{backup_directory, saves_directory};
+use crate::utils::instances::current_instance;
+use crate::worlds::active_world;
+
+pub const DISCORD_BOT_TOKEN: &str = "DISCORD_BOT_TOKEN";
+pub const DISCORD_GUILD_ID: &str = "DISCORD_GUILD_ID";
+pub const DISCORD_ADMIN_ROLES: &str = "DISCORD_ADMIN_ROLES";
+const DISCORD_API: &str = "https://discord.com/api/v10";
+const DISCORD_GATEWAY: &str = "wss://gateway.discord.gg/?v=10&encoding=json";
+/// The longest odin waits before reconnecting to the gateway.
+const MAX_RECONNECT_DELAY: u64 = 60;
+
+#[derive(Debug, Clone)]
+pub struct BotConfig {
+  pub token: String,
+  /// Registers the commands in one guild, where they show up right away instead of within an hour.
+  pub guild_id: Option<String>,
+  /// Role ids allowed to run the commands which change the server.
+  pub admin_roles: Vec<String>,
+  pub api_url: String,
+  pub gateway_url: String,
+}
+
+impl BotConfig {
+  pub fn from_settings(settings: DiscordSettings) -> Result<BotConfig, BotError> {
+    let token = settings.bot_token.ok_or(BotError::MissingToken)?;
+    Ok(BotConfig {
+      token,
+      guild_id: settings.guild_id.map(|id| id.to_string()),
+      admin_roles: settings.admin_roles,
+      api_url: String::from(DISCORD_API),
+      gateway_url: String::from(DISCORD_GATEWAY),
+    })
+  }
+}
+
+/// The odin operations behind the slash commands, each returns the message to reply with.
+pub trait Operations: Send + Sync + 'static {
+  fn status(&self) -> String;
+  fn players(&self) -> String;
+  fn restart(&self) -> Result<String, String>;
+  fn backup(&self) -> Result<String, String>;
+}
+
+/// Runs the operations against the local server.
+pub struct OdinOperations;
+
+impl Operations for OdinOperations {
+  fn status(&self) -> String {
+    let info = ServerInfo::from(local_address());
+    let state = if info.online { "online" } else { "offline" };
+    format!("**{}** is {}\n{}", info.name, state, info)
+  }
+
+  fn players(&self) -> String {
+    let address = local_address();
+    let info = ServerInfo::from(address);
+    if !info.online {
+      return format!("**{}** is offline", info.name);
+    }
+    let names: Vec<String> = A2SClient::new()
+      .and_then(|client| client.players(address))
+      .map(|players| {
+        players
+          .into_iter()
+          .map(|player| player.name)
+          .filter(|name| !name.is_empty())
+          .collect()
+      })
+      .unwrap_or_default();
+    let count = format!("{}/{} players online", info.players, info.max_players);
+    if names.is_empty() {
+      count
+    } else {
+      format!("{}: {}", count, names.join(", "))
+    }
+  }
+
+  fn restart(&self) -> Result<String, String> {
+    if !server::is_running() {
+      return Err(String::from("The server isn't running"));
+    }
+    let odin = std::env::current_exe().map_err(|e| format!("Failed to find odin: {}", e))?;
+    restart_server(&odin)
+  }
+
+  /// Archives the saves like the auto backups do, named like theirs.
+  fn backup(&self) -> Result<String, String> {
+    let directory = backup_directory();
+    fs::create_dir_all(&directory).map_err(|e| format!("Failed to create {}: {}", directory, e))?;
+    let file_name = format!("{}-discord.tar.gz", Local::now().format("%Y%m%d-%H%M%S"));
+    let output = Path::new(&directory).join(&file_name);
+    create_backup(&saves_directory(), &output.to_string_lossy())?;
+    Ok(format!("Backed up {} to {}", active_world(), file_name))
+  }
+}
+
+/// Runs `odin` with `args` against the selected instance and waits for it. Starting the server
+/// daemonizes, which exits the process it starts from, so it can't run in the bot's process.
+fn run_odin(odin: &Path, args: &[&str]) -> Result<(), String> {
+  let mut command = Command::new(odin);
+  if let Some(instance) = current_instance() {
+    command.args(["--instance", &instance]);
+  }
+  let status = command
+    .args(args)
+    .status()
+    .map_err(|e| format!("Failed to run odin {}: {}", args.join(" "), e))?;
+  if status.success() {
+    Ok(())
+  } else {
+    Err(format!("odin {} failed with {}", args.join(" "), status))
+  }
+}
+
+/// Stops the server and starts it again through `odin`.
+fn restart_server(odin: &Path) -> Result<String, String> {
+  run_odin(odin, &["stop"])?;
+  run_odin(odin, &["start"])?;
+  Ok(String::from("Restarted the server"))
+}
+
+/// Runs the bot until Discord rejects it, reconnecting whenever the connection drops.
+pub async fn run(config: BotConfig, operations: Arc<dyn Operations>) -> Result<(), BotError> {
+  let mut delay = 1;
+  loop {
+    match gateway::session(&config, operations.clone()).await {
+      Ok(()) => {
+        info!("Reconnecting to the Discord gateway");
+        delay = 1;
+      }
+      Err(e) if e.is_fatal() => return Err(e),
+      Err(e) => {
+        warn!(
+          "Lost the Discord gateway: {}, reconnecting in {}s",
+          e, delay
+        );
+        tokio::time::sleep(Duration::from_secs(delay)).await;
+        delay = (delay * 2).min(MAX_RECONNECT_DELAY);
+      }
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use std::os::unix::fs::PermissionsExt;
+
+  fn script(path: &Path, body: &str) {
+    fs::write(path, format!("#!/bin/sh\n{}\n", body)).unwrap();
+    fs::set_permissions(path, fs::Permissions::from_mode(0o755)).unwrap();
+  }
+
+  #[test]
+  fn test_restart_server_runs_odin() {
+    let dir = tempfile::tempdir().unwrap();
+    let calls = dir.path().join("calls");
+    let odin = dir.path().join("odin");
+    script(&odin, &format!("echo \"$@\" >> {}", calls.display()));
+    assert_eq!(restart_server(&odin).unwrap(), "Restarted the server");
+    assert_eq!(fs::read_to_string(&calls).unwrap(), "stop\nstart\n");
+
+    let failing = dir.path().join("failing");
+    script(&failing, "exit 3");
+    let error = restart_server(&failing).unwrap_err();
+    assert!(error.starts_with("odin stop failed"), "{}", error);
+  }
+}
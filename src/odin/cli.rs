@@ -1,5 +1,7 @@
 use clap::{Parser, Subcommand};
 
+use crate::mods::modpack::ModpackFormat;
+use crate::notifications::sinks::EVENTS;
 use crate::utils::parse_truthy::parse_truthy;
 
 #[derive(Parser)]
@@ -19,66 +21,110 @@ pub struct Cli {
   #[arg(short = 'r', long, env = "DRY_RUN", value_parser  = parse_truthy)]
   pub dry_run: bool,
 
+  /// Runs the command against a named server instance with its own config, saves and logs.
+  /// Instances live in INSTANCES_LOCATION, you can use the env variable ODIN_INSTANCE to set this as well.
+  #[arg(long, global = true, env = "ODIN_INSTANCE")]
+  pub instance: Option<String>,
+
   #[command(subcommand)]
   pub commands: Commands,
 }
 
+// Parsed once per run, the size of the Configure variant doesn't matter.
+#[allow(clippy::large_enum_variant)]
 #[derive(Subcommand)]
 pub enum Commands {
   /// Initializes Odin with its configuration variables.
+  /// Values are layered as defaults < settings file (odin.yaml) < env variables < these flags.
   Configure {
     /// Sets the name of the server, (Can be set with ENV variable NAME)
-    #[arg(short, long, env = "NAME")]
-    #[arg(default_value_t = String::from("Valheim powered by Odin"))]
-    name: String,
+    #[arg(short, long)]
+    name: Option<String>,
 
-    /// Sets the servers executable path.
-    #[arg(long, env = "SERVER_EXECUTABLE_PATH")]
-    #[arg(default_value_t = format!("./{}", crate::constants::VALHEIM_EXECUTABLE_NAME))]
-    server_executable: String,
+    /// Sets the servers executable path, (Can be set with ENV variable SERVER_EXECUTABLE_PATH)
+    #[arg(long)]
+    server_executable: Option<String>,
 
     /// Sets the port of the server, (Can be set with ENV variable PORT)
-    #[arg(short, long, env = "PORT")]
-    #[arg(default_value_t = 2456)]
-    port: u16,
+    #[arg(short, long)]
+    port: Option<String>,
 
     /// Sets the world of the server, (Can be set with ENV variable WORLD)
-    #[arg(short, long, env = "WORLD")]
-    #[arg(default_value_t = String::from("Dedicated"))]
-    world: String,
+    #[arg(short, long)]
+    world: Option<String>,
 
     /// Sets the password of the server, (Can be set with ENV variable PASSWORD)
-    #[arg(long, env = "PASSWORD")]
-    #[arg(default_value_t = String::from("P@ssw0rd!"))]
-    password: String,
+    #[arg(long)]
+    password: Option<String>,
 
     /// Sets the public state of the server, (Can be set with ENV variable PUBLIC)
-    #[arg(short = 'o', long, env = "PUBLIC")]
-    public: String,
+    #[arg(short = 'o', long)]
+    public: Option<String>,
 
     /// Sets flag modifiers for launching the server, (Can be set with ENV variable MODIFIERS)
     /// This should be comma separated with equal variables, e.g. "raids=none,combat=hard"
-    #[arg(long, env = "MODIFIERS")]
+    #[arg(long)]
     modifiers: Option<String>,
 
     /// Sets flag preset for launching the server, (Can be set with ENV variable PRESET)
-    #[arg(long, env = "PRESET")]
+    #[arg(long)]
     preset: Option<String>,
 
     /// Sets flag set_key for launching the server, (Can be set with ENV variable SET_KEY)
-    #[arg(long, env = "SET_KEY")]
+    /// This can be comma separated to set several global keys, e.g. "nobuildcost,nomap"
+    #[arg(long)]
     set_key: Option<String>,
 
-    /// Sets the save interval in seconds
-    #[arg(long, env = "SAVE_INTERVAL")]
-    save_interval: Option<u16>,
+    /// Passes unknown presets, modifiers and global keys to the server instead of rejecting them,
+    /// for values added by a newer game version, (Can be set with ENV variable MODIFIER_PASSTHROUGH)
+    #[arg(long)]
+    modifier_passthrough: Option<String>,
+
+    /// Lists the known presets, modifiers and global keys and exits without configuring
+    #[arg(long)]
+    list_modifiers: bool,
+
+    /// Sets the save interval in seconds, (Can be set with ENV variable SAVE_INTERVAL)
+    #[arg(long)]
+    save_interval: Option<String>,
+
+    /// Sets the Nexus Mods API key used to download mods from Nexus, (Can be set with ENV variable NEXUS_API_KEY)
+    #[arg(long)]
+    nexus_api_key: Option<String>,
+  },
+
+  /// Shows and validates the layered settings: defaults < settings file (odin.yaml) < env variables < flags.
+  /// The settings file is read from ODIN_SETTINGS_FILE, or odin.yaml in the working or instance directory.
+  Config {
+    #[command(subcommand)]
+    action: ConfigAction,
+  },
+
+  /// Manages the admin, banned and permitted lists of the server.
+  /// Players can be given by id or by a name odin has seen join the server.
+  /// ADMIN_LIST, BANNED_LIST and PERMITTED_LIST replace the lists on every start when set.
+  Access {
+    #[command(subcommand)]
+    list: AccessListCommand,
+  },
+
+  /// Lists, switches, creates, imports and deletes the worlds in the saves directory.
+  World {
+    #[command(subcommand)]
+    action: WorldAction,
   },
 
   /// Installs Valheim with steamcmd
   Install,
 
   /// Starts Valheim
-  Start,
+  Start {
+    /// Runs the server as a child of odin instead of daemonizing it. Output is streamed through
+    /// the log filters, SIGTERM and SIGINT stop the server gracefully and odin exits with the
+    /// server's status. Meant for running odin as PID 1 under Docker or Kubernetes.
+    #[arg(long, env = "ODIN_FOREGROUND", value_parser = parse_truthy)]
+    foreground: bool,
+  },
 
   /// Stops Valheim
   Stop,
@@ -120,18 +166,86 @@ pub enum Commands {
     message: String,
 
     /// Sets the webhook to send a notification to, (Can be set with ENV variable WEBHOOK_URL)
+    /// Without one, the notification goes to every sink in NOTIFICATION_SINKS taking broadcasts.
     #[arg(long, env = "WEBHOOK_URL")]
     webhook_url: Option<String>,
+
+    /// Prints the body every sink taking EVENT would get, rendered with sample data, without sending it.
+    #[arg(long, value_name = "EVENT", value_parser = EVENTS)]
+    preview: Option<String>,
+
+    /// Checks every event template in discord.json and the template files of the sinks renders.
+    #[arg(long, conflicts_with = "preview")]
+    validate: bool,
   },
 
-  /// Installs a mod from a given source by downloading the zip file and then extracting it.
+  /// Installs a mod from a given source by downloading the archive and then extracting it.
+  /// Zip, tar, tar.gz and 7z archives are supported as well as bare dll and cfg files.
   /// Supported platforms are Nexus (with premium account and API key), GitHub, and any other direct download source.
+  /// Nexus mods are installed with "nexus:<mod_id>/<file_id>" and need NEXUS_API_KEY or a configured key.
+  /// Downloads are cached, append "#sha256=<hash>" to the url to verify the download.
   #[command(name = "mod:install")]
   ModInstall {
-    /// Which url you wish to pull from
+    /// Which url, Thunderstore mod string or nexus:<mod_id>/<file_id> you wish to pull from
     url: String,
   },
 
+  /// Lists installed mods which have a newer version available on Thunderstore.
+  #[command(name = "mod:outdated")]
+  ModOutdated {
+    /// Print out as json
+    #[arg(long)]
+    json: bool,
+  },
+
+  /// Upgrades installed mods to the latest version available on Thunderstore.
+  #[command(name = "mod:upgrade")]
+  ModUpgrade {
+    /// Names of the mods to upgrade, e.g. "Jotunn" or "ValheimModding-Jotunn". Upgrades every outdated mod if omitted.
+    names: Vec<String>,
+  },
+
+  /// Reverts mod installs which were interrupted, e.g. by a crash or a restart, leaving the
+  /// plugins as they were before. Pass --finish to install those mods again afterwards.
+  #[command(name = "mod:repair")]
+  ModRepair {
+    /// Install the interrupted mods again after reverting them
+    #[arg(long)]
+    finish: bool,
+  },
+
+  /// Reads and edits the BepInEx configs of installed plugins.
+  /// Settings can also be overridden at startup with BEPINEX_CFG__<plugin>__<section>__<key> environment variables.
+  #[command(name = "mod:config")]
+  ModConfig {
+    #[command(subcommand)]
+    action: ModConfigAction,
+  },
+
+  /// Installs, upgrades, removes and verifies the BepInEx mod framework.
+  #[command(name = "bepinex")]
+  BepInEx {
+    #[command(subcommand)]
+    action: BepInExAction,
+  },
+
+  /// Builds a modpack of the installed mods for players to download.
+  /// Mods with "server_only": true in their manifest.json are left out.
+  #[command(name = "mod:export-client")]
+  ModExportClient {
+    /// The format of the modpack
+    #[arg(long, value_enum, default_value_t = ModpackFormat::Zip)]
+    format: ModpackFormat,
+
+    /// Where to write the modpack, defaults to the one huginn serves at /modpack
+    #[arg(long, short = 'o')]
+    output: Option<String>,
+
+    /// Uploads the r2modman profile to Thunderstore and prints its profile code, implies --format r2modman
+    #[arg(long)]
+    code: bool,
+  },
+
   /// Prints out the status of your server with information about current players, mod support, and a few other details.
   /// Note: If your server has PUBLIC set to 0 it will not be able to be queried!
   Status {
@@ -159,4 +273,242 @@ pub enum Commands {
     #[arg(long, short = 'l', conflicts_with = "watch")]
     lines: Option<u16>,
   },
+
+  /// Runs a Discord bot which answers /status, /players, /restart and /backup
+  Bot,
+}
+
+#[derive(Subcommand)]
+pub enum ModConfigAction {
+  /// Prints the value of a setting
+  Get {
+    /// The plugin GUID the config is named after, e.g. "randyknapp.mods.epicloot"
+    plugin: String,
+
+    /// The setting as <section>.<key>, e.g. "Balance.Magic Find"
+    key: String,
+  },
+
+  /// Changes the value of a setting, keeping the rest of the file as is
+  Set {
+    /// The plugin GUID the config is named after, e.g. "randyknapp.mods.epicloot"
+    plugin: String,
+
+    /// The setting as <section>.<key>, e.g. "Balance.Magic Find"
+    key: String,
+
+    /// The new value
+    value: String,
+  },
+
+  /// Lists the settings of a plugin, or every plugin config if omitted
+  List {
+    /// The plugin GUID the config is named after
+    plugin: Option<String>,
+
+    /// Print out as json
+    #[arg(long)]
+    json: bool,
+  },
+}
+
+#[derive(Subcommand)]
+pub enum BepInExAction {
+  /// Installs BepInExPack_Valheim, skipped if a working BepInEx is already installed
+  Install {
+    /// The pack version to install, e.g. "5.4.2202". Defaults to the latest release
+    #[arg(id = "pack_version", value_name = "VERSION")]
+    version: Option<String>,
+
+    /// Installs the pack from this url instead of Thunderstore
+    #[arg(long, env = "BEPINEX_DOWNLOAD_URL")]
+    url: Option<String>,
+
+    /// Reinstall even if BepInEx is already installed
+    #[arg(long)]
+    force: bool,
+  },
+
+  /// Upgrades BepInExPack_Valheim and removes the files the new version no longer ships
+  Upgrade {
+    /// The pack version to upgrade to. Defaults to the latest release
+    #[arg(id = "pack_version", value_name = "VERSION")]
+    version: Option<String>,
+  },
+
+  /// Removes BepInEx so the server launches vanilla, keeping plugins and their configs
+  Remove {
+    /// Also delete the plugins and configs in the BepInEx directory
+    #[arg(long)]
+    purge: bool,
+  },
+
+  /// Checks the doorstop libraries, the preloader and the files of the installed pack
+  Verify {
+    /// Print out as json
+    #[arg(long)]
+    json: bool,
+  },
+}
+
+#[derive(Subcommand)]
+pub enum ConfigAction {
+  /// Prints the settings from the settings file, secrets are redacted
+  Show {
+    /// Prints the effective value of every setting and where it comes from
+    #[arg(long)]
+    effective: bool,
+
+    /// Print out as json
+    #[arg(long)]
+    json: bool,
+  },
+
+  /// Checks every setting and reports all invalid ones
+  Validate,
+}
+
+#[derive(Subcommand)]
+pub enum AccessListCommand {
+  /// Players who can use admin commands, adminlist.txt
+  Admin {
+    #[command(subcommand)]
+    action: AccessAction,
+  },
+
+  /// Players who can't join, bannedlist.txt
+  Ban {
+    #[command(subcommand)]
+    action: AccessAction,
+  },
+
+  /// Players allowed to join, everyone else is turned away once the list has an entry, permittedlist.txt
+  Permit {
+    #[command(subcommand)]
+    action: AccessAction,
+  },
+}
+
+#[derive(Subcommand)]
+pub enum AccessAction {
+  /// Adds players to the list
+  Add {
+    /// Player ids or names, e.g. 76561198000000000 or "Ragnar"
+    #[arg(required = true)]
+    players: Vec<String>,
+  },
+
+  /// Removes players from the list
+  Remove {
+    /// Player ids or names, e.g. 76561198000000000 or "Ragnar"
+    #[arg(required = true)]
+    players: Vec<String>,
+  },
+
+  /// Prints the players on the list
+  List {
+    /// Print out as json
+    #[arg(long)]
+    json: bool,
+  },
+}
+
+#[derive(Subcommand)]
+pub enum WorldAction {
+  /// Lists the worlds with their seed, size and when they were last saved, * marks the active world
+  List {
+    /// Print out as json
+    #[arg(long)]
+    json: bool,
+  },
+
+  /// Prints the name, seed, uid and versions stored in a world's .fwl
+  Info {
+    /// Name of the world, defaults to the active world
+    name: Option<String>,
+
+    /// Print out as json
+    #[arg(long)]
+    json: bool,
+  },
+
+  /// Prints the day, defeated bosses, global keys and object counts from a world's .db
+  Stats {
+    /// Name of the world, defaults to the active world
+    name: Option<String>,
+
+    /// How many of the most common prefabs to list
+    #[arg(long, default_value_t = 10)]
+    top: usize,
+
+    /// Print out as json
+    #[arg(long)]
+    json: bool,
+  },
+
+  /// Removes dropped items, prefabs or unvisited zones from a world's .db after backing it up
+  Prune {
+    /// Name of the world, defaults to the active world
+    name: Option<String>,
+
+    /// Remove items lying on the ground
+    #[arg(long)]
+    items: bool,
+
+    /// Remove every object of a prefab, by name or by the hash `odin world stats` shows
+    #[arg(long = "prefab", value_name = "PREFAB")]
+    prefabs: Vec<String>,
+
+    /// Reset zones without player builds, tombstones or terrain changes, the server generates them again
+    #[arg(long)]
+    unvisited: bool,
+
+    /// Only report what would be removed
+    #[arg(long)]
+    report: bool,
+
+    /// Print out the report as json
+    #[arg(long)]
+    json: bool,
+  },
+
+  /// Makes an existing world the active one and restarts a running server to load it
+  Switch {
+    /// Name of the world, without .db or .fwl
+    name: String,
+
+    /// Only update the config, the server loads the world on its next start
+    #[arg(long)]
+    no_restart: bool,
+  },
+
+  /// Makes a new world the active one, the server generates it when it starts
+  Create {
+    /// Name of the world
+    name: String,
+
+    /// Only update the config, the server generates the world on its next start
+    #[arg(long)]
+    no_restart: bool,
+  },
+
+  /// Imports a world from its .fwl or .db, a directory or a zip holding the .db/.fwl pair
+  Import {
+    /// Path to the world to import
+    source: String,
+
+    /// Replace a world with the same name, it is backed up first
+    #[arg(long)]
+    force: bool,
+  },
+
+  /// Deletes a world after backing it up to world_backups in the saves directory
+  Delete {
+    /// Name of the world
+    name: String,
+
+    /// Delete the active world as well
+    #[arg(long)]
+    force: bool,
+  },
 }
@@ -0,0 +1,106 @@
+use crate::log_filters::PlayerList;
+use crate::server::access::{self, resolve_player, AccessList};
+
+use log::{error, info, warn};
+use std::process::exit;
+
+fn resolve_players(players: &[String]) -> Vec<String> {
+  let known = PlayerList::default();
+  let resolved: Vec<Result<String, String>> = players
+    .iter()
+    .map(|player| resolve_player(&known, player))
+    .collect();
+  let errors: Vec<&String> = resolved.iter().filter_map(|id| id.as_ref().err()).collect();
+  if !errors.is_empty() {
+    for e in errors {
+      error!("{}", e);
+    }
+    exit(1);
+  }
+  resolved.into_iter().flatten().collect()
+}
+
+fn warn_if_env_managed(list: AccessList) {
+  if std::env::var(list.env_var()).is_ok_and(|value| !value.is_empty()) {
+    warn!(
+      "{} is managed by {}, this change is undone on the next start",
+      list.file_name(),
+      list.env_var()
+    );
+  }
+}
+
+pub fn add(dry_run: bool, list: AccessList, players: &[String]) {
+  let ids = resolve_players(players);
+  if dry_run {
+    info!(
+      "Would have added {} to {}",
+      ids.join(", "),
+      list.file_name()
+    );
+    return;
+  }
+  match access::add(&list.path(), &ids) {
+    Ok(added) if added.is_empty() => info!("Already on {}", list.file_name()),
+    Ok(added) => {
+      info!("Added {} to {}", added.join(", "), list.file_name());
+      warn_if_env_managed(list);
+    }
+    Err(e) => {
+      error!("Failed to update {}: {}", list.file_name(), e);
+      exit(1);
+    }
+  }
+}
+
+pub fn remove(dry_run: bool, list: AccessList, players: &[String]) {
+  let ids = resolve_players(players);
+  if dry_run {
+    info!(
+      "Would have removed {} from {}",
+      ids.join(", "),
+      list.file_name()
+    );
+    return;
+  }
+  match access::remove(&list.path(), &ids) {
+    Ok(removed) if removed.is_empty() => info!("Not on {}", list.file_name()),
+    Ok(removed) => {
+      info!("Removed {} from {}", removed.join(", "), list.file_name());
+      warn_if_env_managed(list);
+    }
+    Err(e) => {
+      error!("Failed to update {}: {}", list.file_name(), e);
+      exit(1);
+    }
+  }
+}
+
+/// Prints the ids on a list, with the name odin last saw each player under.
+pub fn list(list: AccessList, output_json: bool) {
+  let entries = match access::read_entries(&list.path()) {
+    Ok(entries) => entries,
+    Err(e) => {
+      error!("Failed to read {}: {}", list.file_name(), e);
+      exit(1);
+    }
+  };
+  let players = PlayerList::default();
+  let name = |id: &str| players.name_of(id);
+  if output_json {
+    let entries: Vec<serde_json::Value> = entries
+      .iter()
+      .map(|id| serde_json::json!({ "id": id, "name": name(id) }))
+      .collect();
+    println!("{}", serde_json::to_string_pretty(&entries).unwrap());
+  } else if entries.is_empty() {
+    info!("{} is empty", list.file_name());
+  } else {
+    for id in &entries {
+      match name(id) {
+        Some(name) => println!("{} ({})", id, name),
+        None => println!("{}", id),
+      }
+    }
+  }
+}
@@ -5,16 +5,12 @@ use log::{debug, error, info};
 use std::fs::{remove_file, File};
 use std::process::exit;
 
-pub fn invoke(input: String, output: String) {
+/// Archives everything in `input` except the game's own auto backups into the tar.gz `output`.
+pub fn create_backup(input: &str, output: &str) -> Result<(), String> {
   debug!("Creating archive of {input}");
   debug!("Output set to {output}");
-  let tar_gz = match File::create(&output) {
-    Ok(file) => file,
-    Err(_) => {
-      error!("Failed to create backup file at {}", &output);
-      exit(1)
-    }
-  };
+  let tar_gz =
+    File::create(output).map_err(|_| format!("Failed to create backup file at {}", output))?;
   let enc = GzEncoder::new(tar_gz, Compression::default());
   let mut tar = tar::Builder::new(enc);
 
@@ -30,21 +26,61 @@ pub fn invoke(input: String, output: String) {
 
         info!(
           "Adding {name} to backup file, with path {}",
-          name.replace(&input, "")
+          name.replace(input, "")
         );
 
         match tar.append_path_with_name(&name, name.replace(&format!("{input}/"), "")) {
           Ok(_) => debug!("Successfully added {name} to backup file"),
           Err(err) => {
-            error!("Failed to add {name} to backup file");
             error!("{:?}", err);
-            remove_file(&output).unwrap();
-            exit(1)
+            remove_file(output).ok();
+            return Err(format!("Failed to add {name} to backup file"));
           }
         };
       }
       Err(e) => println!("{:?}", e),
     }
   }
-  tar.finish().unwrap();
+  tar
+    .into_inner()
+    .and_then(|enc| enc.finish())
+    .map(|_| ())
+    .map_err(|e| format!("Failed to write backup file {}: {}", output, e))
+}
+
+pub fn invoke(input: String, output: String) {
+  if let Err(e) = create_backup(&input, &output) {
+    error!("{}", e);
+    exit(1)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use flate2::read::GzDecoder;
+  use std::fs;
+  use tempfile::tempdir;
+
+  #[test]
+  fn test_create_backup() {
+    let saves = tempdir().unwrap();
+    let worlds = saves.path().join("worlds_local");
+    fs::create_dir_all(&worlds).unwrap();
+    fs::write(worlds.join("Midgard.db"), "db").unwrap();
+    fs::write(worlds.join("Midgard_backup_auto-20240101.db"), "old").unwrap();
+    let backups = tempdir().unwrap();
+    let output = backups.path().join("backup.tar.gz");
+
+    create_backup(&saves.path().to_string_lossy(), &output.to_string_lossy()).unwrap();
+
+    let mut archive = tar::Archive::new(GzDecoder::new(File::open(&output).unwrap()));
+    let mut names: Vec<String> = archive
+      .entries()
+      .unwrap()
+      .map(|entry| entry.unwrap().path().unwrap().display().to_string())
+      .collect();
+    names.sort();
+    assert_eq!(names, vec!["worlds_local", "worlds_local/Midgard.db"]);
+  }
 }
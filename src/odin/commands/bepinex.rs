@@ -0,0 +1,143 @@
+use crate::commands::exit_on_error;
+use crate::commands::install_mod::process_mod;
+use crate::errors::ValheimModError;
+use crate::mods::bepinex::BepInExEnvironment;
+use crate::mods::bepinex_pack::{
+  latest_version, releases_url, remove_stale_files, BepInExPack, BEPINEX_PACK_MOD,
+};
+use crate::mods::outdated::compare_versions;
+use crate::utils::common_paths::game_directory;
+
+use log::{error, info, warn};
+use reqwest::blocking::Client;
+use std::cmp::Ordering;
+use std::path::PathBuf;
+use std::process::exit;
+
+fn resolve_version(version: Option<String>) -> Result<String, ValheimModError> {
+  match version {
+    Some(version) => Ok(version),
+    None => latest_version(&Client::new(), &releases_url()),
+  }
+}
+
+fn report_problems(environment: &BepInExEnvironment) {
+  for problem in environment.verify() {
+    warn!("{}", problem);
+  }
+}
+
+fn install_pack(
+  dry_run: bool,
+  version: Option<String>,
+  url: Option<String>,
+  force: bool,
+) -> Result<(), ValheimModError> {
+  let environment = BepInExEnvironment::new();
+  if environment.verify().is_empty() && !force {
+    info!(
+      "BepInEx {} is already installed, use --force to reinstall it",
+      environment
+        .version()
+        .as_deref()
+        .unwrap_or("(unknown version)")
+    );
+    return Ok(());
+  }
+
+  let source = match url {
+    Some(url) => url,
+    None => format!("{}-{}", BEPINEX_PACK_MOD, resolve_version(version)?),
+  };
+  if dry_run {
+    info!("Would have installed BepInEx from {}", source);
+    return Ok(());
+  }
+  process_mod(&source)?;
+  report_problems(&BepInExEnvironment::new());
+  Ok(())
+}
+
+fn upgrade_pack(dry_run: bool, version: Option<String>) -> Result<(), ValheimModError> {
+  let game_dir = PathBuf::from(game_directory());
+  let previous = BepInExPack::load(&game_dir);
+  let target = resolve_version(version)?;
+  if let Some(installed) = previous.as_ref().and_then(|pack| pack.version.as_deref()) {
+    if compare_versions(installed, &target) != Ordering::Less {
+      info!("BepInEx {} is up to date", installed);
+      return Ok(());
+    }
+  }
+
+  let source = format!("{}-{}", BEPINEX_PACK_MOD, target);
+  if dry_run {
+    info!("Would have upgraded BepInEx to {}", target);
+    return Ok(());
+  }
+  process_mod(&source)?;
+  if let Some(previous) = previous {
+    remove_stale_files(&game_dir, &previous)?;
+  }
+  info!("Upgraded BepInEx to {}", target);
+  report_problems(&BepInExEnvironment::new());
+  Ok(())
+}
+
+fn remove_pack(dry_run: bool, purge: bool) -> Result<(), ValheimModError> {
+  if dry_run {
+    info!(
+      "Would have removed BepInEx{}",
+      if purge {
+        " with its plugins and configs"
+      } else {
+        ""
+      }
+    );
+    return Ok(());
+  }
+  crate::mods::bepinex_pack::remove(&PathBuf::from(game_directory()), purge)?;
+  if BepInExEnvironment::new().is_installed() {
+    warn!("BepInEx files are still in place, the server will keep launching modded");
+  } else {
+    info!("Removed BepInEx, the server will launch vanilla");
+  }
+  Ok(())
+}
+
+pub fn install(dry_run: bool, version: Option<String>, url: Option<String>, force: bool) {
+  exit_on_error(install_pack(dry_run, version, url, force))
+}
+
+pub fn upgrade(dry_run: bool, version: Option<String>) {
+  exit_on_error(upgrade_pack(dry_run, version))
+}
+
+pub fn remove(dry_run: bool, purge: bool) {
+  exit_on_error(remove_pack(dry_run, purge))
+}
+
+pub fn verify(output_json: bool) {
+  let environment = BepInExEnvironment::new();
+  let problems = environment.verify();
+  let version = environment.version();
+  if output_json {
+    let report = serde_json::json!({
+      "installed": problems.is_empty(),
+      "version": version,
+      "problems": problems,
+    });
+    println!("{}", serde_json::to_string_pretty(&report).unwrap());
+  } else if problems.is_empty() {
+    info!(
+      "BepInEx {} is installed correctly",
+      version.as_deref().unwrap_or("(unknown version)")
+    );
+  } else {
+    for problem in &problems {
+      error!("{}", problem);
+    }
+  }
+  if !problems.is_empty() {
+    exit(1);
+  }
+}
@@ -0,0 +1,35 @@
+use crate::bot::{self, BotConfig, OdinOperations, SlashCommand, DISCORD_ADMIN_ROLES};
+use crate::files::settings::Settings;
+
+use log::{error, info, warn};
+use std::process::exit;
+use std::sync::Arc;
+
+pub async fn invoke(dry_run: bool) {
+  let config = BotConfig::from_settings(Settings::current().discord).unwrap_or_else(|e| {
+    error!("{}", e);
+    exit(1)
+  });
+  if config.admin_roles.is_empty() {
+    warn!(
+      "{} is not set, nobody can run the commands which change the server",
+      DISCORD_ADMIN_ROLES
+    );
+  }
+  let commands: Vec<String> = SlashCommand::ALL
+    .iter()
+    .map(|command| format!("/{}", command.name()))
+    .collect();
+  if dry_run {
+    info!(
+      "Would have connected to Discord with {}",
+      commands.join(", ")
+    );
+    return;
+  }
+  info!("Starting the Discord bot with {}", commands.join(", "));
+  if let Err(e) = bot::run(config, Arc::new(OdinOperations)).await {
+    error!("{}", e);
+    exit(1);
+  }
+}
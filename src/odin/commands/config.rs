@@ -0,0 +1,35 @@
+use crate::files::settings::{settings_file, Settings};
+
+use log::{error, info};
+use std::process::exit;
+
+pub fn show(effective: bool, output_json: bool) {
+  let settings = Settings::load(&[]).redacted(effective);
+  if output_json {
+    println!("{}", serde_json::to_string_pretty(&settings).unwrap());
+    return;
+  }
+  if !effective && settings.is_empty() {
+    info!("No settings found in {:?}", settings_file());
+  }
+  for (key, setting) in settings {
+    if effective {
+      println!("{} = {} ({})", key, setting.value, setting.source);
+    } else {
+      println!("{} = {}", key, setting.value);
+    }
+  }
+}
+
+pub fn validate() {
+  let errors = Settings::load(&[]).validate();
+  if errors.is_empty() {
+    info!("All settings are valid");
+    return;
+  }
+  for e in &errors {
+    error!("{}", e);
+  }
+  error!("Found {} invalid settings", errors.len());
+  exit(1);
+}
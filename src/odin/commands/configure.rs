@@ -1,11 +1,17 @@
-use log::{debug, info};
+use log::{debug, error, info, warn};
 use serde::{Deserialize, Serialize};
 use std::os::unix::fs::PermissionsExt;
 use std::path::Path;
+use std::str::FromStr;
 use std::{fs, io};
 
 use crate::files::config::{config_file, write_config};
 use crate::files::discord::{discord_file, write_discord};
+use crate::files::settings::Settings;
+use crate::notifications::preview::validate_templates;
+use crate::notifications::providers::write_sink_templates;
+use crate::notifications::sinks::sinks;
+use crate::server::world_modifiers::{ModifierKey, Preset, SetKey, WorldSetting, PASSTHROUGH_HINT};
 
 /// See: https://user-images.githubusercontent.com/34519392/273088066-b9c94664-9eef-419d-999a-8b8798462dee.PNG
 /// for a list of modifiers
@@ -18,13 +24,21 @@ pub struct Modifiers {
   pub value: String,
 }
 
-impl From<String> for Modifiers {
-  /// Creates a new modifier from a string
-  fn from(value: String) -> Self {
-    let mut split = value.split('=');
-    let name = split.next().unwrap().to_string();
-    let value = split.next().unwrap().to_string();
-    Modifiers { name, value }
+impl FromStr for Modifiers {
+  type Err = String;
+
+  /// Parses a single `name=value` modifier
+  fn from_str(modifier: &str) -> Result<Self, Self::Err> {
+    match modifier.split_once('=') {
+      Some((name, value)) if !name.trim().is_empty() && !value.trim().is_empty() => Ok(Modifiers {
+        name: name.trim().to_string(),
+        value: value.trim().to_string(),
+      }),
+      _ => Err(format!(
+        "{} is not a modifier like combat=hard",
+        modifier.trim()
+      )),
+    }
   }
 }
 
@@ -58,6 +72,9 @@ pub struct Configuration {
 
   /// Sets the save interval in seconds
   pub save_interval: Option<u16>,
+
+  /// Sets the Nexus Mods API key used to download mods from Nexus, (Can be set with ENV variable NEXUS_API_KEY)
+  pub nexus_api_key: Option<String>,
 }
 
 impl Configuration {
@@ -74,6 +91,7 @@ impl Configuration {
     modifiers: Option<Vec<Modifiers>>,
     set_key: Option<String>,
     save_interval: Option<u16>,
+    nexus_api_key: Option<String>,
   ) -> Self {
     Configuration {
       name,
@@ -86,6 +104,7 @@ impl Configuration {
       modifiers,
       set_key,
       save_interval,
+      nexus_api_key,
     }
   }
 
@@ -151,7 +170,47 @@ impl Configuration {
     let discord = discord_file();
     debug!("Writing Discord config file...");
     write_discord(&discord);
+    debug!("Writing the template files of the notification sinks...");
+    let sinks = sinks();
+    write_sink_templates(&sinks);
+    debug!("Validating the notification templates...");
+    for problem in validate_templates(&sinks) {
+      warn!("{}", problem);
+    }
 
     Ok(())
   }
 }
+
+/// Prints the presets, modifiers and global keys odin knows about.
+pub fn list_modifiers() {
+  println!("Presets (--preset): {}", Preset::names());
+  println!("Modifiers (--modifiers name=value,...):");
+  for key in ModifierKey::ALL {
+    let values: Vec<&str> = key.values().iter().map(|value| value.as_str()).collect();
+    println!("  {}: {}", key.as_str(), values.join(", "));
+  }
+  println!("Global keys (--set-key key,...): {}", SetKey::names());
+  println!("Unknown values are rejected; {}.", PASSTHROUGH_HINT);
+}
+
+/// Resolves the layered settings with the flags passed on the command line and writes the config.
+pub async fn invoke(
+  cli: &[(&'static str, Option<String>)],
+) -> Result<(), Box<dyn std::error::Error>> {
+  match Settings::load(cli).to_configuration() {
+    Ok(configuration) => configuration.invoke().await,
+    Err(errors) => {
+      for e in &errors {
+        error!("{}", e);
+      }
+      Err(
+        format!(
+          "{} invalid settings, see `odin config validate`",
+          errors.len()
+        )
+        .into(),
+      )
+    }
+  }
+}
@@ -0,0 +1,62 @@
+use crate::errors::ValheimModError;
+use crate::files::settings::Settings;
+use crate::mods::modpack::{
+  client_modpack, client_mods, create_profile_code, modpack_directory, profile_code_url, write_r2z,
+  write_zip, ModpackFormat,
+};
+use crate::mods::thunderstore::ThunderstoreClient;
+use crate::utils::common_paths::bepinex_plugin_directory;
+
+use log::{error, info};
+use reqwest::blocking::Client;
+use std::path::{Path, PathBuf};
+use std::process::exit;
+
+fn export(
+  format: ModpackFormat,
+  output: Option<PathBuf>,
+  code: bool,
+) -> Result<(), ValheimModError> {
+  let mods = client_mods(Path::new(&bepinex_plugin_directory()));
+  if mods.is_empty() {
+    return Err(ValheimModError::ModpackError(String::from(
+      "No client mods are installed",
+    )));
+  }
+
+  match format {
+    ModpackFormat::Zip => match output {
+      Some(output) => write_zip(&mods, &output),
+      None => {
+        let output = client_modpack()?;
+        info!("The modpack at {:?} is up to date", output);
+        Ok(())
+      }
+    },
+    ModpackFormat::R2modman => {
+      let output = output.unwrap_or_else(|| modpack_directory().join("modpack.r2z"));
+      let index = ThunderstoreClient::default().fetch_index()?;
+      let settings = Settings::load(&[]);
+      let name = settings.set_value("server.name").unwrap_or("Valheim");
+      write_r2z(name, &mods, &index, &output)?;
+      if code {
+        let profile_code = create_profile_code(&Client::new(), &profile_code_url(), &output)?;
+        info!("Players can import the profile in r2modman with the code below");
+        println!("{}", profile_code);
+      }
+      Ok(())
+    }
+  }
+}
+
+pub fn invoke(format: ModpackFormat, output: Option<String>, code: bool) {
+  let format = if code {
+    ModpackFormat::R2modman
+  } else {
+    format
+  };
+  if let Err(e) = export(format, output.map(PathBuf::from), code) {
+    error!("Failed to export the client modpack: {}", e);
+    exit(1);
+  }
+}
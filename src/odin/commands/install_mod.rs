@@ -1,10 +1,11 @@
+use crate::mods::transaction::{transactions_directory, InstallTransaction};
 use crate::mods::ValheimMod;
 
 use crate::errors::ValheimModError;
-use log::{debug, error, info};
+use log::{debug, error, info, warn};
 use std::process::exit;
 
-fn process_mod(input: &str) -> Result<(), ValheimModError> {
+pub(crate) fn process_mod(input: &str) -> Result<(), ValheimModError> {
   match ValheimMod::try_from(input.to_string()) {
     Ok(mut valheim_mod) => {
       info!("Installing {}", &input);
@@ -28,6 +29,9 @@ fn process_mod(input: &str) -> Result<(), ValheimModError> {
 }
 
 pub fn invoke(input: String) {
+  if !InstallTransaction::pending(&transactions_directory()).is_empty() {
+    warn!("A previous mod install was interrupted, run `odin mod:repair` to clean it up.");
+  }
   if let Err(e) = process_mod(&input) {
     error!("Failed to process mod: {}", e);
     exit(1);
@@ -1,4 +1,6 @@
+use crate::files::settings::Settings;
 use crate::log_filters::{handle_launch_probes, handle_player_events};
+use crate::notifications::dispatcher::resume_spooled;
 use crate::utils::common_paths::log_directory;
 use crate::utils::environment::is_env_var_truthy;
 use anyhow::{Context, Result};
@@ -7,10 +9,11 @@ use std::collections::HashMap;
 use std::ffi::OsStr;
 use std::fs;
 use std::fs::File;
-use std::io::{BufRead, BufReader, Seek, SeekFrom};
+use std::io::{BufRead, BufReader, Seek, SeekFrom, Write};
 use std::path::PathBuf;
-use std::sync::Arc;
+use std::sync::{Arc, OnceLock};
 use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncRead};
 use tokio::task;
 
 /// Struct to keep track of each file's state, including its last read line position.
@@ -29,6 +32,12 @@ impl FileTracker {
   }
 }
 
+/// Whether player joins and leaves are notified, read once instead of for every line.
+fn player_events() -> bool {
+  static PLAYER_EVENTS: OnceLock<bool> = OnceLock::new();
+  *PLAYER_EVENTS.get_or_init(|| Settings::current().notifications.player_events)
+}
+
 /// Processes a line of text from the log and generates appropriate log messages and notifications.
 fn handle_line(path: &PathBuf, line: &str) {
   if line.trim().is_empty() {
@@ -39,7 +48,7 @@ fn handle_line(path: &PathBuf, line: &str) {
     return;
   }
 
-  if is_env_var_truthy("PLAYER_EVENT_NOTIFICATIONS") {
+  if player_events() {
     handle_player_events(line);
   }
 
@@ -70,6 +79,36 @@ fn handle_line(path: &PathBuf, line: &str) {
   handle_launch_probes(outline);
 }
 
+/// Streams the output of a server running in the foreground through the log filters,
+/// appending it to the log file at `path` so `odin logs` keeps working.
+pub(crate) async fn stream_output<R: AsyncRead + Unpin>(output: R, path: PathBuf) {
+  let mut log_file = match fs::OpenOptions::new().create(true).append(true).open(&path) {
+    Ok(file) => Some(file),
+    Err(e) => {
+      error!("Failed to open {:?}, the output is not saved: {}", path, e);
+      None
+    }
+  };
+  let mut reader = tokio::io::BufReader::new(output);
+  let mut buf = Vec::new();
+  loop {
+    buf.clear();
+    match reader.read_until(b'\n', &mut buf).await {
+      Ok(0) => break,
+      Ok(_) => {
+        if let Some(file) = log_file.as_mut() {
+          let _ = file.write_all(&buf);
+        }
+        handle_line(&path, &String::from_utf8_lossy(&buf));
+      }
+      Err(e) => {
+        error!("Failed to read the server output: {}", e);
+        break;
+      }
+    }
+  }
+}
+
 /// Tails the given log file asynchronously, processing new lines as they are written.
 async fn tail_file(mut file_tracker: FileTracker) -> Result<()> {
   let file = File::open(&file_tracker.path).context("Unable to open file for tailing")?;
@@ -169,8 +208,32 @@ pub async fn invoke(lines: Option<u16>, watch: bool) {
   }
 
   if watch {
+    // The watcher sends most notifications, it also sends what earlier runs couldn't.
+    resume_spooled();
     watch_logs(log_path).await;
   } else {
     print_logs(log_path, lines);
   }
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use tempfile::tempdir;
+
+  #[tokio::test]
+  async fn test_stream_output_appends_to_log_file() {
+    let log_dir = tempdir().unwrap();
+    let path = log_dir.path().join("valheim_server.log");
+    fs::write(&path, "earlier line\n").unwrap();
+
+    let output: &[u8] = b"Game server connected\n\xffinvalid utf8\nlast line without newline";
+    stream_output(output, path.clone()).await;
+
+    let content = String::from_utf8_lossy(&fs::read(&path).unwrap()).to_string();
+    assert_eq!(
+      content,
+      "earlier line\nGame server connected\n\u{fffd}invalid utf8\nlast line without newline"
+    );
+  }
+}
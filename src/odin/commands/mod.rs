@@ -1,10 +1,32 @@
+pub mod access;
 pub mod backup;
+pub mod bepinex;
+pub mod bot;
+pub mod config;
 pub mod configure;
+pub mod export_client;
 pub mod install;
 pub mod install_mod;
 pub mod logs;
+pub mod mod_config;
 pub mod notify;
+pub mod outdated_mods;
+pub mod repair_mods;
 pub mod start;
 pub mod status;
 pub mod stop;
 pub mod update;
+pub mod upgrade_mods;
+pub mod world;
+
+use crate::notifications::dispatcher::exit;
+use log::error;
+use std::fmt::Display;
+
+/// What a command produced, or its error logged before odin exits.
+pub(crate) fn exit_on_error<T, E: Display>(result: Result<T, E>) -> T {
+  result.unwrap_or_else(|e| {
+    error!("{}", e);
+    exit(1)
+  })
+}
@@ -0,0 +1,87 @@
+use crate::commands::exit_on_error;
+use crate::errors::ValheimModError;
+use crate::mods::bepinex_config::{
+  config_files, plugin_config_path, split_section_key, BepInExConfig,
+};
+use crate::utils::common_paths::bepinex_config_directory;
+
+use log::info;
+use std::path::PathBuf;
+
+fn load_plugin_config(plugin: &str) -> Result<BepInExConfig, ValheimModError> {
+  let config_dir = PathBuf::from(bepinex_config_directory());
+  BepInExConfig::load(&plugin_config_path(&config_dir, plugin)?)
+}
+
+fn get_value(plugin: &str, key: &str) -> Result<(), ValheimModError> {
+  let (section, name) = split_section_key(key)?;
+  match load_plugin_config(plugin)?.get(section, name) {
+    Some(value) => {
+      println!("{}", value);
+      Ok(())
+    }
+    None => Err(ValheimModError::ConfigKeyNotFound(key.to_string())),
+  }
+}
+
+fn set_value(dry_run: bool, plugin: &str, key: &str, value: &str) -> Result<(), ValheimModError> {
+  let (section, name) = split_section_key(key)?;
+  let mut config = load_plugin_config(plugin)?;
+  let previous = config.get(section, name);
+  config.set(section, name, value);
+  if dry_run {
+    info!(
+      "Would have set [{}] {} to {} (was {})",
+      section,
+      name,
+      value,
+      previous.as_deref().unwrap_or("unset")
+    );
+    return Ok(());
+  }
+  config.save()?;
+  info!("Set [{}] {} to {}", section, name, value);
+  Ok(())
+}
+
+fn list_values(plugin: Option<&str>, output_json: bool) -> Result<(), ValheimModError> {
+  match plugin {
+    Some(plugin) => {
+      let entries = load_plugin_config(plugin)?.entries();
+      if output_json {
+        println!("{}", serde_json::to_string_pretty(&entries).unwrap());
+      } else {
+        for entry in entries {
+          println!("{}.{} = {}", entry.section, entry.key, entry.value);
+        }
+      }
+    }
+    None => {
+      let names: Vec<String> = config_files(&PathBuf::from(bepinex_config_directory()))
+        .iter()
+        .filter_map(|path| path.file_stem())
+        .map(|stem| stem.to_string_lossy().to_string())
+        .collect();
+      if output_json {
+        println!("{}", serde_json::to_string_pretty(&names).unwrap());
+      } else {
+        for name in names {
+          println!("{}", name);
+        }
+      }
+    }
+  }
+  Ok(())
+}
+
+pub fn get(plugin: String, key: String) {
+  exit_on_error(get_value(&plugin, &key))
+}
+
+pub fn set(dry_run: bool, plugin: String, key: String, value: String) {
+  exit_on_error(set_value(dry_run, &plugin, &key, &value))
+}
+
+pub fn list(plugin: Option<String>, output_json: bool) {
+  exit_on_error(list_values(plugin.as_deref(), output_json))
+}
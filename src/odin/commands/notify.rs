@@ -1,15 +1,23 @@
+use crate::files::settings::Settings;
 use crate::notifications::enums::notification_event::{EventType, NotificationEvent};
-use crate::notifications::NotificationMessage;
+use crate::notifications::preview::validate_templates;
+use crate::notifications::sinks::sinks;
+use crate::notifications::{preview, NotificationMessage};
 use crate::utils::get_server_name;
 use chrono::Local;
 use std::env;
+use std::process::exit;
 
 use log::{error, info};
 
 pub fn invoke(title: String, message: String, webhook_url: Option<String>) {
   let name = env::var("TITLE").unwrap_or(title);
   let event_message = env::var("MESSAGE").unwrap_or(message);
-  let webhook_url = env::var("WEBHOOK_URL").unwrap_or_else(|_| webhook_url.unwrap_or_default());
+  let webhook_url = Settings::current()
+    .notifications
+    .webhook_url
+    .or(webhook_url)
+    .unwrap_or_default();
   let notification = NotificationMessage {
     author: get_server_name(),
     event_type: EventType {
@@ -19,13 +27,45 @@ pub fn invoke(title: String, message: String, webhook_url: Option<String>) {
     event_message,
     timestamp: Local::now().to_rfc3339(),
   };
+  info!(
+    "Sending Broadcast: {}",
+    serde_json::to_string_pretty(&notification).unwrap()
+  );
   if !webhook_url.is_empty() {
-    info!(
-      "Sending Broadcast: {}",
-      serde_json::to_string_pretty(&notification).unwrap()
-    );
     NotificationEvent::Broadcast.send_custom_notification(webhook_url.as_str(), &notification)
-  } else {
-    error!("Failed to send notification! Webhook url not provided!")
+  } else if NotificationEvent::Broadcast.send_to_sinks(&notification) == 0 {
+    error!("Failed to send notification! No webhook url or sink taking broadcasts provided!")
   }
 }
+
+/// Prints what every sink taking `event` would be sent, without sending anything.
+pub fn preview(event: &str) {
+  match preview::preview(sinks(), event) {
+    Ok(bodies) => {
+      for (sink, body) in bodies {
+        println!(
+          "{}:\n{}",
+          sink,
+          serde_json::to_string_pretty(&body).unwrap()
+        );
+      }
+    }
+    Err(e) => {
+      error!("Failed to preview {}: {}", event, e);
+      exit(1);
+    }
+  }
+}
+
+/// Checks the notification templates, exits with an error when one of them does not render.
+pub fn validate() {
+  let problems = validate_templates(&sinks());
+  if problems.is_empty() {
+    info!("All notification templates render.");
+    return;
+  }
+  for problem in &problems {
+    error!("{}", problem);
+  }
+  exit(1);
+}
@@ -0,0 +1,28 @@
+use crate::mods::outdated::outdated_mods;
+use crate::mods::thunderstore::ThunderstoreClient;
+
+use log::{error, info};
+use std::process::exit;
+
+pub fn invoke(output_json: bool) {
+  let outdated = match outdated_mods(&ThunderstoreClient::default()) {
+    Ok(outdated) => outdated,
+    Err(e) => {
+      error!("Failed to check for mod updates: {}", e);
+      exit(1);
+    }
+  };
+
+  if output_json {
+    println!("{}", serde_json::to_string_pretty(&outdated).unwrap());
+  } else if outdated.is_empty() {
+    info!("All installed mods are up to date!");
+  } else {
+    for outdated_mod in &outdated {
+      info!(
+        "{}: {} -> {}",
+        outdated_mod.full_name, outdated_mod.installed_version, outdated_mod.latest_version
+      );
+    }
+  }
+}
@@ -0,0 +1,63 @@
+use crate::commands::install_mod::process_mod;
+use crate::errors::ValheimModError;
+use crate::mods::transaction::{transactions_directory, InstallTransaction, TransactionState};
+use crate::mods::ValheimMod;
+
+use log::{error, info};
+use std::process::exit;
+
+fn repair(
+  transaction: InstallTransaction,
+  dry_run: bool,
+  finish: bool,
+) -> Result<(), ValheimModError> {
+  let journal = transaction.journal();
+  let url = journal.url.clone();
+  match journal.state {
+    TransactionState::Applied => {
+      info!("The install of {} completed, cleaning up", url);
+      if !dry_run {
+        transaction.commit()?;
+      }
+    }
+    TransactionState::Applying if dry_run => info!(
+      "Would have {} the interrupted install of {}",
+      if finish { "redone" } else { "reverted" },
+      url
+    ),
+    TransactionState::Applying => {
+      let mut valheim_mod = ValheimMod::from_journal(journal);
+      transaction.rollback()?;
+      info!("Reverted the interrupted install of {}", url);
+      if finish {
+        if valheim_mod.downloaded {
+          valheim_mod.install()?;
+        } else {
+          process_mod(&url)?;
+        }
+        info!("Installed {}", url);
+      }
+    }
+  }
+  Ok(())
+}
+
+pub fn invoke(dry_run: bool, finish: bool) {
+  let pending = InstallTransaction::pending(&transactions_directory());
+  if pending.is_empty() {
+    info!("No interrupted mod installs found, nothing to repair.");
+    return;
+  }
+
+  let mut failed = false;
+  for transaction in pending {
+    let url = transaction.journal().url.clone();
+    if let Err(e) = repair(transaction, dry_run, finish) {
+      error!("Failed to repair the install of {}: {}", url, e);
+      failed = true;
+    }
+  }
+  if failed {
+    exit(1);
+  }
+}
@@ -1,20 +1,41 @@
 use crate::{
   files::config::load_config,
+  notifications::dispatcher::exit,
   notifications::enums::{event_status::EventStatus, notification_event::NotificationEvent},
   server,
 };
 
 use log::{debug, error, info};
-use std::process::exit;
+use std::os::unix::process::ExitStatusExt;
 
-pub fn invoke(dry_run: bool) {
+pub async fn invoke(dry_run: bool, foreground: bool) {
   info!(target: "commands_start", "Setting up start scripts...");
   NotificationEvent::Start(EventStatus::Running).send_notification(None);
   debug!(target: "commands_start", "Loading config file...");
   let config = load_config();
+  if !dry_run {
+    server::access::sync_from_env();
+  }
   debug!(target: "commands_start", "Dry run condition: {}", dry_run);
   info!(target: "commands_start", "Looking for burial mounds...");
-  if !dry_run {
+  if !dry_run && foreground {
+    match server::start_foreground(config).await {
+      Ok(status) => {
+        info!(target: "commands_start", "Server exited with {}", status);
+        // Mirror the shell convention of 128 + signal for servers killed by a signal.
+        exit(
+          status
+            .code()
+            .or_else(|| status.signal().map(|signal| 128 + signal))
+            .unwrap_or(1),
+        );
+      }
+      Err(e) => {
+        error!(target: "commands_start", "Error: {}", e);
+        exit(1);
+      }
+    }
+  } else if !dry_run {
     match server::start_daemonized(config) {
       Ok(_) => info!(target: "commands_start", "Success, daemonized"),
       Err(e) => {
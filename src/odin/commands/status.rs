@@ -1,9 +1,10 @@
+use crate::files::settings::Settings;
 use crate::server::ServerInfo;
 use crate::utils::fetch_public_address;
+use crate::utils::instances::{current_instance, instance_port};
 
 use log::{error, info};
-use std::env;
-use std::net::SocketAddrV4;
+use std::net::{Ipv4Addr, SocketAddrV4};
 use std::process::exit;
 use std::str::FromStr;
 
@@ -17,12 +18,22 @@ fn parse_address(address: &str) -> SocketAddrV4 {
   }
 }
 
+/// The query address of the server on this machine, the query port is the game port plus one.
+pub fn local_address() -> SocketAddrV4 {
+  let port = current_instance()
+    .and_then(|instance| instance_port(&instance))
+    .unwrap_or(2456);
+  SocketAddrV4::new(Ipv4Addr::LOCALHOST, port + 1)
+}
+
 pub fn invoke(output_json: bool, use_local: bool, supplied_address: Option<String>) {
   let address = if use_local {
-    String::from("127.0.0.1:2457")
+    local_address().to_string()
   } else {
-    env::var("ADDRESS")
-      .unwrap_or_else(|_| supplied_address.unwrap_or_else(|| fetch_public_address().to_string()))
+    match Settings::current().http.address {
+      Some(address) => address.to_string(),
+      None => supplied_address.unwrap_or_else(|| fetch_public_address().to_string()),
+    }
   };
   let parsed_address = parse_address(&address);
   let server_info = ServerInfo::from(parsed_address);
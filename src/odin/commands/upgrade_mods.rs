@@ -0,0 +1,56 @@
+use crate::commands::install_mod::process_mod;
+use crate::mods::outdated::outdated_mods;
+use crate::mods::thunderstore::ThunderstoreClient;
+
+use log::{error, info};
+use std::process::exit;
+
+pub fn invoke(dry_run: bool, names: Vec<String>) {
+  let outdated = match outdated_mods(&ThunderstoreClient::default()) {
+    Ok(outdated) => outdated,
+    Err(e) => {
+      error!("Failed to check for mod updates: {}", e);
+      exit(1);
+    }
+  };
+
+  let selected: Vec<_> = outdated
+    .into_iter()
+    .filter(|outdated_mod| {
+      names.is_empty()
+        || names.iter().any(|name| {
+          name.eq_ignore_ascii_case(&outdated_mod.name)
+            || name.eq_ignore_ascii_case(&outdated_mod.full_name)
+        })
+    })
+    .collect();
+
+  if selected.is_empty() {
+    info!("No mods to upgrade!");
+    return;
+  }
+
+  let mut failed = 0;
+  for outdated_mod in &selected {
+    let mod_string = outdated_mod.latest_mod_string();
+    if dry_run {
+      info!(
+        "Dry run: {} would be upgraded from {} to {}",
+        outdated_mod.full_name, outdated_mod.installed_version, outdated_mod.latest_version
+      );
+    } else if let Err(e) = process_mod(&mod_string) {
+      error!("Failed to upgrade {}: {}", outdated_mod.full_name, e);
+      failed += 1;
+    } else {
+      info!(
+        "Upgraded {} to {}",
+        outdated_mod.full_name, outdated_mod.latest_version
+      );
+    }
+  }
+
+  if failed > 0 {
+    error!("Failed to upgrade {} of {} mods", failed, selected.len());
+    exit(1);
+  }
+}
@@ -0,0 +1,280 @@
+use crate::commands::exit_on_error;
+use crate::files::config::{config_file, load_config, write_arguments};
+use crate::files::settings::{Settings, Source};
+use crate::server;
+use crate::worlds::prune::PruneOptions;
+use crate::worlds::{
+  self, active_world, find_world, is_world_file_name, list_worlds, world_metadata, world_stats,
+};
+
+use chrono::{Local, TimeZone};
+use log::{error, info, warn};
+use std::path::Path;
+use std::process::exit;
+
+fn human_size(bytes: u64) -> String {
+  let units = ["B", "KB", "MB", "GB"];
+  let mut size = bytes as f64;
+  let mut unit = 0;
+  while size >= 1024.0 && unit < units.len() - 1 {
+    size /= 1024.0;
+    unit += 1;
+  }
+  format!("{:.1} {}", size, units[unit])
+}
+
+pub fn list(output_json: bool) {
+  let worlds = exit_on_error(list_worlds().map_err(|e| e.to_string()));
+  if output_json {
+    println!("{}", serde_json::to_string_pretty(&worlds).unwrap());
+    return;
+  }
+  if worlds.is_empty() {
+    info!("No worlds found in {:?}", worlds::worlds_directory());
+    return;
+  }
+  let active = active_world();
+  for world in worlds {
+    let modified = world
+      .modified
+      .and_then(|timestamp| Local.timestamp_opt(timestamp, 0).single())
+      .map_or_else(
+        || String::from("unknown"),
+        |time| time.format("%Y-%m-%d %H:%M").to_string(),
+      );
+    println!(
+      "{} {:<24} seed {:<12} {:>10}  saved {}{}",
+      if world.name == active { "*" } else { " " },
+      world.name,
+      world.seed.as_deref().unwrap_or("unknown"),
+      human_size(world.size),
+      modified,
+      if world.has_db { "" } else { " (never saved)" }
+    );
+  }
+}
+
+/// Prints what the .fwl of a world holds, the active world if no name is given.
+pub fn info(name: Option<String>, output_json: bool) {
+  let name = name.unwrap_or_else(active_world);
+  let world = exit_on_error(world_metadata(&name).map_err(|e| format!("{}: {}", name, e)));
+  if output_json {
+    println!("{}", serde_json::to_string_pretty(&world).unwrap());
+    return;
+  }
+  println!("Name: {}", world.name);
+  println!("Seed: {} ({})", world.seed_name, world.seed);
+  println!("UID: {}", world.uid);
+  println!("World version: {}", world.version);
+  println!("World gen version: {}", world.world_gen_version);
+  if !world.starting_global_keys.is_empty() {
+    println!(
+      "Starting global keys: {}",
+      world.starting_global_keys.join(", ")
+    );
+  }
+  if world.needs_db {
+    println!("Not generated yet, the server creates it on the next start");
+  }
+}
+
+/// Prints what the .db of a world says about its progress, the active world if no name is given.
+pub fn stats(name: Option<String>, top: usize, output_json: bool) {
+  let name = name.unwrap_or_else(active_world);
+  let world = exit_on_error(world_stats(&name).map_err(|e| format!("{}: {}", name, e)));
+  if output_json {
+    println!("{}", serde_json::to_string_pretty(&world).unwrap());
+    return;
+  }
+  println!("World: {} (version {})", name, world.version);
+  println!("Day: {}", world.day);
+  println!(
+    "Bosses defeated: {}",
+    if world.defeated_bosses.is_empty() {
+      String::from("none")
+    } else {
+      world.defeated_bosses.join(", ")
+    }
+  );
+  if !world.global_keys.is_empty() {
+    println!("Global keys: {}", world.global_keys.join(", "));
+  }
+  match world.player_built {
+    Some(built) => println!("Objects: {} ({} built by players)", world.zdos, built),
+    None => println!("Objects: {}", world.zdos),
+  }
+  if top > 0 && !world.prefabs.is_empty() {
+    println!("Most common prefabs:");
+    for (prefab, count) in world.top_prefabs(top) {
+      println!("  {:>8}  {}", count, prefab);
+    }
+  }
+  let sections = &world.sections;
+  println!(
+    "Size: {} objects, {} zones, {} header and events",
+    human_size(sections.zdos as u64),
+    human_size(sections.zones as u64),
+    human_size((sections.header + sections.events) as u64)
+  );
+}
+
+/// Removes what `options` selects from a world's .db, the server must not be running it.
+pub fn prune(name: Option<String>, options: &PruneOptions, report_only: bool, output_json: bool) {
+  if options.is_empty() {
+    error!("Nothing to prune, pass --items, --prefab or --unvisited");
+    exit(1);
+  }
+  let name = name.unwrap_or_else(active_world);
+  if find_world(&name).is_none() {
+    error!("There is no world named {}", name);
+    exit(1);
+  }
+  if !report_only && server::is_world_loaded(&name) {
+    error!(
+      "{} is in use by the running server, stop it first or use --report",
+      name
+    );
+    exit(1);
+  }
+  let report = exit_on_error(worlds::prune_world(&name, options, report_only));
+  if output_json {
+    println!("{}", serde_json::to_string_pretty(&report).unwrap());
+    return;
+  }
+  if options.item_drops {
+    info!("Dropped items: {}", report.item_drops);
+  }
+  for prefab in &options.prefabs {
+    info!("{}: {}", prefab, report.prefabs.get(prefab).unwrap_or(&0));
+  }
+  if options.unvisited_zones {
+    info!(
+      "Unvisited zones: {} with {} objects",
+      report.reset_zones, report.zone_zdos
+    );
+  }
+  let removed = report.removed();
+  if removed == 0 && report.reset_zones == 0 {
+    info!("Nothing to remove from {}", name);
+  } else if report_only {
+    info!(
+      "Would remove {} of {} objects, {} to {}",
+      removed,
+      report.zdos_before,
+      human_size(report.size_before as u64),
+      human_size(report.size_after as u64)
+    );
+  } else {
+    info!(
+      "Removed {} of {} objects, {} to {}",
+      removed,
+      report.zdos_before,
+      human_size(report.size_before as u64),
+      human_size(report.size_after as u64)
+    );
+  }
+}
+
+/// Restarts a running server so it loads the world from the config.
+fn restart() {
+  if !server::is_running() {
+    info!("The server isn't running, the world is loaded on the next start");
+    return;
+  }
+  exit_on_error(server::restart());
+  info!("Server daemon started");
+}
+
+/// Points the config at another world. With `create` the world must not exist yet and the
+/// server generates it on start.
+pub fn switch(dry_run: bool, name: &str, create: bool, restart_server: bool) {
+  if !is_world_file_name(name) {
+    error!("{} is not a valid world name", name);
+    exit(1);
+  }
+  match (find_world(name).is_some(), create) {
+    (false, false) => {
+      error!(
+        "There is no world named {}, see `odin world list` or use `odin world create`",
+        name
+      );
+      exit(1);
+    }
+    (true, true) => {
+      error!(
+        "A world named {} already exists, use `odin world switch`",
+        name
+      );
+      exit(1);
+    }
+    _ => {}
+  }
+
+  let mut config = load_config();
+  if config.world == name {
+    info!("{} is already the active world", name);
+    return;
+  }
+  if dry_run {
+    info!("Would have switched from {} to {}", config.world, name);
+    return;
+  }
+  let previous = std::mem::replace(&mut config.world, name.to_string());
+  if !write_arguments(config_file(), &config) {
+    exit(1);
+  }
+  info!("Switched from {} to {}", previous, name);
+
+  // odin configure writes WORLD from the settings again, e.g. when the container restarts.
+  if let Some(setting) = Settings::load(&[]).redacted(true).get("server.world") {
+    if setting.source != Source::Default && setting.value != name {
+      warn!(
+        "The world is set to {} in the {} settings, update it there to keep {} after the next `odin configure`",
+        setting.value, setting.source, name
+      );
+    }
+  }
+  if restart_server {
+    restart();
+  }
+}
+
+pub fn import(dry_run: bool, source: &str, force: bool) {
+  let source = Path::new(source);
+  if dry_run {
+    info!("Would have imported the world from {:?}", source);
+    return;
+  }
+  let world = exit_on_error(worlds::import_world(source, force));
+  info!(
+    "Imported {} with seed {}, use `odin world switch {}` to play it",
+    world.name,
+    world.seed.as_deref().unwrap_or("unknown"),
+    world.name
+  );
+}
+
+pub fn delete(dry_run: bool, name: &str, force: bool) {
+  if find_world(name).is_none() {
+    error!("There is no world named {}", name);
+    exit(1);
+  }
+  let is_active = active_world() == name;
+  if is_active && !force {
+    error!(
+      "{} is the active world, switch to another world first or use --force",
+      name
+    );
+    exit(1);
+  }
+  if dry_run {
+    info!("Would have backed up and deleted {}", name);
+    return;
+  }
+  if server::is_world_loaded(name) {
+    error!("{} is in use by the running server, stop it first", name);
+    exit(1);
+  }
+  let backup = exit_on_error(worlds::delete_world(name));
+  info!("Deleted {}, a backup was kept at {:?}", name, backup);
+}
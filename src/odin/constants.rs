@@ -3,7 +3,9 @@ pub const ODIN_WORKING_DIR: &str = "ODIN_WORKING_DIR";
 pub const SAVE_LOCATION: &str = "SAVE_LOCATION";
 pub const MODS_LOCATION: &str = "MODS_LOCATION";
 pub const GAME_LOCATION: &str = "GAME_LOCATION";
-// pub const BACKUP_LOCATION: &str = "BACKUP_LOCATION";
+pub const ODIN_INSTANCE: &str = "ODIN_INSTANCE";
+pub const INSTANCES_LOCATION: &str = "INSTANCES_LOCATION";
+pub const BACKUP_LOCATION: &str = "BACKUP_LOCATION";
 
 // Valheim
 pub const VALHEIM_EXECUTABLE_NAME: &str = "valheim_server.x86_64";
@@ -14,7 +16,7 @@ pub const LD_LIBRARY_PATH_VAR: &str = "LD_LIBRARY_PATH";
 pub const LD_PRELOAD_VAR: &str = "LD_PRELOAD";
 
 // Mods
-pub const SUPPORTED_FILE_TYPES: &[&str] = &["zip", "dll", "cfg"];
+pub const SUPPORTED_FILE_TYPES: &[&str] = &["zip", "tar", "gz", "tgz", "7z", "dll", "cfg"];
 
 // Job Names
 pub const AUTO_UPDATE_JOB: &str = "AUTO_UPDATE";
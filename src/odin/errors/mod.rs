@@ -34,6 +34,8 @@ pub enum ValheimModError {
   ZipArchiveError(String),
   #[error("Download error: {0}")]
   DownloadError(String),
+  #[error("Download rejected: {0}")]
+  DownloadRejected(String),
   #[error("File creation error: {0}")]
   FileCreateError(String),
   #[error("File move error: {0}")]
@@ -42,4 +44,67 @@ pub enum ValheimModError {
   TempDirCreationError(String),
   #[error("Failed to deserialize manifest file: {0}")]
   ManifestDeserializeError(String),
+  #[error("Checksum mismatch! Expected sha256 {0} but downloaded {1}")]
+  ChecksumMismatch(String, String),
+  #[error("Failed to fetch the Thunderstore package index: {0}")]
+  PackageIndexFetchError(String),
+  #[error("Failed to parse the Thunderstore package index: {0}")]
+  PackageIndexParseError(String),
+  #[error(
+    "A Nexus Mods API key is required, set NEXUS_API_KEY or run `odin configure --nexus-api-key`"
+  )]
+  NexusApiKeyMissing,
+  #[error("Nexus Mods API error: {0}")]
+  NexusApiError(String),
+  #[error("No BepInEx config found for plugin {0}")]
+  ConfigNotFound(String),
+  #[error("No setting {0} found")]
+  ConfigKeyNotFound(String),
+  #[error("Invalid setting {0}, expected <section>.<key>")]
+  InvalidConfigKey(String),
+  #[error("Failed to export the modpack: {0}")]
+  ModpackError(String),
+}
+
+#[derive(Debug, Error)]
+pub enum WorldFileError {
+  #[error("Failed to read the world file: {0}")]
+  Io(#[from] std::io::Error),
+  #[error("The world file ends in the middle of its {0}")]
+  Truncated(&'static str),
+  #[error("Invalid world file: {0}")]
+  Invalid(String),
+}
+
+#[derive(Debug, Error)]
+pub enum BotError {
+  #[error("A bot token is required, set DISCORD_BOT_TOKEN or discord.bot_token in odin.yaml")]
+  MissingToken,
+  #[error("Discord rejected the bot with {0}: {1}")]
+  Rejected(u16, String),
+  #[error("Gateway error: {0}")]
+  Gateway(String),
+  #[error("WebSocket error: {0}")]
+  WebSocket(Box<tokio_tungstenite::tungstenite::Error>),
+  #[error("Failed to connect: {0}")]
+  Io(#[from] std::io::Error),
+  #[error("Invalid gateway payload: {0}")]
+  Payload(#[from] serde_json::Error),
+  #[error("Discord API request failed: {0}")]
+  Http(#[from] reqwest::Error),
+  #[error("Discord API responded with {0}: {1}")]
+  Api(u16, String),
+}
+
+impl From<tokio_tungstenite::tungstenite::Error> for BotError {
+  fn from(error: tokio_tungstenite::tungstenite::Error) -> Self {
+    BotError::WebSocket(Box::new(error))
+  }
+}
+
+impl BotError {
+  /// Errors reconnecting won't fix.
+  pub fn is_fatal(&self) -> bool {
+    matches!(self, BotError::MissingToken | BotError::Rejected(..))
+  }
 }
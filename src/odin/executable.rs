@@ -1,3 +1,4 @@
+use crate::notifications::dispatcher;
 use log::{error, info};
 use std::path::Path;
 use std::process::{exit, Command, ExitStatus};
@@ -5,7 +6,7 @@ use std::process::{exit, Command, ExitStatus};
 pub fn find_command(executable: &str) -> Option<Command> {
   let script_file = Path::new(executable);
   if script_file.exists() {
-    info!("Executing: {} .....", executable.to_string());
+    info!("Executing: {} .....", executable);
     Option::from(Command::new(executable))
   } else {
     match which::which(executable) {
@@ -47,7 +48,7 @@ pub fn handle_exit_status(result: std::io::Result<ExitStatus>, success_message:
         match exit_status.code() {
           Some(code) => {
             error!("Exited with http code: {}", code);
-            exit(code)
+            dispatcher::exit(code)
           }
           None => info!("Process terminated by signal"),
         }
@@ -55,7 +56,7 @@ pub fn handle_exit_status(result: std::io::Result<ExitStatus>, success_message:
     }
     _ => {
       error!("An error has occurred and the command returned no exit code!");
-      exit(1)
+      dispatcher::exit(1)
     }
   }
 }
@@ -1,17 +1,25 @@
+use std::collections::BTreeMap;
 use std::{fs, path::PathBuf, process::exit};
 
-use log::{debug, error};
+use log::{debug, error, info, warn};
 use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
 
 use crate::commands::configure::{Configuration, Modifiers};
+use crate::files::migrations::{config_version, migrate, CONFIG_VERSION};
 use crate::files::{FileManager, ManagedFile};
 use crate::traits::AsOneOrZero;
 use crate::utils::environment::fetch_var;
+use crate::utils::instances::{current_instance, instance_config_path};
 
 const ODIN_CONFIG_FILE_VAR: &str = "ODIN_CONFIG_FILE";
 
 #[derive(Deserialize, Serialize, Debug, Clone)]
 pub struct ValheimArguments {
+  /// The schema version of the file, older files are migrated on load
+  #[serde(default)]
+  pub(crate) version: u64,
+
   /// The port of the server, (Can be set with ENV variable PORT)
   pub(crate) port: String,
 
@@ -45,6 +53,14 @@ pub struct ValheimArguments {
   /// Sets the save interval in seconds
   #[serde(skip_serializing_if = "Option::is_none")]
   pub save_interval: Option<u16>,
+
+  /// The Nexus Mods API key used to download mods from Nexus, (Can be set with ENV variable NEXUS_API_KEY)
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub(crate) nexus_api_key: Option<String>,
+
+  /// Fields this version of odin doesn't know, warned about and written back as they were
+  #[serde(flatten)]
+  pub(crate) unknown: BTreeMap<String, Value>,
 }
 
 impl From<Configuration> for ValheimArguments {
@@ -59,6 +75,7 @@ impl From<Configuration> for ValheimArguments {
     };
 
     ValheimArguments {
+      version: CONFIG_VERSION,
       port: value.port.to_string(),
       name: value.name,
       world: value.world,
@@ -69,6 +86,8 @@ impl From<Configuration> for ValheimArguments {
       modifiers: value.modifiers,
       set_key: value.set_key,
       save_interval: value.save_interval,
+      nexus_api_key: value.nexus_api_key,
+      unknown: BTreeMap::new(),
     }
   }
 }
@@ -88,18 +107,95 @@ pub fn load_config() -> ValheimArguments {
 
 /// Creates a new config file
 pub fn config_file() -> ManagedFile {
-  let name = fetch_var(ODIN_CONFIG_FILE_VAR, "config.json");
+  config_file_of(current_instance().as_deref())
+}
+
+/// The config file of an instance, or of the default server
+pub fn config_file_of(instance: Option<&str>) -> ManagedFile {
+  let name = match instance {
+    Some(instance) => instance_config_path(instance),
+    None => fetch_var(ODIN_CONFIG_FILE_VAR, "config.json"),
+  };
   debug!("Config file set to: {}", name);
   ManagedFile { name }
 }
 
-/// Reads the config file
+/// Keeps the file as it was before migrating it, next to it as `config.json.v<version>.bak`.
+fn write_migrated(
+  config: &ManagedFile,
+  content: &str,
+  version: u64,
+  migrated: &Map<String, Value>,
+) {
+  let backup = format!("{}.v{}.bak", config.path(), version);
+  if let Err(e) = fs::write(&backup, content) {
+    warn!(
+      "Failed to back up {} to {}, keeping the migration in memory: {}",
+      config.path(),
+      backup,
+      e
+    );
+    return;
+  }
+  info!("Backed up the previous config to {}", backup);
+  config.write(serde_json::to_string_pretty(migrated).unwrap());
+}
+
+/// Reads the config file, migrating it to the current version if it was written by an older odin
 pub fn read_config(config: ManagedFile) -> ValheimArguments {
   let content = config.read();
   if content.is_empty() {
     panic!("Please initialize odin with `odin configure`. See `odin configure --help`")
   }
-  serde_json::from_str(content.as_str()).unwrap()
+  let mut value: Map<String, Value> = match serde_json::from_str(content.as_str()) {
+    Ok(value) => value,
+    Err(e) => {
+      error!(
+        "{} is not a valid config file: {}. Run `odin configure` to recreate it.",
+        config.path(),
+        e
+      );
+      exit(1)
+    }
+  };
+
+  let version = config_version(&value);
+  if version > CONFIG_VERSION {
+    warn!(
+      "{} was written by a newer odin (config version {}, this odin knows {}), reading it anyway",
+      config.path(),
+      version,
+      CONFIG_VERSION
+    );
+  }
+  let migrations = migrate(&mut value);
+  if !migrations.is_empty() {
+    for migration in &migrations {
+      info!("Migrating config from version {}: {}", version, migration);
+    }
+    write_migrated(&config, &content, version, &value);
+  }
+
+  let arguments: ValheimArguments = match serde_json::from_value(Value::Object(value)) {
+    Ok(arguments) => arguments,
+    Err(e) => {
+      error!(
+        "Failed to read {}: {}. Run `odin configure` to recreate it.",
+        config.path(),
+        e
+      );
+      exit(1)
+    }
+  };
+  for field in arguments.unknown.keys() {
+    warn!("Ignoring unknown field `{}` in {}", field, config.path());
+  }
+  arguments
+}
+
+/// Writes changes to an existing config, e.g. a different world
+pub fn write_arguments(config: ManagedFile, args: &ValheimArguments) -> bool {
+  config.write(serde_json::to_string_pretty(args).unwrap())
 }
 
 /// Writes the config file
@@ -140,4 +236,33 @@ mod tests {
     );
     read_config(config_file());
   }
+
+  #[test]
+  fn test_read_config_migrates_with_backup() {
+    let directory = tempfile::tempdir().unwrap();
+    let path = directory.path().join("config.json");
+    let legacy = r#"{"port": 2456, "name": "Legacy", "world": "Dedicated", "public": false, "password": "P@ssw0rd!", "command": "./valheim_server.x86_64", "motd": "hello"}"#;
+    fs::write(&path, legacy).unwrap();
+    let file = || ManagedFile {
+      name: path.to_str().unwrap().to_string(),
+    };
+
+    let config = read_config(file());
+    assert_eq!(config.port, "2456");
+    assert_eq!(config.public, "0");
+    assert_eq!(config.version, CONFIG_VERSION);
+    assert_eq!(config.unknown.keys().collect::<Vec<_>>(), vec!["motd"]);
+
+    let backup = directory.path().join("config.json.v0.bak");
+    assert_eq!(fs::read_to_string(backup).unwrap(), legacy);
+    let migrated: Value = serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+    assert_eq!(migrated["version"], CONFIG_VERSION);
+    // Unknown fields survive the migration, they might belong to a newer odin.
+    assert_eq!(migrated["motd"], "hello");
+
+    // Reading it again doesn't migrate or back up anything.
+    fs::remove_file(directory.path().join("config.json.v0.bak")).unwrap();
+    read_config(file());
+    assert!(!directory.path().join("config.json.v0.bak").exists());
+  }
 }
@@ -4,12 +4,13 @@ use crate::{
   utils::{environment::fetch_var, path_exists},
 };
 
-use log::debug;
+use log::{debug, error};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 
 const ODIN_DISCORD_FILE_VAR: &str = "ODIN_DISCORD_FILE";
 
+#[allow(dead_code)]
 #[derive(Deserialize, Serialize)]
 pub struct DiscordConfigEvents {
   broadcast: DiscordWebHookBody,
@@ -55,11 +56,17 @@ pub fn read_discord(discord: &dyn FileManager) -> DiscordConfig {
   }
 
   let config =
-    serde_json::from_str::<HashMap<String, HashMap<String, DiscordWebHookBody>>>(content.as_str())
-      .unwrap()
-      .get("events")
-      .unwrap_or(&default.events)
-      .clone();
+    match serde_json::from_str::<HashMap<String, HashMap<String, DiscordWebHookBody>>>(&content) {
+      Ok(mut config) => config.remove("events").unwrap_or_default(),
+      Err(e) => {
+        error!(
+          "{} is not valid, using the default templates: {}",
+          discord.path(),
+          e
+        );
+        return default;
+      }
+    };
   let mut events: HashMap<String, DiscordWebHookBody> = HashMap::new();
 
   for (key, value) in default.events {
@@ -178,7 +185,7 @@ mod tests {
     );
     assert_eq!(
       config.events.get("broadcast").unwrap().embeds[0].color,
-      12345
+      Some(12345)
     );
   }
 
@@ -254,11 +261,16 @@ mod tests {
       .expect_read()
       .return_const(String::from(malformed_json));
 
-    let result = std::panic::catch_unwind(|| read_discord(&mock_file));
+    mock_file
+      .expect_path()
+      .return_const(String::from("discord.json"));
 
-    assert!(
-      result.is_err(),
-      "Expected a panic when the JSON is malformed"
+    let config = read_discord(&mock_file);
+
+    assert_eq!(
+      config.events.get("broadcast").unwrap().content,
+      DiscordWebHookBody::default().content,
+      "Expected the default templates when the JSON is malformed"
     );
   }
 
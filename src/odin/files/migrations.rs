@@ -0,0 +1,101 @@
+use serde_json::{Map, Value};
+
+/// The schema version of config.json written by this build of odin.
+pub const CONFIG_VERSION: u64 = 1;
+
+struct Migration {
+  /// The version the config has after this migration ran.
+  to: u64,
+  description: &'static str,
+  apply: fn(&mut Map<String, Value>),
+}
+
+/// Every step from an older config to the next version, in order. A change to
+/// `ValheimArguments` which breaks existing files bumps `CONFIG_VERSION` and adds a step here.
+const MIGRATIONS: &[Migration] = &[Migration {
+  to: 1,
+  description: "store port, public and save_interval the way odin configure writes them",
+  apply: normalize_scalars,
+}];
+
+/// Configs from before versioning were often written by hand with numbers and booleans
+/// where odin expects strings.
+fn normalize_scalars(config: &mut Map<String, Value>) {
+  for key in ["port", "public"] {
+    let value = match config.get(key) {
+      Some(Value::Number(number)) => number.to_string(),
+      Some(Value::Bool(flag)) => String::from(if *flag { "1" } else { "0" }),
+      _ => continue,
+    };
+    config.insert(key.to_string(), Value::String(value));
+  }
+  if let Some(Value::String(interval)) = config.get("save_interval") {
+    let interval = interval
+      .trim()
+      .parse::<u16>()
+      .map_or(Value::Null, Value::from);
+    config.insert(String::from("save_interval"), interval);
+  }
+}
+
+/// The version a config was written with, configs from before versioning are version 0.
+pub fn config_version(config: &Map<String, Value>) -> u64 {
+  config.get("version").and_then(Value::as_u64).unwrap_or(0)
+}
+
+/// Brings a config up to `CONFIG_VERSION`, returns what was done.
+pub fn migrate(config: &mut Map<String, Value>) -> Vec<&'static str> {
+  let version = config_version(config);
+  let applied = MIGRATIONS
+    .iter()
+    .filter(|migration| migration.to > version)
+    .map(|migration| {
+      (migration.apply)(config);
+      migration.description
+    })
+    .collect();
+  if version < CONFIG_VERSION {
+    config.insert(String::from("version"), Value::from(CONFIG_VERSION));
+  }
+  applied
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use serde_json::json;
+
+  fn object(value: Value) -> Map<String, Value> {
+    value.as_object().unwrap().clone()
+  }
+
+  #[test]
+  fn test_migrate_unversioned_config() {
+    let mut config = object(json!({
+      "port": 2456,
+      "public": true,
+      "save_interval": "600",
+      "name": "My Server",
+    }));
+    assert_eq!(config_version(&config), 0);
+    assert_eq!(migrate(&mut config).len(), 1);
+    assert_eq!(
+      Value::Object(config),
+      json!({
+        "port": "2456",
+        "public": "1",
+        "save_interval": 600,
+        "name": "My Server",
+        "version": CONFIG_VERSION,
+      })
+    );
+  }
+
+  #[test]
+  fn test_current_config_is_untouched() {
+    let original = object(json!({"port": "2456", "version": CONFIG_VERSION}));
+    let mut config = original.clone();
+    assert!(migrate(&mut config).is_empty());
+    assert_eq!(config, original);
+  }
+}
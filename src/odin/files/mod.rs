@@ -1,5 +1,8 @@
 pub mod config;
 pub mod discord;
+pub mod migrations;
+pub mod settings;
+pub mod templates;
 
 // use crate::executable::create_execution;
 use crate::utils::get_working_dir;
@@ -58,7 +61,7 @@ impl FileManager for ManagedFile {
   fn path(&self) -> String {
     let supplied_path = Path::new(self.name.as_str());
     debug!("Managed File: Path - {}", self.name.as_str());
-    if supplied_path.parent().unwrap().exists() {
+    if supplied_path.is_absolute() || supplied_path.parent().unwrap().exists() {
       supplied_path.to_str().unwrap().to_string()
     } else {
       format!("{}/{}", get_working_dir(), self.name)
@@ -0,0 +1,927 @@
+use std::collections::BTreeMap;
+use std::env;
+use std::fmt::{Display, Formatter};
+use std::fs;
+use std::net::SocketAddrV4;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use log::warn;
+use serde::Serialize;
+use serde_yaml::Value;
+
+use crate::bot::{DISCORD_ADMIN_ROLES, DISCORD_BOT_TOKEN, DISCORD_GUILD_ID};
+use crate::commands::configure::{Configuration, Modifiers};
+use crate::constants;
+use crate::notifications::sinks::{parse_sinks, Sink};
+use crate::notifications::{NOTIFICATION_SINKS, WEBHOOK_URL};
+use crate::server::world_modifiers::{
+  check_modifiers, check_preset, check_set_keys, WorldSettingError, PASSTHROUGH_HINT,
+};
+use crate::utils::get_working_dir;
+use crate::utils::instances::{current_instance, instance_directory};
+use crate::utils::is_valid_url;
+
+const SETTINGS_FILE_VAR: &str = "ODIN_SETTINGS_FILE";
+const SETTINGS_FILE: &str = "odin.yaml";
+const REDACTED: &str = "********";
+
+/// Where a setting got its value from, later layers win.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Source {
+  Default,
+  File,
+  Env,
+  Cli,
+}
+
+impl Display for Source {
+  fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    let source = match self {
+      Source::Default => "default",
+      Source::File => "file",
+      Source::Env => "env",
+      Source::Cli => "cli",
+    };
+    write!(f, "{}", source)
+  }
+}
+
+/// How a setting is written in the settings file besides a single value.
+#[derive(Clone, Copy)]
+enum Kind {
+  Value,
+  Modifiers,
+  List,
+  Sinks,
+}
+
+/// The value a setting has when nothing sets it.
+#[derive(Clone, Copy)]
+enum DefaultValue {
+  None,
+  Value(&'static str),
+  Computed(fn() -> String),
+}
+
+impl DefaultValue {
+  fn value(self) -> Option<String> {
+    match self {
+      DefaultValue::None => None,
+      DefaultValue::Value(value) => Some(value.to_string()),
+      DefaultValue::Computed(value) => Some(value()),
+    }
+  }
+}
+
+struct Field {
+  key: &'static str,
+  env: &'static str,
+  default: DefaultValue,
+  kind: Kind,
+  secret: bool,
+}
+
+const fn field(
+  key: &'static str,
+  env: &'static str,
+  default: Option<&'static str>,
+  kind: Kind,
+) -> Field {
+  Field {
+    key,
+    env,
+    default: match default {
+      Some(value) => DefaultValue::Value(value),
+      None => DefaultValue::None,
+    },
+    kind,
+    secret: false,
+  }
+}
+
+const fn secret(key: &'static str, env: &'static str, kind: Kind) -> Field {
+  Field {
+    key,
+    env,
+    default: DefaultValue::None,
+    kind,
+    secret: true,
+  }
+}
+
+fn default_executable() -> String {
+  format!("./{}", constants::VALHEIM_EXECUTABLE_NAME)
+}
+
+/// Every setting odin knows, with the env variable that overrides it.
+const FIELDS: &[Field] = &[
+  field(
+    "server.name",
+    "NAME",
+    Some("Valheim powered by Odin"),
+    Kind::Value,
+  ),
+  Field {
+    key: "server.executable",
+    env: "SERVER_EXECUTABLE_PATH",
+    default: DefaultValue::Computed(default_executable),
+    kind: Kind::Value,
+    secret: false,
+  },
+  field("server.port", "PORT", Some("2456"), Kind::Value),
+  field("server.world", "WORLD", Some("Dedicated"), Kind::Value),
+  // The defaults `odin configure` always had, PUBLIC never had one and stays required.
+  Field {
+    key: "server.password",
+    env: "PASSWORD",
+    default: DefaultValue::Value("P@ssw0rd!"),
+    kind: Kind::Value,
+    secret: true,
+  },
+  field("server.public", "PUBLIC", None, Kind::Value),
+  field("server.preset", "PRESET", None, Kind::Value),
+  field("server.modifiers", "MODIFIERS", None, Kind::Modifiers),
+  field("server.set_key", "SET_KEY", None, Kind::List),
+  field(
+    "server.modifier_passthrough",
+    "MODIFIER_PASSTHROUGH",
+    Some("0"),
+    Kind::Value,
+  ),
+  field("server.save_interval", "SAVE_INTERVAL", None, Kind::Value),
+  field(
+    "server.crossplay",
+    "ENABLE_CROSSPLAY",
+    Some("0"),
+    Kind::Value,
+  ),
+  field(
+    "server.extra_launch_args",
+    "SERVER_EXTRA_LAUNCH_ARGS",
+    None,
+    Kind::Value,
+  ),
+  field("server.type", "TYPE", Some("vanilla"), Kind::Value),
+  secret("mods.nexus_api_key", "NEXUS_API_KEY", Kind::Value),
+  secret("notifications.webhook_url", WEBHOOK_URL, Kind::Value),
+  secret("notifications.sinks", NOTIFICATION_SINKS, Kind::Sinks),
+  field(
+    "notifications.player_events",
+    "PLAYER_EVENT_NOTIFICATIONS",
+    Some("0"),
+    Kind::Value,
+  ),
+  secret("discord.bot_token", DISCORD_BOT_TOKEN, Kind::Value),
+  field("discord.guild_id", DISCORD_GUILD_ID, None, Kind::Value),
+  field("discord.admin_roles", DISCORD_ADMIN_ROLES, None, Kind::List),
+  field("http.port", "HTTP_PORT", None, Kind::Value),
+  field("http.address", "ADDRESS", None, Kind::Value),
+];
+
+fn find_field(key: &str) -> Option<&'static Field> {
+  FIELDS.iter().find(|field| field.key == key)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SettingError {
+  pub key: String,
+  pub message: String,
+}
+
+impl Display for SettingError {
+  fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    write!(f, "{}: {}", self.key, self.message)
+  }
+}
+
+fn error(key: &str, message: impl Into<String>) -> SettingError {
+  SettingError {
+    key: key.to_string(),
+    message: message.into(),
+  }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SettingValue {
+  pub value: String,
+  pub source: Source,
+}
+
+/// The settings file odin reads, `odin.yaml` in the working directory or the instance directory.
+pub fn settings_file() -> PathBuf {
+  if let Ok(path) = env::var(SETTINGS_FILE_VAR) {
+    return PathBuf::from(path);
+  }
+  match current_instance() {
+    Some(instance) => PathBuf::from(instance_directory(&instance)).join(SETTINGS_FILE),
+    None => PathBuf::from(get_working_dir()).join(SETTINGS_FILE),
+  }
+}
+
+fn scalar(value: &Value) -> Option<String> {
+  match value {
+    Value::String(value) => Some(value.clone()),
+    Value::Number(value) => Some(value.to_string()),
+    Value::Bool(value) => Some(value.to_string()),
+    _ => None,
+  }
+}
+
+/// Flattens the nested file into dotted keys, e.g. `server: {port: 2456}` into `server.port`.
+fn flatten(prefix: &str, value: &Value, values: &mut BTreeMap<String, Value>) {
+  match value {
+    Value::Mapping(mapping) if find_field(prefix).is_none() => {
+      for (key, value) in mapping {
+        let Some(key) = scalar(key) else { continue };
+        let key = if prefix.is_empty() {
+          key
+        } else {
+          format!("{}.{}", prefix, key)
+        };
+        flatten(&key, value, values);
+      }
+    }
+    Value::Null => {}
+    value => {
+      values.insert(prefix.to_string(), value.clone());
+    }
+  }
+}
+
+/// Turns a file value into the string form the env variables use. Modifiers can be written
+/// as a mapping (`combat: hard`) or a list (`- combat=hard`), sinks are a list kept as JSON.
+fn file_value(key: &str, value: &Value) -> Result<String, SettingError> {
+  if let Some(value) = scalar(value) {
+    return Ok(value);
+  }
+  match (find_field(key).map(|field| field.kind), value) {
+    (Some(Kind::Modifiers), Value::Mapping(mapping)) => Ok(
+      mapping
+        .iter()
+        .filter_map(|(name, value)| Some(format!("{}={}", scalar(name)?, scalar(value)?)))
+        .collect::<Vec<_>>()
+        .join(","),
+    ),
+    (Some(Kind::Modifiers | Kind::List), Value::Sequence(items)) => Ok(
+      items
+        .iter()
+        .filter_map(scalar)
+        .collect::<Vec<_>>()
+        .join(","),
+    ),
+    (Some(Kind::Sinks), Value::Sequence(_)) => {
+      serde_json::to_string(value).map_err(|e| error(key, e.to_string()))
+    }
+    _ => Err(error(key, "expected a single value")),
+  }
+}
+
+/// Reads the settings file into dotted keys, `None` if there is no file.
+fn read_file(path: &PathBuf) -> Result<Option<BTreeMap<String, Value>>, SettingError> {
+  let content = match fs::read_to_string(path) {
+    Ok(content) => content,
+    Err(_) => return Ok(None),
+  };
+  let document: Value = serde_yaml::from_str(&content)
+    .map_err(|e| error(&path.display().to_string(), e.to_string()))?;
+  let mut values = BTreeMap::new();
+  flatten("", &document, &mut values);
+  Ok(Some(values))
+}
+
+fn env_value(name: &str) -> Option<String> {
+  env::var(name).ok().filter(|value| !value.is_empty())
+}
+
+/// The settings of odin, layered as defaults < settings file < env variables < command line.
+#[derive(Debug)]
+pub struct Settings {
+  values: BTreeMap<&'static str, SettingValue>,
+  /// Problems with the file itself, reported together with the invalid values.
+  file_errors: Vec<SettingError>,
+}
+
+impl Settings {
+  /// Loads the settings, `cli` holds the values passed as flags, keyed like the file.
+  pub fn load(cli: &[(&'static str, Option<String>)]) -> Settings {
+    let (file, file_errors) = match read_file(&settings_file()) {
+      Ok(file) => (file.unwrap_or_default(), vec![]),
+      Err(e) => (BTreeMap::new(), vec![e]),
+    };
+    Settings::resolve(file, file_errors, env_value, cli)
+  }
+
+  fn resolve(
+    file: BTreeMap<String, Value>,
+    mut file_errors: Vec<SettingError>,
+    env: impl Fn(&str) -> Option<String>,
+    cli: &[(&'static str, Option<String>)],
+  ) -> Settings {
+    let mut values = BTreeMap::new();
+    for field in FIELDS {
+      let layers = [
+        (Source::Default, field.default.value()),
+        (
+          Source::File,
+          file
+            .get(field.key)
+            .and_then(|value| match file_value(field.key, value) {
+              Ok(value) => Some(value),
+              Err(e) => {
+                file_errors.push(e);
+                None
+              }
+            }),
+        ),
+        (Source::Env, env(field.env)),
+        (
+          Source::Cli,
+          cli
+            .iter()
+            .find(|(key, _)| *key == field.key)
+            .and_then(|(_, value)| value.clone()),
+        ),
+      ];
+      if let Some((source, Some(value))) =
+        layers.into_iter().rev().find(|(_, value)| value.is_some())
+      {
+        values.insert(field.key, SettingValue { value, source });
+      }
+    }
+    for key in file.keys().filter(|key| find_field(key).is_none()) {
+      file_errors.push(error(key, "unknown setting"));
+    }
+    Settings {
+      values,
+      file_errors,
+    }
+  }
+
+  pub fn get(&self, key: &str) -> Option<&str> {
+    self.values.get(key).map(|setting| setting.value.as_str())
+  }
+
+  /// Whether the file, an env variable or a flag sets `key`, rather than its default.
+  pub fn is_set(&self, key: &str) -> bool {
+    self
+      .values
+      .get(key)
+      .is_some_and(|setting| setting.source > Source::Default)
+  }
+
+  /// The value of `key` when it is set, see `is_set`.
+  pub fn set_value(&self, key: &str) -> Option<&str> {
+    self.is_set(key).then(|| self.get(key)).flatten()
+  }
+
+  /// Parses every setting into its type, collecting all problems instead of stopping at the first.
+  pub fn typed(&self) -> Result<OdinSettings, Vec<SettingError>> {
+    let (settings, mut errors, warnings) = self.read();
+    for warning in warnings {
+      warn!("{}", warning);
+    }
+    // Only required to configure, running odin takes it from config.json.
+    if self.get("server.public").is_none() {
+      errors.push(error("server.public", "is required, set it to 1 or 0"));
+      sort_errors(&mut errors);
+    }
+    match errors.is_empty() {
+      true => Ok(settings),
+      false => Err(errors),
+    }
+  }
+
+  /// The typed settings, with the invalid ones left at their default.
+  fn read(&self) -> (OdinSettings, Vec<SettingError>, Vec<String>) {
+    let mut reader = Reader {
+      settings: self,
+      errors: self.file_errors.clone(),
+      warnings: vec![],
+    };
+    let passthrough = reader
+      .parse("server.modifier_passthrough", parse_bool)
+      .unwrap_or_default();
+    let server = ServerSettings {
+      name: reader.text("server.name").unwrap_or_default(),
+      executable: reader.text("server.executable").unwrap_or_default(),
+      port: reader.parse("server.port", parse_port).unwrap_or_default(),
+      world: reader.text("server.world").unwrap_or_default(),
+      password: reader
+        .parse("server.password", parse_password)
+        .unwrap_or_default(),
+      public: reader
+        .parse("server.public", parse_bool)
+        .unwrap_or_default(),
+      preset: reader.world_setting("server.preset", passthrough, check_preset, |preset| {
+        preset.trim().to_string()
+      }),
+      modifiers: reader
+        .world_setting(
+          "server.modifiers",
+          passthrough,
+          check_modifiers,
+          |modifiers| {
+            list(modifiers)
+              .filter_map(|modifier| Modifiers::from_str(&modifier).ok())
+              .collect()
+          },
+        )
+        .unwrap_or_default(),
+      set_key: reader
+        .world_setting("server.set_key", passthrough, check_set_keys, |keys| {
+          list(keys).collect()
+        })
+        .unwrap_or_default(),
+      save_interval: reader.parse("server.save_interval", parse_seconds),
+      crossplay: reader
+        .parse("server.crossplay", parse_bool)
+        .unwrap_or_default(),
+      extra_launch_args: reader.text("server.extra_launch_args"),
+      server_type: reader
+        .parse("server.type", ServerType::from_str)
+        .unwrap_or_default(),
+    };
+    let mods = ModSettings {
+      nexus_api_key: reader.text("mods.nexus_api_key"),
+    };
+    let notifications = NotificationSettings {
+      webhook_url: reader.parse("notifications.webhook_url", parse_url),
+      sinks: reader
+        .parse("notifications.sinks", parse_sinks)
+        .unwrap_or_default(),
+      player_events: reader
+        .parse("notifications.player_events", parse_bool)
+        .unwrap_or(false),
+    };
+    let discord = DiscordSettings {
+      bot_token: reader.text("discord.bot_token"),
+      guild_id: reader.parse("discord.guild_id", |id| {
+        id.trim()
+          .parse()
+          .map_err(|_| format!("{} is not a guild id", id))
+      }),
+      admin_roles: reader
+        .parse("discord.admin_roles", |roles| Ok(list(roles).collect()))
+        .unwrap_or_default(),
+    };
+    let http = HttpSettings {
+      port: reader.parse("http.port", parse_port),
+      address: reader.parse("http.address", |address| {
+        SocketAddrV4::from_str(address)
+          .map_err(|_| format!("{} is not an address like 127.0.0.1:2457", address))
+      }),
+    };
+
+    if server.password.is_empty() && (server.public || server.server_type == ServerType::Vanilla) {
+      reader.errors.push(error(
+        "server.password",
+        "is required for public and vanilla servers",
+      ));
+    }
+    sort_errors(&mut reader.errors);
+    let settings = OdinSettings {
+      server,
+      mods,
+      notifications,
+      discord,
+      http,
+    };
+    (settings, reader.errors, reader.warnings)
+  }
+
+  /// The settings odin runs with, loaded from the file and env variables. Invalid values are
+  /// warned about and left at their default.
+  pub fn current() -> OdinSettings {
+    Settings::load(&[]).lenient()
+  }
+
+  /// The typed settings, invalid values are warned about and left at their default.
+  pub fn lenient(&self) -> OdinSettings {
+    let (settings, errors, _) = self.read();
+    for e in errors {
+      warn!("Ignoring {}", e);
+    }
+    settings
+  }
+
+  /// Checks every setting and returns all problems instead of stopping at the first.
+  pub fn validate(&self) -> Vec<SettingError> {
+    self.typed().err().unwrap_or_default()
+  }
+
+  /// The settings as shown by `odin config show`, secrets are redacted.
+  pub fn redacted(&self, effective: bool) -> BTreeMap<&'static str, SettingValue> {
+    self
+      .values
+      .iter()
+      .filter(|(_, setting)| effective || setting.source == Source::File)
+      .map(|(key, setting)| {
+        let secret = find_field(key).is_some_and(|field| field.secret);
+        let value = if secret && !setting.value.is_empty() {
+          String::from(REDACTED)
+        } else {
+          setting.value.clone()
+        };
+        (
+          *key,
+          SettingValue {
+            value,
+            source: setting.source,
+          },
+        )
+      })
+      .collect()
+  }
+
+  /// Builds the configuration `odin configure` writes, or every reason it can't.
+  pub fn to_configuration(&self) -> Result<Configuration, Vec<SettingError>> {
+    let settings = self.typed()?;
+    let ServerSettings {
+      name,
+      executable,
+      port,
+      world,
+      password,
+      public,
+      preset,
+      modifiers,
+      set_key,
+      save_interval,
+      ..
+    } = settings.server;
+    Ok(Configuration::new(
+      name,
+      executable,
+      port,
+      world,
+      password,
+      public,
+      preset,
+      (!modifiers.is_empty()).then_some(modifiers),
+      (!set_key.is_empty()).then(|| set_key.join(",")),
+      save_interval,
+      settings.mods.nexus_api_key,
+    ))
+  }
+}
+
+/// Problems with the file come first, then the settings in the order they are listed.
+fn sort_errors(errors: &mut [SettingError]) {
+  errors.sort_by_key(|e| FIELDS.iter().position(|field| field.key == e.key));
+}
+
+/// Reads the settings one by one, remembering what is wrong with them.
+struct Reader<'a> {
+  settings: &'a Settings,
+  errors: Vec<SettingError>,
+  warnings: Vec<String>,
+}
+
+impl Reader<'_> {
+  /// The setting parsed by `parse`, its default when it isn't valid.
+  fn parse<T>(&mut self, key: &str, parse: impl Fn(&str) -> Result<T, String>) -> Option<T> {
+    let value = self.settings.get(key)?;
+    match parse(value) {
+      Ok(value) => Some(value),
+      Err(message) => {
+        self.errors.push(error(key, message));
+        let default = find_field(key).and_then(|field| field.default.value())?;
+        parse(&default).ok()
+      }
+    }
+  }
+
+  fn text(&mut self, key: &str) -> Option<String> {
+    self.parse(key, |value| Ok(value.to_string()))
+  }
+
+  /// Unknown presets, modifiers and keys are only warned about with `server.modifier_passthrough`,
+  /// so values added by a new game version can be used before odin knows them.
+  fn world_setting<T>(
+    &mut self,
+    key: &str,
+    passthrough: bool,
+    check: fn(&str) -> Result<(), WorldSettingError>,
+    parse: impl FnOnce(&str) -> T,
+  ) -> Option<T> {
+    let value = self.settings.get(key)?;
+    let message = match check(value) {
+      Ok(()) => return Some(parse(value)),
+      Err(WorldSettingError::Unknown(message)) if passthrough => {
+        self.warnings.push(format!(
+          "{}: {}, passing it through to the server",
+          key, message
+        ));
+        return Some(parse(value));
+      }
+      Err(WorldSettingError::Unknown(message)) => format!("{}; {}", message, PASSTHROUGH_HINT),
+      Err(WorldSettingError::Malformed(message)) => message,
+    };
+    self.errors.push(error(key, message));
+    None
+  }
+}
+
+/// The items of a comma separated setting.
+fn list(value: &str) -> impl Iterator<Item = String> + '_ {
+  value
+    .split(',')
+    .map(str::trim)
+    .filter(|item| !item.is_empty())
+    .map(String::from)
+}
+
+fn parse_port(value: &str) -> Result<u16, String> {
+  match value.parse::<u16>() {
+    Ok(port) if port > 0 => Ok(port),
+    _ => Err(format!("{} is not a valid port", value)),
+  }
+}
+
+fn parse_seconds(value: &str) -> Result<u16, String> {
+  value
+    .parse()
+    .map_err(|_| format!("{} is not a number of seconds up to 65535", value))
+}
+
+// parse_truthy reads anything unknown as false, so check the spelling here.
+fn parse_bool(value: &str) -> Result<bool, String> {
+  match value.to_lowercase().as_str() {
+    "1" | "true" => Ok(true),
+    "0" | "false" => Ok(false),
+    _ => Err(format!("{} is not a boolean, use 1/0 or true/false", value)),
+  }
+}
+
+fn parse_password(value: &str) -> Result<String, String> {
+  if !value.is_empty() && value.len() < 5 {
+    return Err(String::from("must be 5 characters or longer"));
+  }
+  Ok(value.to_string())
+}
+
+fn parse_url(value: &str) -> Result<String, String> {
+  // Quoted values were common when the url was only read from the env.
+  let value = value.trim_matches('"');
+  if !is_valid_url(value) {
+    return Err(String::from("is not a valid url"));
+  }
+  Ok(value.to_string())
+}
+
+/// Which server odin runs, mods are only loaded by BepInEx.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ServerType {
+  #[default]
+  Vanilla,
+  BepInEx,
+}
+
+impl FromStr for ServerType {
+  type Err = String;
+
+  fn from_str(value: &str) -> Result<Self, Self::Err> {
+    match value.to_lowercase().as_str() {
+      "vanilla" => Ok(ServerType::Vanilla),
+      "bepinex" => Ok(ServerType::BepInEx),
+      _ => Err(format!("{} is not one of vanilla, bepinex", value)),
+    }
+  }
+}
+
+/// The settings of odin with their real types, see `Settings::typed`.
+#[derive(Debug, Clone)]
+pub struct OdinSettings {
+  pub server: ServerSettings,
+  pub mods: ModSettings,
+  pub notifications: NotificationSettings,
+  pub discord: DiscordSettings,
+  pub http: HttpSettings,
+}
+
+#[derive(Debug, Clone)]
+pub struct ServerSettings {
+  pub name: String,
+  pub executable: String,
+  pub port: u16,
+  pub world: String,
+  pub password: String,
+  pub public: bool,
+  pub preset: Option<String>,
+  pub modifiers: Vec<Modifiers>,
+  pub set_key: Vec<String>,
+  pub save_interval: Option<u16>,
+  pub crossplay: bool,
+  pub extra_launch_args: Option<String>,
+  pub server_type: ServerType,
+}
+
+#[derive(Debug, Clone)]
+pub struct ModSettings {
+  pub nexus_api_key: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct NotificationSettings {
+  pub webhook_url: Option<String>,
+  pub sinks: Vec<Sink>,
+  pub player_events: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct DiscordSettings {
+  pub bot_token: Option<String>,
+  pub guild_id: Option<u64>,
+  pub admin_roles: Vec<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct HttpSettings {
+  /// Only huginn serves http, odin itself doesn't read it.
+  #[allow(dead_code)]
+  pub port: Option<u16>,
+  pub address: Option<SocketAddrV4>,
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn file(content: &str) -> BTreeMap<String, Value> {
+    let mut values = BTreeMap::new();
+    flatten("", &serde_yaml::from_str(content).unwrap(), &mut values);
+    values
+  }
+
+  fn resolve(
+    content: &str,
+    env: &[(&str, &str)],
+    cli: &[(&'static str, Option<String>)],
+  ) -> Settings {
+    let env: Vec<(String, String)> = env
+      .iter()
+      .map(|(name, value)| (name.to_string(), value.to_string()))
+      .collect();
+    Settings::resolve(
+      file(content),
+      vec![],
+      |name| {
+        env
+          .iter()
+          .find(|(key, _)| key == name)
+          .map(|(_, value)| value.clone())
+      },
+      cli,
+    )
+  }
+
+  #[test]
+  fn test_layer_precedence() {
+    let settings = resolve(
+      "server:\n  name: From File\n  port: 2466\n  world: FileWorld\n  public: 1\n",
+      &[("PORT", "2476"), ("WORLD", "EnvWorld")],
+      &[("server.world", Some(String::from("CliWorld")))],
+    );
+    assert_eq!(settings.get("server.name"), Some("From File"));
+    assert_eq!(settings.get("server.port"), Some("2476"));
+    assert_eq!(settings.get("server.world"), Some("CliWorld"));
+    assert_eq!(
+      settings.get("server.executable"),
+      Some("./valheim_server.x86_64")
+    );
+    let typed = settings.typed().unwrap();
+    assert_eq!(typed.server.port, 2476);
+    assert!(typed.server.public);
+    assert_eq!(typed.server.server_type, ServerType::Vanilla);
+    assert_eq!(typed.http.address, None);
+
+    let shown = settings.redacted(true);
+    assert_eq!(shown["server.name"].source, Source::File);
+    assert_eq!(shown["server.port"].source, Source::Env);
+    assert_eq!(shown["server.world"].source, Source::Cli);
+    assert_eq!(shown["server.password"].source, Source::Default);
+    assert_eq!(shown["server.password"].value, REDACTED);
+    assert_eq!(settings.redacted(false).len(), 2);
+    assert!(settings.is_set("server.name"));
+    assert!(!settings.is_set("server.password"));
+  }
+
+  #[test]
+  fn test_modifiers_from_file() {
+    let settings = resolve(
+      "server:\n  public: 0\n  modifiers:\n    combat: hard\n    raids: none\n",
+      &[],
+      &[],
+    );
+    assert_eq!(
+      settings.get("server.modifiers"),
+      Some("combat=hard,raids=none")
+    );
+    assert!(settings.validate().is_empty());
+
+    let settings = resolve("server:\n  modifiers: [combat=hard]\n", &[], &[]);
+    assert_eq!(settings.get("server.modifiers"), Some("combat=hard"));
+  }
+
+  #[test]
+  fn test_sinks_from_file() {
+    let settings = resolve(
+      "server:\n  password: secret\n  public: 1\nnotifications:\n  sinks:\n    - name: ops\n      url: https://example.com/ops\n      events: [\"*:failed\"]\n",
+      &[],
+      &[],
+    );
+    let sinks = parse_sinks(settings.get("notifications.sinks").unwrap()).unwrap();
+    assert_eq!(sinks[0].label(), "ops");
+    assert_eq!(sinks[0].events, vec!["*:failed"]);
+    assert!(settings.validate().is_empty());
+    assert_eq!(
+      settings.redacted(true)["notifications.sinks"].value,
+      REDACTED
+    );
+
+    let settings = resolve(
+      "server:\n  password: secret\n  public: 1\n",
+      &[(
+        "NOTIFICATION_SINKS",
+        r#"[{"url": "https://example.com", "events": ["crash"]}]"#,
+      )],
+      &[],
+    );
+    assert_eq!(settings.validate()[0].key, "notifications.sinks");
+  }
+
+  #[test]
+  fn test_validate_reports_every_error() {
+    let settings = resolve(
+      "server:\n  port: 99999\n  public: maybe\n  typo: 1\n  type: forge\nhttp:\n  address: localhost\n",
+      &[("PASSWORD", "abc"), ("MODIFIERS", "combat")],
+      &[],
+    );
+    let keys: Vec<String> = settings
+      .validate()
+      .into_iter()
+      .map(|error| error.key)
+      .collect();
+    assert_eq!(
+      keys,
+      vec![
+        "server.typo",
+        "server.port",
+        "server.password",
+        "server.public",
+        "server.modifiers",
+        "server.type",
+        "http.address",
+      ]
+    );
+  }
+
+  #[test]
+  fn test_modifier_passthrough() {
+    let env = [
+      ("PUBLIC", "1"),
+      ("PRESET", "nightmare"),
+      ("SET_KEY", "nomap,godmode"),
+    ];
+    let settings = resolve("", &env, &[]);
+    let keys: Vec<String> = settings
+      .validate()
+      .into_iter()
+      .map(|error| error.key)
+      .collect();
+    assert_eq!(keys, vec!["server.preset", "server.set_key"]);
+
+    let settings = resolve("server:\n  modifier_passthrough: 1\n", &env, &[]);
+    assert!(settings.validate().is_empty());
+    let configuration = settings.to_configuration().unwrap();
+    assert_eq!(configuration.preset.as_deref(), Some("nightmare"));
+    assert_eq!(configuration.set_key.as_deref(), Some("nomap,godmode"));
+
+    // Passthrough doesn't make malformed modifiers acceptable.
+    let settings = resolve(
+      "server:\n  modifier_passthrough: 1\n  public: 1\n",
+      &[("MODIFIERS", "combat")],
+      &[],
+    );
+    assert_eq!(settings.validate()[0].key, "server.modifiers");
+  }
+
+  #[test]
+  fn test_missing_password() {
+    let settings = resolve(
+      "server:\n  password: ''\n  public: 0\n  type: BepInEx\n",
+      &[],
+      &[],
+    );
+    assert!(settings.validate().is_empty());
+    let settings = resolve("server:\n  password: ''\n", &[], &[]);
+    let keys: Vec<String> = settings
+      .validate()
+      .into_iter()
+      .map(|error| error.key)
+      .collect();
+    assert_eq!(keys, vec!["server.password", "server.public"]);
+  }
+}
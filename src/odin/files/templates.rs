@@ -0,0 +1,77 @@
+use crate::{
+  files::{FileManager, ManagedFile},
+  utils::{environment::fetch_var, path_exists},
+};
+
+use log::debug;
+use serde_json::{json, Value};
+use std::collections::{BTreeMap, HashMap};
+
+/// The template file of a notification provider, `<provider>.json` unless `ODIN_<PROVIDER>_FILE`
+/// points somewhere else.
+pub fn template_file(provider: &str) -> ManagedFile {
+  let var = format!("ODIN_{}_FILE", provider.to_uppercase());
+  let name = fetch_var(&var, &format!("{}.json", provider));
+  debug!("Template file of {} set to: {}", provider, name);
+  ManagedFile { name }
+}
+
+/// Reads the event templates of a file shaped like `discord.json`, none when it is missing.
+pub fn read_templates(file: &dyn FileManager) -> Result<HashMap<String, Value>, String> {
+  let content = file.read();
+  if content.is_empty() {
+    return Ok(HashMap::new());
+  }
+  let mut document = serde_json::from_str::<HashMap<String, HashMap<String, Value>>>(&content)
+    .map_err(|e| format!("{} is not a valid template file: {}", file.path(), e))?;
+  Ok(document.remove("events").unwrap_or_default())
+}
+
+pub fn write_templates(file: &dyn FileManager, templates: BTreeMap<&str, Value>) -> bool {
+  if path_exists(&file.path()) {
+    debug!(
+      "Template file {} already exists, doing nothing.",
+      file.path()
+    );
+    return true;
+  }
+
+  let content_to_write = serde_json::to_string_pretty(&json!({ "events": templates })).unwrap();
+  debug!("Writing template file: \n{}", content_to_write);
+
+  file.write(content_to_write)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use std::fs;
+
+  #[test]
+  fn test_read_and_write_templates() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = ManagedFile {
+      name: dir.path().join("slack.json").to_string_lossy().to_string(),
+    };
+    assert!(read_templates(&file).unwrap().is_empty());
+
+    let templates = BTreeMap::from([("start", json!({ "text": "{{title}}" }))]);
+    assert!(write_templates(&file, templates));
+    assert_eq!(
+      read_templates(&file).unwrap()["start"],
+      json!({ "text": "{{title}}" })
+    );
+
+    // An existing file is never overwritten.
+    assert!(write_templates(&file, BTreeMap::new()));
+    assert_eq!(read_templates(&file).unwrap().len(), 1);
+
+    fs::write(file.path(), "{ \"events\": ").unwrap();
+    assert!(read_templates(&file).is_err());
+  }
+
+  #[test]
+  fn test_template_file() {
+    assert_eq!(template_file("ntfy").name, "ntfy.json");
+  }
+}
@@ -1,3 +1,4 @@
+pub mod bot;
 pub mod commands;
 pub mod constants;
 pub mod errors;
@@ -11,5 +12,6 @@ pub mod server;
 pub mod steamcmd;
 pub mod traits;
 pub mod utils;
+pub mod worlds;
 
 mod log_filters;
@@ -2,4 +2,5 @@ mod player;
 mod probes;
 
 pub use player::handle_player_events;
+pub(crate) use player::PlayerList;
 pub use probes::handle_launch_probes;
@@ -7,12 +7,18 @@ use regex::Regex;
 use serde::{Deserialize, Serialize};
 use std::fmt::Display;
 
+/// How long a connection waits for its character before odin forgets it, in seconds.
+const CONNECTING_TIMEOUT: i64 = 120;
+
 #[derive(Serialize, Deserialize, Debug)]
 struct Player {
   id: u64,
   zdo_index: u16,
   name: String,
   last_seen: i64,
+  /// The Steam or PlayFab id the access lists hold, the ZDO id changes with every session.
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  platform_id: Option<String>,
 }
 
 impl Clone for Player {
@@ -22,10 +28,18 @@ impl Clone for Player {
       zdo_index: self.zdo_index,
       name: String::from(&self.name),
       last_seen: self.last_seen,
+      platform_id: self.platform_id.clone(),
     }
   }
 }
 
+/// A player who connected but whose character hasn't shown up yet.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct Connecting {
+  platform_id: String,
+  at: i64,
+}
+
 impl Default for Player {
   fn default() -> Self {
     let now = Utc::now();
@@ -36,6 +50,7 @@ impl Default for Player {
       zdo_index: 0,
       name: "Unknown".to_string(),
       last_seen: epoch,
+      platform_id: None,
     }
   }
 }
@@ -43,6 +58,8 @@ impl Default for Player {
 #[derive(Serialize, Deserialize, Debug)]
 pub struct PlayerList {
   players: Vec<Player>,
+  #[serde(default, skip_serializing_if = "Vec::is_empty")]
+  connecting: Vec<Connecting>,
 }
 
 impl PlayerList {
@@ -50,6 +67,80 @@ impl PlayerList {
     self.write(self.to_string()) // Ensure this writes correctly
   }
 
+  fn with_name<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a Player> {
+    self
+      .players
+      .iter()
+      .filter(move |player| player.name.eq_ignore_ascii_case(name.trim()))
+  }
+
+  /// Whether a player with this name was seen, names are matched without regard to case.
+  pub fn has_seen(&self, name: &str) -> bool {
+    self.with_name(name).next().is_some()
+  }
+
+  /// The platform ids every player seen with this name has used, the ones odin saw connect.
+  pub fn ids_by_name(&self, name: &str) -> Vec<String> {
+    let mut ids: Vec<String> = self
+      .with_name(name)
+      .filter_map(|player| player.platform_id.clone())
+      .collect();
+    ids.sort();
+    ids.dedup();
+    ids
+  }
+
+  /// The name a player with this platform id was last seen with.
+  pub fn name_of(&self, platform_id: &str) -> Option<&str> {
+    self
+      .players
+      .iter()
+      .filter(|player| {
+        player
+          .platform_id
+          .as_deref()
+          .is_some_and(|id| same_platform_id(id, platform_id))
+      })
+      .max_by_key(|player| player.last_seen)
+      .map(|player| player.name.as_str())
+  }
+
+  /// Remembers the platform id of a connecting player until their character shows up.
+  fn connect(&mut self, platform_id: String, now: i64) {
+    self
+      .connecting
+      .retain(|connecting| now - connecting.at < CONNECTING_TIMEOUT);
+    self
+      .connecting
+      .retain(|connecting| connecting.platform_id != platform_id);
+    self.connecting.push(Connecting {
+      platform_id,
+      at: now,
+    });
+  }
+
+  /// The platform id of the player whose character just showed up. The log doesn't tie the two
+  /// together, so this is only known when no one else is connecting at the same time.
+  fn take_connecting(&mut self, name: &str, now: i64) -> Option<String> {
+    self
+      .connecting
+      .retain(|connecting| now - connecting.at < CONNECTING_TIMEOUT);
+    match self.connecting.len() {
+      0 => None,
+      1 => self
+        .connecting
+        .pop()
+        .map(|connecting| connecting.platform_id),
+      _ => {
+        debug!(
+          "Several players are connecting, the platform id of {} is unknown",
+          name
+        );
+        None
+      }
+    }
+  }
+
   fn get_player_by_id(id: u64) -> Option<Player> {
     let list = Self::default();
     list
@@ -61,6 +152,7 @@ impl PlayerList {
         zdo_index: player.zdo_index,
         name: player.name.clone(),
         last_seen: player.last_seen,
+        platform_id: player.platform_id.clone(),
       })
   }
 
@@ -72,6 +164,9 @@ impl PlayerList {
     {
       // Update the `last_seen` timestamp if both id and zdo_index match
       existing_player.last_seen = player.last_seen;
+      if player.platform_id.is_some() {
+        existing_player.platform_id = player.platform_id;
+      }
     } else {
       // Otherwise, add the new player
       self.players.push(player);
@@ -87,16 +182,24 @@ impl PlayerList {
       .send_notification(Some(format!("Player {name} has joined the adventure!")));
 
     // Update or add the player to the list
+    let platform_id = list.take_connecting(&name, last_seen);
     list.update_or_push(Player {
       id,
       zdo_index,
       name,
       last_seen,
+      platform_id,
     });
 
     list.save(); // Save changes to the list
   }
 
+  pub fn connected_event(platform_id: String) {
+    let mut list = PlayerList::default();
+    list.connect(platform_id, Utc::now().timestamp());
+    list.save();
+  }
+
   pub fn left_event(id: u64, zdo_index: u16) {
     let list = PlayerList::default(); // Fetch or initialize player list
                                       // Find the player by both ID and ZDO index
@@ -120,7 +223,10 @@ impl PlayerList {
 impl Default for PlayerList {
   #[cfg(not(test))]
   fn default() -> Self {
-    let list = PlayerList { players: vec![] };
+    let list = PlayerList {
+      players: vec![],
+      connecting: vec![],
+    };
     let read_list = list.read();
 
     if read_list.is_empty() {
@@ -132,7 +238,10 @@ impl Default for PlayerList {
 
   #[cfg(test)]
   fn default() -> Self {
-    PlayerList { players: vec![] }
+    PlayerList {
+      players: vec![],
+      connecting: vec![],
+    }
   }
 }
 
@@ -165,6 +274,12 @@ impl Display for PlayerList {
   }
 }
 
+/// Crossplay lists take ids like `Steam_7656...`, Steam only servers the bare number.
+fn same_platform_id(a: &str, b: &str) -> bool {
+  let bare = |id: &str| id.strip_prefix("Steam_").unwrap_or(id).to_string();
+  a == b || bare(a) == bare(b)
+}
+
 /// Handles player-related events such as joining or leaving.
 /// It uses regex to extract information from log lines and triggers appropriate events.
 ///
@@ -176,11 +291,27 @@ pub fn handle_player_events(line: &str) {
     Regex::new(r"\d{2}/\d{2}/\d{4} \d{2}:\d{2}:\d{2}: Got character ZDOID from (.*) : (\d+:\d+)")
       .expect("Failed to compile joined_regex");
 
+  // Regex to capture the Steam id of a connecting player, or the platform id behind PlayFab
+  let connected_regex = Regex::new(
+    r"\d{2}/\d{2}/\d{4} \d{2}:\d{2}:\d{2}: (?:Got connection SteamID (\d+)|PlayFab socket with remote ID .* received local Platform ID ([A-Za-z]+_\w+))"
+  ).expect("Failed to compile connected_regex");
+
   // Regex to capture player leaving event with player ID and ZDO index
   let left_regex = Regex::new(
     r"\d{2}/\d{2}/\d{4} \d{2}:\d{2}:\d{2}: Destroying abandoned non persistent zdo (\d+:\d+) owner \d+"
   ).expect("Failed to compile left_regex");
 
+  // Handle a player connecting, before their character shows up
+  if let Some(captures) = connected_regex.captures(line) {
+    if let Some(platform_id) = captures.get(1).or(captures.get(2)) {
+      debug!(
+        "Player with platform id '{}' is connecting",
+        platform_id.as_str()
+      );
+      PlayerList::connected_event(platform_id.as_str().to_string());
+    }
+  }
+
   // Handle player joining event
   if let Some(captures) = joined_regex.captures(line) {
     debug!("Matched joining event: '{:?}'", captures);
@@ -280,12 +411,13 @@ mod tests {
 
   #[test]
   fn test_update_or_push() {
-    let mut player_list = PlayerList { players: vec![] };
+    let mut player_list = PlayerList::default();
     let player = Player {
       id: 1,
       zdo_index: 0,
       name: "Player1".to_string(),
       last_seen: Utc::now().timestamp(),
+      platform_id: None,
     };
 
     player_list.update_or_push(player.clone());
@@ -298,6 +430,7 @@ mod tests {
       zdo_index: 0,
       name: "Player1".to_string(),
       last_seen: Utc::now().timestamp() + 100,
+      platform_id: None,
     };
     player_list.update_or_push(updated_player.clone());
     assert_eq!(player_list.players.len(), 1);
@@ -319,16 +452,46 @@ mod tests {
       zdo_index: 0,
       name: "Player1".to_string(),
       last_seen: Utc::now().timestamp(),
+      platform_id: None,
     };
 
     let player_list = PlayerList {
       players: vec![player],
+      connecting: vec![],
     };
     player_list.save();
 
     PlayerList::left_event(id, 0);
   }
 
+  #[test]
+  fn test_platform_id_of_joining_player() {
+    let mut list = PlayerList::default();
+    list.connect(String::from("Steam_76561198000000001"), 100);
+    let platform_id = list.take_connecting("Ragnar", 110);
+    assert_eq!(platform_id.as_deref(), Some("Steam_76561198000000001"));
+    list.update_or_push(Player {
+      id: 1234,
+      zdo_index: 1,
+      name: String::from("Ragnar"),
+      last_seen: 110,
+      platform_id,
+    });
+    assert_eq!(list.ids_by_name("ragnar"), vec!["Steam_76561198000000001"]);
+    assert_eq!(list.name_of("76561198000000001"), Some("Ragnar"));
+
+    // Two players connecting at once can't be told apart.
+    list.connect(String::from("76561198000000002"), 200);
+    list.connect(String::from("76561198000000003"), 201);
+    assert_eq!(list.take_connecting("Bjorn", 210), None);
+    // A connection which never got a character is forgotten.
+    assert_eq!(
+      list.take_connecting("Bjorn", 200 + CONNECTING_TIMEOUT + 5),
+      None
+    );
+    assert!(list.connecting.is_empty());
+  }
+
   #[test]
   fn test_player_default() {
     let player = Player::default();
@@ -346,6 +509,7 @@ mod tests {
   fn test_player_list_save() {
     let player_list = PlayerList {
       players: vec![Player::default()],
+      connecting: vec![],
     };
     let result = player_list.save();
     assert!(result);
@@ -1,15 +1,18 @@
 use clap::Parser;
 use dotenv::dotenv;
-use log::debug;
+use log::{debug, error};
 
-use crate::cli::{Cli, Commands};
-use commands::configure::Configuration;
-
-use crate::commands::configure::Modifiers;
+use crate::cli::{
+  AccessAction, AccessListCommand, BepInExAction, Cli, Commands, ConfigAction, ModConfigAction,
+  WorldAction,
+};
 use crate::executable::handle_exit_status;
 use crate::logger::debug_mode;
 use crate::messages::about;
+use crate::server::access::AccessList;
+use crate::worlds::prune::PruneOptions;
 
+mod bot;
 mod cli;
 pub mod commands;
 mod constants;
@@ -25,6 +28,7 @@ pub mod server;
 mod steamcmd;
 pub mod traits;
 pub mod utils;
+mod worlds;
 
 #[tokio::main]
 async fn main() {
@@ -36,7 +40,19 @@ async fn main() {
     debug!("Debug mode enabled!");
   }
 
+  if let Some(instance) = &cli.instance {
+    if !utils::instances::select_instance(instance) {
+      error!(
+        "Invalid instance name {}, only letters, digits, - and _ are allowed",
+        instance
+      );
+      std::process::exit(1);
+    }
+    debug!("Using instance {}", instance);
+  }
+
   handle_commands(cli).await;
+  notifications::dispatcher::flush(notifications::dispatcher::FLUSH_TIMEOUT);
 }
 
 fn initialize_cli() -> Cli {
@@ -59,32 +75,80 @@ async fn handle_commands(cli: Cli) {
       modifiers,
       preset,
       set_key,
+      modifier_passthrough,
+      list_modifiers,
       save_interval,
-    } => Configuration::new(
-      name,
-      server_executable,
-      port,
-      world,
-      password,
-      public.eq("1"),
-      preset,
-      modifiers.map(|m| {
-        m.split(',')
-          .map(|modifier| Modifiers::from(modifier.to_string()))
-          .collect()
-      }),
-      set_key,
-      save_interval,
-    )
-    .invoke()
+      nexus_api_key,
+    } if !list_modifiers => commands::configure::invoke(&[
+      ("server.name", name),
+      ("server.executable", server_executable),
+      ("server.port", port),
+      ("server.world", world),
+      ("server.password", password),
+      ("server.public", public),
+      ("server.modifiers", modifiers),
+      ("server.preset", preset),
+      ("server.set_key", set_key),
+      ("server.modifier_passthrough", modifier_passthrough),
+      ("server.save_interval", save_interval),
+      ("mods.nexus_api_key", nexus_api_key),
+    ])
     .await
-    .expect("Failed to configure server"),
-    Commands::Install {} => handle_exit_status(
+    .unwrap_or_else(|e| {
+      error!("Failed to configure server: {}", e);
+      std::process::exit(1);
+    }),
+    Commands::Configure { .. } => commands::configure::list_modifiers(),
+    Commands::Config { action } => match action {
+      ConfigAction::Show { effective, json } => commands::config::show(effective, json),
+      ConfigAction::Validate => commands::config::validate(),
+    },
+    Commands::Access { list } => {
+      let (list, action) = match list {
+        AccessListCommand::Admin { action } => (AccessList::Admin, action),
+        AccessListCommand::Ban { action } => (AccessList::Banned, action),
+        AccessListCommand::Permit { action } => (AccessList::Permitted, action),
+      };
+      match action {
+        AccessAction::Add { players } => commands::access::add(cli.dry_run, list, &players),
+        AccessAction::Remove { players } => commands::access::remove(cli.dry_run, list, &players),
+        AccessAction::List { json } => commands::access::list(list, json),
+      }
+    }
+    Commands::World { action } => match action {
+      WorldAction::List { json } => commands::world::list(json),
+      WorldAction::Info { name, json } => commands::world::info(name, json),
+      WorldAction::Stats { name, top, json } => commands::world::stats(name, top, json),
+      WorldAction::Prune {
+        name,
+        items,
+        prefabs,
+        unvisited,
+        report,
+        json,
+      } => {
+        let options = PruneOptions {
+          item_drops: items,
+          prefabs,
+          unvisited_zones: unvisited,
+        };
+        commands::world::prune(name, &options, report || cli.dry_run, json)
+      }
+      WorldAction::Switch { name, no_restart } => {
+        commands::world::switch(cli.dry_run, &name, false, !no_restart)
+      }
+      WorldAction::Create { name, no_restart } => {
+        commands::world::switch(cli.dry_run, &name, true, !no_restart)
+      }
+      WorldAction::Import { source, force } => commands::world::import(cli.dry_run, &source, force),
+      WorldAction::Delete { name, force } => commands::world::delete(cli.dry_run, &name, force),
+    },
+    Commands::Install => handle_exit_status(
       commands::install::invoke(constants::GAME_ID),
       "Successfully installed Valheim!".to_string(),
     ),
-    Commands::Start {} => commands::start::invoke(cli.dry_run),
-    Commands::Stop {} => commands::stop::invoke(cli.dry_run),
+    Commands::Start { foreground } => commands::start::invoke(cli.dry_run, foreground).await,
+    Commands::Stop => commands::stop::invoke(cli.dry_run),
     Commands::Backup {
       input_directory,
       output_file,
@@ -94,15 +158,51 @@ async fn handle_commands(cli: Cli) {
       title,
       message,
       webhook_url,
-    } => commands::notify::invoke(title, message, webhook_url),
+      preview,
+      validate,
+    } => {
+      if validate {
+        commands::notify::validate()
+      } else if let Some(event) = preview {
+        commands::notify::preview(&event)
+      } else {
+        commands::notify::invoke(title, message, webhook_url)
+      }
+    }
     Commands::ModInstall { url } => commands::install_mod::invoke(url),
+    Commands::ModOutdated { json } => commands::outdated_mods::invoke(json),
+    Commands::ModUpgrade { names } => commands::upgrade_mods::invoke(cli.dry_run, names),
+    Commands::ModRepair { finish } => commands::repair_mods::invoke(cli.dry_run, finish),
+    Commands::ModExportClient {
+      format,
+      output,
+      code,
+    } => commands::export_client::invoke(format, output, code),
+    Commands::ModConfig { action } => match action {
+      ModConfigAction::Get { plugin, key } => commands::mod_config::get(plugin, key),
+      ModConfigAction::Set { plugin, key, value } => {
+        commands::mod_config::set(cli.dry_run, plugin, key, value)
+      }
+      ModConfigAction::List { plugin, json } => commands::mod_config::list(plugin, json),
+    },
+    Commands::BepInEx { action } => match action {
+      BepInExAction::Install {
+        version,
+        url,
+        force,
+      } => commands::bepinex::install(cli.dry_run, version, url, force),
+      BepInExAction::Upgrade { version } => commands::bepinex::upgrade(cli.dry_run, version),
+      BepInExAction::Remove { purge } => commands::bepinex::remove(cli.dry_run, purge),
+      BepInExAction::Verify { json } => commands::bepinex::verify(json),
+    },
     Commands::Status {
       json,
       local,
       address,
     } => commands::status::invoke(json, local, address),
-    Commands::About {} => about(env!("GIT_HASH")),
+    Commands::About => about(env!("GIT_HASH")),
     Commands::Logs { lines, watch } => commands::logs::invoke(lines, watch).await,
+    Commands::Bot => commands::bot::invoke(cli.dry_run).await,
   }
 }
 
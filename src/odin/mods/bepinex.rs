@@ -1,10 +1,12 @@
 use std::ops::Add;
-use std::process::{Child, Command};
+use std::path::{Path, PathBuf};
+use std::process::Command;
 
 use log::{debug, info};
 use serde::{Deserialize, Serialize};
 
 use crate::constants;
+use crate::mods::bepinex_pack::BepInExPack;
 use crate::utils::common_paths::{bepinex_directory, bepinex_plugin_directory, game_directory};
 use crate::utils::{environment, path_exists};
 
@@ -110,6 +112,37 @@ impl BepInExEnvironment {
     output
   }
 
+  /// Version of the installed BepInExPack, known for packs installed by odin.
+  pub fn version(&self) -> Option<String> {
+    BepInExPack::load(Path::new(&game_directory())).and_then(|pack| pack.version)
+  }
+
+  /// Checks everything doorstop needs to load BepInEx and lists the problems found.
+  pub fn verify(&self) -> Vec<String> {
+    let mut problems = vec![];
+    let checks = [
+      ("doorstop library", &self.dyld_insert_libraries),
+      ("BepInEx preloader", &self.doorstop_invoke_dll),
+      ("unstripped corlib", &self.doorstop_corlib_override_path),
+    ];
+    for (name, path) in checks {
+      if !path_exists(path) {
+        problems.push(format!("Missing {} at {}", name, path));
+      }
+    }
+    let core_dll = format!("{}/core/BepInEx.dll", bepinex_directory());
+    if !path_exists(&core_dll) {
+      problems.push(format!("Missing BepInEx core at {}", core_dll));
+    }
+    let game_dir = PathBuf::from(game_directory());
+    if let Some(pack) = BepInExPack::load(&game_dir) {
+      for file in pack.missing_files(&game_dir) {
+        problems.push(format!("Missing {} file {}", pack.name, file.display()));
+      }
+    }
+    problems
+  }
+
   pub fn list_mods(&self) -> Vec<ModInfo> {
     if self.is_installed() {
       glob::glob(&format!("{}/**/*.dll", bepinex_plugin_directory()))
@@ -132,7 +165,8 @@ impl BepInExEnvironment {
     }
   }
 
-  pub fn launch(&self, mut command: Command) -> std::io::Result<Child> {
+  /// Sets up the doorstop environment on the server command.
+  pub fn apply<'a>(&self, command: &'a mut Command) -> &'a mut Command {
     info!("BepInEx found! Setting up Environment...");
     command
       // DOORSTOP_ENABLE must not have quotes around it.
@@ -154,6 +188,5 @@ impl BepInExEnvironment {
       )
       // DYLD_INSERT_LIBRARIES must not have quotes around it.
       .env(DYLD_INSERT_LIBRARIES_VAR, &self.dyld_insert_libraries)
-      .spawn()
   }
 }
@@ -0,0 +1,393 @@
+use crate::errors::ValheimModError;
+use crate::utils::common_paths::bepinex_config_directory;
+use log::{debug, info, warn};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const ENV_OVERRIDE_PREFIX: &str = "BEPINEX_CFG__";
+
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Eq)]
+pub struct ConfigEntry {
+  pub section: String,
+  pub key: String,
+  pub value: String,
+}
+
+enum Line<'a> {
+  Section(&'a str),
+  Entry(&'a str, &'a str),
+  Other,
+}
+
+fn parse_line(line: &str) -> Line<'_> {
+  let trimmed = line.trim();
+  if trimmed.is_empty() || trimmed.starts_with('#') || trimmed.starts_with(';') {
+    Line::Other
+  } else if trimmed.starts_with('[') && trimmed.ends_with(']') {
+    Line::Section(trimmed[1..trimmed.len() - 1].trim())
+  } else if let Some((key, value)) = trimmed.split_once('=') {
+    Line::Entry(key.trim(), value.trim())
+  } else {
+    Line::Other
+  }
+}
+
+/// A BepInEx plugin config, the INI like `.cfg` files written by `Config.Bind`.
+///
+/// The file is kept line by line so rewriting a value leaves comments, ordering and
+/// formatting of everything else untouched.
+pub struct BepInExConfig {
+  path: PathBuf,
+  lines: Vec<String>,
+  line_ending: &'static str,
+  trailing_newline: bool,
+}
+
+impl BepInExConfig {
+  pub fn parse(path: &Path, content: &str) -> BepInExConfig {
+    BepInExConfig {
+      path: path.to_path_buf(),
+      lines: content.lines().map(String::from).collect(),
+      line_ending: if content.contains("\r\n") {
+        "\r\n"
+      } else {
+        "\n"
+      },
+      trailing_newline: content.is_empty() || content.ends_with('\n'),
+    }
+  }
+
+  pub fn load(path: &Path) -> Result<BepInExConfig, ValheimModError> {
+    let content =
+      fs::read_to_string(path).map_err(|e| ValheimModError::FileOpenError(e.to_string()))?;
+    Ok(Self::parse(path, &content))
+  }
+
+  pub fn save(&self) -> Result<(), ValheimModError> {
+    debug!("Writing BepInEx config {:?}", self.path);
+    fs::write(&self.path, self.to_string())
+      .map_err(|e| ValheimModError::FileCreateError(e.to_string()))
+  }
+
+  pub fn entries(&self) -> Vec<ConfigEntry> {
+    let mut section = "";
+    let mut entries = vec![];
+    for line in &self.lines {
+      match parse_line(line) {
+        Line::Section(name) => section = name,
+        Line::Entry(key, value) => entries.push(ConfigEntry {
+          section: section.to_string(),
+          key: key.to_string(),
+          value: value.to_string(),
+        }),
+        Line::Other => {}
+      }
+    }
+    entries
+  }
+
+  pub fn get(&self, section: &str, key: &str) -> Option<String> {
+    self
+      .entries()
+      .into_iter()
+      .find(|entry| {
+        entry.section.eq_ignore_ascii_case(section) && entry.key.eq_ignore_ascii_case(key)
+      })
+      .map(|entry| entry.value)
+  }
+
+  /// Sets a value in place, adding the key or section if they don't exist yet.
+  pub fn set(&mut self, section: &str, key: &str, value: &str) {
+    let mut in_section = false;
+    let mut section_end = None;
+    for index in 0..self.lines.len() {
+      let line = &self.lines[index];
+      match parse_line(line) {
+        Line::Section(name) => {
+          in_section = name.eq_ignore_ascii_case(section);
+          if in_section {
+            section_end = Some(index + 1);
+          }
+        }
+        Line::Entry(existing, _) if in_section && existing.eq_ignore_ascii_case(key) => {
+          let (prefix, rest) = line.split_once('=').unwrap();
+          let spacing = &rest[..rest.len() - rest.trim_start().len()];
+          self.lines[index] = format!("{}={}{}", prefix, spacing, value);
+          return;
+        }
+        _ if in_section && !line.trim().is_empty() => section_end = Some(index + 1),
+        _ => {}
+      }
+    }
+
+    let entry = format!("{} = {}", key, value);
+    match section_end {
+      Some(index) => self.lines.insert(index, entry),
+      None => {
+        if self
+          .lines
+          .last()
+          .is_some_and(|line| !line.trim().is_empty())
+        {
+          self.lines.push(String::new());
+        }
+        self.lines.push(format!("[{}]", section));
+        self.lines.push(entry);
+      }
+    }
+  }
+}
+
+impl std::fmt::Display for BepInExConfig {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    write!(f, "{}", self.lines.join(self.line_ending))?;
+    if self.trailing_newline && !self.lines.is_empty() {
+      write!(f, "{}", self.line_ending)?;
+    }
+    Ok(())
+  }
+}
+
+/// Splits a `<section>.<key>` argument, sections may contain dots themselves.
+pub fn split_section_key(input: &str) -> Result<(&str, &str), ValheimModError> {
+  match input.rsplit_once('.') {
+    Some((section, key)) if !section.is_empty() && !key.is_empty() => Ok((section, key)),
+    _ => Err(ValheimModError::InvalidConfigKey(input.to_string())),
+  }
+}
+
+/// Lists the `.cfg` files in the config directory.
+pub fn config_files(config_dir: &Path) -> Vec<PathBuf> {
+  let mut files: Vec<PathBuf> = fs::read_dir(config_dir)
+    .map(|entries| {
+      entries
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| {
+          path.is_file()
+            && path
+              .extension()
+              .is_some_and(|extension| extension.eq_ignore_ascii_case("cfg"))
+        })
+        .collect()
+    })
+    .unwrap_or_default();
+  files.sort();
+  files
+}
+
+fn config_name(path: &Path) -> String {
+  path
+    .file_stem()
+    .map(|stem| stem.to_string_lossy().to_string())
+    .unwrap_or_default()
+}
+
+/// Finds the config of a plugin by its GUID, which is what BepInEx names the file after.
+pub fn plugin_config_path(config_dir: &Path, plugin: &str) -> Result<PathBuf, ValheimModError> {
+  config_files(config_dir)
+    .into_iter()
+    .find(|path| config_name(path).eq_ignore_ascii_case(plugin))
+    .ok_or_else(|| ValheimModError::ConfigNotFound(plugin.to_string()))
+}
+
+/// Environment variable names can't hold dots or spaces, so overrides are matched
+/// ignoring anything that isn't alphanumeric.
+fn normalize(name: &str) -> String {
+  name
+    .chars()
+    .filter(|c| c.is_ascii_alphanumeric())
+    .collect::<String>()
+    .to_lowercase()
+}
+
+/// Applies `BEPINEX_CFG__<plugin>__<section>__<key>` overrides to the configs in the directory.
+/// Only existing values are overridden, BepInEx has to have generated the config first.
+pub fn apply_overrides(
+  config_dir: &Path,
+  vars: impl IntoIterator<Item = (String, String)>,
+) -> usize {
+  let files = config_files(config_dir);
+  let mut configs: HashMap<PathBuf, BepInExConfig> = HashMap::new();
+  let mut applied = 0;
+
+  for (name, value) in vars {
+    let Some(target) = name.strip_prefix(ENV_OVERRIDE_PREFIX) else {
+      continue;
+    };
+    let [plugin, section, key] = target.split("__").collect::<Vec<&str>>()[..] else {
+      warn!(
+        "Ignoring {}, expected {}<plugin>__<section>__<key>",
+        name, ENV_OVERRIDE_PREFIX
+      );
+      continue;
+    };
+
+    let Some(path) = files
+      .iter()
+      .find(|path| normalize(&config_name(path)) == normalize(plugin))
+    else {
+      warn!(
+        "Ignoring {}, no config found for {}. Start the server once to let the plugin generate it.",
+        name, plugin
+      );
+      continue;
+    };
+    if !configs.contains_key(path) {
+      match BepInExConfig::load(path) {
+        Ok(config) => {
+          configs.insert(path.clone(), config);
+        }
+        Err(e) => {
+          warn!("Ignoring {}, failed to read {:?}: {}", name, path, e);
+          continue;
+        }
+      }
+    }
+    let config = configs.get_mut(path).unwrap();
+
+    match config.entries().into_iter().find(|entry| {
+      normalize(&entry.section) == normalize(section) && normalize(&entry.key) == normalize(key)
+    }) {
+      Some(entry) => {
+        debug!("Overriding [{}] {} in {:?}", entry.section, entry.key, path);
+        config.set(&entry.section, &entry.key, &value);
+        applied += 1;
+      }
+      None => warn!(
+        "Ignoring {}, {:?} has no setting {} in section {}",
+        name, path, key, section
+      ),
+    }
+  }
+
+  for config in configs.values() {
+    if let Err(e) = config.save() {
+      warn!("Failed to apply config overrides: {}", e);
+    }
+  }
+  if applied > 0 {
+    info!("Applied {} BepInEx config override(s)", applied);
+  }
+  applied
+}
+
+/// Applies the config overrides from the environment, run before the server starts.
+pub fn apply_env_overrides() -> usize {
+  apply_overrides(Path::new(&bepinex_config_directory()), env::vars())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use tempfile::tempdir;
+
+  const CONFIG: &str = "## Settings file was created by plugin Epic Loot v0.10.3
+## Plugin GUID: randyknapp.mods.epicloot
+
+[Balance]
+
+## Chance for items to drop
+# Setting type: Single
+# Default value: 1
+Items To Materials Drop Ratio = 1
+
+[Logging.Console]
+
+Enabled = false
+";
+
+  #[test]
+  fn test_get_and_entries() {
+    let config = BepInExConfig::parse(Path::new("test.cfg"), CONFIG);
+    assert_eq!(
+      config
+        .get("balance", "items to materials drop ratio")
+        .as_deref(),
+      Some("1")
+    );
+    assert_eq!(
+      config.get("Logging.Console", "Enabled").as_deref(),
+      Some("false")
+    );
+    assert_eq!(config.get("Balance", "Missing"), None);
+    assert_eq!(config.entries().len(), 2);
+  }
+
+  #[test]
+  fn test_set_keeps_comments_and_ordering() {
+    let mut config = BepInExConfig::parse(Path::new("test.cfg"), CONFIG);
+    config.set("Balance", "Items To Materials Drop Ratio", "0.5");
+    assert_eq!(
+      config.to_string(),
+      CONFIG.replace("Ratio = 1", "Ratio = 0.5")
+    );
+  }
+
+  #[test]
+  fn test_set_adds_missing_keys_and_sections() {
+    let mut config = BepInExConfig::parse(Path::new("test.cfg"), CONFIG);
+    config.set("Balance", "Magic Find", "2");
+    config.set("Abilities", "Enabled", "true");
+    let content = config.to_string();
+    assert!(content.contains("Items To Materials Drop Ratio = 1\nMagic Find = 2\n"));
+    assert!(content.ends_with("Enabled = false\n\n[Abilities]\nEnabled = true\n"));
+  }
+
+  #[test]
+  fn test_split_section_key() {
+    assert_eq!(
+      split_section_key("Logging.Console.Enabled").unwrap(),
+      ("Logging.Console", "Enabled")
+    );
+    assert!(split_section_key("Enabled").is_err());
+  }
+
+  #[test]
+  fn test_apply_overrides() {
+    let dir = tempdir().unwrap();
+    let path = dir.path().join("randyknapp.mods.epicloot.cfg");
+    fs::write(&path, CONFIG).unwrap();
+
+    let applied = apply_overrides(
+      dir.path(),
+      vec![
+        (
+          "BEPINEX_CFG__randyknapp_mods_epicloot__Balance__Items_To_Materials_Drop_Ratio"
+            .to_string(),
+          "0.25".to_string(),
+        ),
+        (
+          "BEPINEX_CFG__RANDYKNAPP_MODS_EPICLOOT__LOGGING_CONSOLE__ENABLED".to_string(),
+          "true".to_string(),
+        ),
+        (
+          "BEPINEX_CFG__missing_plugin__Section__Key".to_string(),
+          "1".to_string(),
+        ),
+        ("BEPINEX_CFG__malformed".to_string(), "1".to_string()),
+        ("PATH".to_string(), "/usr/bin".to_string()),
+      ],
+    );
+
+    assert_eq!(applied, 2);
+    let config = BepInExConfig::load(&path).unwrap();
+    assert_eq!(
+      config
+        .get("Balance", "Items To Materials Drop Ratio")
+        .as_deref(),
+      Some("0.25")
+    );
+    assert_eq!(
+      config.get("Logging.Console", "Enabled").as_deref(),
+      Some("true")
+    );
+    assert_eq!(
+      plugin_config_path(dir.path(), "RandyKnapp.Mods.EpicLoot").unwrap(),
+      path
+    );
+  }
+}
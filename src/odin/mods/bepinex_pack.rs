@@ -0,0 +1,302 @@
+use crate::errors::ValheimModError;
+use crate::utils::environment::fetch_var;
+use log::{debug, info};
+use reqwest::blocking::Client;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use walkdir::WalkDir;
+
+const BEPINEX_RELEASES_URL_VAR: &str = "BEPINEX_RELEASES_URL";
+const BEPINEX_RELEASES_URL: &str =
+  "https://thunderstore.io/api/experimental/package/denikson/BepInExPack_Valheim/";
+/// The Thunderstore mod string of the pack, without the version.
+pub const BEPINEX_PACK_MOD: &str = "denikson-BepInExPack_Valheim";
+const BEPINEX_PACK_FILE: &str = "BepInEx/bepinex_pack.json";
+
+/// Files BepInExPack_Valheim puts into the game directory, used to remove packs which were
+/// installed before odin kept track of them.
+const BEPINEX_PACK_PATHS: &[&str] = &[
+  "BepInEx/core",
+  "doorstop_libs",
+  "unstripped_corlib",
+  "doorstop_config.ini",
+  "start_game_bepinex.sh",
+  "start_server_bepinex.sh",
+  "winhttp.dll",
+  "changelog.txt",
+];
+
+#[derive(Deserialize)]
+struct PackageRelease {
+  version_number: String,
+}
+
+#[derive(Deserialize)]
+struct PackageListing {
+  latest: PackageRelease,
+}
+
+/// Fetches the latest version of BepInExPack_Valheim from Thunderstore.
+pub fn latest_version(client: &Client, url: &str) -> Result<String, ValheimModError> {
+  let response = client
+    .get(url)
+    .send()
+    .and_then(|response| response.error_for_status())
+    .map_err(|e| ValheimModError::PackageIndexFetchError(e.to_string()))?;
+  let listing: PackageListing = response
+    .json()
+    .map_err(|e| ValheimModError::PackageIndexParseError(e.to_string()))?;
+  Ok(listing.latest.version_number)
+}
+
+pub fn releases_url() -> String {
+  fetch_var(BEPINEX_RELEASES_URL_VAR, BEPINEX_RELEASES_URL)
+}
+
+/// Record of the installed BepInExPack, written next to the BepInEx directory it installs.
+#[derive(Debug, Deserialize, Serialize, PartialEq, Eq)]
+pub struct BepInExPack {
+  pub name: String,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub version: Option<String>,
+  /// Every file the pack installed, relative to the game directory.
+  pub files: Vec<PathBuf>,
+}
+
+impl BepInExPack {
+  /// Builds the record from the directory about to be moved into the game directory.
+  pub fn from_source(name: &str, version: Option<String>, source: &Path) -> BepInExPack {
+    let files = WalkDir::new(source)
+      .into_iter()
+      .flatten()
+      .filter(|entry| !entry.file_type().is_dir())
+      .filter_map(|entry| {
+        entry
+          .path()
+          .strip_prefix(source)
+          .ok()
+          .map(Path::to_path_buf)
+      })
+      .collect();
+    BepInExPack {
+      name: name.to_string(),
+      version,
+      files,
+    }
+  }
+
+  pub fn path(game_dir: &Path) -> PathBuf {
+    game_dir.join(BEPINEX_PACK_FILE)
+  }
+
+  pub fn load(game_dir: &Path) -> Option<BepInExPack> {
+    let content = fs::read_to_string(Self::path(game_dir)).ok()?;
+    serde_json::from_str(&content).ok()
+  }
+
+  pub fn write(&self, game_dir: &Path) -> Result<(), ValheimModError> {
+    let path = Self::path(game_dir);
+    fs::create_dir_all(path.parent().unwrap())
+      .map_err(|e| ValheimModError::DirectoryCreationError(e.to_string()))?;
+    let content = serde_json::to_string_pretty(self)
+      .map_err(|e| ValheimModError::FileCreateError(e.to_string()))?;
+    fs::write(path, content).map_err(|e| ValheimModError::FileCreateError(e.to_string()))
+  }
+
+  /// Lists the installed files which have gone missing.
+  pub fn missing_files(&self, game_dir: &Path) -> Vec<PathBuf> {
+    self
+      .files
+      .iter()
+      .filter(|file| !game_dir.join(file).exists())
+      .cloned()
+      .collect()
+  }
+}
+
+fn remove_path(path: &Path) -> std::io::Result<()> {
+  let result = if path.is_dir() {
+    fs::remove_dir_all(path)
+  } else {
+    fs::remove_file(path)
+  };
+  match result {
+    Err(e) if e.kind() == ErrorKind::NotFound => Ok(()),
+    result => result,
+  }
+}
+
+/// Removes directories left empty, walking up until the game directory.
+fn remove_empty_parents(game_dir: &Path, path: &Path) {
+  let mut parent = path.parent();
+  while let Some(directory) = parent.filter(|directory| *directory != game_dir) {
+    if fs::remove_dir(directory).is_err() {
+      break;
+    }
+    parent = directory.parent();
+  }
+}
+
+/// Removes BepInEx from the game directory so the server launches vanilla again.
+/// Plugins and their configs are kept unless `purge` is set.
+pub fn remove(game_dir: &Path, purge: bool) -> Result<(), ValheimModError> {
+  let error = |e: std::io::Error| ValheimModError::FileMoveError(e.to_string());
+  match BepInExPack::load(game_dir) {
+    Some(pack) => {
+      info!(
+        "Removing {} {}",
+        pack.name,
+        pack.version.as_deref().unwrap_or("(unknown version)")
+      );
+      for file in &pack.files {
+        let path = game_dir.join(file);
+        debug!("Removing {:?}", path);
+        remove_path(&path).map_err(error)?;
+        remove_empty_parents(game_dir, &path);
+      }
+      remove_path(&BepInExPack::path(game_dir)).map_err(error)?;
+    }
+    None => {
+      info!("No record of the installed BepInEx pack found, removing the known files.");
+      for file in BEPINEX_PACK_PATHS {
+        remove_path(&game_dir.join(file)).map_err(error)?;
+      }
+    }
+  }
+  if purge {
+    info!("Purging the BepInEx directory including plugins and configs");
+    remove_path(&game_dir.join("BepInEx")).map_err(error)?;
+  }
+  Ok(())
+}
+
+/// Removes the files an earlier pack installed which the current one no longer ships.
+pub fn remove_stale_files(game_dir: &Path, previous: &BepInExPack) -> Result<(), ValheimModError> {
+  let current = BepInExPack::load(game_dir).map_or_else(Vec::new, |pack| pack.files);
+  for file in previous.files.iter().filter(|file| !current.contains(file)) {
+    let path = game_dir.join(file);
+    debug!("Removing stale {:?}", path);
+    remove_path(&path).map_err(|e| ValheimModError::FileMoveError(e.to_string()))?;
+    remove_empty_parents(game_dir, &path);
+  }
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use tempfile::tempdir;
+
+  fn install_pack(game_dir: &Path) -> BepInExPack {
+    let source = tempdir().unwrap();
+    for file in [
+      "BepInEx/core/BepInEx.Preloader.dll",
+      "doorstop_libs/libdoorstop_x64.so",
+      "winhttp.dll",
+    ] {
+      let path = source.path().join(file);
+      fs::create_dir_all(path.parent().unwrap()).unwrap();
+      fs::write(&path, "content").unwrap();
+      fs::create_dir_all(game_dir.join(file).parent().unwrap()).unwrap();
+      fs::write(game_dir.join(file), "content").unwrap();
+    }
+    let pack = BepInExPack::from_source(
+      "BepInExPack_Valheim",
+      Some("5.4.2202".to_string()),
+      source.path(),
+    );
+    pack.write(game_dir).unwrap();
+    pack
+  }
+
+  #[test]
+  fn test_pack_record() {
+    let game_dir = tempdir().unwrap();
+    let pack = install_pack(game_dir.path());
+    assert_eq!(pack.files.len(), 3);
+    assert_eq!(BepInExPack::load(game_dir.path()).unwrap(), pack);
+    assert!(pack.missing_files(game_dir.path()).is_empty());
+
+    fs::remove_file(game_dir.path().join("winhttp.dll")).unwrap();
+    assert_eq!(
+      pack.missing_files(game_dir.path()),
+      vec![PathBuf::from("winhttp.dll")]
+    );
+  }
+
+  #[test]
+  fn test_remove_keeps_plugins() {
+    let game_dir = tempdir().unwrap();
+    install_pack(game_dir.path());
+    let plugin = game_dir.path().join("BepInEx/plugins/Jotunn/Jotunn.dll");
+    fs::create_dir_all(plugin.parent().unwrap()).unwrap();
+    fs::write(&plugin, "content").unwrap();
+
+    remove(game_dir.path(), false).unwrap();
+    assert!(!game_dir.path().join("BepInEx/core").exists());
+    assert!(!game_dir.path().join("doorstop_libs").exists());
+    assert!(!game_dir.path().join("winhttp.dll").exists());
+    assert!(BepInExPack::load(game_dir.path()).is_none());
+    assert!(plugin.exists());
+
+    remove(game_dir.path(), true).unwrap();
+    assert!(!game_dir.path().join("BepInEx").exists());
+  }
+
+  #[test]
+  fn test_remove_stale_files() {
+    let game_dir = tempdir().unwrap();
+    let mut previous = install_pack(game_dir.path());
+    previous
+      .files
+      .push(PathBuf::from("doorstop_libs/libdoorstop_x86.so"));
+    fs::write(
+      game_dir.path().join("doorstop_libs/libdoorstop_x86.so"),
+      "old",
+    )
+    .unwrap();
+
+    remove_stale_files(game_dir.path(), &previous).unwrap();
+    assert!(!game_dir
+      .path()
+      .join("doorstop_libs/libdoorstop_x86.so")
+      .exists());
+    assert!(game_dir
+      .path()
+      .join("doorstop_libs/libdoorstop_x64.so")
+      .exists());
+  }
+
+  #[test]
+  fn test_remove_without_record() {
+    let game_dir = tempdir().unwrap();
+    install_pack(game_dir.path());
+    fs::remove_file(BepInExPack::path(game_dir.path())).unwrap();
+
+    remove(game_dir.path(), false).unwrap();
+    assert!(!game_dir.path().join("BepInEx/core").exists());
+    assert!(!game_dir.path().join("doorstop_libs").exists());
+    assert!(!game_dir.path().join("winhttp.dll").exists());
+  }
+
+  #[test]
+  fn test_latest_version() {
+    let mut server = mockito::Server::new();
+    let mock = server
+      .mock("GET", "/api/experimental/package/denikson/BepInExPack_Valheim/")
+      .with_body(r#"{"full_name": "denikson-BepInExPack_Valheim", "latest": {"version_number": "5.4.2202", "download_url": "https://thunderstore.io/package/download/denikson/BepInExPack_Valheim/5.4.2202/"}}"#)
+      .create();
+    let version = latest_version(
+      &Client::new(),
+      &format!(
+        "{}/api/experimental/package/denikson/BepInExPack_Valheim/",
+        server.url()
+      ),
+    )
+    .unwrap();
+    mock.assert();
+    assert_eq!(version, "5.4.2202");
+  }
+}
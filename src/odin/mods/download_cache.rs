@@ -0,0 +1,647 @@
+use crate::errors::ValheimModError;
+use crate::utils::common_paths::cache_directory;
+use crate::utils::environment::fetch_var;
+use crate::utils::get_md5_hash;
+use log::{debug, info, warn};
+use reqwest::blocking::Client;
+use reqwest::header::{HeaderMap, ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED, RANGE};
+use reqwest::StatusCode;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fs::{self, File, OpenOptions};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::Duration;
+
+const SHA256_SUFFIX: &str = "#sha256=";
+const MOD_DOWNLOAD_RETRIES_VAR: &str = "MOD_DOWNLOAD_RETRIES";
+const DEFAULT_RETRIES: u32 = 3;
+const DEFAULT_BACKOFF: Duration = Duration::from_secs(1);
+
+/// Splits an optional `#sha256=<hash>` suffix off of a mod url or mod string.
+pub fn split_sha256(input: &str) -> (&str, Option<String>) {
+  match input.rsplit_once(SHA256_SUFFIX) {
+    Some((source, hash)) if !hash.trim().is_empty() => {
+      (source, Some(hash.trim().to_ascii_lowercase()))
+    }
+    _ => (input, None),
+  }
+}
+
+pub fn sha256_file(path: &Path) -> io::Result<String> {
+  let mut hasher = Sha256::new();
+  io::copy(&mut File::open(path)?, &mut hasher)?;
+  Ok(format!("{:x}", hasher.finalize()))
+}
+
+/// What the server said about a download, used to ask it later whether the file changed.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Deserialize, Serialize)]
+struct Validators {
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  etag: Option<String>,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  last_modified: Option<String>,
+}
+
+impl Validators {
+  fn from_headers(headers: &HeaderMap) -> Self {
+    let header = |name| {
+      headers
+        .get(name)
+        .and_then(|value| value.to_str().ok())
+        .map(String::from)
+    };
+    Validators {
+      etag: header(ETAG),
+      last_modified: header(LAST_MODIFIED),
+    }
+  }
+
+  fn is_empty(&self) -> bool {
+    self.etag.is_none() && self.last_modified.is_none()
+  }
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+struct CacheEntry {
+  url: String,
+  final_url: String,
+  sha256: String,
+  #[serde(default, flatten)]
+  validators: Validators,
+}
+
+#[derive(Debug)]
+pub struct CachedDownload {
+  /// Location of the cached file, named after its SHA-256 hash.
+  pub path: PathBuf,
+  /// The url the file was served from after following redirects.
+  pub url: String,
+  pub sha256: String,
+}
+
+/// A content addressed cache for mod downloads.
+///
+/// Files are stored by their SHA-256 hash and indexed by the url they were downloaded from,
+/// so reinstalling the same mods does not download them again. A url without a pinned hash may
+/// serve a newer file later, like GitHub's `releases/latest`, so its cached copy is only used after
+/// the server confirms it with its ETag or Last-Modified date. Interrupted downloads are
+/// resumed with an HTTP Range request and failed attempts are retried with exponential backoff.
+pub struct DownloadCache {
+  client: Client,
+  cache_dir: PathBuf,
+  retries: u32,
+  backoff: Duration,
+}
+
+impl Default for DownloadCache {
+  fn default() -> Self {
+    let retries = fetch_var(MOD_DOWNLOAD_RETRIES_VAR, "")
+      .parse()
+      .unwrap_or(DEFAULT_RETRIES);
+    Self::new(
+      Client::new(),
+      PathBuf::from(cache_directory()).join("downloads"),
+    )
+    .with_retries(retries, DEFAULT_BACKOFF)
+  }
+}
+
+impl DownloadCache {
+  pub fn new(client: Client, cache_dir: PathBuf) -> Self {
+    DownloadCache {
+      client,
+      cache_dir,
+      retries: DEFAULT_RETRIES,
+      backoff: DEFAULT_BACKOFF,
+    }
+  }
+
+  pub fn with_retries(mut self, retries: u32, backoff: Duration) -> Self {
+    self.retries = retries;
+    self.backoff = backoff;
+    self
+  }
+
+  fn blob_path(&self, sha256: &str) -> PathBuf {
+    self.cache_dir.join("blobs").join(sha256)
+  }
+
+  fn entry_path(&self, url: &str) -> PathBuf {
+    self
+      .cache_dir
+      .join("urls")
+      .join(format!("{}.json", get_md5_hash(url)))
+  }
+
+  fn partial_path(&self, url: &str) -> PathBuf {
+    self
+      .cache_dir
+      .join("partial")
+      .join(format!("{}.part", get_md5_hash(url)))
+  }
+
+  fn read_entry(&self, url: &str) -> Option<CacheEntry> {
+    let content = fs::read_to_string(self.entry_path(url)).ok()?;
+    serde_json::from_str(&content).ok()
+  }
+
+  fn write_entry(&self, entry: &CacheEntry) -> io::Result<()> {
+    let path = self.entry_path(&entry.url);
+    fs::create_dir_all(path.parent().unwrap())?;
+    fs::write(path, serde_json::to_string_pretty(entry)?)
+  }
+
+  /// Returns the blob for the hash if it exists and its content still matches.
+  fn verified_blob(&self, sha256: &str) -> Option<PathBuf> {
+    let path = self.blob_path(sha256);
+    match sha256_file(&path) {
+      Ok(actual) if actual == sha256 => Some(path),
+      Ok(_) => {
+        warn!("Cached download {:?} is corrupted, removing it.", path);
+        fs::remove_file(&path).ok();
+        None
+      }
+      Err(_) => None,
+    }
+  }
+
+  /// Asks the server whether the file behind a cached url changed since it was downloaded. It is
+  /// a HEAD request, a changed file is downloaded by `fetch` afterwards.
+  fn is_current(&self, entry: &CacheEntry) -> bool {
+    if entry.validators.is_empty() {
+      debug!(
+        "{} came without an ETag or Last-Modified date, downloading it again",
+        entry.url
+      );
+      return false;
+    }
+    let mut request = self.client.head(&entry.url);
+    if let Some(etag) = &entry.validators.etag {
+      request = request.header(IF_NONE_MATCH, etag);
+    }
+    if let Some(last_modified) = &entry.validators.last_modified {
+      request = request.header(IF_MODIFIED_SINCE, last_modified);
+    }
+    match request.send() {
+      Ok(response) if response.status() == StatusCode::NOT_MODIFIED => true,
+      // Not every server answers a conditional HEAD, it still tells which version it serves.
+      Ok(response)
+        if response.status().is_success()
+          && Validators::from_headers(response.headers()) == entry.validators =>
+      {
+        true
+      }
+      Ok(response) if response.status().is_server_error() => {
+        warn!(
+          "Failed to revalidate the cached download of {}, using it anyway: {}",
+          entry.url,
+          response.status()
+        );
+        true
+      }
+      Ok(response) => {
+        debug!(
+          "{} changed since it was cached ({})",
+          entry.url,
+          response.status()
+        );
+        false
+      }
+      Err(e) => {
+        warn!(
+          "Failed to revalidate the cached download of {}, using it anyway: {}",
+          entry.url, e
+        );
+        true
+      }
+    }
+  }
+
+  /// The cached copy of the file at `key`. Unless the hash is pinned or the key always names the
+  /// same file, the server is asked whether it changed first.
+  fn lookup(
+    &self,
+    key: &str,
+    expected_sha256: Option<&str>,
+    immutable: bool,
+  ) -> Option<CachedDownload> {
+    let entry = self.read_entry(key);
+    let sha256 = match (expected_sha256, &entry) {
+      (Some(expected), _) => expected.to_string(),
+      (None, Some(entry)) => entry.sha256.clone(),
+      (None, None) => return None,
+    };
+    let path = self.verified_blob(&sha256)?;
+    if expected_sha256.is_none()
+      && !immutable
+      && !entry.as_ref().is_some_and(|entry| self.is_current(entry))
+    {
+      return None;
+    }
+    let final_url = entry
+      .filter(|entry| entry.sha256 == sha256)
+      .map(|entry| entry.final_url)
+      .unwrap_or_else(|| key.to_string());
+    Some(CachedDownload {
+      path,
+      url: final_url,
+      sha256,
+    })
+  }
+
+  /// Downloads into the partial file of `key`, resuming from where a previous attempt stopped.
+  /// Returns the final url of the download and what identifies the version it got.
+  fn download_partial(
+    &self,
+    key: &str,
+    url: &str,
+  ) -> Result<(String, Validators), ValheimModError> {
+    let partial_path = self.partial_path(key);
+    fs::create_dir_all(partial_path.parent().unwrap())
+      .map_err(|e| ValheimModError::DirectoryCreationError(e.to_string()))?;
+    let offset = fs::metadata(&partial_path).map(|m| m.len()).unwrap_or(0);
+
+    let mut request = self.client.get(url);
+    if offset > 0 {
+      debug!("Resuming download of {} from byte {}", url, offset);
+      request = request.header(RANGE, format!("bytes={}-", offset));
+    }
+    let mut response = request
+      .send()
+      .map_err(|e| ValheimModError::DownloadError(e.to_string()))?;
+
+    let mut file = match response.status() {
+      StatusCode::PARTIAL_CONTENT => OpenOptions::new().append(true).open(&partial_path),
+      status if status.is_success() => File::create(&partial_path),
+      StatusCode::RANGE_NOT_SATISFIABLE => {
+        fs::remove_file(&partial_path).ok();
+        return Err(ValheimModError::DownloadError(String::from(
+          "Server rejected the resume request, restarting download",
+        )));
+      }
+      // Asking again won't change the answer, unless the server asks to wait.
+      status
+        if status.is_client_error()
+          && status != StatusCode::REQUEST_TIMEOUT
+          && status != StatusCode::TOO_MANY_REQUESTS =>
+      {
+        return Err(ValheimModError::DownloadRejected(format!(
+          "{} for {}",
+          status, url
+        )))
+      }
+      status => {
+        return Err(ValheimModError::DownloadError(format!(
+          "Unexpected status {}",
+          status
+        )))
+      }
+    }
+    .map_err(|e| ValheimModError::FileCreateError(e.to_string()))?;
+
+    let final_url = response.url().to_string();
+    let validators = Validators::from_headers(response.headers());
+    response
+      .copy_to(&mut file)
+      .map_err(|e| ValheimModError::DownloadError(e.to_string()))?;
+    Ok((final_url, validators))
+  }
+
+  /// Fetches the url into the cache, or returns the cached copy if there is one.
+  /// When an expected hash is supplied the download is rejected if it does not match.
+  pub fn fetch(
+    &self,
+    url: &str,
+    expected_sha256: Option<&str>,
+  ) -> Result<CachedDownload, ValheimModError> {
+    self.fetch_entry(url, url, expected_sha256, false)
+  }
+
+  /// Fetches the file `key` always names, like `nexus:<mod_id>/<file_id>`, from a url that can
+  /// differ between downloads, like the expiring download links of Nexus.
+  pub fn fetch_as(
+    &self,
+    key: &str,
+    url: &str,
+    expected_sha256: Option<&str>,
+  ) -> Result<CachedDownload, ValheimModError> {
+    self.fetch_entry(key, url, expected_sha256, true)
+  }
+
+  /// The cached copy of the file `key` always names, without asking anyone.
+  pub fn cached_as(&self, key: &str, expected_sha256: Option<&str>) -> Option<CachedDownload> {
+    let expected_sha256 = expected_sha256.map(|hash| hash.to_ascii_lowercase());
+    self.lookup(key, expected_sha256.as_deref(), true)
+  }
+
+  fn fetch_entry(
+    &self,
+    key: &str,
+    url: &str,
+    expected_sha256: Option<&str>,
+    immutable: bool,
+  ) -> Result<CachedDownload, ValheimModError> {
+    let expected_sha256 = expected_sha256.map(|hash| hash.to_ascii_lowercase());
+    if let Some(cached) = self.lookup(key, expected_sha256.as_deref(), immutable) {
+      info!("Using cached download for {}", key);
+      return Ok(cached);
+    }
+
+    let mut attempt = 0;
+    let (final_url, validators) = loop {
+      match self.download_partial(key, url) {
+        Ok(downloaded) => break downloaded,
+        Err(e) if attempt < self.retries && !matches!(e, ValheimModError::DownloadRejected(_)) => {
+          let delay = self.backoff * 2u32.pow(attempt);
+          attempt += 1;
+          warn!(
+            "Download attempt {} of {} failed: {}. Retrying in {:?}...",
+            attempt,
+            self.retries + 1,
+            e,
+            delay
+          );
+          thread::sleep(delay);
+        }
+        Err(e) => return Err(e),
+      }
+    };
+
+    let partial_path = self.partial_path(key);
+    let sha256 =
+      sha256_file(&partial_path).map_err(|e| ValheimModError::FileOpenError(e.to_string()))?;
+    if let Some(expected) = expected_sha256 {
+      if expected != sha256 {
+        fs::remove_file(&partial_path).ok();
+        return Err(ValheimModError::ChecksumMismatch(expected, sha256));
+      }
+    }
+
+    let blob_path = self.blob_path(&sha256);
+    fs::create_dir_all(blob_path.parent().unwrap())
+      .and_then(|_| fs::rename(&partial_path, &blob_path))
+      .map_err(|e| ValheimModError::FileMoveError(e.to_string()))?;
+    let entry = CacheEntry {
+      url: key.to_string(),
+      final_url,
+      sha256,
+      validators,
+    };
+    if let Err(e) = self.write_entry(&entry) {
+      warn!("Failed to index cached download for {}: {}", key, e);
+    }
+
+    Ok(CachedDownload {
+      path: blob_path,
+      url: entry.final_url,
+      sha256: entry.sha256,
+    })
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use tempfile::tempdir;
+
+  const CONTENT: &str = "Im a teapot";
+  const MISMATCHED_SHA256: &str =
+    "0000000000000000000000000000000000000000000000000000000000000000";
+
+  fn content_sha256() -> String {
+    format!("{:x}", Sha256::digest(CONTENT.as_bytes()))
+  }
+
+  fn cache(dir: &Path) -> DownloadCache {
+    DownloadCache::new(Client::new(), dir.into()).with_retries(2, Duration::from_millis(1))
+  }
+
+  #[test]
+  fn test_split_sha256() {
+    assert_eq!(
+      split_sha256("https://example.com/mod.zip#sha256=ABC123"),
+      ("https://example.com/mod.zip", Some(String::from("abc123")))
+    );
+    assert_eq!(
+      split_sha256("RandyKnapp-EpicLoot-0.10.3"),
+      ("RandyKnapp-EpicLoot-0.10.3", None)
+    );
+  }
+
+  #[test]
+  fn test_fetch_reuses_cached_download() {
+    let mut server = mockito::Server::new();
+    let not_modified = server
+      .mock("HEAD", "/mod.zip")
+      .match_header("if-none-match", "\"v1\"")
+      .with_status(304)
+      .expect(1)
+      .create();
+    let mock = server
+      .mock("GET", "/mod.zip")
+      .with_status(200)
+      .with_header("etag", "\"v1\"")
+      .with_body(CONTENT)
+      .expect(1)
+      .create();
+    let dir = tempdir().unwrap();
+    let cache = cache(dir.path());
+    let url = format!("{}/mod.zip", server.url());
+
+    let first = cache.fetch(&url, None).unwrap();
+    let second = cache.fetch(&url, None).unwrap();
+    mock.assert();
+    not_modified.assert();
+    assert_eq!(first.sha256, content_sha256());
+    assert_eq!(first.path, second.path);
+    assert_eq!(fs::read_to_string(second.path).unwrap(), CONTENT);
+  }
+
+  #[test]
+  fn test_fetch_downloads_changed_file_again() {
+    let mut server = mockito::Server::new();
+    let first = server
+      .mock("GET", "/releases/latest/mod.zip")
+      .with_status(200)
+      .with_header("last-modified", "Mon, 01 Jan 2024 00:00:00 GMT")
+      .with_body(CONTENT)
+      .expect(1)
+      .create();
+    let dir = tempdir().unwrap();
+    let cache = cache(dir.path());
+    let url = format!("{}/releases/latest/mod.zip", server.url());
+    cache.fetch(&url, None).unwrap();
+    first.assert();
+    first.remove();
+
+    // Asked whether it changed, the server serves another version, which is then downloaded once.
+    let head = server
+      .mock("HEAD", "/releases/latest/mod.zip")
+      .with_status(200)
+      .with_header("last-modified", "Tue, 02 Jan 2024 00:00:00 GMT")
+      .expect(1)
+      .create();
+    let changed = server
+      .mock("GET", "/releases/latest/mod.zip")
+      .with_status(200)
+      .with_body("Im a newer teapot")
+      .expect(1)
+      .create();
+    let download = cache.fetch(&url, None).unwrap();
+    head.assert();
+    changed.assert();
+    assert_eq!(
+      fs::read_to_string(download.path).unwrap(),
+      "Im a newer teapot"
+    );
+  }
+
+  #[test]
+  fn test_fetch_reuses_download_the_server_still_serves() {
+    let mut server = mockito::Server::new();
+    let mock = server
+      .mock("GET", "/mod.zip")
+      .with_status(200)
+      .with_header("etag", "\"v1\"")
+      .with_body(CONTENT)
+      .expect(1)
+      .create();
+    // A server ignoring the conditional request, with the version it serves unchanged.
+    let head = server
+      .mock("HEAD", "/mod.zip")
+      .with_status(200)
+      .with_header("etag", "\"v1\"")
+      .expect(1)
+      .create();
+    let dir = tempdir().unwrap();
+    let cache = cache(dir.path());
+    let url = format!("{}/mod.zip", server.url());
+
+    cache.fetch(&url, None).unwrap();
+    cache.fetch(&url, None).unwrap();
+    mock.assert();
+    head.assert();
+  }
+
+  #[test]
+  fn test_fetch_pinned_download_skips_revalidation() {
+    let mut server = mockito::Server::new();
+    let mock = server
+      .mock("GET", "/mod.zip")
+      .with_status(200)
+      .with_body(CONTENT)
+      .expect(1)
+      .create();
+    let dir = tempdir().unwrap();
+    let cache = cache(dir.path());
+    let url = format!("{}/mod.zip", server.url());
+
+    cache.fetch(&url, Some(&content_sha256())).unwrap();
+    cache.fetch(&url, Some(&content_sha256())).unwrap();
+    mock.assert();
+  }
+
+  #[test]
+  fn test_fetch_as_ignores_changing_urls() {
+    let mut server = mockito::Server::new();
+    let mock = server
+      .mock("GET", "/mod.zip")
+      .match_query(mockito::Matcher::Any)
+      .with_status(200)
+      .with_body(CONTENT)
+      .expect(1)
+      .create();
+    let dir = tempdir().unwrap();
+    let cache = cache(dir.path());
+
+    let first_url = format!("{}/mod.zip?md5=abc&expires=1", server.url());
+    let second_url = format!("{}/mod.zip?md5=def&expires=2", server.url());
+    let first = cache.fetch_as("nexus:387/1234", &first_url, None).unwrap();
+    let second = cache.fetch_as("nexus:387/1234", &second_url, None).unwrap();
+    mock.assert();
+    assert_eq!(first.path, second.path);
+  }
+
+  #[test]
+  fn test_fetch_rejects_checksum_mismatch() {
+    let mut server = mockito::Server::new();
+    server
+      .mock("GET", "/mod.zip")
+      .with_status(200)
+      .with_body(CONTENT)
+      .create();
+    let dir = tempdir().unwrap();
+    let url = format!("{}/mod.zip", server.url());
+
+    let result = cache(dir.path()).fetch(&url, Some(MISMATCHED_SHA256));
+    assert!(matches!(
+      result,
+      Err(ValheimModError::ChecksumMismatch(_, _))
+    ));
+    assert!(!dir.path().join("blobs").join(MISMATCHED_SHA256).exists());
+  }
+
+  #[test]
+  fn test_fetch_retries_failed_download() {
+    let mut server = mockito::Server::new();
+    let failure = server
+      .mock("GET", "/mod.zip")
+      .with_status(500)
+      .expect(1)
+      .create();
+    let success = server
+      .mock("GET", "/mod.zip")
+      .with_status(200)
+      .with_body(CONTENT)
+      .create();
+    let dir = tempdir().unwrap();
+    let url = format!("{}/mod.zip", server.url());
+
+    let download = cache(dir.path())
+      .fetch(&url, Some(&content_sha256()))
+      .unwrap();
+    failure.assert();
+    success.assert();
+    assert_eq!(fs::read_to_string(download.path).unwrap(), CONTENT);
+  }
+
+  #[test]
+  fn test_fetch_does_not_retry_rejected_download() {
+    let mut server = mockito::Server::new();
+    let missing = server
+      .mock("GET", "/mod.zip")
+      .with_status(404)
+      .expect(1)
+      .create();
+    let dir = tempdir().unwrap();
+    let url = format!("{}/mod.zip", server.url());
+
+    let result = cache(dir.path()).fetch(&url, None);
+    missing.assert();
+    assert!(matches!(result, Err(ValheimModError::DownloadRejected(_))));
+  }
+
+  #[test]
+  fn test_fetch_resumes_partial_download() {
+    let mut server = mockito::Server::new();
+    let mock = server
+      .mock("GET", "/mod.zip")
+      .match_header("range", "bytes=4-")
+      .with_status(206)
+      .with_body(&CONTENT[4..])
+      .create();
+    let dir = tempdir().unwrap();
+    let cache = cache(dir.path());
+    let url = format!("{}/mod.zip", server.url());
+    let partial_path = cache.partial_path(&url);
+    fs::create_dir_all(partial_path.parent().unwrap()).unwrap();
+    fs::write(&partial_path, &CONTENT[..4]).unwrap();
+
+    let download = cache.fetch(&url, None).unwrap();
+    mock.assert();
+    assert_eq!(download.sha256, content_sha256());
+    assert!(!partial_path.exists());
+  }
+}
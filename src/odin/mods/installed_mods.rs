@@ -1,24 +1,17 @@
-use crate::constants::MODS_LOCATION;
 use crate::mods::manifest::Manifest;
+use crate::utils::common_paths;
 use glob::glob;
 use log::{debug, error};
 use serde_json::from_reader;
-use std::env;
 use std::fs::File;
 use std::io::BufReader;
 
+/// The manifests of the mods installed in the BepInEx plugin directory.
 pub fn installed_mods() -> Vec<Manifest> {
-  // Retrieve the MODS_LOCATION environment variable
-  let mods_location = match env::var(MODS_LOCATION) {
-    Ok(path) => path,
-    Err(e) => {
-      error!("Failed to read MODS_LOCATION environment variable: {}", e);
-      return vec![];
-    }
-  };
+  let plugins = common_paths::bepinex_plugin_directory();
 
   // Construct the glob pattern to find all manifest.json files
-  let pattern = format!("{}/**/manifest.json", mods_location);
+  let pattern = format!("{}/**/manifest.json", plugins);
 
   // Use glob to find files matching the pattern
   let paths = match glob(&pattern) {
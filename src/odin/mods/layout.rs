@@ -0,0 +1,241 @@
+use crate::errors::ValheimModError;
+use flate2::read::GzDecoder;
+use log::debug;
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use walkdir::WalkDir;
+use zip::ZipArchive;
+
+const ZIP_MAGIC: &[u8] = b"PK";
+const GZIP_MAGIC: &[u8] = &[0x1F, 0x8B];
+const SEVEN_ZIP_MAGIC: &[u8] = &[0x37, 0x7A, 0xBC, 0xAF, 0x27, 0x1C];
+const TAR_MAGIC: &[u8] = b"ustar";
+const TAR_MAGIC_OFFSET: usize = 257;
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum ArchiveFormat {
+  Zip,
+  Tar,
+  TarGz,
+  SevenZip,
+}
+
+impl ArchiveFormat {
+  /// Detects the archive format from the file header, download urls rarely say what they are.
+  pub fn detect(path: &Path) -> Result<ArchiveFormat, ValheimModError> {
+    let mut header = Vec::with_capacity(TAR_MAGIC_OFFSET + TAR_MAGIC.len());
+    File::open(path)
+      .map_err(|e| ValheimModError::FileOpenError(e.to_string()))?
+      .take((TAR_MAGIC_OFFSET + TAR_MAGIC.len()) as u64)
+      .read_to_end(&mut header)
+      .map_err(|e| ValheimModError::FileOpenError(e.to_string()))?;
+
+    if header.starts_with(ZIP_MAGIC) {
+      Ok(ArchiveFormat::Zip)
+    } else if header.starts_with(GZIP_MAGIC) {
+      Ok(ArchiveFormat::TarGz)
+    } else if header.starts_with(SEVEN_ZIP_MAGIC) {
+      Ok(ArchiveFormat::SevenZip)
+    } else if header.get(TAR_MAGIC_OFFSET..) == Some(TAR_MAGIC) {
+      Ok(ArchiveFormat::Tar)
+    } else {
+      Err(ValheimModError::ExtractionError(format!(
+        "Unsupported archive format: {:?}",
+        path
+      )))
+    }
+  }
+
+  pub fn extract(&self, archive: &Path, destination: &Path) -> Result<(), ValheimModError> {
+    debug!("Extracting {:?} archive {:?}", self, archive);
+    let file = File::open(archive).map_err(|e| ValheimModError::FileOpenError(e.to_string()))?;
+    match self {
+      ArchiveFormat::Zip => ZipArchive::new(file)
+        .map_err(|e| ValheimModError::ZipArchiveError(e.to_string()))?
+        .extract(destination)
+        .map_err(|e| ValheimModError::ExtractionError(e.to_string())),
+      ArchiveFormat::Tar => tar::Archive::new(file)
+        .unpack(destination)
+        .map_err(|e| ValheimModError::ExtractionError(e.to_string())),
+      ArchiveFormat::TarGz => tar::Archive::new(GzDecoder::new(file))
+        .unpack(destination)
+        .map_err(|e| ValheimModError::ExtractionError(e.to_string())),
+      ArchiveFormat::SevenZip => sevenz_rust::decompress(file, destination)
+        .map_err(|e| ValheimModError::ExtractionError(e.to_string())),
+    }
+  }
+}
+
+/// How the contents of an extracted mod archive are laid out.
+#[derive(Debug, PartialEq, Eq)]
+pub enum ModLayout {
+  /// A mod framework like BepInEx, installed into the game directory.
+  Framework,
+  /// A Thunderstore package with a `manifest.json` at its root.
+  Thunderstore,
+  /// An archive mirroring the BepInEx directory, like most Nexus mods.
+  /// Holds the extracted directory which maps onto `BepInEx`.
+  BepInExTree(PathBuf),
+  /// Anything else, installed into a plugin directory of its own.
+  Loose,
+}
+
+impl ModLayout {
+  /// Detects the layout of a mod which is not a framework.
+  pub fn detect(extract_path: &Path) -> ModLayout {
+    if extract_path.join("manifest.json").exists() {
+      ModLayout::Thunderstore
+    } else if let Some(root) = find_bepinex_root(extract_path) {
+      ModLayout::BepInExTree(root)
+    } else {
+      ModLayout::Loose
+    }
+  }
+}
+
+fn find_bepinex_root(extract_path: &Path) -> Option<PathBuf> {
+  let nested = WalkDir::new(extract_path)
+    .min_depth(1)
+    .max_depth(3)
+    .into_iter()
+    .flatten()
+    .find(|entry| entry.file_type().is_dir() && entry.file_name().eq_ignore_ascii_case("BepInEx"));
+  if let Some(entry) = nested {
+    return Some(entry.into_path());
+  }
+
+  ["plugins", "config"]
+    .iter()
+    .any(|name| extract_path.join(name).is_dir())
+    .then(|| extract_path.to_path_buf())
+}
+
+/// Derives a mod name from a downloaded file, e.g. `EpicLoot.tar.gz` becomes `EpicLoot`.
+pub fn mod_name_from_file(path: &Path) -> String {
+  let file_name = path
+    .file_name()
+    .map(|name| name.to_string_lossy().to_string())
+    .unwrap_or_default();
+  let mut name = file_name.as_str();
+  for extension in [".gz", ".tgz", ".tar", ".zip", ".7z", ".dll", ".cfg"] {
+    if name.len() > extension.len() && name.to_lowercase().ends_with(extension) {
+      name = &name[..name.len() - extension.len()];
+    }
+  }
+  name.to_string()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use std::fs;
+  use std::io::Write;
+  use tempfile::tempdir;
+  use zip::write::SimpleFileOptions;
+
+  fn write_zip(path: &Path, files: &[&str]) {
+    let mut zip = zip::ZipWriter::new(File::create(path).unwrap());
+    for file in files {
+      zip
+        .start_file(file.to_string(), SimpleFileOptions::default())
+        .unwrap();
+      zip.write_all(b"content").unwrap();
+    }
+    zip.finish().unwrap();
+  }
+
+  #[test]
+  fn test_detect_and_extract_formats() {
+    let dir = tempdir().unwrap();
+    let source = dir.path().join("source");
+    fs::create_dir_all(source.join("plugins")).unwrap();
+    fs::write(source.join("plugins").join("Mod.dll"), "content").unwrap();
+
+    let zip_path = dir.path().join("mod.zip");
+    write_zip(&zip_path, &["plugins/Mod.dll"]);
+
+    let tar_path = dir.path().join("mod.tar");
+    let mut tar = tar::Builder::new(File::create(&tar_path).unwrap());
+    tar.append_dir_all(".", &source).unwrap();
+    tar.finish().unwrap();
+
+    let tar_gz_path = dir.path().join("mod.tar.gz");
+    let encoder = flate2::write::GzEncoder::new(
+      File::create(&tar_gz_path).unwrap(),
+      flate2::Compression::default(),
+    );
+    let mut tar_gz = tar::Builder::new(encoder);
+    tar_gz.append_dir_all(".", &source).unwrap();
+    tar_gz.into_inner().unwrap().finish().unwrap();
+
+    let seven_zip_path = dir.path().join("mod.7z");
+    sevenz_rust::compress_to_path(&source, &seven_zip_path).unwrap();
+
+    for (path, format) in [
+      (&zip_path, ArchiveFormat::Zip),
+      (&tar_path, ArchiveFormat::Tar),
+      (&tar_gz_path, ArchiveFormat::TarGz),
+      (&seven_zip_path, ArchiveFormat::SevenZip),
+    ] {
+      let detected = ArchiveFormat::detect(path).unwrap();
+      assert_eq!(detected, format);
+      let destination = tempdir().unwrap();
+      detected.extract(path, destination.path()).unwrap();
+      assert!(
+        destination.path().join("plugins").join("Mod.dll").exists(),
+        "{:?} was not extracted",
+        format
+      );
+    }
+  }
+
+  #[test]
+  fn test_detect_unsupported_format() {
+    let dir = tempdir().unwrap();
+    let path = dir.path().join("mod.rar");
+    fs::write(&path, "Rar!").unwrap();
+    assert!(ArchiveFormat::detect(&path).is_err());
+  }
+
+  #[test]
+  fn test_detect_layout() {
+    let thunderstore = tempdir().unwrap();
+    fs::write(thunderstore.path().join("manifest.json"), "{}").unwrap();
+    fs::create_dir_all(thunderstore.path().join("plugins")).unwrap();
+    assert_eq!(
+      ModLayout::detect(thunderstore.path()),
+      ModLayout::Thunderstore
+    );
+
+    let nexus = tempdir().unwrap();
+    let bepinex = nexus.path().join("MyMod").join("BepInEx");
+    fs::create_dir_all(bepinex.join("plugins")).unwrap();
+    assert_eq!(
+      ModLayout::detect(nexus.path()),
+      ModLayout::BepInExTree(bepinex)
+    );
+
+    let flat = tempdir().unwrap();
+    fs::create_dir_all(flat.path().join("config")).unwrap();
+    assert_eq!(
+      ModLayout::detect(flat.path()),
+      ModLayout::BepInExTree(flat.path().to_path_buf())
+    );
+
+    let loose = tempdir().unwrap();
+    fs::write(loose.path().join("Mod.dll"), "content").unwrap();
+    assert_eq!(ModLayout::detect(loose.path()), ModLayout::Loose);
+  }
+
+  #[test]
+  fn test_mod_name_from_file() {
+    assert_eq!(
+      mod_name_from_file(Path::new("/tmp/EpicLoot.zip")),
+      "EpicLoot"
+    );
+    assert_eq!(mod_name_from_file(Path::new("Mod-1.0.tar.gz")), "Mod-1.0");
+    assert_eq!(mod_name_from_file(Path::new("Plugin.DLL")), "Plugin");
+    assert_eq!(mod_name_from_file(Path::new("BepInEx.cfg")), "BepInEx");
+  }
+}
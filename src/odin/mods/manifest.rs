@@ -1,15 +1,56 @@
 use crate::errors::ValheimModError::ManifestDeserializeError;
+use crate::mods::nexus::NexusMetadata;
 use log::debug;
 use serde::{Deserialize, Serialize};
 use std::fs::File;
 use std::io::Read;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, Default)]
 pub struct Manifest {
   pub name: String,
   pub dependencies: Option<Vec<String>>,
   pub version_number: Option<String>,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub website_url: Option<String>,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub nexus: Option<NexusMetadata>,
+  /// Set to keep a mod out of the client modpack, e.g. for server side admin tools.
+  #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+  pub server_only: bool,
+}
+
+impl Manifest {
+  /// Creates a manifest for mods which don't ship one, like bare DLLs or Nexus archives.
+  pub fn synthesized(name: &str, source_url: &str) -> Manifest {
+    Manifest {
+      name: name.to_string(),
+      dependencies: None,
+      version_number: None,
+      website_url: Some(source_url.to_string()),
+      nexus: None,
+      server_only: false,
+    }
+  }
+
+  /// Records where a mod installed from Nexus came from.
+  pub fn with_nexus(mut self, nexus: &NexusMetadata) -> Manifest {
+    self.version_number = nexus.version.clone();
+    self.website_url = Some(nexus.mod_page_url());
+    self.nexus = Some(nexus.clone());
+    self
+  }
+
+  /// Writes the manifest into the directory unless one already exists.
+  pub fn write_to(&self, directory: &Path) -> std::io::Result<()> {
+    let path = directory.join("manifest.json");
+    if path.exists() {
+      debug!("Manifest already exists at {:?}", path);
+      return Ok(());
+    }
+    std::fs::create_dir_all(directory)?;
+    std::fs::write(path, serde_json::to_string_pretty(self)?)
+  }
 }
 
 impl TryFrom<PathBuf> for Manifest {
@@ -1,5 +1,15 @@
 pub mod bepinex;
+pub mod bepinex_config;
+pub mod bepinex_pack;
+pub mod download_cache;
+mod installed_mods;
+mod layout;
 mod manifest;
+pub mod modpack;
+pub mod nexus;
+pub mod outdated;
+pub mod thunderstore;
+pub mod transaction;
 mod valheim_mod;
 
 pub use valheim_mod::*;
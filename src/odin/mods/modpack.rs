@@ -0,0 +1,483 @@
+use crate::errors::ValheimModError;
+use crate::mods::manifest::Manifest;
+use crate::mods::thunderstore::{find_package, Package};
+use crate::utils::common_paths::{bepinex_plugin_directory, cache_directory};
+use crate::utils::environment::fetch_var;
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use log::{debug, info, warn};
+use reqwest::blocking::Client;
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File};
+use std::io;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, PoisonError};
+use std::time::SystemTime;
+use tempfile::NamedTempFile;
+use walkdir::WalkDir;
+use zip::write::SimpleFileOptions;
+use zip::ZipWriter;
+
+const THUNDERSTORE_PROFILE_URL_VAR: &str = "THUNDERSTORE_PROFILE_URL";
+const THUNDERSTORE_PROFILE_URL: &str =
+  "https://thunderstore.io/api/experimental/legacyprofile/create/";
+const R2MODMAN_PROFILE_HEADER: &str = "#r2modman\n";
+const R2X_FILE: &str = "export.r2x";
+
+static REBUILD: Mutex<()> = Mutex::new(());
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum ModpackFormat {
+  /// A zip of the plugins, extracted into the game directory by players
+  Zip,
+  /// An r2modman profile, imported through "Import / update profile"
+  R2modman,
+}
+
+/// An installed mod which players need as well.
+pub struct ClientMod {
+  pub directory: PathBuf,
+  pub manifest: Manifest,
+}
+
+#[derive(Debug, Deserialize, Serialize, PartialEq, Eq)]
+struct R2Version {
+  major: u64,
+  minor: u64,
+  patch: u64,
+}
+
+#[derive(Debug, Deserialize, Serialize, PartialEq, Eq)]
+struct R2Mod {
+  name: String,
+  version: R2Version,
+  enabled: bool,
+}
+
+#[derive(Debug, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+struct R2Profile {
+  profile_name: String,
+  mods: Vec<R2Mod>,
+}
+
+#[derive(Deserialize)]
+struct ProfileCodeResponse {
+  key: String,
+}
+
+pub fn modpack_directory() -> PathBuf {
+  PathBuf::from(cache_directory()).join("modpack")
+}
+
+/// Where the client zip is written, huginn serves it from here.
+pub fn modpack_zip_path() -> PathBuf {
+  modpack_directory().join("modpack.zip")
+}
+
+/// Lists the installed mods with a manifest, leaving out the ones marked as `server_only`.
+pub fn client_mods(plugin_dir: &Path) -> Vec<ClientMod> {
+  let mut directories: Vec<PathBuf> = fs::read_dir(plugin_dir)
+    .map(|entries| {
+      entries
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| path.is_dir())
+        .collect()
+    })
+    .unwrap_or_default();
+  directories.sort();
+
+  directories
+    .into_iter()
+    .filter_map(
+      |directory| match Manifest::try_from(directory.join("manifest.json")) {
+        Ok(manifest) if manifest.server_only => {
+          debug!(
+            "Leaving server only mod {} out of the modpack",
+            manifest.name
+          );
+          None
+        }
+        Ok(manifest) => Some(ClientMod {
+          directory,
+          manifest,
+        }),
+        Err(e) => {
+          warn!("Leaving {:?} out of the modpack: {}", directory, e);
+          None
+        }
+      },
+    )
+    .collect()
+}
+
+fn zip_error(e: impl ToString) -> ValheimModError {
+  ValheimModError::ModpackError(e.to_string())
+}
+
+/// Starts a zip in a temporary file next to `output`, `finish_output` moves it into place once it
+/// is complete, so a download running meanwhile never gets a partial zip.
+fn create_output(output: &Path) -> Result<ZipWriter<NamedTempFile>, ValheimModError> {
+  let parent = match output.parent() {
+    Some(parent) if !parent.as_os_str().is_empty() => parent,
+    _ => Path::new("."),
+  };
+  fs::create_dir_all(parent).map_err(|e| ValheimModError::DirectoryCreationError(e.to_string()))?;
+  let file =
+    NamedTempFile::new_in(parent).map_err(|e| ValheimModError::FileCreateError(e.to_string()))?;
+  Ok(ZipWriter::new(file))
+}
+
+fn finish_output(zip: ZipWriter<NamedTempFile>, output: &Path) -> Result<(), ValheimModError> {
+  let file = zip.finish().map_err(zip_error)?;
+  fs::set_permissions(file.path(), fs::Permissions::from_mode(0o644))
+    .map_err(|e| ValheimModError::FileCreateError(e.to_string()))?;
+  file
+    .persist(output)
+    .map_err(|e| ValheimModError::FileMoveError(e.to_string()))?;
+  Ok(())
+}
+
+/// Writes a zip mirroring `BepInEx/plugins` which players can extract into their game directory.
+pub fn write_zip(mods: &[ClientMod], output: &Path) -> Result<(), ValheimModError> {
+  let mut zip = create_output(output)?;
+  for client_mod in mods {
+    let name = client_mod.directory.file_name().unwrap().to_string_lossy();
+    for entry in WalkDir::new(&client_mod.directory).into_iter().flatten() {
+      if !entry.file_type().is_file() {
+        continue;
+      }
+      let relative = entry
+        .path()
+        .strip_prefix(&client_mod.directory)
+        .map_err(zip_error)?
+        .to_string_lossy()
+        .replace('\\', "/");
+      zip
+        .start_file(
+          format!("BepInEx/plugins/{}/{}", name, relative),
+          SimpleFileOptions::default(),
+        )
+        .map_err(zip_error)?;
+      let mut file =
+        File::open(entry.path()).map_err(|e| ValheimModError::FileOpenError(e.to_string()))?;
+      io::copy(&mut file, &mut zip).map_err(zip_error)?;
+    }
+  }
+  finish_output(zip, output)?;
+  info!("Wrote modpack with {} mod(s) to {:?}", mods.len(), output);
+  Ok(())
+}
+
+fn parse_version(version: &str) -> Option<R2Version> {
+  let mut parts = version.split('.').map(|part| part.parse::<u64>().ok());
+  Some(R2Version {
+    major: parts.next()??,
+    minor: parts.next().unwrap_or(Some(0))?,
+    patch: parts.next().unwrap_or(Some(0))?,
+  })
+}
+
+/// Builds an r2modman profile, which refers to mods by their Thunderstore name.
+/// Mods which can't be found on Thunderstore, like the ones from Nexus, are left out.
+fn r2_profile(profile_name: &str, mods: &[ClientMod], index: &[Package]) -> R2Profile {
+  let mods = mods
+    .iter()
+    .filter_map(|client_mod| {
+      let manifest = &client_mod.manifest;
+      let package = match manifest.nexus {
+        Some(_) => None,
+        None => find_package(index, &manifest.name),
+      };
+      let version = manifest.version_number.as_deref().and_then(parse_version);
+      match (package, version) {
+        (Some(package), Some(version)) => Some(R2Mod {
+          name: package.full_name.clone(),
+          version,
+          enabled: true,
+        }),
+        _ => {
+          warn!(
+            "Leaving {} out of the r2modman profile, it is not a versioned Thunderstore mod",
+            manifest.name
+          );
+          None
+        }
+      }
+    })
+    .collect();
+  R2Profile {
+    profile_name: profile_name.to_string(),
+    mods,
+  }
+}
+
+/// Writes an r2modman profile export (`.r2z`).
+pub fn write_r2z(
+  profile_name: &str,
+  mods: &[ClientMod],
+  index: &[Package],
+  output: &Path,
+) -> Result<(), ValheimModError> {
+  let profile = r2_profile(profile_name, mods, index);
+  let content = serde_yaml::to_string(&profile).map_err(zip_error)?;
+  let mut zip = create_output(output)?;
+  zip
+    .start_file(R2X_FILE, SimpleFileOptions::default())
+    .map_err(zip_error)?;
+  io::Write::write_all(&mut zip, content.as_bytes()).map_err(zip_error)?;
+  finish_output(zip, output)?;
+  info!(
+    "Wrote r2modman profile with {} mod(s) to {:?}",
+    profile.mods.len(),
+    output
+  );
+  Ok(())
+}
+
+pub fn profile_code_url() -> String {
+  fetch_var(THUNDERSTORE_PROFILE_URL_VAR, THUNDERSTORE_PROFILE_URL)
+}
+
+/// Uploads an r2modman profile to Thunderstore and returns the code players import it with.
+pub fn create_profile_code(
+  client: &Client,
+  url: &str,
+  r2z: &Path,
+) -> Result<String, ValheimModError> {
+  let content = fs::read(r2z).map_err(|e| ValheimModError::FileOpenError(e.to_string()))?;
+  debug!("Uploading profile to {}", url);
+  let response = client
+    .post(url)
+    .header(reqwest::header::CONTENT_TYPE, "application/octet-stream")
+    .body(format!(
+      "{}{}",
+      R2MODMAN_PROFILE_HEADER,
+      STANDARD.encode(content)
+    ))
+    .send()
+    .map_err(zip_error)?;
+  if !response.status().is_success() {
+    return Err(ValheimModError::ModpackError(format!(
+      "Thunderstore rejected the profile with status {}",
+      response.status()
+    )));
+  }
+  let response: ProfileCodeResponse = response.json().map_err(zip_error)?;
+  Ok(response.key)
+}
+
+fn last_modified(path: &Path) -> Option<SystemTime> {
+  WalkDir::new(path)
+    .into_iter()
+    .flatten()
+    .filter_map(|entry| entry.metadata().ok()?.modified().ok())
+    .max()
+}
+
+/// Returns the client zip, building it again if a plugin changed since it was written.
+pub fn ensure_client_zip(plugin_dir: &Path, output: &Path) -> Result<PathBuf, ValheimModError> {
+  // One rebuild at a time, the requests waiting on it then find the zip up to date.
+  let _rebuild = REBUILD.lock().unwrap_or_else(PoisonError::into_inner);
+  let written = fs::metadata(output)
+    .and_then(|metadata| metadata.modified())
+    .ok();
+  if written.is_some() && written >= last_modified(plugin_dir) {
+    return Ok(output.to_path_buf());
+  }
+
+  let mods = client_mods(plugin_dir);
+  if mods.is_empty() {
+    return Err(ValheimModError::ModpackError(String::from(
+      "No client mods are installed",
+    )));
+  }
+  write_zip(&mods, output)?;
+  Ok(output.to_path_buf())
+}
+
+/// The client zip for the installed plugins, as served by huginn.
+pub fn client_modpack() -> Result<PathBuf, ValheimModError> {
+  ensure_client_zip(Path::new(&bepinex_plugin_directory()), &modpack_zip_path())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::mods::thunderstore::PackageVersion;
+  use tempfile::tempdir;
+  use zip::ZipArchive;
+
+  fn install(plugin_dir: &Path, name: &str, manifest: &str) {
+    let directory = plugin_dir.join(name);
+    fs::create_dir_all(&directory).unwrap();
+    fs::write(directory.join(format!("{}.dll", name)), "dll").unwrap();
+    fs::write(directory.join("manifest.json"), manifest).unwrap();
+  }
+
+  fn plugins() -> tempfile::TempDir {
+    let plugin_dir = tempdir().unwrap();
+    install(
+      plugin_dir.path(),
+      "Jotunn",
+      r#"{"name": "Jotunn", "version_number": "2.23.2"}"#,
+    );
+    install(
+      plugin_dir.path(),
+      "EpicLoot",
+      r#"{"name": "Epic Loot", "version_number": "0.10.3", "nexus": {"mod_id": 387, "file_id": 1, "name": "Epic Loot", "file_name": "EpicLoot.zip"}}"#,
+    );
+    install(
+      plugin_dir.path(),
+      "ServerDevcommands",
+      r#"{"name": "ServerDevcommands", "version_number": "1.0.0", "server_only": true}"#,
+    );
+    fs::create_dir_all(plugin_dir.path().join("NoManifest")).unwrap();
+    plugin_dir
+  }
+
+  #[test]
+  fn test_client_mods_skips_server_only() {
+    let plugin_dir = plugins();
+    let names: Vec<String> = client_mods(plugin_dir.path())
+      .into_iter()
+      .map(|client_mod| client_mod.manifest.name)
+      .collect();
+    assert_eq!(names, vec!["Epic Loot", "Jotunn"]);
+  }
+
+  #[test]
+  fn test_write_zip() {
+    let plugin_dir = plugins();
+    let output_dir = tempdir().unwrap();
+    let output = output_dir.path().join("modpack.zip");
+    write_zip(&client_mods(plugin_dir.path()), &output).unwrap();
+
+    let archive = ZipArchive::new(File::open(&output).unwrap()).unwrap();
+    let mut names: Vec<String> = archive.file_names().map(String::from).collect();
+    names.sort();
+    assert_eq!(
+      names,
+      vec![
+        "BepInEx/plugins/EpicLoot/EpicLoot.dll",
+        "BepInEx/plugins/EpicLoot/manifest.json",
+        "BepInEx/plugins/Jotunn/Jotunn.dll",
+        "BepInEx/plugins/Jotunn/manifest.json",
+      ]
+    );
+  }
+
+  #[test]
+  fn test_write_r2z() {
+    let plugin_dir = plugins();
+    let output_dir = tempdir().unwrap();
+    let output = output_dir.path().join("modpack.r2z");
+    let index = vec![Package {
+      name: "Jotunn".to_string(),
+      full_name: "ValheimModding-Jotunn".to_string(),
+      owner: "ValheimModding".to_string(),
+      is_deprecated: false,
+      versions: vec![PackageVersion {
+        name: "Jotunn".to_string(),
+        full_name: "ValheimModding-Jotunn-2.23.2".to_string(),
+        version_number: "2.23.2".to_string(),
+        download_url: String::new(),
+      }],
+    }];
+    write_r2z("Odin", &client_mods(plugin_dir.path()), &index, &output).unwrap();
+
+    let mut archive = ZipArchive::new(File::open(&output).unwrap()).unwrap();
+    let profile: R2Profile = serde_yaml::from_reader(archive.by_name(R2X_FILE).unwrap()).unwrap();
+    assert_eq!(
+      profile,
+      R2Profile {
+        profile_name: "Odin".to_string(),
+        mods: vec![R2Mod {
+          name: "ValheimModding-Jotunn".to_string(),
+          version: R2Version {
+            major: 2,
+            minor: 23,
+            patch: 2
+          },
+          enabled: true,
+        }],
+      }
+    );
+  }
+
+  #[test]
+  fn test_create_profile_code() {
+    let mut server = mockito::Server::new();
+    let mock = server
+      .mock("POST", "/api/experimental/legacyprofile/create/")
+      .match_header("content-type", "application/octet-stream")
+      .match_body(mockito::Matcher::Regex(String::from(
+        "^#r2modman\n[A-Za-z0-9+/=]+$",
+      )))
+      .with_body(r#"{"key": "01912345-6789-abcd-ef01-23456789abcd"}"#)
+      .create();
+    let output_dir = tempdir().unwrap();
+    let output = output_dir.path().join("modpack.r2z");
+    write_r2z("Odin", &[], &[], &output).unwrap();
+
+    let code = create_profile_code(
+      &Client::new(),
+      &format!("{}/api/experimental/legacyprofile/create/", server.url()),
+      &output,
+    )
+    .unwrap();
+    mock.assert();
+    assert_eq!(code, "01912345-6789-abcd-ef01-23456789abcd");
+  }
+
+  #[test]
+  fn test_ensure_client_zip_rebuilds_when_stale() {
+    let plugin_dir = plugins();
+    let output_dir = tempdir().unwrap();
+    let output = output_dir.path().join("modpack.zip");
+
+    ensure_client_zip(plugin_dir.path(), &output).unwrap();
+    let first = fs::metadata(&output).unwrap().modified().unwrap();
+    ensure_client_zip(plugin_dir.path(), &output).unwrap();
+    assert_eq!(fs::metadata(&output).unwrap().modified().unwrap(), first);
+
+    std::thread::sleep(std::time::Duration::from_millis(20));
+    install(
+      plugin_dir.path(),
+      "Valheim_Plus",
+      r#"{"name": "Valheim_Plus", "version_number": "0.9.9"}"#,
+    );
+    ensure_client_zip(plugin_dir.path(), &output).unwrap();
+    let archive = ZipArchive::new(File::open(&output).unwrap()).unwrap();
+    assert!(archive
+      .file_names()
+      .any(|name| name.starts_with("BepInEx/plugins/Valheim_Plus/")));
+  }
+
+  #[test]
+  fn test_ensure_client_zip_concurrent_requests() {
+    let plugin_dir = plugins();
+    let output_dir = tempdir().unwrap();
+    let output = output_dir.path().join("modpack.zip");
+
+    std::thread::scope(|scope| {
+      for _ in 0..4 {
+        scope.spawn(|| {
+          let path = ensure_client_zip(plugin_dir.path(), &output).unwrap();
+          assert!(ZipArchive::new(File::open(path).unwrap()).is_ok());
+        });
+      }
+    });
+    let files: Vec<_> = fs::read_dir(output_dir.path()).unwrap().collect();
+    assert_eq!(files.len(), 1, "temporary zips are left behind");
+  }
+
+  #[test]
+  fn test_ensure_client_zip_without_mods() {
+    let plugin_dir = tempdir().unwrap();
+    let output_dir = tempdir().unwrap();
+    assert!(ensure_client_zip(plugin_dir.path(), &output_dir.path().join("modpack.zip")).is_err());
+  }
+}
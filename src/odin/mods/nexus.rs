@@ -0,0 +1,378 @@
+use crate::errors::ValheimModError;
+use crate::files::config::{config_file, read_config};
+use crate::files::settings::Settings;
+use crate::files::FileManager;
+use crate::mods::download_cache::DownloadCache;
+use crate::utils::common_paths::cache_directory;
+use crate::utils::environment::fetch_var;
+use log::{debug, warn};
+use reqwest::blocking::Client;
+use reqwest::StatusCode;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const NEXUS_API_URL_VAR: &str = "NEXUS_API_URL";
+const NEXUS_API_URL: &str = "https://api.nexusmods.com";
+const NEXUS_GAME_DOMAIN: &str = "valheim";
+const NEXUS_PREFIX: &str = "nexus:";
+
+/// Nexus details recorded in the manifest of a mod installed from Nexus.
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Eq)]
+pub struct NexusMetadata {
+  pub mod_id: u64,
+  pub file_id: u64,
+  pub name: String,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub author: Option<String>,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub version: Option<String>,
+  pub file_name: String,
+}
+
+impl NexusMetadata {
+  /// The mod page on Nexus, download links expire so this is what gets recorded instead.
+  pub fn mod_page_url(&self) -> String {
+    format!(
+      "https://www.nexusmods.com/{}/mods/{}",
+      NEXUS_GAME_DOMAIN, self.mod_id
+    )
+  }
+
+  /// The `nexus:<mod_id>/<file_id>` identifier, a file id always names the same file.
+  pub fn id(&self) -> String {
+    format!("{}{}/{}", NEXUS_PREFIX, self.mod_id, self.file_id)
+  }
+}
+
+/// A resolved download link along with the metadata of the file behind it.
+pub struct NexusDownload {
+  pub url: String,
+  pub metadata: NexusMetadata,
+}
+
+#[derive(Deserialize)]
+struct NexusModInfo {
+  name: String,
+  author: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct NexusFileInfo {
+  version: Option<String>,
+  file_name: String,
+}
+
+#[derive(Deserialize)]
+struct NexusDownloadLink {
+  #[serde(rename = "URI")]
+  uri: String,
+}
+
+/// Parses a `nexus:<mod_id>/<file_id>` identifier.
+pub fn parse_nexus_id(input: &str) -> Option<(u64, u64)> {
+  let (mod_id, file_id) = input.strip_prefix(NEXUS_PREFIX)?.split_once('/')?;
+  Some((mod_id.trim().parse().ok()?, file_id.trim().parse().ok()?))
+}
+
+/// The API key comes from `mods.nexus_api_key`, falling back to the one stored by `odin configure`.
+fn api_key() -> Option<String> {
+  Settings::current()
+    .mods
+    .nexus_api_key
+    .or_else(|| {
+      let config = config_file();
+      config
+        .exists()
+        .then(|| read_config(config).nexus_api_key)
+        .flatten()
+    })
+    .filter(|key| !key.is_empty())
+}
+
+fn metadata_path(directory: &Path, mod_id: u64, file_id: u64) -> PathBuf {
+  directory.join(format!("{}-{}.json", mod_id, file_id))
+}
+
+/// Resolves a file of a mod, from the download cache when it has the file. A file id always
+/// names the same file, so installs keep working while the API is down or premium lapsed.
+pub fn nexus_download(
+  mod_id: u64,
+  file_id: u64,
+  sha256: Option<&str>,
+) -> Result<NexusDownload, ValheimModError> {
+  resolve_cached(
+    &NexusClient::default(),
+    &DownloadCache::default(),
+    &PathBuf::from(cache_directory()).join("nexus"),
+    (mod_id, file_id),
+    sha256,
+  )
+}
+
+/// `nexus_download` with the metadata of resolved files kept in `metadata_directory`.
+fn resolve_cached(
+  client: &NexusClient,
+  cache: &DownloadCache,
+  metadata_directory: &Path,
+  (mod_id, file_id): (u64, u64),
+  sha256: Option<&str>,
+) -> Result<NexusDownload, ValheimModError> {
+  let path = metadata_path(metadata_directory, mod_id, file_id);
+  let cached = fs::read_to_string(&path)
+    .ok()
+    .and_then(|content| serde_json::from_str::<NexusMetadata>(&content).ok())
+    .and_then(|metadata| {
+      let download = cache.cached_as(&metadata.id(), sha256)?;
+      Some(NexusDownload {
+        url: download.url,
+        metadata,
+      })
+    });
+  if let Some(download) = cached {
+    debug!("Using the cached download of {}", download.metadata.id());
+    return Ok(download);
+  }
+
+  let download = client.resolve(mod_id, file_id)?;
+  let written = fs::create_dir_all(metadata_directory).and_then(|_| {
+    fs::write(
+      &path,
+      serde_json::to_string_pretty(&download.metadata).unwrap(),
+    )
+  });
+  if let Err(e) = written {
+    warn!(
+      "Failed to cache the details of {}: {}",
+      download.metadata.id(),
+      e
+    );
+  }
+  Ok(download)
+}
+
+/// Client for the Nexus Mods API, used to resolve download links for `nexus:` identifiers.
+pub struct NexusClient {
+  client: Client,
+  base_url: String,
+  api_key: Option<String>,
+}
+
+impl Default for NexusClient {
+  fn default() -> Self {
+    Self::new(
+      Client::new(),
+      &fetch_var(NEXUS_API_URL_VAR, NEXUS_API_URL),
+      api_key(),
+    )
+  }
+}
+
+impl NexusClient {
+  pub fn new(client: Client, base_url: &str, api_key: Option<String>) -> Self {
+    NexusClient {
+      client,
+      base_url: base_url.trim_end_matches('/').to_string(),
+      api_key,
+    }
+  }
+
+  fn get<T: DeserializeOwned>(&self, path: &str) -> Result<T, ValheimModError> {
+    let api_key = self
+      .api_key
+      .as_ref()
+      .ok_or(ValheimModError::NexusApiKeyMissing)?;
+    let url = format!("{}/v1/games/{}/{}", self.base_url, NEXUS_GAME_DOMAIN, path);
+    debug!("Requesting {}", url);
+    let response = self
+      .client
+      .get(&url)
+      .header("apikey", api_key)
+      .header("Application-Name", "odin")
+      .header("Application-Version", env!("CARGO_PKG_VERSION"))
+      .send()
+      .map_err(|e| ValheimModError::NexusApiError(e.to_string()))?;
+
+    match response.status() {
+      status if status.is_success() => response
+        .json()
+        .map_err(|e| ValheimModError::NexusApiError(e.to_string())),
+      StatusCode::UNAUTHORIZED => Err(ValheimModError::NexusApiError(String::from(
+        "The API key was rejected",
+      ))),
+      StatusCode::FORBIDDEN => Err(ValheimModError::NexusApiError(format!(
+        "Access to {} was denied, downloading through the API requires a premium account",
+        path
+      ))),
+      status => Err(ValheimModError::NexusApiError(format!(
+        "Unexpected status {} for {}",
+        status, path
+      ))),
+    }
+  }
+
+  /// Resolves the download link for a file of a mod along with its metadata.
+  pub fn resolve(&self, mod_id: u64, file_id: u64) -> Result<NexusDownload, ValheimModError> {
+    let mod_info: NexusModInfo = self.get(&format!("mods/{}.json", mod_id))?;
+    let file_info: NexusFileInfo = self.get(&format!("mods/{}/files/{}.json", mod_id, file_id))?;
+    let links: Vec<NexusDownloadLink> = self.get(&format!(
+      "mods/{}/files/{}/download_link.json",
+      mod_id, file_id
+    ))?;
+    let link = links.into_iter().next().ok_or_else(|| {
+      ValheimModError::NexusApiError(format!("No download link for {}/{}", mod_id, file_id))
+    })?;
+
+    Ok(NexusDownload {
+      url: link.uri,
+      metadata: NexusMetadata {
+        mod_id,
+        file_id,
+        name: mod_info.name,
+        author: mod_info.author,
+        version: file_info.version,
+        file_name: file_info.file_name,
+      },
+    })
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn client(server: &mockito::Server, api_key: Option<&str>) -> NexusClient {
+    NexusClient::new(Client::new(), &server.url(), api_key.map(String::from))
+  }
+
+  #[test]
+  fn test_parse_nexus_id() {
+    assert_eq!(parse_nexus_id("nexus:387/1234"), Some((387, 1234)));
+    assert_eq!(parse_nexus_id("nexus:387"), None);
+    assert_eq!(parse_nexus_id("nexus:abc/1234"), None);
+    assert_eq!(parse_nexus_id("ValheimModding-Jotunn-2.23.2"), None);
+  }
+
+  #[test]
+  fn test_resolve() {
+    let mut server = mockito::Server::new();
+    let mod_mock = server
+      .mock("GET", "/v1/games/valheim/mods/387.json")
+      .match_header("apikey", "secret")
+      .with_body(r#"{"name": "Epic Loot", "author": "RandyKnapp", "version": "0.10.3"}"#)
+      .create();
+    let file_mock = server
+      .mock("GET", "/v1/games/valheim/mods/387/files/1234.json")
+      .match_header("apikey", "secret")
+      .with_body(
+        r#"{"file_id": 1234, "version": "0.10.3", "file_name": "EpicLoot-387-0-10-3.zip"}"#,
+      )
+      .create();
+    let link_mock = server
+      .mock("GET", "/v1/games/valheim/mods/387/files/1234/download_link.json")
+      .match_header("apikey", "secret")
+      .with_body(r#"[{"name": "Nexus CDN", "short_name": "Nexus CDN", "URI": "https://cf-files.nexusmods.com/cdn/3667/387/EpicLoot-387-0-10-3.zip?md5=abc&expires=1"}]"#)
+      .create();
+
+    let download = client(&server, Some("secret")).resolve(387, 1234).unwrap();
+    mod_mock.assert();
+    file_mock.assert();
+    link_mock.assert();
+    assert!(download.url.starts_with("https://cf-files.nexusmods.com/"));
+    assert_eq!(
+      download.metadata,
+      NexusMetadata {
+        mod_id: 387,
+        file_id: 1234,
+        name: "Epic Loot".to_string(),
+        author: Some("RandyKnapp".to_string()),
+        version: Some("0.10.3".to_string()),
+        file_name: "EpicLoot-387-0-10-3.zip".to_string(),
+      }
+    );
+    assert_eq!(
+      download.metadata.mod_page_url(),
+      "https://www.nexusmods.com/valheim/mods/387"
+    );
+  }
+
+  #[test]
+  fn test_resolve_cached_skips_the_api() {
+    let mut server = mockito::Server::new();
+    let api = server
+      .mock("GET", mockito::Matcher::Regex(String::from("^/v1/")))
+      .expect(0)
+      .create();
+    let file = server
+      .mock("GET", "/EpicLoot.zip")
+      .match_query(mockito::Matcher::Any)
+      .with_body("Im a teapot")
+      .expect(1)
+      .create();
+    let dir = tempfile::tempdir().unwrap();
+    let cache = DownloadCache::new(Client::new(), dir.path().join("downloads"));
+    let metadata = NexusMetadata {
+      mod_id: 387,
+      file_id: 1234,
+      name: "Epic Loot".to_string(),
+      author: None,
+      version: Some("0.10.3".to_string()),
+      file_name: "EpicLoot-387-0-10-3.zip".to_string(),
+    };
+    let url = format!("{}/EpicLoot.zip?expires=1", server.url());
+    cache.fetch_as(&metadata.id(), &url, None).unwrap();
+    fs::write(
+      metadata_path(dir.path(), 387, 1234),
+      serde_json::to_string(&metadata).unwrap(),
+    )
+    .unwrap();
+
+    let download = resolve_cached(
+      &client(&server, None),
+      &cache,
+      dir.path(),
+      (387, 1234),
+      None,
+    )
+    .unwrap();
+    api.assert();
+    file.assert();
+    assert_eq!(download.metadata, metadata);
+    assert_eq!(download.url, url);
+  }
+
+  #[test]
+  fn test_resolve_requires_api_key() {
+    let server = mockito::Server::new();
+    assert!(matches!(
+      client(&server, None).resolve(387, 1234),
+      Err(ValheimModError::NexusApiKeyMissing)
+    ));
+  }
+
+  #[test]
+  fn test_resolve_without_premium() {
+    let mut server = mockito::Server::new();
+    server
+      .mock("GET", "/v1/games/valheim/mods/387.json")
+      .with_body(r#"{"name": "Epic Loot"}"#)
+      .create();
+    server
+      .mock("GET", "/v1/games/valheim/mods/387/files/1234.json")
+      .with_body(r#"{"file_name": "EpicLoot-387-0-10-3.zip"}"#)
+      .create();
+    server
+      .mock(
+        "GET",
+        "/v1/games/valheim/mods/387/files/1234/download_link.json",
+      )
+      .with_status(403)
+      .create();
+
+    match client(&server, Some("secret")).resolve(387, 1234) {
+      Err(ValheimModError::NexusApiError(message)) => assert!(message.contains("premium")),
+      _ => panic!("Expected a Nexus API error"),
+    }
+  }
+}
@@ -0,0 +1,156 @@
+use crate::errors::ValheimModError;
+use crate::mods::installed_mods::installed_mods;
+use crate::mods::manifest::Manifest;
+use crate::mods::thunderstore::{find_package, Package, ThunderstoreClient};
+use log::debug;
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Eq)]
+pub struct OutdatedMod {
+  pub name: String,
+  pub full_name: String,
+  pub installed_version: String,
+  pub latest_version: String,
+}
+
+impl OutdatedMod {
+  /// The `Author-Name-Version` string for the latest version, as accepted by `mod:install`.
+  pub fn latest_mod_string(&self) -> String {
+    format!("{}-{}", self.full_name, self.latest_version)
+  }
+}
+
+/// Compares two version strings component by component, numerically where possible.
+pub(crate) fn compare_versions(left: &str, right: &str) -> Ordering {
+  let mut left_parts = left.trim_start_matches('v').split('.');
+  let mut right_parts = right.trim_start_matches('v').split('.');
+  loop {
+    match (left_parts.next(), right_parts.next()) {
+      (None, None) => return Ordering::Equal,
+      (left_part, right_part) => {
+        let left_part = left_part.unwrap_or("0");
+        let right_part = right_part.unwrap_or("0");
+        let ordering = match (left_part.parse::<u64>(), right_part.parse::<u64>()) {
+          (Ok(l), Ok(r)) => l.cmp(&r),
+          _ => left_part.cmp(right_part),
+        };
+        if ordering != Ordering::Equal {
+          return ordering;
+        }
+      }
+    }
+  }
+}
+
+/// Compares installed manifests against the package index and returns the mods with a newer version.
+/// Mods installed from Nexus are skipped, a Thunderstore package sharing their name is a different mod.
+pub fn find_outdated_mods(manifests: &[Manifest], index: &[Package]) -> Vec<OutdatedMod> {
+  manifests
+    .iter()
+    .filter_map(|manifest| {
+      if manifest.nexus.is_some() {
+        debug!("Skipping {}, it was installed from Nexus", manifest.name);
+        return None;
+      }
+      let installed_version = manifest.version_number.as_ref()?;
+      let package = match find_package(index, &manifest.name) {
+        Some(package) => package,
+        None => {
+          debug!("No Thunderstore package found for {}", manifest.name);
+          return None;
+        }
+      };
+      let latest = package.latest_version()?;
+      if compare_versions(&latest.version_number, installed_version) == Ordering::Greater {
+        Some(OutdatedMod {
+          name: manifest.name.clone(),
+          full_name: package.full_name.clone(),
+          installed_version: installed_version.clone(),
+          latest_version: latest.version_number.clone(),
+        })
+      } else {
+        None
+      }
+    })
+    .collect()
+}
+
+/// Fetches the package index and lists every installed mod that has a newer version available.
+pub fn outdated_mods(client: &ThunderstoreClient) -> Result<Vec<OutdatedMod>, ValheimModError> {
+  let index = client.fetch_index()?;
+  Ok(find_outdated_mods(&installed_mods(), &index))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::mods::thunderstore::PackageVersion;
+
+  fn manifest(name: &str, version: Option<&str>) -> Manifest {
+    Manifest {
+      name: name.to_string(),
+      dependencies: None,
+      version_number: version.map(String::from),
+      ..Default::default()
+    }
+  }
+
+  fn package(owner: &str, name: &str, versions: &[&str]) -> Package {
+    Package {
+      name: name.to_string(),
+      full_name: format!("{}-{}", owner, name),
+      owner: owner.to_string(),
+      is_deprecated: false,
+      versions: versions
+        .iter()
+        .map(|version| PackageVersion {
+          name: name.to_string(),
+          full_name: format!("{}-{}-{}", owner, name, version),
+          version_number: version.to_string(),
+          download_url: format!(
+            "https://thunderstore.io/package/download/{}/{}/{}/",
+            owner, name, version
+          ),
+        })
+        .collect(),
+    }
+  }
+
+  #[test]
+  fn test_compare_versions() {
+    assert_eq!(compare_versions("1.2.10", "1.2.9"), Ordering::Greater);
+    assert_eq!(compare_versions("1.2.0", "1.2"), Ordering::Equal);
+    assert_eq!(compare_versions("v2.0.0", "2.0.1"), Ordering::Less);
+    assert_eq!(compare_versions("5.4.2202", "5.4.2200"), Ordering::Greater);
+  }
+
+  #[test]
+  fn test_find_outdated_mods() {
+    let index = vec![
+      package("ValheimModding", "Jotunn", &["2.23.2", "2.23.1"]),
+      package("RandyKnapp", "EpicLoot", &["0.10.3"]),
+    ];
+    let manifests = vec![
+      manifest("Jotunn", Some("2.23.1")),
+      manifest("EpicLoot", Some("0.10.3")),
+      manifest("NotOnThunderstore", Some("1.0.0")),
+      manifest("Unversioned", None),
+    ];
+
+    let outdated = find_outdated_mods(&manifests, &index);
+    assert_eq!(
+      outdated,
+      vec![OutdatedMod {
+        name: "Jotunn".to_string(),
+        full_name: "ValheimModding-Jotunn".to_string(),
+        installed_version: "2.23.1".to_string(),
+        latest_version: "2.23.2".to_string(),
+      }]
+    );
+    assert_eq!(
+      outdated[0].latest_mod_string(),
+      "ValheimModding-Jotunn-2.23.2"
+    );
+  }
+}
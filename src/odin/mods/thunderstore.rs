@@ -0,0 +1,317 @@
+use crate::errors::ValheimModError;
+use crate::utils::common_paths::cache_directory;
+use crate::utils::environment::fetch_var;
+use log::{debug, warn};
+use reqwest::blocking::Client;
+use reqwest::header::{ETAG, IF_NONE_MATCH};
+use reqwest::StatusCode;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::PathBuf;
+
+const THUNDERSTORE_API_URL_VAR: &str = "THUNDERSTORE_API_URL";
+const THUNDERSTORE_API_URL: &str = "https://thunderstore.io/c/valheim";
+const PACKAGE_INDEX_PATH: &str = "api/v1/package/";
+const PACKAGE_INDEX_FILE: &str = "package_index.json";
+const PACKAGE_INDEX_ETAG_FILE: &str = "package_index.etag";
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct PackageVersion {
+  pub name: String,
+  pub full_name: String,
+  pub version_number: String,
+  pub download_url: String,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct Package {
+  pub name: String,
+  pub full_name: String,
+  pub owner: String,
+  #[serde(default)]
+  pub is_deprecated: bool,
+  pub versions: Vec<PackageVersion>,
+}
+
+impl Package {
+  /// Thunderstore lists the versions of a package newest first.
+  pub fn latest_version(&self) -> Option<&PackageVersion> {
+    self.versions.first()
+  }
+}
+
+/// Client for the Thunderstore package API.
+///
+/// The package index is large, so the last response is cached on disk along with its ETag
+/// and only downloaded again when Thunderstore reports a change.
+pub struct ThunderstoreClient {
+  client: Client,
+  base_url: String,
+  cache_dir: PathBuf,
+}
+
+impl Default for ThunderstoreClient {
+  fn default() -> Self {
+    Self::new(
+      Client::new(),
+      &fetch_var(THUNDERSTORE_API_URL_VAR, THUNDERSTORE_API_URL),
+      PathBuf::from(cache_directory()).join("thunderstore"),
+    )
+  }
+}
+
+impl ThunderstoreClient {
+  pub fn new(client: Client, base_url: &str, cache_dir: PathBuf) -> Self {
+    ThunderstoreClient {
+      client,
+      base_url: base_url.trim_end_matches('/').to_string(),
+      cache_dir,
+    }
+  }
+
+  fn index_url(&self) -> String {
+    format!("{}/{}", self.base_url, PACKAGE_INDEX_PATH)
+  }
+
+  fn cached_index(&self) -> Option<String> {
+    fs::read_to_string(self.cache_dir.join(PACKAGE_INDEX_FILE)).ok()
+  }
+
+  fn cached_etag(&self) -> Option<String> {
+    fs::read_to_string(self.cache_dir.join(PACKAGE_INDEX_ETAG_FILE))
+      .ok()
+      .map(|etag| etag.trim().to_string())
+      .filter(|etag| !etag.is_empty())
+  }
+
+  fn write_cache(&self, content: &str, etag: Option<&str>) {
+    let result = fs::create_dir_all(&self.cache_dir)
+      .and_then(|_| fs::write(self.cache_dir.join(PACKAGE_INDEX_FILE), content))
+      .and_then(|_| match etag {
+        Some(etag) => fs::write(self.cache_dir.join(PACKAGE_INDEX_ETAG_FILE), etag),
+        None => match fs::remove_file(self.cache_dir.join(PACKAGE_INDEX_ETAG_FILE)) {
+          Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e),
+          _ => Ok(()),
+        },
+      });
+    if let Err(e) = result {
+      warn!("Failed to cache the Thunderstore package index: {}", e);
+    }
+  }
+
+  fn parse_index(content: &str) -> Result<Vec<Package>, ValheimModError> {
+    serde_json::from_str(content)
+      .map_err(|e| ValheimModError::PackageIndexParseError(e.to_string()))
+  }
+
+  /// Fetches the package index, reusing the cached copy when it is still current.
+  /// If Thunderstore can't be reached the cached copy is used as a fallback.
+  pub fn fetch_index(&self) -> Result<Vec<Package>, ValheimModError> {
+    let cached_index = self.cached_index();
+    let mut request = self.client.get(self.index_url());
+    if let (Some(etag), Some(_)) = (self.cached_etag(), &cached_index) {
+      debug!("Requesting package index with ETag {}", etag);
+      request = request.header(IF_NONE_MATCH, etag);
+    }
+
+    let error = match request.send() {
+      Ok(response) if response.status() == StatusCode::NOT_MODIFIED => {
+        debug!("Package index has not changed, using cached copy.");
+        if let Some(content) = cached_index {
+          return Self::parse_index(&content);
+        }
+        String::from("Received 304 Not Modified without a cached index")
+      }
+      Ok(response) if response.status().is_success() => {
+        let etag = response
+          .headers()
+          .get(ETAG)
+          .and_then(|value| value.to_str().ok())
+          .map(String::from);
+        let content = response
+          .text()
+          .map_err(|e| ValheimModError::PackageIndexFetchError(e.to_string()))?;
+        let index = Self::parse_index(&content)?;
+        self.write_cache(&content, etag.as_deref());
+        return Ok(index);
+      }
+      Ok(response) => format!("Unexpected status {}", response.status()),
+      Err(e) => e.to_string(),
+    };
+
+    match cached_index {
+      Some(content) => {
+        warn!(
+          "Failed to fetch the Thunderstore package index ({}), using cached copy.",
+          error
+        );
+        Self::parse_index(&content)
+      }
+      None => Err(ValheimModError::PackageIndexFetchError(error)),
+    }
+  }
+}
+
+/// Finds the package for an installed mod by its manifest name.
+///
+/// Installed manifests don't record the package owner, so when several packages share a name
+/// deprecated ones are ignored and the lookup only succeeds if a single candidate remains.
+pub fn find_package<'a>(index: &'a [Package], name: &str) -> Option<&'a Package> {
+  let candidates: Vec<&Package> = index
+    .iter()
+    .filter(|package| package.name.eq_ignore_ascii_case(name))
+    .collect();
+  if candidates.len() <= 1 {
+    return candidates.first().copied();
+  }
+
+  let active: Vec<&Package> = candidates
+    .iter()
+    .copied()
+    .filter(|package| !package.is_deprecated)
+    .collect();
+  if active.len() == 1 {
+    Some(active[0])
+  } else {
+    warn!(
+      "Found multiple Thunderstore packages named {}: {}",
+      name,
+      candidates
+        .iter()
+        .map(|package| package.full_name.as_str())
+        .collect::<Vec<&str>>()
+        .join(", ")
+    );
+    None
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use tempfile::tempdir;
+
+  const INDEX: &str = r#"[
+    {
+      "name": "Jotunn",
+      "full_name": "ValheimModding-Jotunn",
+      "owner": "ValheimModding",
+      "is_deprecated": false,
+      "versions": [
+        {
+          "name": "Jotunn",
+          "full_name": "ValheimModding-Jotunn-2.23.2",
+          "version_number": "2.23.2",
+          "download_url": "https://thunderstore.io/package/download/ValheimModding/Jotunn/2.23.2/"
+        },
+        {
+          "name": "Jotunn",
+          "full_name": "ValheimModding-Jotunn-2.23.1",
+          "version_number": "2.23.1",
+          "download_url": "https://thunderstore.io/package/download/ValheimModding/Jotunn/2.23.1/"
+        }
+      ]
+    }
+  ]"#;
+
+  fn package(owner: &str, name: &str, is_deprecated: bool) -> Package {
+    Package {
+      name: name.to_string(),
+      full_name: format!("{}-{}", owner, name),
+      owner: owner.to_string(),
+      is_deprecated,
+      versions: vec![],
+    }
+  }
+
+  #[test]
+  fn test_fetch_index_caches_response() {
+    let mut server = mockito::Server::new();
+    let mock = server
+      .mock("GET", "/api/v1/package/")
+      .with_status(200)
+      .with_header("etag", "\"abc123\"")
+      .with_body(INDEX)
+      .create();
+    let cache = tempdir().unwrap();
+    let client = ThunderstoreClient::new(Client::new(), &server.url(), cache.path().into());
+
+    let index = client.fetch_index().unwrap();
+    mock.assert();
+    assert_eq!(index.len(), 1);
+    assert_eq!(index[0].latest_version().unwrap().version_number, "2.23.2");
+    assert!(cache.path().join(PACKAGE_INDEX_FILE).exists());
+    assert_eq!(client.cached_etag().unwrap(), "\"abc123\"");
+  }
+
+  #[test]
+  fn test_fetch_index_uses_cache_when_not_modified() {
+    let mut server = mockito::Server::new();
+    let mock = server
+      .mock("GET", "/api/v1/package/")
+      .match_header("if-none-match", "\"abc123\"")
+      .with_status(304)
+      .create();
+    let cache = tempdir().unwrap();
+    fs::write(cache.path().join(PACKAGE_INDEX_FILE), INDEX).unwrap();
+    fs::write(cache.path().join(PACKAGE_INDEX_ETAG_FILE), "\"abc123\"").unwrap();
+    let client = ThunderstoreClient::new(Client::new(), &server.url(), cache.path().into());
+
+    let index = client.fetch_index().unwrap();
+    mock.assert();
+    assert_eq!(index[0].full_name, "ValheimModding-Jotunn");
+  }
+
+  #[test]
+  fn test_fetch_index_falls_back_to_cache() {
+    let mut server = mockito::Server::new();
+    let mock = server
+      .mock("GET", "/api/v1/package/")
+      .with_status(500)
+      .create();
+    let cache = tempdir().unwrap();
+    fs::write(cache.path().join(PACKAGE_INDEX_FILE), INDEX).unwrap();
+    let client = ThunderstoreClient::new(Client::new(), &server.url(), cache.path().into());
+
+    let index = client.fetch_index().unwrap();
+    mock.assert();
+    assert_eq!(index.len(), 1);
+  }
+
+  #[test]
+  fn test_fetch_index_fails_without_cache() {
+    let mut server = mockito::Server::new();
+    server
+      .mock("GET", "/api/v1/package/")
+      .with_status(500)
+      .create();
+    let cache = tempdir().unwrap();
+    let client = ThunderstoreClient::new(Client::new(), &server.url(), cache.path().into());
+
+    assert!(matches!(
+      client.fetch_index(),
+      Err(ValheimModError::PackageIndexFetchError(_))
+    ));
+  }
+
+  #[test]
+  fn test_find_package() {
+    let index = vec![
+      package("ValheimModding", "Jotunn", false),
+      package("Someone", "Duplicate", true),
+      package("SomeoneElse", "Duplicate", false),
+      package("First", "Ambiguous", false),
+      package("Second", "Ambiguous", false),
+    ];
+    assert_eq!(
+      find_package(&index, "jotunn").unwrap().full_name,
+      "ValheimModding-Jotunn"
+    );
+    assert_eq!(
+      find_package(&index, "Duplicate").unwrap().owner,
+      "SomeoneElse"
+    );
+    assert!(find_package(&index, "Ambiguous").is_none());
+    assert!(find_package(&index, "Missing").is_none());
+  }
+}
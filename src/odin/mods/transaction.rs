@@ -0,0 +1,332 @@
+use crate::errors::ValheimModError;
+use crate::mods::nexus::NexusMetadata;
+use crate::utils::common_paths::cache_directory;
+use chrono::Utc;
+use log::{debug, info, warn};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use walkdir::WalkDir;
+
+const JOURNAL_FILE: &str = "journal.json";
+const BACKUP_DIRECTORY: &str = "backup";
+
+pub fn transactions_directory() -> PathBuf {
+  PathBuf::from(cache_directory()).join("transactions")
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum TransactionState {
+  /// Files are being moved into place, an interrupted install has to be reverted or redone.
+  Applying,
+  /// Every file is in place, only the backups are left to clean up.
+  Applied,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+struct JournalEntry {
+  path: PathBuf,
+  /// Where the previous version of the file was saved, `None` if the install creates it.
+  backup: Option<PathBuf>,
+}
+
+/// Everything needed to revert or redo an install after a crash.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct Journal {
+  pub id: String,
+  pub url: String,
+  pub file_type: String,
+  pub staging_location: PathBuf,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub nexus: Option<NexusMetadata>,
+  pub state: TransactionState,
+  entries: Vec<JournalEntry>,
+  created_directories: Vec<PathBuf>,
+}
+
+/// Makes a mod install all or nothing.
+///
+/// Every path an install writes to is protected before it is touched: existing files are
+/// backed up and new ones are recorded, so a failed install can be rolled back. The journal
+/// is kept on disk until the install completes so `odin mod:repair` can pick up after a crash.
+pub struct InstallTransaction {
+  directory: PathBuf,
+  journal: Journal,
+}
+
+impl InstallTransaction {
+  pub fn begin(
+    root: &Path,
+    url: &str,
+    file_type: &str,
+    staging_location: &Path,
+    nexus: Option<NexusMetadata>,
+  ) -> Result<InstallTransaction, ValheimModError> {
+    let id = Utc::now().format("%Y%m%d%H%M%S%f").to_string();
+    let directory = root.join(&id);
+    fs::create_dir_all(directory.join(BACKUP_DIRECTORY))
+      .map_err(|e| ValheimModError::DirectoryCreationError(e.to_string()))?;
+    let transaction = InstallTransaction {
+      directory,
+      journal: Journal {
+        id,
+        url: url.to_string(),
+        file_type: file_type.to_string(),
+        staging_location: staging_location.to_path_buf(),
+        nexus,
+        state: TransactionState::Applying,
+        entries: vec![],
+        created_directories: vec![],
+      },
+    };
+    transaction.save()?;
+    debug!("Started install transaction {}", transaction.journal.id);
+    Ok(transaction)
+  }
+
+  /// Loads the transactions a previous run didn't finish.
+  pub fn pending(root: &Path) -> Vec<InstallTransaction> {
+    let mut directories: Vec<PathBuf> = fs::read_dir(root)
+      .map(|entries| entries.flatten().map(|entry| entry.path()).collect())
+      .unwrap_or_default();
+    directories.sort();
+    directories
+      .into_iter()
+      .filter_map(|directory| {
+        let content = fs::read_to_string(directory.join(JOURNAL_FILE)).ok()?;
+        match serde_json::from_str(&content) {
+          Ok(journal) => Some(InstallTransaction { directory, journal }),
+          Err(e) => {
+            warn!("Skipping unreadable journal in {:?}: {}", directory, e);
+            None
+          }
+        }
+      })
+      .collect()
+  }
+
+  pub fn journal(&self) -> &Journal {
+    &self.journal
+  }
+
+  fn save(&self) -> Result<(), ValheimModError> {
+    // Write and rename so a crash never leaves a half written journal behind.
+    let content = serde_json::to_string_pretty(&self.journal)
+      .map_err(|e| ValheimModError::FileCreateError(e.to_string()))?;
+    let temporary = self.directory.join(format!("{}.tmp", JOURNAL_FILE));
+    fs::write(&temporary, content)
+      .and_then(|_| fs::rename(&temporary, self.directory.join(JOURNAL_FILE)))
+      .map_err(|e| ValheimModError::FileCreateError(e.to_string()))
+  }
+
+  fn record(&mut self, path: &Path) -> Result<(), ValheimModError> {
+    if self.journal.entries.iter().any(|entry| entry.path == path) {
+      return Ok(());
+    }
+
+    let mut parent = path.parent();
+    while let Some(directory) = parent.filter(|directory| !directory.exists()) {
+      if !self
+        .journal
+        .created_directories
+        .iter()
+        .any(|created| created == directory)
+      {
+        self
+          .journal
+          .created_directories
+          .push(directory.to_path_buf());
+      }
+      parent = directory.parent();
+    }
+
+    let backup = if path.is_file() {
+      let backup = self
+        .directory
+        .join(BACKUP_DIRECTORY)
+        .join(self.journal.entries.len().to_string());
+      fs::copy(path, &backup).map_err(|e| ValheimModError::FileCreateError(e.to_string()))?;
+      Some(backup)
+    } else {
+      None
+    };
+    self.journal.entries.push(JournalEntry {
+      path: path.to_path_buf(),
+      backup,
+    });
+    Ok(())
+  }
+
+  /// Protects a single file which is about to be written.
+  pub fn protect_file(&mut self, path: &Path) -> Result<(), ValheimModError> {
+    self.record(path)?;
+    self.save()
+  }
+
+  /// Protects the files a `move_dir` of the source's content into the destination writes.
+  pub fn protect_tree(&mut self, source: &Path, destination: &Path) -> Result<(), ValheimModError> {
+    for entry in WalkDir::new(source).into_iter().flatten() {
+      if entry.file_type().is_dir() {
+        continue;
+      }
+      if let Ok(relative) = entry.path().strip_prefix(source) {
+        self.record(&destination.join(relative))?;
+      }
+    }
+    self.save()
+  }
+
+  fn cleanup(&self) -> Result<(), ValheimModError> {
+    fs::remove_dir_all(&self.directory).map_err(|e| ValheimModError::FileMoveError(e.to_string()))
+  }
+
+  /// Marks the install as complete and throws away the backups.
+  pub fn commit(mut self) -> Result<(), ValheimModError> {
+    self.journal.state = TransactionState::Applied;
+    self.save()?;
+    self.cleanup()?;
+    debug!("Committed install transaction {}", self.journal.id);
+    Ok(())
+  }
+
+  /// Puts every protected file back the way it was before the install.
+  pub fn rollback(self) -> Result<(), ValheimModError> {
+    info!("Rolling back install of {}", self.journal.url);
+    for entry in self.journal.entries.iter().rev() {
+      let result = match &entry.backup {
+        Some(backup) => entry
+          .path
+          .parent()
+          .map_or(Ok(()), fs::create_dir_all)
+          .and_then(|_| fs::copy(backup, &entry.path).map(|_| ())),
+        None => match fs::remove_file(&entry.path) {
+          Err(e) if e.kind() == ErrorKind::NotFound => Ok(()),
+          result => result,
+        },
+      };
+      result.map_err(|e| {
+        ValheimModError::FileMoveError(format!("Failed to restore {:?}: {}", entry.path, e))
+      })?;
+    }
+
+    let mut created_directories: Vec<&PathBuf> = self.journal.created_directories.iter().collect();
+    created_directories.sort_by_key(|directory| std::cmp::Reverse(directory.components().count()));
+    for directory in created_directories {
+      // Only empty directories go, anything else was put there by someone else.
+      let _ = fs::remove_dir(directory);
+    }
+    self.cleanup()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use tempfile::tempdir;
+
+  fn begin(root: &Path) -> InstallTransaction {
+    InstallTransaction::begin(
+      root,
+      "https://example.com/mod.zip",
+      "zip",
+      Path::new("/tmp/mod.zip"),
+      None,
+    )
+    .unwrap()
+  }
+
+  fn target() -> tempfile::TempDir {
+    let target = tempdir().unwrap();
+    fs::create_dir_all(target.path().join("plugins/Existing")).unwrap();
+    fs::write(target.path().join("plugins/Existing/Existing.dll"), "old").unwrap();
+    target
+  }
+
+  fn source() -> tempfile::TempDir {
+    let source = tempdir().unwrap();
+    fs::create_dir_all(source.path().join("plugins/Existing")).unwrap();
+    fs::create_dir_all(source.path().join("plugins/New/nested")).unwrap();
+    fs::write(source.path().join("plugins/Existing/Existing.dll"), "new").unwrap();
+    fs::write(source.path().join("plugins/New/nested/New.dll"), "new").unwrap();
+    source
+  }
+
+  fn apply(source: &Path, target: &Path) {
+    for file in [
+      "plugins/Existing/Existing.dll",
+      "plugins/New/nested/New.dll",
+    ] {
+      fs::create_dir_all(target.join(file).parent().unwrap()).unwrap();
+      fs::copy(source.join(file), target.join(file)).unwrap();
+    }
+  }
+
+  #[test]
+  fn test_rollback_restores_target() {
+    let root = tempdir().unwrap();
+    let (source, target) = (source(), target());
+    let mut transaction = begin(root.path());
+    transaction
+      .protect_tree(source.path(), target.path())
+      .unwrap();
+    apply(source.path(), target.path());
+
+    transaction.rollback().unwrap();
+    assert_eq!(
+      fs::read_to_string(target.path().join("plugins/Existing/Existing.dll")).unwrap(),
+      "old"
+    );
+    assert!(!target.path().join("plugins/New").exists());
+    assert!(InstallTransaction::pending(root.path()).is_empty());
+  }
+
+  #[test]
+  fn test_commit_keeps_changes() {
+    let root = tempdir().unwrap();
+    let (source, target) = (source(), target());
+    let mut transaction = begin(root.path());
+    transaction
+      .protect_tree(source.path(), target.path())
+      .unwrap();
+    apply(source.path(), target.path());
+
+    transaction.commit().unwrap();
+    assert_eq!(
+      fs::read_to_string(target.path().join("plugins/Existing/Existing.dll")).unwrap(),
+      "new"
+    );
+    assert!(target.path().join("plugins/New/nested/New.dll").exists());
+    assert!(InstallTransaction::pending(root.path()).is_empty());
+  }
+
+  #[test]
+  fn test_pending_survives_crash() {
+    let root = tempdir().unwrap();
+    let (source, target) = (source(), target());
+    let mut transaction = begin(root.path());
+    transaction
+      .protect_tree(source.path(), target.path())
+      .unwrap();
+    transaction
+      .protect_file(&target.path().join("config/mod.cfg"))
+      .unwrap();
+    apply(source.path(), target.path());
+    // Simulate a crash by forgetting about the transaction.
+    drop(transaction);
+
+    let pending = InstallTransaction::pending(root.path());
+    assert_eq!(pending.len(), 1);
+    assert_eq!(pending[0].journal().url, "https://example.com/mod.zip");
+    assert_eq!(pending[0].journal().state, TransactionState::Applying);
+
+    pending.into_iter().next().unwrap().rollback().unwrap();
+    assert_eq!(
+      fs::read_to_string(target.path().join("plugins/Existing/Existing.dll")).unwrap(),
+      "old"
+    );
+    assert!(!target.path().join("plugins/New").exists());
+    assert!(!target.path().join("config").exists());
+  }
+}
@@ -1,5 +1,10 @@
 use crate::errors::ValheimModError;
+use crate::mods::bepinex_pack::BepInExPack;
+use crate::mods::download_cache::{split_sha256, DownloadCache};
+use crate::mods::layout::{mod_name_from_file, ArchiveFormat, ModLayout};
 use crate::mods::manifest::Manifest;
+use crate::mods::nexus::{nexus_download, parse_nexus_id, NexusDownload, NexusMetadata};
+use crate::mods::transaction::{transactions_directory, InstallTransaction, Journal};
 use crate::utils::normalize_paths::normalize_paths;
 use crate::utils::{is_valid_url, parse_mod_string};
 use crate::{
@@ -11,11 +16,10 @@ use fs_extra::dir::CopyOptions;
 use log::{debug, error, info};
 use reqwest::Url;
 use std::convert::TryFrom;
-use std::fs::{create_dir_all, File};
+use std::fs::{self, create_dir_all};
 use std::path::{Path, PathBuf};
 use tempfile::tempdir;
 use walkdir::WalkDir;
-use zip::ZipArchive;
 
 pub struct ValheimMod {
   pub(crate) url: String,
@@ -24,6 +28,10 @@ pub struct ValheimMod {
   pub(crate) staging_location: PathBuf,
   pub(crate) installed: bool,
   pub(crate) downloaded: bool,
+  /// Expected SHA-256 of the download, supplied with a `#sha256=<hash>` suffix.
+  pub(crate) sha256: Option<String>,
+  /// Set for mods resolved from a `nexus:<mod_id>/<file_id>` identifier.
+  pub(crate) nexus: Option<NexusMetadata>,
   // Optionally, add fields like author or mod_name if needed later.
 }
 
@@ -36,9 +44,38 @@ impl ValheimMod {
       staging_location: common_paths::mods_staging_directory().into(),
       installed: false,
       downloaded: false,
+      sha256: None,
+      nexus: None,
     }
   }
 
+  /// Creates a mod from a resolved Nexus download, the download link itself says little
+  /// so the file type is taken from the file name Nexus reports.
+  pub fn from_nexus(download: NexusDownload) -> Self {
+    let mut valheim_mod = ValheimMod::new(&download.url);
+    valheim_mod.file_type = url_parse_file_type(&download.metadata.file_name);
+    valheim_mod.nexus = Some(download.metadata);
+    valheim_mod
+  }
+
+  /// Recreates a mod from the journal of an interrupted install, using its staged download.
+  pub fn from_journal(journal: &Journal) -> Self {
+    ValheimMod {
+      url: journal.url.clone(),
+      file_type: journal.file_type.clone(),
+      staging_location: journal.staging_location.clone(),
+      installed: false,
+      downloaded: journal.staging_location.is_file(),
+      sha256: None,
+      nexus: journal.nexus.clone(),
+    }
+  }
+
+  pub fn with_sha256(mut self, sha256: Option<String>) -> Self {
+    self.sha256 = sha256;
+    self
+  }
+
   /// Determines whether the mod is a framework by inspecting the extracted files.
   fn is_mod_framework(&self, extract_path: &Path) -> bool {
     debug!("Checking mod if it is a framework like bepinex");
@@ -48,21 +85,20 @@ impl ValheimMod {
         manifest.name.to_lowercase().starts_with("bepinex")
       }
       Err(_) => {
-        for entry in WalkDir::new(extract_path).into_iter().flatten() {
-          if entry
-            .file_name()
-            .to_string_lossy()
-            .eq_ignore_ascii_case("winhttp.dll")
-          {
-            return true;
-          }
-        }
-        false
+        // Packs without a manifest still ship the doorstop proxy or the preloader.
+        WalkDir::new(extract_path)
+          .into_iter()
+          .flatten()
+          .any(|entry| {
+            let file_name = entry.file_name().to_string_lossy();
+            file_name.eq_ignore_ascii_case("winhttp.dll")
+              || file_name.eq_ignore_ascii_case("BepInEx.Preloader.dll")
+          })
       }
     }
   }
 
-  /// Download: Downloads the mod ZIP from the URL into the staging location.
+  /// Download: Fetches the mod through the download cache and copies it into the staging location.
   pub fn download(&mut self) -> Result<(), ValheimModError> {
     debug!("Initializing mod download...");
     if !self.staging_location.exists() {
@@ -70,13 +106,17 @@ impl ValheimMod {
     }
 
     let parsed_url = Url::parse(&self.url).map_err(|_| ValheimModError::InvalidUrl)?;
-    let mut response = reqwest::blocking::get(parsed_url)
-      .map_err(|e| ValheimModError::DownloadError(e.to_string()))?;
+    let cache = DownloadCache::default();
+    let download = match &self.nexus {
+      // Nexus links expire, the file id is what stays the same.
+      Some(nexus) => cache.fetch_as(&nexus.id(), parsed_url.as_str(), self.sha256.as_deref())?,
+      None => cache.fetch(parsed_url.as_str(), self.sha256.as_deref())?,
+    };
 
     if !SUPPORTED_FILE_TYPES.contains(&self.file_type.as_str()) {
-      debug!("Using redirect URL: {}", &self.url);
-      self.url = response.url().to_string();
-      self.file_type = url_parse_file_type(response.url().as_ref());
+      debug!("Using redirect URL: {}", &download.url);
+      self.url = download.url.clone();
+      self.file_type = url_parse_file_type(&download.url);
     }
 
     let file_name = parse_file_name(
@@ -84,104 +124,314 @@ impl ValheimMod {
       &format!("{}.{}", get_md5_hash(&self.url), &self.file_type),
     );
     self.staging_location = self.staging_location.join(file_name);
-    debug!("Downloading to: {:?}", self.staging_location);
+    debug!("Copying download to: {:?}", self.staging_location);
 
-    let mut file = File::create(&self.staging_location)
+    fs::copy(&download.path, &self.staging_location)
       .map_err(|e| ValheimModError::FileCreateError(e.to_string()))?;
-    response
-      .copy_to(&mut file)
-      .map_err(|e| ValheimModError::DownloadError(e.to_string()))?;
     self.downloaded = true;
-    debug!("Download complete: {}", &self.url);
+    debug!(
+      "Download complete: {} (sha256 {})",
+      &self.url, &download.sha256
+    );
     debug!("Download output: {:?}", self.staging_location);
     Ok(())
   }
 
-  /// Install: Creates a temporary directory, extracts the ZIP there, validates the mod,
-  /// moves extracted files to their final destination, and cleans up the temp directory.
-  pub fn install(&mut self) -> Result<(), ValheimModError> {
-    // Ensure that the staging location is a file (the downloaded ZIP).
-    if self.staging_location.is_dir() {
-      error!(
-        "Failed to install mod! Staging location is a directory: {:?}",
-        self.staging_location
-      );
-      return Err(ValheimModError::InvalidStagingLocation);
-    }
-
-    // Create a temporary directory for extraction.
-    let temp_dir = tempdir().map_err(|e| {
-      ValheimModError::TempDirCreationError(format!("Failed to create temp dir: {}", e))
-    })?;
-    debug!("Created temporary directory at {:?}", temp_dir.path());
-
-    // Extract the ZIP file (from staging) into the temporary directory.
-    {
-      let zip_file = File::open(&self.staging_location)
-        .map_err(|e| ValheimModError::FileOpenError(e.to_string()))?;
-      let mut archive =
-        ZipArchive::new(zip_file).map_err(|e| ValheimModError::ZipArchiveError(e.to_string()))?;
-      archive.extract(temp_dir.path()).map_err(|e| {
-        error!("Failed to extract archive: {}", e);
-        ValheimModError::ExtractionError(e.to_string())
-      })?;
-
-      normalize_paths(temp_dir.path())
-        .map_err(|e| ValheimModError::ExtractionError(e.to_string()))?;
-    }
-    debug!("Extraction complete to {:?}", temp_dir.path());
-
-    // Validate mod type by inspecting the extracted files.
-    let is_framework = self.is_mod_framework(temp_dir.path());
-
-    let mut options = CopyOptions {
+  fn move_options() -> CopyOptions {
+    CopyOptions {
       overwrite: true,
       skip_exist: false,
       buffer_size: 0,
       copy_inside: false,
       content_only: true,
       depth: 0,
+    }
+  }
+
+  /// Names a mod without a manifest after its Nexus page, or after the downloaded file.
+  fn mod_name(&self) -> String {
+    let nexus_name: Option<String> = self.nexus.as_ref().map(|nexus| {
+      nexus
+        .name
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric() || *c == '-' || *c == '_')
+        .collect()
+    });
+    match nexus_name {
+      Some(name) if !name.is_empty() => name,
+      _ => mod_name_from_file(&self.staging_location),
+    }
+  }
+
+  /// Writes a synthesized manifest into the plugin directory of a mod which shipped without one.
+  fn synthesize_manifest(
+    &self,
+    transaction: &mut InstallTransaction,
+    name: &str,
+  ) -> Result<(), ValheimModError> {
+    let final_dir = PathBuf::from(&common_paths::bepinex_plugin_directory()).join(name);
+    debug!("Synthesizing manifest for {} in {:?}", name, final_dir);
+    transaction.protect_file(&final_dir.join("manifest.json"))?;
+    let manifest = Manifest::synthesized(name, &self.url);
+    match &self.nexus {
+      Some(nexus) => manifest.with_nexus(nexus),
+      None => manifest,
+    }
+    .write_to(&final_dir)
+    .map_err(|e| ValheimModError::FileCreateError(e.to_string()))
+  }
+
+  /// Install: Places a bare DLL into its own plugin directory.
+  fn install_plugin_file(
+    &self,
+    transaction: &mut InstallTransaction,
+  ) -> Result<(), ValheimModError> {
+    let name = mod_name_from_file(&self.staging_location);
+    info!("Installing Plugin {}...", name);
+    let final_dir = PathBuf::from(&common_paths::bepinex_plugin_directory()).join(&name);
+    let final_file = final_dir.join(format!("{}.dll", name));
+    transaction.protect_file(&final_file)?;
+    create_dir_all(&final_dir)
+      .map_err(|e| ValheimModError::DirectoryCreationError(e.to_string()))?;
+    fs::copy(&self.staging_location, final_file)
+      .map_err(|e| ValheimModError::FileMoveError(e.to_string()))?;
+    self.synthesize_manifest(transaction, &name)
+  }
+
+  /// Install: Places a config file into the BepInEx config directory.
+  fn install_config_file(
+    &self,
+    transaction: &mut InstallTransaction,
+  ) -> Result<(), ValheimModError> {
+    let file_name = self
+      .staging_location
+      .file_name()
+      .ok_or(ValheimModError::InvalidStagingLocation)?;
+    info!("Installing Config {:?}...", file_name);
+    let config_dir = PathBuf::from(&common_paths::bepinex_config_directory());
+    transaction.protect_file(&config_dir.join(file_name))?;
+    create_dir_all(&config_dir)
+      .map_err(|e| ValheimModError::DirectoryCreationError(e.to_string()))?;
+    fs::copy(&self.staging_location, config_dir.join(file_name))
+      .map_err(|e| ValheimModError::FileMoveError(e.to_string()))?;
+    Ok(())
+  }
+
+  /// Install: Moves a framework like BepInEx into the game directory.
+  fn install_framework(
+    &self,
+    transaction: &mut InstallTransaction,
+    extract_path: &Path,
+  ) -> Result<(), ValheimModError> {
+    info!("Installing Framework...");
+    // Thunderstore packs nest the framework in a directory named after the package.
+    let manifest = Manifest::try_from(extract_path.join("manifest.json")).ok();
+    let source = match &manifest {
+      Some(manifest) if extract_path.join(&manifest.name).is_dir() => {
+        extract_path.join(&manifest.name)
+      }
+      _ => extract_path.to_path_buf(),
     };
+    let final_dir = PathBuf::from(&common_paths::game_directory());
+    // Record what the pack installs so `odin bepinex remove` can take it out again.
+    let pack = match manifest {
+      Some(manifest) => BepInExPack::from_source(&manifest.name, manifest.version_number, &source),
+      None => BepInExPack::from_source("BepInEx", None, &source),
+    };
+    transaction.protect_tree(&source, &final_dir)?;
+    transaction.protect_file(&BepInExPack::path(&final_dir))?;
+    dir::move_dir(source, &final_dir, &Self::move_options())
+      .map_err(|e| ValheimModError::FileMoveError(e.to_string()))?;
+    pack.write(&final_dir)?;
+    Ok(())
+  }
 
-    let manifest = Manifest::try_from(temp_dir.path().join("manifest.json"))
+  /// Install: Moves a Thunderstore package into a plugin directory named after its manifest.
+  fn install_thunderstore(
+    &self,
+    transaction: &mut InstallTransaction,
+    extract_path: &Path,
+  ) -> Result<(), ValheimModError> {
+    info!("Installing Mod...");
+    let mut options = Self::move_options();
+    let manifest = Manifest::try_from(extract_path.join("manifest.json"))
       .map_err(|e| ValheimModError::ManifestDeserializeError(format!("Ayyre buddy {}", e)))?;
 
-    // Move extracted files to the appropriate final destination.
-    if is_framework {
-      info!("Installing Framework...");
-      let final_dir = PathBuf::from(&common_paths::game_directory());
-      dir::move_dir(temp_dir.path().join(&manifest.name), &final_dir, &options)
+    let final_dir = PathBuf::from(&common_paths::bepinex_plugin_directory()).join(&manifest.name);
+    // If a manifest exists, use its name for a subdirectory.
+    create_dir_all(&final_dir)
+      .map_err(|e| ValheimModError::DirectoryCreationError(e.to_string()))?;
+
+    // Path to the 'plugins' directory within the temp directory
+    let plugins_path = extract_path.join("plugins");
+
+    if extract_path.join("Plugins").exists() {
+      debug!("Looks like someone used Plugins instead of plugins, lets fix that.");
+      dir::move_dir(extract_path.join("Plugins"), &plugins_path, &options)
         .map_err(|e| ValheimModError::FileMoveError(e.to_string()))?;
-    } else {
-      info!("Installing Mod...");
-      let final_dir = PathBuf::from(&common_paths::bepinex_plugin_directory()).join(&manifest.name);
-      // If a manifest exists, use its name for a subdirectory.
-      create_dir_all(&final_dir)
-        .map_err(|e| ValheimModError::DirectoryCreationError(e.to_string()))?;
-
-      // Path to the 'plugins' directory within the temp directory
-      let plugins_path = temp_dir.path().join("plugins");
-
-      if temp_dir.path().join("Plugins").exists() {
-        debug!("Looks like someone used Plugins instead of plugins, lets fix that.");
-        dir::move_dir(temp_dir.path().join("Plugins"), &plugins_path, &options)
+    }
+
+    // Check if the 'plugins' directory exists
+    if plugins_path.exists() && plugins_path.is_dir() {
+      let mut plugin_options = options.clone();
+      plugin_options.copy_inside = true;
+      transaction.protect_tree(&plugins_path, &final_dir)?;
+      dir::move_dir(&plugins_path, &final_dir, &plugin_options)
+        .map_err(|e| ValheimModError::FileMoveError(e.to_string()))?;
+      // Set options depth of one to maintain manifest.json
+      options.depth = 1
+    }
+    transaction.protect_tree(extract_path, &final_dir)?;
+    dir::move_dir(extract_path, &final_dir, &options)
+      .map_err(|e| ValheimModError::FileMoveError(e.to_string()))?;
+    Ok(())
+  }
+
+  /// Install: Merges an archive mirroring the BepInEx directory into BepInEx.
+  fn install_bepinex_tree(
+    &self,
+    transaction: &mut InstallTransaction,
+    bepinex_root: &Path,
+  ) -> Result<(), ValheimModError> {
+    info!("Installing BepInEx structured Mod...");
+    // Name the mod after its plugin directory if it only has one.
+    let plugin_dirs: Vec<PathBuf> = fs::read_dir(bepinex_root.join("plugins"))
+      .map(|entries| {
+        entries
+          .flatten()
+          .map(|entry| entry.path())
+          .filter(|path| path.is_dir())
+          .collect()
+      })
+      .unwrap_or_default();
+    let name = match plugin_dirs.as_slice() {
+      [plugin_dir] => plugin_dir
+        .file_name()
+        .unwrap()
+        .to_string_lossy()
+        .to_string(),
+      _ => self.mod_name(),
+    };
+
+    let final_dir = PathBuf::from(&common_paths::bepinex_directory());
+    transaction.protect_tree(bepinex_root, &final_dir)?;
+    create_dir_all(&final_dir)
+      .map_err(|e| ValheimModError::DirectoryCreationError(e.to_string()))?;
+    dir::move_dir(bepinex_root, &final_dir, &Self::move_options())
+      .map_err(|e| ValheimModError::FileMoveError(e.to_string()))?;
+    self.synthesize_manifest(transaction, &name)
+  }
+
+  /// Install: Moves an archive without any known structure into its own plugin directory,
+  /// routing config files into the BepInEx config directory.
+  fn install_loose(
+    &self,
+    transaction: &mut InstallTransaction,
+    extract_path: &Path,
+  ) -> Result<(), ValheimModError> {
+    let name = self.mod_name();
+    info!("Installing Mod {} without a manifest...", name);
+    let config_dir = PathBuf::from(&common_paths::bepinex_config_directory());
+    for entry in WalkDir::new(extract_path).into_iter().flatten() {
+      let is_config = entry
+        .path()
+        .extension()
+        .is_some_and(|extension| extension.eq_ignore_ascii_case("cfg"));
+      if entry.file_type().is_file() && is_config {
+        transaction.protect_file(&config_dir.join(entry.file_name()))?;
+        create_dir_all(&config_dir)
+          .map_err(|e| ValheimModError::DirectoryCreationError(e.to_string()))?;
+        fs::rename(entry.path(), config_dir.join(entry.file_name()))
+          .or_else(|_| {
+            fs::copy(entry.path(), config_dir.join(entry.file_name()))
+              .and_then(|_| fs::remove_file(entry.path()))
+          })
           .map_err(|e| ValheimModError::FileMoveError(e.to_string()))?;
       }
+    }
 
-      // Check if the 'plugins' directory exists
-      if plugins_path.exists() && plugins_path.is_dir() {
-        let mut plugin_options = options.clone();
-        plugin_options.copy_inside = true;
-        dir::move_dir(&plugins_path, &final_dir, &plugin_options)
-          .map_err(|e| ValheimModError::FileMoveError(e.to_string()))?;
-        // Set options depth of one to maintain manifest.json
-        options.depth = 1
+    let final_dir = PathBuf::from(&common_paths::bepinex_plugin_directory()).join(&name);
+    transaction.protect_tree(extract_path, &final_dir)?;
+    create_dir_all(&final_dir)
+      .map_err(|e| ValheimModError::DirectoryCreationError(e.to_string()))?;
+    dir::move_dir(extract_path, &final_dir, &Self::move_options())
+      .map_err(|e| ValheimModError::FileMoveError(e.to_string()))?;
+    self.synthesize_manifest(transaction, &name)
+  }
+
+  /// Install: Installs bare DLLs and config files directly. Archives are extracted into a
+  /// temporary directory, their layout is detected and the files are moved to their final
+  /// destination, after which the temp directory is cleaned up.
+  fn apply(&self, transaction: &mut InstallTransaction) -> Result<(), ValheimModError> {
+    match self.file_type.to_lowercase().as_str() {
+      "dll" => self.install_plugin_file(transaction)?,
+      "cfg" => self.install_config_file(transaction)?,
+      _ => {
+        // Create a temporary directory for extraction.
+        let temp_dir = tempdir().map_err(|e| {
+          ValheimModError::TempDirCreationError(format!("Failed to create temp dir: {}", e))
+        })?;
+        debug!("Created temporary directory at {:?}", temp_dir.path());
+
+        // Extract the archive (from staging) into the temporary directory.
+        ArchiveFormat::detect(&self.staging_location)?
+          .extract(&self.staging_location, temp_dir.path())
+          .inspect_err(|e| error!("Failed to extract archive: {}", e))?;
+        normalize_paths(temp_dir.path())
+          .map_err(|e| ValheimModError::ExtractionError(e.to_string()))?;
+        debug!("Extraction complete to {:?}", temp_dir.path());
+
+        // Validate mod type by inspecting the extracted files.
+        let layout = if self.is_mod_framework(temp_dir.path()) {
+          ModLayout::Framework
+        } else {
+          ModLayout::detect(temp_dir.path())
+        };
+        debug!("Detected mod layout: {:?}", layout);
+
+        match layout {
+          ModLayout::Framework => self.install_framework(transaction, temp_dir.path())?,
+          ModLayout::Thunderstore => self.install_thunderstore(transaction, temp_dir.path())?,
+          ModLayout::BepInExTree(root) => self.install_bepinex_tree(transaction, &root)?,
+          ModLayout::Loose => self.install_loose(transaction, temp_dir.path())?,
+        }
+        // The temporary directory is automatically removed here.
       }
-      dir::move_dir(temp_dir, &final_dir, &options)
-        .map_err(|e| ValheimModError::FileMoveError(e.to_string()))?;
     }
+    Ok(())
+  }
+
+  /// Install: Applies the mod within a transaction, if anything fails every file touched so
+  /// far is restored. Should odin die halfway `odin mod:repair` picks up the journal.
+  pub fn install(&mut self) -> Result<(), ValheimModError> {
+    // Ensure that the staging location is a file (the downloaded archive).
+    if self.staging_location.is_dir() {
+      error!(
+        "Failed to install mod! Staging location is a directory: {:?}",
+        self.staging_location
+      );
+      return Err(ValheimModError::InvalidStagingLocation);
+    }
+
+    let mut transaction = InstallTransaction::begin(
+      &transactions_directory(),
+      &self.url,
+      &self.file_type,
+      &self.staging_location,
+      self.nexus.clone(),
+    )?;
+    if let Err(e) = self.apply(&mut transaction) {
+      error!("Failed to install mod, rolling back: {}", e);
+      if let Err(rollback_error) = transaction.rollback() {
+        error!(
+          "Failed to roll back, run `odin mod:repair` to try again: {}",
+          rollback_error
+        );
+      }
+      return Err(e);
+    }
+    transaction.commit()?;
 
-    // The temporary directory is automatically removed here.
     self.installed = true;
     Ok(())
   }
@@ -190,15 +440,19 @@ impl ValheimMod {
 impl TryFrom<String> for ValheimMod {
   type Error = ValheimModError;
 
-  fn try_from(url: String) -> Result<Self, Self::Error> {
-    if is_valid_url(&url) {
-      Ok(ValheimMod::new(&url))
-    } else if let Some((author, mod_name, version)) = parse_mod_string(&url) {
+  fn try_from(input: String) -> Result<Self, Self::Error> {
+    let (url, sha256) = split_sha256(&input);
+    if is_valid_url(url) {
+      Ok(ValheimMod::new(url).with_sha256(sha256))
+    } else if let Some((mod_id, file_id)) = parse_nexus_id(url) {
+      let download = nexus_download(mod_id, file_id, sha256.as_deref())?;
+      Ok(ValheimMod::from_nexus(download).with_sha256(sha256))
+    } else if let Some((author, mod_name, version)) = parse_mod_string(url) {
       let constructed_url = format!(
         "https://gcdn.thunderstore.io/live/repository/packages/{}-{}-{}.zip",
         author, mod_name, version
       );
-      Ok(ValheimMod::new(&constructed_url))
+      Ok(ValheimMod::new(&constructed_url).with_sha256(sha256))
     } else {
       Err(ValheimModError::InvalidUrl)
     }
@@ -208,6 +462,12 @@ impl TryFrom<String> for ValheimMod {
 #[cfg(test)]
 mod install_test {
   use super::*;
+  use crate::constants::GAME_LOCATION;
+  use serial_test::serial;
+  use std::env::{remove_var, set_var};
+  use std::fs::File;
+  use std::io::Write;
+  use zip::write::SimpleFileOptions;
 
   // Helper to create a ValheimMod instance with a given staging location.
   fn valheim_mod_with_staging(url: String, staging: PathBuf) -> ValheimMod {
@@ -216,11 +476,14 @@ mod install_test {
       staging_location: staging,
       installed: false,
       downloaded: false,
+      sha256: None,
+      nexus: None,
       file_type: "zip".to_string(),
     }
   }
 
   #[test]
+  #[serial]
   fn test_install_framework() {
     // Use a test resource ZIP that represents a framework mod.
     let staging = PathBuf::from("tests/resources/manifest.framework.zip");
@@ -232,6 +495,7 @@ mod install_test {
   }
 
   #[test]
+  #[serial]
   fn test_install_mod() {
     // Use a test resource ZIP that represents a regular mod.
     let staging = PathBuf::from("tests/resources/manifest.mod.zip");
@@ -241,4 +505,234 @@ mod install_test {
     assert!(result.is_ok(), "{:?}", result.err());
     assert!(mod_inst.installed);
   }
+
+  // Installs a mod into a temporary game directory and returns that directory.
+  fn install_into_temp_game(staging: PathBuf, file_type: &str) -> tempfile::TempDir {
+    let mut mod_inst = valheim_mod_with_staging("https://example.com/mod".to_string(), staging);
+    mod_inst.file_type = file_type.to_string();
+    install_mod_into_temp_game(mod_inst)
+  }
+
+  fn install_mod_into_temp_game(mut mod_inst: ValheimMod) -> tempfile::TempDir {
+    let game_dir = tempdir().unwrap();
+    set_var(GAME_LOCATION, game_dir.path());
+    let result = mod_inst.install();
+    remove_var(GAME_LOCATION);
+    assert!(result.is_ok(), "{:?}", result.err());
+    assert!(mod_inst.installed);
+    game_dir
+  }
+
+  #[test]
+  #[serial]
+  fn test_install_plugin_dll() {
+    let staging_dir = tempdir().unwrap();
+    let staging = staging_dir.path().join("MyPlugin.dll");
+    fs::write(&staging, "dll").unwrap();
+
+    let game_dir = install_into_temp_game(staging, "dll");
+    let plugin_dir = game_dir.path().join("BepInEx/plugins/MyPlugin");
+    assert!(plugin_dir.join("MyPlugin.dll").exists());
+    let manifest = Manifest::try_from(plugin_dir.join("manifest.json")).unwrap();
+    assert_eq!(manifest.name, "MyPlugin");
+  }
+
+  #[test]
+  #[serial]
+  fn test_install_config_file() {
+    let staging_dir = tempdir().unwrap();
+    let staging = staging_dir.path().join("com.example.mod.cfg");
+    fs::write(&staging, "[General]").unwrap();
+
+    let game_dir = install_into_temp_game(staging, "cfg");
+    assert!(game_dir
+      .path()
+      .join("BepInEx/config/com.example.mod.cfg")
+      .exists());
+  }
+
+  #[test]
+  #[serial]
+  fn test_install_bepinex_tree() {
+    let staging_dir = tempdir().unwrap();
+    let staging = staging_dir.path().join("NexusMod-123-1-0.zip");
+    let mut zip = zip::ZipWriter::new(File::create(&staging).unwrap());
+    for file in [
+      "NexusMod/BepInEx/plugins/NexusMod/NexusMod.dll",
+      "NexusMod/BepInEx/config/nexus.cfg",
+    ] {
+      zip.start_file(file, SimpleFileOptions::default()).unwrap();
+      zip.write_all(b"content").unwrap();
+    }
+    zip.finish().unwrap();
+
+    let game_dir = install_into_temp_game(staging, "zip");
+    let bepinex = game_dir.path().join("BepInEx");
+    assert!(bepinex.join("plugins/NexusMod/NexusMod.dll").exists());
+    assert!(bepinex.join("plugins/NexusMod/manifest.json").exists());
+    assert!(bepinex.join("config/nexus.cfg").exists());
+  }
+
+  #[test]
+  #[serial]
+  fn test_install_loose_tar_gz() {
+    let staging_dir = tempdir().unwrap();
+    let staging = staging_dir.path().join("LooseMod.tar.gz");
+    let encoder = flate2::write::GzEncoder::new(
+      File::create(&staging).unwrap(),
+      flate2::Compression::default(),
+    );
+    let mut tar = tar::Builder::new(encoder);
+    for file in ["LooseMod.dll", "loose.cfg"] {
+      let mut header = tar::Header::new_gnu();
+      header.set_size(7);
+      header.set_mode(0o644);
+      header.set_cksum();
+      tar.append_data(&mut header, file, &b"content"[..]).unwrap();
+    }
+    tar.into_inner().unwrap().finish().unwrap();
+
+    let game_dir = install_into_temp_game(staging, "gz");
+    let bepinex = game_dir.path().join("BepInEx");
+    assert!(bepinex.join("plugins/LooseMod/LooseMod.dll").exists());
+    assert!(bepinex.join("plugins/LooseMod/manifest.json").exists());
+    assert!(bepinex.join("config/loose.cfg").exists());
+  }
+
+  #[test]
+  #[serial]
+  fn test_installed_mod_reported_outdated() {
+    use crate::mods::installed_mods::installed_mods;
+    use crate::mods::outdated::find_outdated_mods;
+    use crate::mods::thunderstore::{Package, PackageVersion};
+
+    let staging_dir = tempdir().unwrap();
+    let staging = staging_dir.path().join("ValheimModding-Jotunn-2.0.0.zip");
+    let mut zip = zip::ZipWriter::new(File::create(&staging).unwrap());
+    zip
+      .start_file("manifest.json", SimpleFileOptions::default())
+      .unwrap();
+    zip
+      .write_all(br#"{"name": "Jotunn", "version_number": "2.0.0"}"#)
+      .unwrap();
+    zip
+      .start_file("Jotunn.dll", SimpleFileOptions::default())
+      .unwrap();
+    zip.write_all(b"content").unwrap();
+    zip.finish().unwrap();
+
+    let game_dir = install_into_temp_game(staging, "zip");
+    let index = vec![Package {
+      name: "Jotunn".to_string(),
+      full_name: "ValheimModding-Jotunn".to_string(),
+      owner: "ValheimModding".to_string(),
+      is_deprecated: false,
+      versions: vec![PackageVersion {
+        name: "Jotunn".to_string(),
+        full_name: "ValheimModding-Jotunn-2.1.0".to_string(),
+        version_number: "2.1.0".to_string(),
+        download_url: "https://thunderstore.io/package/download/ValheimModding/Jotunn/2.1.0/"
+          .to_string(),
+      }],
+    }];
+    set_var(GAME_LOCATION, game_dir.path());
+    let outdated = find_outdated_mods(&installed_mods(), &index);
+    remove_var(GAME_LOCATION);
+
+    assert_eq!(outdated.len(), 1);
+    assert_eq!(outdated[0].installed_version, "2.0.0");
+    assert_eq!(
+      outdated[0].latest_mod_string(),
+      "ValheimModding-Jotunn-2.1.0"
+    );
+  }
+
+  #[test]
+  #[serial]
+  fn test_install_bepinex_pack_and_remove() {
+    use crate::mods::bepinex::BepInExEnvironment;
+    use crate::mods::bepinex_pack::{remove, BepInExPack};
+
+    let staging_dir = tempdir().unwrap();
+    let staging = staging_dir.path().join("BepInExPack_Valheim.zip");
+    let mut zip = zip::ZipWriter::new(File::create(&staging).unwrap());
+    zip
+      .start_file("manifest.json", SimpleFileOptions::default())
+      .unwrap();
+    zip
+      .write_all(br#"{"name": "BepInExPack_Valheim", "version_number": "5.4.2202"}"#)
+      .unwrap();
+    for file in [
+      "BepInExPack_Valheim/BepInEx/core/BepInEx.Preloader.dll",
+      "BepInExPack_Valheim/BepInEx/core/BepInEx.dll",
+      "BepInExPack_Valheim/doorstop_libs/libdoorstop_x64.so",
+      "BepInExPack_Valheim/unstripped_corlib/mscorlib.dll",
+      "BepInExPack_Valheim/winhttp.dll",
+    ] {
+      zip.start_file(file, SimpleFileOptions::default()).unwrap();
+      zip.write_all(b"content").unwrap();
+    }
+    zip.finish().unwrap();
+
+    let game_dir = install_into_temp_game(staging, "zip");
+    let pack = BepInExPack::load(game_dir.path()).unwrap();
+    assert_eq!(pack.version.as_deref(), Some("5.4.2202"));
+    assert_eq!(pack.files.len(), 5);
+
+    let plugin = game_dir.path().join("BepInEx/plugins/Jotunn/Jotunn.dll");
+    fs::create_dir_all(plugin.parent().unwrap()).unwrap();
+    fs::write(&plugin, "content").unwrap();
+
+    set_var(GAME_LOCATION, game_dir.path());
+    let environment = BepInExEnvironment::new();
+    assert!(environment.is_installed());
+    assert!(
+      environment.verify().is_empty(),
+      "{:?}",
+      environment.verify()
+    );
+    assert_eq!(environment.version().as_deref(), Some("5.4.2202"));
+
+    remove(game_dir.path(), false).unwrap();
+    let environment = BepInExEnvironment::new();
+    remove_var(GAME_LOCATION);
+    assert!(!environment.is_installed());
+    assert!(!game_dir.path().join("winhttp.dll").exists());
+    assert!(!game_dir.path().join("unstripped_corlib").exists());
+    assert!(plugin.exists());
+  }
+
+  #[test]
+  #[serial]
+  fn test_install_nexus_mod_records_metadata() {
+    let staging_dir = tempdir().unwrap();
+    let staging = staging_dir.path().join("EpicLoot-387-0-10-3.zip");
+    let mut zip = zip::ZipWriter::new(File::create(&staging).unwrap());
+    zip
+      .start_file("EpicLoot.dll", SimpleFileOptions::default())
+      .unwrap();
+    zip.write_all(b"content").unwrap();
+    zip.finish().unwrap();
+
+    let mut mod_inst = valheim_mod_with_staging("https://example.com/mod".to_string(), staging);
+    mod_inst.nexus = Some(NexusMetadata {
+      mod_id: 387,
+      file_id: 1234,
+      name: "Epic Loot".to_string(),
+      author: Some("RandyKnapp".to_string()),
+      version: Some("0.10.3".to_string()),
+      file_name: "EpicLoot-387-0-10-3.zip".to_string(),
+    });
+    let game_dir = install_mod_into_temp_game(mod_inst);
+
+    let plugin_dir = game_dir.path().join("BepInEx/plugins/EpicLoot");
+    assert!(plugin_dir.join("EpicLoot.dll").exists());
+    let manifest = Manifest::try_from(plugin_dir.join("manifest.json")).unwrap();
+    assert_eq!(manifest.version_number.as_deref(), Some("0.10.3"));
+    assert_eq!(
+      manifest.website_url.as_deref(),
+      Some("https://www.nexusmods.com/valheim/mods/387")
+    );
+    assert_eq!(manifest.nexus.unwrap().file_id, 1234);
+  }
 }
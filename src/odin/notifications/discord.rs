@@ -1,11 +1,20 @@
+use crate::commands::status::local_address;
 use crate::files::discord::load_discord;
 use crate::notifications::enums::event_status::EventStatus;
-use crate::notifications::enums::notification_event::parse_server_name_for_notification;
+use crate::notifications::enums::notification_event::{
+  parse_server_name_for_notification, EventType,
+};
 use crate::notifications::enums::player::PlayerStatus;
+use crate::notifications::sinks::render;
 use crate::notifications::NotificationMessage;
-use handlebars::Handlebars;
-use log::debug;
+use crate::utils::fetch_public_address;
+use crate::worlds::active_world;
+use a2s::A2SClient;
+use log::{debug, warn};
+use regex::Regex;
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::str::FromStr;
 
 #[derive(Debug, PartialEq)]
 enum Color {
@@ -44,51 +53,124 @@ pub fn is_discord_webhook(webhook_url: &str) -> bool {
   webhook_url.starts_with(DISCORD_WEBHOOK_BASE) || webhook_url.starts_with(DISCORDAPP_WEBHOOK_BASE)
 }
 
-#[derive(Deserialize, Serialize)]
+#[derive(Clone, Default, Deserialize, Serialize)]
+pub struct DiscordEmbedField {
+  pub(crate) name: String,
+  pub(crate) value: String,
+  #[serde(default)]
+  pub(crate) inline: bool,
+}
+
+#[derive(Clone, Default, Deserialize, Serialize)]
+pub struct DiscordEmbedFooter {
+  pub(crate) text: String,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub(crate) icon_url: Option<String>,
+}
+
+#[derive(Clone, Default, Deserialize, Serialize)]
+pub struct DiscordEmbedAuthor {
+  pub(crate) name: String,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub(crate) url: Option<String>,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub(crate) icon_url: Option<String>,
+}
+
+/// The thumbnail or image of an embed.
+#[derive(Clone, Default, Deserialize, Serialize)]
+pub struct DiscordEmbedImage {
+  pub(crate) url: String,
+}
+
+/// An embed as Discord takes it, the color is filled from the event status when left out.
+#[derive(Clone, Default, Deserialize, Serialize)]
 pub struct DiscordWebHookEmbed {
+  #[serde(default)]
   pub(crate) title: String,
+  #[serde(default)]
   pub(crate) description: String,
-  pub(crate) color: i32,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub(crate) color: Option<i32>,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub(crate) url: Option<String>,
+  /// ISO 8601, like the `{{timestamp}}` of the notification.
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub(crate) timestamp: Option<String>,
+  #[serde(default, skip_serializing_if = "Vec::is_empty")]
+  pub(crate) fields: Vec<DiscordEmbedField>,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub(crate) footer: Option<DiscordEmbedFooter>,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub(crate) author: Option<DiscordEmbedAuthor>,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub(crate) thumbnail: Option<DiscordEmbedImage>,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub(crate) image: Option<DiscordEmbedImage>,
 }
 
-impl Clone for DiscordWebHookEmbed {
-  fn clone(&self) -> Self {
-    DiscordWebHookEmbed {
-      title: String::from(&self.title),
-      description: String::from(&self.description),
-      color: self.color,
-    }
-  }
-}
-
-#[derive(Deserialize, Serialize)]
+#[derive(Clone, Deserialize, Serialize)]
 pub struct DiscordWebHookBody {
+  #[serde(default)]
   pub(crate) content: String,
+  /// Overrides the name the webhook posts as.
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub(crate) username: Option<String>,
+  /// Overrides the avatar the webhook posts with.
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub(crate) avatar_url: Option<String>,
+  #[serde(default)]
   pub(crate) embeds: Vec<DiscordWebHookEmbed>,
 }
 
-impl Clone for DiscordWebHookBody {
-  fn clone(&self) -> Self {
-    DiscordWebHookBody {
-      content: String::from(&self.content),
-      embeds: self.embeds.clone(),
-    }
-  }
-}
-
 impl Default for DiscordWebHookBody {
   fn default() -> Self {
     DiscordWebHookBody {
       content: "Notification: {{server_name}}".to_string(),
+      username: None,
+      avatar_url: None,
       embeds: vec![DiscordWebHookEmbed {
         title: "{{title}}".to_string(),
         description: "{{description}}".to_string(),
-        color: 16388413,
+        timestamp: Some("{{timestamp}}".to_string()),
+        footer: Some(DiscordEmbedFooter {
+          text: "{{server_name}}".to_string(),
+          icon_url: None,
+        }),
+        ..DiscordWebHookEmbed::default()
       }],
     }
   }
 }
 
+/// The color of the embeds of a notification.
+fn embed_color(event_type: &EventType) -> Color {
+  match (event_type.name.as_str(), event_type.status.as_str()) {
+    ("Player", "Joined") => Color::Join,
+    ("Player", "Left") => Color::Leave,
+    (_, status) => EventStatus::from_str(status)
+      .map(Color::from)
+      .unwrap_or(Color::Generic),
+  }
+}
+
+/// Colors the embeds of a rendered payload that don't set a color of their own.
+pub fn color_embeds(payload: &mut Value, event_type: &EventType) {
+  let color = embed_color(event_type) as i32;
+  let embeds = payload
+    .get_mut("embeds")
+    .and_then(Value::as_array_mut)
+    .into_iter()
+    .flatten()
+    .filter_map(Value::as_object_mut);
+  for embed in embeds {
+    if embed.get("color").is_none_or(Value::is_null) {
+      embed.insert(String::from("color"), Value::from(color));
+    }
+  }
+}
+
+/// The values templates render with.
 #[derive(Deserialize, Serialize)]
 pub struct IncomingNotification {
   title: String,
@@ -96,6 +178,99 @@ pub struct IncomingNotification {
   status: String,
   timestamp: String,
   server_name: String,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  player_count: Option<u8>,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  max_players: Option<u8>,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  version: Option<String>,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  world: Option<String>,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  public_ip: Option<String>,
+}
+
+/// The variables the handlebars expressions of `template` name, string literals aside.
+fn template_variables(template: &str) -> Vec<String> {
+  let expression = Regex::new(r"\{\{\{?([^}]*)\}?\}\}").unwrap();
+  let literal = Regex::new(r#""[^"]*"|'[^']*'"#).unwrap();
+  let variable = Regex::new(r"[A-Za-z_]\w*").unwrap();
+  expression
+    .captures_iter(template)
+    .flat_map(|captures| {
+      // The templates are rendered from JSON, which escapes the quotes of literals.
+      let expression = captures[1].replace("\\\"", "\"");
+      let expression = literal.replace_all(&expression, " ").to_string();
+      variable
+        .find_iter(&expression)
+        .map(|name| name.as_str().to_string())
+        .collect::<Vec<_>>()
+    })
+    .collect()
+}
+
+/// The values the templates of one notification render with. The server details are looked up
+/// once, when a template first uses them, as they take a query to the server or a request to an
+/// IP service.
+pub struct TemplateValues {
+  values: IncomingNotification,
+  server_info: bool,
+  world: bool,
+  public_ip: bool,
+}
+
+impl TemplateValues {
+  pub fn new(notification: &NotificationMessage) -> TemplateValues {
+    TemplateValues {
+      values: IncomingNotification::from(notification),
+      server_info: false,
+      world: false,
+      public_ip: false,
+    }
+  }
+
+  /// Fixed server details for previews, which never query the server or the network.
+  pub fn sample(notification: &NotificationMessage) -> TemplateValues {
+    let mut values = IncomingNotification::from(notification);
+    values.player_count = Some(3);
+    values.max_players = Some(10);
+    values.version = Some(String::from("0.219.16"));
+    values.world = Some(String::from("Dedicated"));
+    values.public_ip = Some(String::from("203.0.113.7"));
+    TemplateValues {
+      values,
+      server_info: true,
+      world: true,
+      public_ip: true,
+    }
+  }
+
+  /// The values with the server details `template` uses.
+  pub fn for_template(&mut self, template: &str) -> &IncomingNotification {
+    let variables = template_variables(template);
+    let uses = |name: &str| variables.iter().any(|variable| variable == name);
+    if !self.server_info && (uses("player_count") || uses("max_players") || uses("version")) {
+      self.server_info = true;
+      let info = A2SClient::new().and_then(|client| client.info(local_address()));
+      match info {
+        Ok(info) => {
+          self.values.player_count = Some(info.players);
+          self.values.max_players = Some(info.max_players);
+          self.values.version = info.extended_server_info.keywords;
+        }
+        Err(e) => debug!("Server details unavailable for the notification: {}", e),
+      }
+    }
+    if !self.world && uses("world") {
+      self.world = true;
+      self.values.world = Some(active_world());
+    }
+    if !self.public_ip && uses("public_ip") {
+      self.public_ip = true;
+      self.values.public_ip = Some(fetch_public_address().ip);
+    }
+    &self.values
+  }
 }
 
 impl From<&NotificationMessage> for IncomingNotification {
@@ -106,38 +281,43 @@ impl From<&NotificationMessage> for IncomingNotification {
       status: String::from(&notification.event_type.status),
       timestamp: String::from(&notification.timestamp),
       server_name: parse_server_name_for_notification(),
+      player_count: None,
+      max_players: None,
+      version: None,
+      world: None,
+      public_ip: None,
     }
   }
 }
 
+/// Renders a `discord.json` template for a notification.
+fn render_body(
+  template: &DiscordWebHookBody,
+  event: &NotificationMessage,
+  values: &mut TemplateValues,
+) -> Result<DiscordWebHookBody, String> {
+  let template = serde_json::to_value(template).map_err(|e| e.to_string())?;
+  let mut payload = render(&template, values)?;
+  color_embeds(&mut payload, &event.event_type);
+  serde_json::from_value(payload).map_err(|e| e.to_string())
+}
+
 impl From<&NotificationMessage> for DiscordWebHookBody {
   fn from(event: &NotificationMessage) -> Self {
     let discord_file = load_discord();
-    let mut handlebars = Handlebars::new();
     let default_event = DiscordWebHookBody::default();
-    let discord_event = &discord_file
+    let discord_event = discord_file
       .events
       .get(&event.event_type.name.as_str().to_lowercase())
       .unwrap_or(&default_event);
-    let source = serde_json::to_string(&discord_event).unwrap();
-    debug!("Discord Notification Template: {}", &source);
-    handlebars
-      .register_template_string("notification", source)
-      .unwrap();
-
-    let values = IncomingNotification::from(event);
-    debug!(
-      "Discord Notification Values: {}",
-      serde_json::to_string(&values).unwrap()
-    );
-    let rendered = match handlebars.render("notification", &values) {
-      Ok(value) => {
-        debug!("Discord Notification Parsed: \n{}", value);
-        value
-      }
-      Err(msg) => panic!("{}", msg.to_string()),
-    };
-    serde_json::from_str(&rendered).unwrap()
+    let mut values = TemplateValues::new(event);
+    render_body(discord_event, event, &mut values).unwrap_or_else(|e| {
+      warn!(
+        "Failed to render the Discord template, using the default one: {}",
+        e
+      );
+      render_body(&default_event, event, &mut values).expect("the default Discord template renders")
+    })
   }
 }
 
@@ -149,6 +329,7 @@ mod tests {
   use crate::notifications::enums::player::PlayerStatus;
   use crate::notifications::NotificationMessage;
   use chrono::Local;
+  use serial_test::serial;
 
   #[test]
   fn test_color_from_event_status() {
@@ -170,7 +351,108 @@ mod tests {
     assert_eq!(template.embeds.len(), 1);
     assert_eq!(template.embeds[0].title, "{{title}}");
     assert_eq!(template.embeds[0].description, "{{description}}");
-    assert_eq!(template.embeds[0].color, 16388413);
+    assert_eq!(template.embeds[0].color, None);
+    assert_eq!(
+      template.embeds[0].timestamp.as_deref(),
+      Some("{{timestamp}}")
+    );
+  }
+
+  #[test]
+  fn test_embed_model() {
+    let body: DiscordWebHookBody = serde_json::from_value(serde_json::json!({
+      "username": "Odin",
+      "embeds": [{
+        "title": "{{title}}",
+        "fields": [
+          { "name": "Players", "value": "{{player_count}}/{{max_players}}", "inline": true },
+          { "name": "World", "value": "{{world}}" }
+        ],
+        "thumbnail": { "url": "https://example.com/valheim.png" }
+      }]
+    }))
+    .unwrap();
+    assert_eq!(body.content, "");
+    assert_eq!(body.username.as_deref(), Some("Odin"));
+    let embed = &body.embeds[0];
+    assert_eq!(embed.fields.len(), 2);
+    assert!(embed.fields[0].inline);
+    assert!(!embed.fields[1].inline);
+    assert_eq!(
+      embed.thumbnail.as_ref().unwrap().url,
+      "https://example.com/valheim.png"
+    );
+
+    // Unset parts are left out of the payload.
+    let payload = serde_json::to_value(&body).unwrap();
+    assert!(payload.get("avatar_url").is_none());
+    assert!(payload["embeds"][0].get("footer").is_none());
+  }
+
+  #[test]
+  fn test_color_embeds() {
+    let mut payload = serde_json::json!({
+      "embeds": [{ "title": "Joined" }, { "title": "Custom", "color": 255 }]
+    });
+    let joined = NotificationEvent::Player(PlayerStatus::Joined).to_event_type();
+    color_embeds(&mut payload, &joined);
+    assert_eq!(payload["embeds"][0]["color"], Color::Join as i32);
+    assert_eq!(payload["embeds"][1]["color"], 255);
+
+    let mut payload = serde_json::json!({ "embeds": [{ "title": "Update" }] });
+    let failed = NotificationEvent::Update(EventStatus::Failed).to_event_type();
+    color_embeds(&mut payload, &failed);
+    assert_eq!(payload["embeds"][0]["color"], Color::Failure as i32);
+
+    let mut payload = serde_json::json!({ "embeds": [{ "title": "Broadcast" }] });
+    let broadcast = NotificationEvent::Broadcast.to_event_type();
+    color_embeds(&mut payload, &broadcast);
+    assert_eq!(payload["embeds"][0]["color"], Color::Generic as i32);
+  }
+
+  #[test]
+  #[serial]
+  fn test_template_values() {
+    let notification = NotificationMessage {
+      author: String::from("odin"),
+      event_type: NotificationEvent::Start(EventStatus::Successful).to_event_type(),
+      event_message: String::from("Server started"),
+      timestamp: String::from("2024-01-01T00:00:00+00:00"),
+    };
+    std::env::set_var("NAME", "Midgard");
+    std::env::set_var("WORLD", "Yggdrasil");
+    let mut values = TemplateValues::new(&notification);
+    let plain = values
+      .for_template("Welcome to the world, {{title}} version")
+      .world
+      .clone();
+    let with_world = values.for_template("{{title}} in {{world}}").world.clone();
+    std::env::set_var("WORLD", "Asgard");
+    let again = values
+      .for_template("{{#if world}}{{world}}{{/if}}")
+      .world
+      .clone();
+    std::env::remove_var("WORLD");
+    std::env::remove_var("NAME");
+    assert_eq!(plain, None);
+    assert_eq!(with_world.as_deref(), Some("Yggdrasil"));
+    assert_eq!(again.as_deref(), Some("Yggdrasil"));
+    assert_eq!(values.values.server_name, "Midgard");
+    assert!(!values.server_info && !values.public_ip);
+  }
+
+  #[test]
+  fn test_template_variables() {
+    assert_eq!(
+      template_variables(
+        r#"{"content": "The world {{#if (eq status \"world\")}}{{{title}}}{{/if}}"}"#
+      ),
+      ["if", "eq", "status", "title", "if"]
+    );
+    assert_eq!(
+      template_variables("version {{ server_name }}"),
+      ["server_name"]
+    );
   }
 
   #[test]
@@ -0,0 +1,636 @@
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::{mpsc as std_mpsc, Mutex, PoisonError};
+use std::thread;
+use std::time::Duration;
+
+use chrono::Utc;
+use log::{debug, error, info, warn};
+use reqwest::StatusCode;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender};
+use tokio::time::{sleep, timeout, timeout_at, Instant};
+
+use crate::notifications::providers::WebhookRequest;
+use crate::notifications::sinks::Sink;
+use crate::notifications::NotificationMessage;
+use crate::utils::common_paths::notification_spool_path;
+
+/// How long the dispatcher waits for more notifications before sending, so a flood like every
+/// player joining after a restart goes out together.
+const COALESCE_WINDOW: Duration = Duration::from_secs(2);
+/// Notifications of one event to one sink are merged into one once this many pile up.
+const COALESCE_AT: usize = 3;
+/// The messages a merged notification lists before counting the rest.
+const COALESCED_MESSAGES: usize = 10;
+const MAX_ATTEMPTS: u32 = 5;
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(60);
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+const MAX_SPOOLED: usize = 100;
+/// Spooled notifications older than this are dropped, they aren't news anymore.
+const SPOOL_TTL_SECONDS: i64 = 24 * 60 * 60;
+/// How often an idle dispatcher, e.g. of `odin logs --watch`, tries the spool again.
+const SPOOL_RETRY_INTERVAL: Duration = Duration::from_secs(5 * 60);
+/// How long odin waits for queued notifications before exiting, the rest is spooled.
+pub const FLUSH_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// A notification waiting to be delivered to a sink.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Queued {
+  pub sink: Sink,
+  /// The key of the event, e.g. `player_join`.
+  pub event: String,
+  pub notification: NotificationMessage,
+  pub queued_at: i64,
+}
+
+impl Queued {
+  pub fn new(sink: Sink, event: &str, notification: NotificationMessage) -> Queued {
+    Queued {
+      sink,
+      event: event.to_string(),
+      notification,
+      queued_at: Utc::now().timestamp(),
+    }
+  }
+}
+
+enum Command {
+  Send(Box<Queued>),
+  /// Delivers what is queued right away, spools what doesn't go out by the deadline and acks.
+  Flush(Instant, std_mpsc::Sender<()>),
+}
+
+/// The queue of the dispatcher and the process it runs in.
+struct Queue {
+  pid: u32,
+  sender: UnboundedSender<Command>,
+}
+
+static QUEUE: Mutex<Option<Queue>> = Mutex::new(None);
+
+fn start_dispatcher() -> UnboundedSender<Command> {
+  let (sender, receiver) = unbounded_channel();
+  let spawned = thread::Builder::new()
+    .name(String::from("notifications"))
+    .spawn(move || {
+      match tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+      {
+        Ok(runtime) => runtime.block_on(Dispatcher::new(notification_spool_path()).run(receiver)),
+        Err(e) => error!("Failed to start the notification dispatcher: {}", e),
+      }
+    });
+  if let Err(e) = spawned {
+    error!("Failed to start the notification dispatcher: {}", e);
+  }
+  sender
+}
+
+/// The queue of the dispatcher, which runs on a thread of its own so it works the same for
+/// the log watcher and for commands outside of any runtime. A forked child, like the daemonized
+/// server, doesn't get that thread and starts a dispatcher of its own.
+fn queue() -> UnboundedSender<Command> {
+  let mut queue = QUEUE.lock().unwrap_or_else(PoisonError::into_inner);
+  let pid = std::process::id();
+  match queue.as_ref() {
+    Some(queue) if queue.pid == pid => queue.sender.clone(),
+    _ => {
+      let sender = start_dispatcher();
+      *queue = Some(Queue {
+        pid,
+        sender: sender.clone(),
+      });
+      sender
+    }
+  }
+}
+
+/// Queues a notification, it is sent in the background.
+pub fn dispatch(queued: Queued) {
+  debug!("[{}]: Queueing {}", queued.sink.label(), queued.event);
+  if queue().send(Command::Send(Box::new(queued))).is_err() {
+    error!("The notification dispatcher is gone, dropping the notification");
+  }
+}
+
+/// Starts the dispatcher to send what a previous run couldn't deliver.
+pub fn resume_spooled() {
+  if Path::new(&notification_spool_path()).exists() {
+    queue();
+  }
+}
+
+/// Waits up to `timeout` for the queued notifications to go out, the rest is spooled to disk
+/// for the next run of odin. Call before exiting or forking.
+pub fn flush(timeout: Duration) {
+  let queue = match QUEUE
+    .lock()
+    .unwrap_or_else(PoisonError::into_inner)
+    .as_ref()
+  {
+    Some(queue) if queue.pid == std::process::id() => queue.sender.clone(),
+    _ => return,
+  };
+  let (ack, done) = std_mpsc::channel();
+  if queue
+    .send(Command::Flush(Instant::now() + timeout, ack))
+    .is_ok()
+  {
+    // The dispatcher spools whatever is left at the deadline, give it a moment for that.
+    let _ = done.recv_timeout(timeout + Duration::from_secs(1));
+  }
+}
+
+/// Flushes the notifications and exits, `process::exit` would skip the flush at the end of main.
+pub fn exit(code: i32) -> ! {
+  flush(FLUSH_TIMEOUT);
+  std::process::exit(code)
+}
+
+#[derive(Debug)]
+enum Failure {
+  /// The sink won't ever take the notification, e.g. a deleted webhook.
+  Rejected(String),
+  /// It might work later.
+  Undelivered(String),
+}
+
+#[derive(Debug)]
+enum Attempt {
+  Delivered,
+  RateLimited(Option<Duration>),
+  Failed(String),
+  Rejected(String),
+}
+
+struct Dispatcher {
+  client: reqwest::Client,
+  spool: PathBuf,
+  attempts: u32,
+  /// The delay before the first retry, doubled for every retry after it.
+  retry_delay: Duration,
+  coalesce_window: Duration,
+  spool_retry: Duration,
+}
+
+impl Dispatcher {
+  fn new(spool: impl Into<PathBuf>) -> Dispatcher {
+    Dispatcher {
+      client: reqwest::Client::builder()
+        .timeout(REQUEST_TIMEOUT)
+        .build()
+        .unwrap_or_default(),
+      spool: spool.into(),
+      attempts: MAX_ATTEMPTS,
+      retry_delay: Duration::from_secs(1),
+      coalesce_window: COALESCE_WINDOW,
+      spool_retry: SPOOL_RETRY_INTERVAL,
+    }
+  }
+
+  async fn run(self, mut receiver: UnboundedReceiver<Command>) {
+    let mut pending = take_spool(&self.spool);
+    if !pending.is_empty() {
+      info!("Sending {} spooled notifications", pending.len());
+    }
+    loop {
+      if pending.is_empty() {
+        match timeout(self.spool_retry, receiver.recv()).await {
+          Ok(Some(Command::Send(queued))) => pending.push(*queued),
+          Ok(Some(Command::Flush(_, ack))) => {
+            let _ = ack.send(());
+            continue;
+          }
+          Ok(None) => return,
+          // Idle, try what this or another odin couldn't deliver before.
+          Err(_) => {
+            pending = take_spool(&self.spool);
+            if !pending.is_empty() {
+              info!("Retrying {} spooled notifications", pending.len());
+            }
+            continue;
+          }
+        }
+      }
+      let window = Instant::now() + self.coalesce_window;
+      let mut flush = None;
+      while flush.is_none() {
+        match timeout_at(window, receiver.recv()).await {
+          Ok(Some(Command::Send(queued))) => pending.push(*queued),
+          Ok(Some(Command::Flush(deadline, ack))) => flush = Some((deadline, ack)),
+          Ok(None) | Err(_) => break,
+        }
+      }
+      let deadline = flush.as_ref().map(|(deadline, _)| *deadline);
+      let undelivered = self
+        .deliver_all(coalesce(std::mem::take(&mut pending)), deadline)
+        .await;
+      if !undelivered.is_empty() {
+        warn!(
+          "Spooling {} undelivered notifications to {}",
+          undelivered.len(),
+          self.spool.display()
+        );
+        spool(&self.spool, undelivered);
+      }
+      if let Some((_, ack)) = flush {
+        let _ = ack.send(());
+      }
+    }
+  }
+
+  /// Delivers the notifications in order and returns the ones to try again later.
+  async fn deliver_all(&self, batch: Vec<Queued>, deadline: Option<Instant>) -> Vec<Queued> {
+    let mut undelivered = vec![];
+    for queued in batch {
+      if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+        undelivered.push(queued);
+        continue;
+      }
+      let label = queued.sink.label().to_string();
+      match self.deliver(&queued, deadline).await {
+        Ok(()) => info!("[{}]: Webhook message sent successfully!", label),
+        Err(Failure::Rejected(e)) => error!("[{}]: Dropping the {}: {}", label, queued.event, e),
+        Err(Failure::Undelivered(e)) => {
+          warn!("[{}]: Failed to send the {}: {}", label, queued.event, e);
+          undelivered.push(queued);
+        }
+      }
+    }
+    undelivered
+  }
+
+  async fn deliver(&self, queued: &Queued, deadline: Option<Instant>) -> Result<(), Failure> {
+    let request = render(queued.clone()).await.map_err(Failure::Rejected)?;
+    debug!("Webhook Payload: {}", request.body);
+    let mut attempt = 1;
+    loop {
+      let (delay, reason) = match self.attempt(&request).await {
+        Attempt::Delivered => return Ok(()),
+        Attempt::Rejected(e) => return Err(Failure::Rejected(e)),
+        Attempt::RateLimited(after) => (
+          after.unwrap_or_else(|| self.backoff(attempt)),
+          String::from("rate limited"),
+        ),
+        Attempt::Failed(e) => (self.backoff(attempt), e),
+      };
+      if attempt >= self.attempts {
+        return Err(Failure::Undelivered(reason));
+      }
+      let delay = delay.min(MAX_RETRY_DELAY);
+      if deadline.is_some_and(|deadline| Instant::now() + delay > deadline) {
+        return Err(Failure::Undelivered(reason));
+      }
+      debug!("{}, retrying in {:?}", reason, delay);
+      sleep(delay).await;
+      attempt += 1;
+    }
+  }
+
+  fn backoff(&self, attempt: u32) -> Duration {
+    self.retry_delay * 2u32.saturating_pow(attempt - 1)
+  }
+
+  async fn attempt(&self, request: &WebhookRequest) -> Attempt {
+    let response = match request.build(&self.client).send().await {
+      Ok(response) => response,
+      Err(e) => return Attempt::Failed(e.to_string()),
+    };
+    let status = response.status();
+    if status.is_success() {
+      return Attempt::Delivered;
+    }
+    let header = response
+      .headers()
+      .get("retry-after")
+      .and_then(|value| value.to_str().ok())
+      .and_then(|value| value.parse::<f64>().ok());
+    let body = response.text().await.unwrap_or_default();
+    match status {
+      // Discord says how long to wait in the body, in seconds with a fraction.
+      StatusCode::TOO_MANY_REQUESTS => Attempt::RateLimited(
+        serde_json::from_str::<Value>(&body)
+          .ok()
+          .and_then(|body| body["retry_after"].as_f64())
+          .or(header)
+          .filter(|seconds| seconds.is_finite() && *seconds >= 0.0)
+          .map(Duration::from_secs_f64),
+      ),
+      StatusCode::REQUEST_TIMEOUT => Attempt::Failed(format!("{}, {}", status, body)),
+      status if status.is_server_error() => Attempt::Failed(format!("{}, {}", status, body)),
+      status => Attempt::Rejected(format!("{}, {}", status, body)),
+    }
+  }
+}
+
+/// Renders the request, which reads template files and may look up the public ip.
+async fn render(queued: Queued) -> Result<WebhookRequest, String> {
+  tokio::task::spawn_blocking(move || queued.sink.request(&queued.event, &queued.notification))
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+/// Merges the notifications of one event to one sink once `COALESCE_AT` of them pile up.
+fn coalesce(batch: Vec<Queued>) -> Vec<Queued> {
+  let mut groups: Vec<Vec<Queued>> = vec![];
+  for queued in batch {
+    match groups
+      .iter_mut()
+      .find(|group| group[0].sink == queued.sink && group[0].event == queued.event)
+    {
+      Some(group) => group.push(queued),
+      None => groups.push(vec![queued]),
+    }
+  }
+  let mut coalesced = vec![];
+  for group in groups {
+    if group.len() < COALESCE_AT {
+      coalesced.extend(group);
+      continue;
+    }
+    let mut messages: Vec<&str> = group
+      .iter()
+      .take(COALESCED_MESSAGES)
+      .map(|queued| queued.notification.event_message.as_str())
+      .collect();
+    let more = group.len().saturating_sub(COALESCED_MESSAGES);
+    let more = format!("...and {} more", more);
+    if group.len() > COALESCED_MESSAGES {
+      messages.push(&more);
+    }
+    let event_message = messages.join("\n");
+    let last = group.last().unwrap();
+    debug!(
+      "[{}]: Merged {} {} notifications",
+      last.sink.label(),
+      group.len(),
+      last.event
+    );
+    coalesced.push(Queued {
+      notification: NotificationMessage {
+        event_message,
+        ..last.notification.clone()
+      },
+      ..last.clone()
+    });
+  }
+  coalesced
+}
+
+fn read_spool(path: &Path) -> Vec<Queued> {
+  let Ok(content) = fs::read_to_string(path) else {
+    return vec![];
+  };
+  serde_json::from_str(&content).unwrap_or_else(|e| {
+    warn!(
+      "Ignoring the spooled notifications in {}: {}",
+      path.display(),
+      e
+    );
+    vec![]
+  })
+}
+
+fn fresh(mut spooled: Vec<Queued>) -> Vec<Queued> {
+  let oldest = Utc::now().timestamp() - SPOOL_TTL_SECONDS;
+  spooled.retain(|queued| queued.queued_at >= oldest);
+  let excess = spooled.len().saturating_sub(MAX_SPOOLED);
+  spooled.drain(..excess);
+  spooled
+}
+
+/// Claims the spool so another odin doesn't send the same notifications, and reads it.
+fn take_spool(path: &Path) -> Vec<Queued> {
+  let claimed = path.with_extension(format!("spool.{}", std::process::id()));
+  if fs::rename(path, &claimed).is_err() {
+    return vec![];
+  }
+  let spooled = read_spool(&claimed);
+  let _ = fs::remove_file(&claimed);
+  fresh(spooled)
+}
+
+/// Adds notifications to the spool, which is only readable by its owner as sinks hold tokens.
+fn spool(path: &Path, undelivered: Vec<Queued>) {
+  let mut spooled = read_spool(path);
+  spooled.extend(undelivered);
+  let spooled = fresh(spooled);
+  let temporary = path.with_extension("spool.tmp");
+  let written = (|| -> std::io::Result<()> {
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    let mut file = options.open(&temporary)?;
+    file.write_all(serde_json::to_string(&spooled)?.as_bytes())?;
+    fs::rename(&temporary, path)
+  })();
+  if let Err(e) = written {
+    error!(
+      "Failed to spool the notifications to {}: {}",
+      path.display(),
+      e
+    );
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::notifications::enums::notification_event::NotificationEvent;
+  use crate::notifications::enums::player::PlayerStatus;
+  use crate::notifications::sinks::SinkKind;
+  use mockito::Matcher;
+  use serde_json::json;
+
+  fn joined(sink: &Sink, name: &str) -> Queued {
+    let event = NotificationEvent::Player(PlayerStatus::Joined);
+    Queued::new(
+      sink.clone(),
+      event.key(),
+      NotificationMessage {
+        author: String::from("odin"),
+        event_type: event.to_event_type(),
+        event_message: format!("Player {} has joined the adventure!", name),
+        timestamp: String::from("2024-01-01T00:00:00+00:00"),
+      },
+    )
+  }
+
+  fn dispatcher(spool: &Path) -> Dispatcher {
+    Dispatcher {
+      retry_delay: Duration::from_millis(10),
+      coalesce_window: Duration::from_millis(50),
+      spool_retry: Duration::from_millis(100),
+      ..Dispatcher::new(spool)
+    }
+  }
+
+  fn json_sink(url: String) -> Sink {
+    Sink {
+      url,
+      kind: Some(SinkKind::Json),
+      ..Sink::default()
+    }
+  }
+
+  #[tokio::test]
+  async fn test_retries_and_rate_limits() {
+    let dir = tempfile::tempdir().unwrap();
+    let mut server = mockito::Server::new_async().await;
+    let limited = server
+      .mock("POST", "/hook")
+      .with_status(429)
+      .with_body(
+        r#"{"message": "You are being rate limited.", "retry_after": 0.05, "global": false}"#,
+      )
+      .expect(1)
+      .create_async()
+      .await;
+    let failed = server
+      .mock("POST", "/hook")
+      .with_status(502)
+      .expect(1)
+      .create_async()
+      .await;
+    let delivered = server
+      .mock("POST", "/hook")
+      .with_status(204)
+      .expect(1)
+      .create_async()
+      .await;
+    let sink = json_sink(format!("{}/hook", server.url()));
+    let started = Instant::now();
+    let dispatcher = dispatcher(&dir.path().join("spool"));
+    assert!(dispatcher
+      .deliver(&joined(&sink, "Bjorn"), None)
+      .await
+      .is_ok());
+    assert!(started.elapsed() >= Duration::from_millis(50));
+    limited.assert_async().await;
+    failed.assert_async().await;
+    delivered.assert_async().await;
+
+    let gone = server
+      .mock("POST", "/gone")
+      .with_status(404)
+      .expect(1)
+      .create_async()
+      .await;
+    let sink = json_sink(format!("{}/gone", server.url()));
+    assert!(matches!(
+      dispatcher.deliver(&joined(&sink, "Bjorn"), None).await,
+      Err(Failure::Rejected(_))
+    ));
+    gone.assert_async().await;
+  }
+
+  #[tokio::test]
+  async fn test_spool_undelivered_and_resend() {
+    let dir = tempfile::tempdir().unwrap();
+    let spool_path = dir.path().join("notifications.spool");
+    let mut server = mockito::Server::new_async().await;
+    let down = server
+      .mock("POST", "/hook")
+      .with_status(503)
+      .expect(MAX_ATTEMPTS as usize)
+      .create_async()
+      .await;
+    let sink = json_sink(format!("{}/hook", server.url()));
+    let dispatcher = dispatcher(&spool_path);
+    let undelivered = dispatcher
+      .deliver_all(vec![joined(&sink, "Bjorn")], None)
+      .await;
+    assert_eq!(undelivered.len(), 1);
+    spool(&spool_path, undelivered);
+    down.assert_async().await;
+
+    let up = server
+      .mock("POST", "/hook")
+      .match_body(Matcher::PartialJson(json!({
+        "event_message": "Player Bjorn has joined the adventure!"
+      })))
+      .with_status(204)
+      .expect(1)
+      .create_async()
+      .await;
+    let (sender, receiver) = unbounded_channel();
+    let run = tokio::spawn(dispatcher.run(receiver));
+    let (ack, done) = std_mpsc::channel();
+    sender
+      .send(Command::Flush(Instant::now() + Duration::from_secs(5), ack))
+      .ok();
+    tokio::task::spawn_blocking(move || done.recv_timeout(Duration::from_secs(10)))
+      .await
+      .unwrap()
+      .unwrap();
+    drop(sender);
+    run.await.unwrap();
+    up.assert_async().await;
+    assert!(!spool_path.exists());
+  }
+
+  #[tokio::test]
+  async fn test_retry_spool_while_idle() {
+    let dir = tempfile::tempdir().unwrap();
+    let spool_path = dir.path().join("notifications.spool");
+    let mut server = mockito::Server::new_async().await;
+    let up = server
+      .mock("POST", "/hook")
+      .with_status(204)
+      .expect(1)
+      .create_async()
+      .await;
+    let sink = json_sink(format!("{}/hook", server.url()));
+    let (sender, receiver) = unbounded_channel();
+    let run = tokio::spawn(dispatcher(&spool_path).run(receiver));
+    sleep(Duration::from_millis(50)).await;
+    spool(&spool_path, vec![joined(&sink, "Bjorn")]);
+    sleep(Duration::from_millis(500)).await;
+    drop(sender);
+    run.await.unwrap();
+    up.assert_async().await;
+    assert!(!spool_path.exists());
+  }
+
+  #[test]
+  fn test_coalesce_floods() {
+    let general = json_sink(String::from("https://example.com/general"));
+    let ops = json_sink(String::from("https://example.com/ops"));
+    let mut batch: Vec<Queued> = (1..=12)
+      .map(|n| joined(&general, &format!("Viking{}", n)))
+      .collect();
+    batch.insert(3, joined(&ops, "Bjorn"));
+    batch.push(joined(&ops, "Freya"));
+
+    let coalesced = coalesce(batch);
+    assert_eq!(coalesced.len(), 3);
+    let message = &coalesced[0].notification.event_message;
+    assert_eq!(message.lines().count(), COALESCED_MESSAGES + 1);
+    assert!(message.starts_with("Player Viking1 has joined"));
+    assert!(message.ends_with("...and 2 more"));
+    assert_eq!(coalesced[1].sink, ops);
+    assert_eq!(coalesced[2].sink, ops);
+  }
+
+  #[test]
+  fn test_spool_keeps_fresh_notifications() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("notifications.spool");
+    let sink = json_sink(String::from("https://example.com/hook"));
+    let mut stale = joined(&sink, "Old");
+    stale.queued_at -= SPOOL_TTL_SECONDS + 1;
+    spool(&path, vec![stale, joined(&sink, "New")]);
+    spool(&path, vec![joined(&sink, "Newer")]);
+
+    let spooled = take_spool(&path);
+    assert_eq!(spooled.len(), 2);
+    assert!(spooled[0].notification.event_message.contains("New"));
+    assert!(!path.exists());
+    assert!(take_spool(&path).is_empty());
+  }
+}
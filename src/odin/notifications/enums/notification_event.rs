@@ -1,16 +1,18 @@
 use crate::errors::VariantNotFound;
+use crate::files::settings::Settings;
 use crate::notifications::enums::event_status::EventStatus;
 use crate::notifications::enums::player::PlayerStatus;
 use crate::notifications::{
-  discord::{is_discord_webhook, DiscordWebHookBody},
-  NotificationMessage, WEBHOOK_INCLUDE_PUBLIC_IP, WEBHOOK_URL,
+  dispatcher::{dispatch, Queued},
+  sinks::{sinks, Sink},
+  NotificationMessage, WEBHOOK_INCLUDE_PUBLIC_IP,
 };
 use crate::utils::environment::fetch_var;
 use crate::utils::{fetch_public_address, get_server_name};
 use chrono::Local;
 use inflections::case::to_title_case;
-use log::{debug, error, info, warn};
-use reqwest::{blocking::RequestBuilder, StatusCode, Url};
+use log::{debug, warn};
+use reqwest::Url;
 use serde::{Deserialize, Serialize};
 use std::fmt;
 
@@ -23,20 +25,20 @@ pub enum NotificationEvent {
   Player(PlayerStatus),
 }
 
-#[derive(Debug, Deserialize, Serialize, PartialEq, Eq)]
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq)]
 pub struct EventType {
   pub(crate) name: String,
   pub(crate) status: String,
 }
 
-fn fetch_webhook_url() -> String {
-  fetch_var(WEBHOOK_URL, "")
-    .trim_start_matches('"')
-    .trim_end_matches('"')
-    .to_string()
+pub(crate) fn fetch_webhook_url() -> String {
+  Settings::current()
+    .notifications
+    .webhook_url
+    .unwrap_or_default()
 }
 
-fn is_webhook_enabled() -> bool {
+pub(crate) fn is_webhook_enabled() -> bool {
   let url = fetch_webhook_url();
   if !url.is_empty() {
     debug!("Webhook Url found!: {}", url);
@@ -74,7 +76,20 @@ pub fn parse_server_name_for_notification() -> String {
 }
 
 impl NotificationEvent {
-  fn create_notification_message(&self) -> NotificationMessage {
+  /// The event sinks and templates know by `key`, server events taken as successful.
+  pub fn from_key(key: &str) -> Option<NotificationEvent> {
+    use NotificationEvent::{Broadcast, Player, Start, Stop, Update};
+    match key {
+      "broadcast" => Some(Broadcast),
+      "start" => Some(Start(EventStatus::Successful)),
+      "stop" => Some(Stop(EventStatus::Successful)),
+      "update" => Some(Update(EventStatus::Successful)),
+      "player_join" => Some(Player(PlayerStatus::Joined)),
+      "player_leave" => Some(Player(PlayerStatus::Left)),
+      _ => None,
+    }
+  }
+  pub(crate) fn create_notification_message(&self) -> NotificationMessage {
     NotificationMessage {
       author: format!("Notification: {}", get_server_name()),
       event_type: self.to_event_type(),
@@ -85,71 +100,57 @@ impl NotificationEvent {
       timestamp: Local::now().to_rfc3339(),
     }
   }
-  fn handle_request(&self, request: RequestBuilder) {
-    let response = request.send();
-    if let Ok(parsed_response) = response {
-      let response_status = parsed_response.status();
-      let response_message = parsed_response.text().unwrap();
-      match response_status.as_u16() {
-        204 | 201 => info!("[{}]: Webhook message sent successfully!", self),
-        _ => error!("Request failed! {}, {}", response_status, response_message),
-      }
-    } else {
-      error!(
-        "[{}]: Error with webhook! Status {}",
-        self,
-        response
-          .err()
-          .unwrap()
-          .status()
-          .unwrap_or(StatusCode::INTERNAL_SERVER_ERROR)
-          .as_str()
-      );
-    }
-  }
-  fn build_request(&self, webhook_url: &str) -> RequestBuilder {
-    let client = reqwest::blocking::Client::new();
-    debug!("Webhook URL: {}", webhook_url);
-    client.post(webhook_url)
-  }
   pub fn send_custom_notification(&self, webhook_url: &str, notification: &NotificationMessage) {
-    debug!("Webhook enabled, sending notification {}", self.to_string());
+    self.send_to_sink(&Sink::from_url(webhook_url), notification);
+  }
+  fn send_to_sink(&self, sink: &Sink, notification: &NotificationMessage) {
+    debug!("[{}]: Sending notification {}", sink.label(), self);
     debug!(
       "Event Received: {}",
       serde_json::to_string_pretty(&notification).unwrap()
     );
-    let mut req = self.build_request(webhook_url);
-    req = if is_discord_webhook(webhook_url) {
-      info!("Sending discord notification <3");
-      req.json(&DiscordWebHookBody::from(notification))
-    } else {
-      debug!(
-        "Webhook Payload: {}",
-        serde_json::to_string(&notification).unwrap()
-      );
-      req.json(&notification)
-    };
-    self.handle_request(req);
+    dispatch(Queued::new(sink.clone(), self.key(), notification.clone()));
+  }
+  fn matching_sinks(&self) -> Vec<Sink> {
+    sinks()
+      .into_iter()
+      .filter(|sink| sink.accepts(self))
+      .collect()
+  }
+  /// Queues `notification` for every sink whose filter takes this event, returns how many do.
+  pub fn send_to_sinks(&self, notification: &NotificationMessage) -> usize {
+    let sinks = self.matching_sinks();
+    for sink in &sinks {
+      self.send_to_sink(sink, notification);
+    }
+    sinks.len()
   }
   pub fn send_notification(&self, message: Option<String>) {
     debug!("Checking for notification information...");
-    if is_webhook_enabled() {
-      debug!("Webhook found! Starting notification process...");
-      let mut event = self.create_notification_message();
-      let enabled_var = format!("WEBHOOK_STATUS_{}", event.event_type.status).to_uppercase();
-
-      if let Some(msg) = message {
-        event.event_message = msg;
-      }
+    let sinks = self.matching_sinks();
+    if sinks.is_empty() {
+      debug!("Skipping notification, no sink takes {}", self);
+      return;
+    }
+    let mut event = self.create_notification_message();
+    if let Some(msg) = message {
+      event.event_message = msg;
+    }
+    for sink in &sinks {
+      self.send_to_sink(sink, &event);
+    }
+  }
 
-      debug!("Checking ENV Var: {}", &enabled_var);
-      if fetch_var(&enabled_var, "0").eq("1") {
-        self.send_custom_notification(&fetch_webhook_url(), &event);
-      } else {
-        debug!("Skipping notification, {} is set to 0", enabled_var);
-      }
-    } else {
-      debug!("Skipping notification, no webhook supplied!");
+  /// The name sinks and templates know the event by.
+  pub fn key(&self) -> &'static str {
+    use NotificationEvent::{Broadcast, Player, Start, Stop, Update};
+    match self {
+      Broadcast => "broadcast",
+      Update(_) => "update",
+      Start(_) => "start",
+      Stop(_) => "stop",
+      Player(PlayerStatus::Joined) => "player_join",
+      Player(PlayerStatus::Left) => "player_leave",
     }
   }
 
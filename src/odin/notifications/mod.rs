@@ -2,12 +2,17 @@ use crate::notifications::enums::notification_event::EventType;
 use serde::{Deserialize, Serialize};
 
 pub mod discord;
+pub mod dispatcher;
 pub mod enums;
+pub mod preview;
+pub mod providers;
+pub mod sinks;
 
 pub const WEBHOOK_URL: &str = "WEBHOOK_URL";
+pub const NOTIFICATION_SINKS: &str = "NOTIFICATION_SINKS";
 pub const WEBHOOK_INCLUDE_PUBLIC_IP: &str = "WEBHOOK_INCLUDE_PUBLIC_IP";
 
-#[derive(Deserialize, Serialize)]
+#[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct NotificationMessage {
   pub(crate) author: String,
   pub(crate) event_type: EventType,
@@ -0,0 +1,217 @@
+use std::collections::BTreeMap;
+
+use log::warn;
+use serde_json::Value;
+
+use crate::files::templates::{read_templates, template_file};
+use crate::files::{FileManager, ManagedFile};
+use crate::notifications::discord::TemplateValues;
+use crate::notifications::enums::notification_event::NotificationEvent;
+use crate::notifications::providers::NotificationProvider;
+use crate::notifications::sinks::{render, Sink, SinkKind, EVENTS};
+use crate::notifications::NotificationMessage;
+
+/// `event` with the notification odin would send for it. Render it with
+/// `TemplateValues::sample`, previews shouldn't wait on the server or the network.
+fn sample(event: &str) -> Result<(NotificationEvent, NotificationMessage), String> {
+  let notification_event = NotificationEvent::from_key(event)
+    .ok_or_else(|| format!("unknown event {}, use one of {}", event, EVENTS.join(", ")))?;
+  let notification = notification_event.create_notification_message();
+  Ok((notification_event, notification))
+}
+
+/// The sinks taking `event`, or the Discord templates when there are none.
+fn preview_sinks(sinks: Vec<Sink>, event: &NotificationEvent) -> Vec<Sink> {
+  let sinks: Vec<Sink> = sinks
+    .into_iter()
+    .filter(|sink| sink.accepts(event))
+    .collect();
+  if !sinks.is_empty() {
+    return sinks;
+  }
+  vec![Sink {
+    name: Some(template_file(SinkKind::Discord.provider().name()).name),
+    kind: Some(SinkKind::Discord),
+    ..Sink::default()
+  }]
+}
+
+/// The bodies `event` would be posted with, rendered with sample data. A template that fails to
+/// render is reported in the logs and replaced by the next one, like when sending.
+pub fn preview(sinks: Vec<Sink>, event: &str) -> Result<Vec<(String, Value)>, String> {
+  let (notification_event, notification) = sample(event)?;
+  Ok(
+    preview_sinks(sinks, &notification_event)
+      .iter()
+      .map(|sink| {
+        let label = format!("{} ({:?})", sink.label(), sink.kind());
+        let payload = sink.payload_with(event, &notification, TemplateValues::sample);
+        (label, payload)
+      })
+      .collect(),
+  )
+}
+
+/// Renders `template` with sample data and checks the service would take the result.
+fn check_template(
+  provider: &dyn NotificationProvider,
+  event: &str,
+  template: &Value,
+) -> Result<(), String> {
+  let (_, notification) = sample(event)?;
+  let body = render(template, &mut TemplateValues::sample(&notification))?;
+  provider.check_body(&body)
+}
+
+/// Checks every event template of `discord.json` and of the template files the sinks use,
+/// returns what is wrong with them. Templates of other events are never sent, they're only
+/// warned about.
+pub fn validate_templates(sinks: &[Sink]) -> Vec<String> {
+  let discord = SinkKind::Discord.provider();
+  let mut files = vec![(template_file(discord.name()), discord, false)];
+  for sink in sinks {
+    let provider = sink.kind().provider();
+    let file = template_file(provider.name());
+    if !files.iter().any(|(known, _, _)| known.name == file.name) {
+      files.push((file, provider, false));
+    }
+    if let Some(name) = &sink.template {
+      let file = ManagedFile { name: name.clone() };
+      if !files.iter().any(|(known, _, _)| known.name == file.name) {
+        files.push((file, provider, true));
+      }
+    }
+  }
+
+  let mut problems = vec![];
+  for (file, provider, required) in files {
+    if !file.exists() {
+      if required {
+        problems.push(format!("{} not found", file.path()));
+      }
+      continue;
+    }
+    let templates = match read_templates(&file) {
+      Ok(templates) => templates,
+      Err(e) => {
+        problems.push(e);
+        continue;
+      }
+    };
+    for (event, template) in templates.into_iter().collect::<BTreeMap<_, _>>() {
+      if !EVENTS.contains(&event.as_str()) {
+        warn!(
+          "{}: Skipping the {} template, odin sends no such event",
+          file.path(),
+          event
+        );
+        continue;
+      }
+      if let Err(e) = check_template(provider, &event, &template) {
+        problems.push(format!("{}: the {} template: {}", file.path(), event, e));
+      }
+    }
+  }
+  problems
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use serde_json::json;
+  use serial_test::serial;
+  use std::fs;
+
+  #[test]
+  #[serial]
+  fn test_validate_templates() {
+    let dir = tempfile::tempdir().unwrap();
+    let discord = dir.path().join("discord.json");
+    fs::write(
+      &discord,
+      json!({
+        "events": {
+          "start": { "content": "{{server_name}} {{title}}" },
+          "stop": { "content": "{{#if title}}" },
+          "update": { "embeds": [{ "title": "{{title}}", "color": "red" }] },
+          "player_join": { "content": "{{player_count}}/{{max_players}} {{version}} {{public_ip}}" },
+          "restart": { "content": "{{#if title}}" }
+        }
+      })
+      .to_string(),
+    )
+    .unwrap();
+    let sink = Sink {
+      url: String::from("https://hooks.slack.com/services/T/B/X"),
+      template: Some(
+        dir
+          .path()
+          .join("missing.json")
+          .to_string_lossy()
+          .to_string(),
+      ),
+      ..Sink::default()
+    };
+    std::env::set_var("NAME", "Midgard");
+    std::env::set_var("ODIN_DISCORD_FILE", discord.to_string_lossy().to_string());
+    let problems = validate_templates(&[sink]);
+    std::env::remove_var("ODIN_DISCORD_FILE");
+    std::env::remove_var("NAME");
+
+    assert_eq!(problems.len(), 3, "{:?}", problems);
+    assert!(problems[0].contains("the stop template"));
+    assert!(problems[1].contains("the update template"));
+    assert!(problems[2].ends_with("missing.json not found"));
+  }
+
+  #[test]
+  #[serial]
+  fn test_preview_falls_back_to_the_default_template() {
+    let dir = tempfile::tempdir().unwrap();
+    let discord = dir.path().join("discord.json");
+    fs::write(
+      &discord,
+      json!({ "events": { "player_join": { "content": "{{#each}}" } } }).to_string(),
+    )
+    .unwrap();
+    std::env::set_var("NAME", "Midgard");
+    std::env::set_var("ODIN_DISCORD_FILE", discord.to_string_lossy().to_string());
+    let bodies = preview(vec![], "player_join").unwrap();
+    std::env::remove_var("ODIN_DISCORD_FILE");
+    std::env::remove_var("NAME");
+
+    assert_eq!(bodies.len(), 1);
+    let (label, body) = &bodies[0];
+    assert!(label.ends_with("discord.json (Discord)"));
+    assert_eq!(body["content"], "Notification: Midgard");
+    assert_eq!(body["embeds"][0]["title"], "Player");
+    assert_eq!(body["embeds"][0]["color"], 0x34_98_DB);
+
+    assert!(preview(vec![], "restart").is_err());
+  }
+
+  #[test]
+  #[serial]
+  fn test_preview_renders_sample_values() {
+    let dir = tempfile::tempdir().unwrap();
+    let discord = dir.path().join("discord.json");
+    fs::write(
+      &discord,
+      json!({ "events": { "start": {
+        "content": "{{world}} {{player_count}}/{{max_players}} {{version}} {{public_ip}}"
+      } } })
+      .to_string(),
+    )
+    .unwrap();
+    std::env::set_var("NAME", "Midgard");
+    std::env::set_var("ODIN_DISCORD_FILE", discord.to_string_lossy().to_string());
+    let bodies = preview(vec![], "start").unwrap();
+    std::env::remove_var("ODIN_DISCORD_FILE");
+    std::env::remove_var("NAME");
+
+    assert_eq!(
+      bodies[0].1["content"],
+      "Dedicated 3/10 0.219.16 203.0.113.7"
+    );
+  }
+}
@@ -0,0 +1,29 @@
+use serde::Deserialize;
+use serde_json::Value;
+
+use crate::notifications::discord::{color_embeds, DiscordWebHookBody};
+use crate::notifications::providers::NotificationProvider;
+use crate::notifications::NotificationMessage;
+
+/// Discord webhooks, templated by `discord.json`.
+pub struct Discord;
+
+impl NotificationProvider for Discord {
+  fn name(&self) -> &'static str {
+    "discord"
+  }
+
+  fn default_template(&self, _event: &str) -> Option<Value> {
+    serde_json::to_value(DiscordWebHookBody::default()).ok()
+  }
+
+  fn check_body(&self, body: &Value) -> Result<(), String> {
+    DiscordWebHookBody::deserialize(body)
+      .map(|_| ())
+      .map_err(|e| e.to_string())
+  }
+
+  fn decorate(&self, body: &mut Value, notification: &NotificationMessage) {
+    color_embeds(body, &notification.event_type);
+  }
+}
@@ -0,0 +1,73 @@
+use serde_json::{json, Value};
+
+use crate::notifications::providers::{required, NotificationProvider, WebhookRequest};
+use crate::notifications::sinks::Sink;
+
+/// Gotify servers, `token` is the token of the application posting the messages.
+pub struct Gotify;
+
+impl NotificationProvider for Gotify {
+  fn name(&self) -> &'static str {
+    "gotify"
+  }
+
+  fn default_template(&self, event: &str) -> Option<Value> {
+    let priority = match event {
+      "player_join" | "player_leave" => 2,
+      _ => 5,
+    };
+    Some(json!({
+      "title": "{{server_name}}: {{title}} {{status}}",
+      "message": "{{description}}",
+      "priority": priority
+    }))
+  }
+
+  fn check(&self, sink: &Sink) -> Result<(), String> {
+    required(&sink.token, "token, the token of a Gotify application").map(|_| ())
+  }
+
+  fn request(&self, sink: &Sink, body: Value) -> Result<WebhookRequest, String> {
+    let url = format!("{}/message", sink.url.trim_end_matches('/'));
+    Ok(
+      WebhookRequest::post(&url, body)
+        .header("X-Gotify-Key", required(&sink.token, "token")?.to_string()),
+    )
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::notifications::enums::notification_event::NotificationEvent;
+  use crate::notifications::enums::player::PlayerStatus;
+  use crate::notifications::providers::tests::send;
+  use crate::notifications::sinks::SinkKind;
+  use mockito::Matcher;
+  use serial_test::serial;
+
+  #[tokio::test]
+  #[serial]
+  async fn test_send_to_gotify() {
+    let mut server = mockito::Server::new_async().await;
+    let mock = server
+      .mock("POST", "/message")
+      .match_header("x-gotify-key", "app_secret")
+      .match_body(Matcher::Json(json!({
+        "title": "Midgard: Player Joined",
+        "message": "Sent by \"odin\"",
+        "priority": 2
+      })))
+      .with_status(200)
+      .create_async()
+      .await;
+    let sink = Sink {
+      url: server.url(),
+      kind: Some(SinkKind::Gotify),
+      token: Some(String::from("app_secret")),
+      ..Sink::default()
+    };
+    send(&sink, NotificationEvent::Player(PlayerStatus::Joined)).await;
+    mock.assert_async().await;
+  }
+}
@@ -0,0 +1,20 @@
+use serde_json::Value;
+
+use crate::notifications::providers::NotificationProvider;
+
+/// Any other webhook, it gets the notification as it is.
+pub struct Json;
+
+impl NotificationProvider for Json {
+  fn name(&self) -> &'static str {
+    "json"
+  }
+
+  fn default_template(&self, _event: &str) -> Option<Value> {
+    None
+  }
+
+  fn template(&self, _event: &str) -> Option<Value> {
+    None
+  }
+}
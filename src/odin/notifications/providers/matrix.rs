@@ -0,0 +1,114 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use chrono::Utc;
+use reqwest::{Method, Url};
+use serde_json::{json, Value};
+
+use crate::notifications::providers::{required, NotificationProvider, WebhookRequest};
+use crate::notifications::sinks::Sink;
+
+/// Keeps transaction ids unique when two notifications are sent in the same nanosecond.
+static TRANSACTIONS: AtomicU64 = AtomicU64::new(0);
+
+/// Matrix rooms, posted as notices through the client-server API of the homeserver in `url`.
+pub struct Matrix;
+
+impl NotificationProvider for Matrix {
+  fn name(&self) -> &'static str {
+    "matrix"
+  }
+
+  fn default_template(&self, _event: &str) -> Option<Value> {
+    Some(json!({
+      "msgtype": "m.notice",
+      "body": "{{server_name}}: {{title}} {{status}}\n{{description}}",
+      "format": "org.matrix.custom.html",
+      "formatted_body": "<b>{{server_name}}</b>: {{title}} {{status}}<br>{{description}}"
+    }))
+  }
+
+  fn check(&self, sink: &Sink) -> Result<(), String> {
+    required(&sink.token, "token, the access token of the bot account")?;
+    required(&sink.room, "room, the room id like !abc123:matrix.org")?;
+    Ok(())
+  }
+
+  fn request(&self, sink: &Sink, body: Value) -> Result<WebhookRequest, String> {
+    self.check(sink)?;
+    let transaction = format!(
+      "odin{}.{}",
+      Utc::now().timestamp_nanos_opt().unwrap_or_default(),
+      TRANSACTIONS.fetch_add(1, Ordering::Relaxed)
+    );
+    let mut url = Url::parse(&sink.url).map_err(|e| e.to_string())?;
+    url
+      .path_segments_mut()
+      .map_err(|_| format!("{} can't be a homeserver url", sink.url))?
+      .pop_if_empty()
+      .extend([
+        "_matrix",
+        "client",
+        "v3",
+        "rooms",
+        required(&sink.room, "room")?,
+        "send",
+        "m.room.message",
+        &transaction,
+      ]);
+    Ok(
+      WebhookRequest {
+        method: Method::PUT,
+        ..WebhookRequest::post(url.as_str(), body)
+      }
+      .header(
+        "Authorization",
+        format!("Bearer {}", required(&sink.token, "token")?),
+      ),
+    )
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::notifications::enums::event_status::EventStatus;
+  use crate::notifications::enums::notification_event::NotificationEvent;
+  use crate::notifications::providers::tests::send;
+  use crate::notifications::sinks::SinkKind;
+  use mockito::Matcher;
+  use serial_test::serial;
+
+  #[tokio::test]
+  #[serial]
+  async fn test_send_to_matrix() {
+    let mut server = mockito::Server::new_async().await;
+    let mock = server
+      .mock(
+        "PUT",
+        Matcher::Regex(String::from(
+          r"^/_matrix/client/v3/rooms/!valheim:example\.org/send/m\.room\.message/odin\d+\.\d+$",
+        )),
+      )
+      .match_header("authorization", "Bearer syt_secret")
+      .match_body(Matcher::PartialJson(json!({
+        "msgtype": "m.notice",
+        "body": "Midgard: Stop Failed\nSent by \"odin\""
+      })))
+      .with_status(200)
+      .with_body(r#"{"event_id": "$1"}"#)
+      .create_async()
+      .await;
+    let sink = Sink {
+      url: format!("{}/", server.url()),
+      kind: Some(SinkKind::Matrix),
+      token: Some(String::from("syt_secret")),
+      room: Some(String::from("!valheim:example.org")),
+      ..Sink::default()
+    };
+    send(&sink, NotificationEvent::Stop(EventStatus::Failed)).await;
+    mock.assert_async().await;
+
+    let without_room = Sink { room: None, ..sink };
+    assert!(Matrix.check(&without_room).unwrap_err().contains("room"));
+  }
+}
@@ -0,0 +1,212 @@
+mod discord;
+mod gotify;
+mod json;
+mod matrix;
+mod ntfy;
+mod slack;
+mod telegram;
+
+pub use discord::Discord;
+pub use gotify::Gotify;
+pub use json::Json;
+pub use matrix::Matrix;
+pub use ntfy::Ntfy;
+pub use slack::Slack;
+pub use telegram::Telegram;
+
+use std::collections::BTreeMap;
+
+use log::warn;
+use reqwest::{Client, Method, RequestBuilder, Url};
+use serde_json::Value;
+
+use crate::files::templates::{read_templates, template_file, write_templates};
+use crate::notifications::discord::is_discord_webhook;
+use crate::notifications::sinks::{Sink, SinkKind, EVENTS};
+use crate::notifications::NotificationMessage;
+
+/// A request a provider wants sent, independent of the client sending it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct WebhookRequest {
+  pub method: Method,
+  pub url: String,
+  pub headers: Vec<(&'static str, String)>,
+  pub body: Value,
+}
+
+impl WebhookRequest {
+  pub fn post(url: &str, body: Value) -> WebhookRequest {
+    WebhookRequest {
+      method: Method::POST,
+      url: url.to_string(),
+      headers: vec![],
+      body,
+    }
+  }
+
+  pub fn header(mut self, name: &'static str, value: String) -> WebhookRequest {
+    self.headers.push((name, value));
+    self
+  }
+
+  pub fn build(&self, client: &Client) -> RequestBuilder {
+    let mut request = client
+      .request(self.method.clone(), &self.url)
+      .json(&self.body);
+    for (name, value) in &self.headers {
+      request = request.header(*name, value);
+    }
+    request
+  }
+}
+
+/// A service notifications are posted to, with its own payload shape and templates.
+pub trait NotificationProvider: Sync {
+  /// Names the provider as sink `type` and its template file, e.g. `slack` and `slack.json`.
+  fn name(&self) -> &'static str;
+
+  /// The body template of `event` when the template file has none, `None` posts the
+  /// notification as it is.
+  fn default_template(&self, event: &str) -> Option<Value>;
+
+  /// The body template of `event` from the provider's template file.
+  fn template(&self, event: &str) -> Option<Value> {
+    match read_templates(&template_file(self.name())) {
+      Ok(mut templates) => templates.remove(event),
+      Err(e) => {
+        warn!("Ignoring the {} templates: {}", self.name(), e);
+        None
+      }
+    }
+  }
+
+  /// Adjusts a rendered body to what the notification is about.
+  fn decorate(&self, _body: &mut Value, _notification: &NotificationMessage) {}
+
+  /// Checks a rendered body has the shape the service takes.
+  fn check_body(&self, _body: &Value) -> Result<(), String> {
+    Ok(())
+  }
+
+  /// Checks the sink has what the provider needs besides the url.
+  fn check(&self, _sink: &Sink) -> Result<(), String> {
+    Ok(())
+  }
+
+  /// The request posting `body` to the sink.
+  fn request(&self, sink: &Sink, body: Value) -> Result<WebhookRequest, String> {
+    Ok(WebhookRequest::post(&sink.url, body))
+  }
+
+  /// Writes the default templates to the template file, unless there already is one.
+  fn write_templates(&self) -> bool {
+    let templates: BTreeMap<&str, Value> = EVENTS
+      .iter()
+      .filter_map(|event| Some((*event, self.default_template(event)?)))
+      .collect();
+    templates.is_empty() || write_templates(&template_file(self.name()), templates)
+  }
+}
+
+impl SinkKind {
+  pub fn provider(&self) -> &'static dyn NotificationProvider {
+    match self {
+      SinkKind::Discord => &Discord,
+      SinkKind::Slack => &Slack,
+      SinkKind::Matrix => &Matrix,
+      SinkKind::Telegram => &Telegram,
+      SinkKind::Ntfy => &Ntfy,
+      SinkKind::Gotify => &Gotify,
+      SinkKind::Json => &Json,
+    }
+  }
+
+  /// The provider a url belongs to, Matrix and Gotify run on any host so they need a `type`.
+  pub fn detect(url: &str) -> SinkKind {
+    if is_discord_webhook(url) {
+      return SinkKind::Discord;
+    }
+    let host = Url::parse(url)
+      .ok()
+      .and_then(|url| url.host_str().map(String::from))
+      .unwrap_or_default();
+    match host.as_str() {
+      "hooks.slack.com" => SinkKind::Slack,
+      "api.telegram.org" => SinkKind::Telegram,
+      "ntfy.sh" => SinkKind::Ntfy,
+      _ => SinkKind::Json,
+    }
+  }
+}
+
+/// Writes the template files of the providers the sinks use.
+pub fn write_sink_templates(sinks: &[Sink]) {
+  let mut kinds: Vec<SinkKind> = vec![];
+  for kind in sinks.iter().map(Sink::kind) {
+    if !kinds.contains(&kind) {
+      kinds.push(kind);
+    }
+  }
+  for kind in kinds {
+    kind.provider().write_templates();
+  }
+}
+
+/// Adds `value` to the body under `key` unless the template already set it.
+fn insert_default(body: &mut Value, key: &str, value: &str) {
+  if let Some(body) = body.as_object_mut() {
+    body
+      .entry(key)
+      .or_insert_with(|| Value::String(value.to_string()));
+  }
+}
+
+/// The value of a setting a provider can't do without.
+fn required<'a>(value: &'a Option<String>, name: &str) -> Result<&'a str, String> {
+  value
+    .as_deref()
+    .filter(|value| !value.is_empty())
+    .ok_or_else(|| format!("needs a {}", name))
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+  use super::*;
+  use crate::notifications::enums::notification_event::NotificationEvent;
+
+  /// Sends `event` through the sink like odin would.
+  pub(crate) async fn send(sink: &Sink, event: NotificationEvent) {
+    let notification = NotificationMessage {
+      author: String::from("odin"),
+      event_type: event.to_event_type(),
+      event_message: String::from("Sent by \"odin\""),
+      timestamp: String::from("2024-01-01T00:00:00+00:00"),
+    };
+    std::env::set_var("NAME", "Midgard");
+    let request = sink.request(event.key(), &notification).unwrap();
+    std::env::remove_var("NAME");
+    let response = request.build(&Client::new()).send().await.unwrap();
+    assert!(response.status().is_success());
+  }
+
+  #[test]
+  fn test_detect_provider() {
+    assert_eq!(
+      SinkKind::detect("https://discord.com/api/webhooks/1/token"),
+      SinkKind::Discord
+    );
+    assert_eq!(
+      SinkKind::detect("https://hooks.slack.com/services/T/B/X"),
+      SinkKind::Slack
+    );
+    assert_eq!(
+      SinkKind::detect("https://api.telegram.org"),
+      SinkKind::Telegram
+    );
+    assert_eq!(SinkKind::detect("https://ntfy.sh/valheim"), SinkKind::Ntfy);
+    assert_eq!(
+      SinkKind::detect("https://matrix.example.com"),
+      SinkKind::Json
+    );
+  }
+}
@@ -0,0 +1,101 @@
+use reqwest::Url;
+use serde_json::{json, Value};
+
+use crate::notifications::providers::{insert_default, NotificationProvider, WebhookRequest};
+use crate::notifications::sinks::Sink;
+
+/// ntfy topics, `url` is the topic url like https://ntfy.sh/valheim.
+pub struct Ntfy;
+
+/// Splits the topic url into the server url the JSON is posted to and the topic.
+fn topic(url: &str) -> Result<(Url, String), String> {
+  let mut url = Url::parse(url).map_err(|e| e.to_string())?;
+  let topic = url
+    .path_segments()
+    .and_then(|mut segments| segments.next_back())
+    .filter(|topic| !topic.is_empty())
+    .map(String::from)
+    .ok_or_else(|| String::from("needs the topic in the url, like https://ntfy.sh/valheim"))?;
+  url
+    .path_segments_mut()
+    .map_err(|_| String::from("needs an http url"))?
+    .pop();
+  Ok((url, topic))
+}
+
+impl NotificationProvider for Ntfy {
+  fn name(&self) -> &'static str {
+    "ntfy"
+  }
+
+  fn default_template(&self, event: &str) -> Option<Value> {
+    let tag = match event {
+      "start" => "rocket",
+      "stop" => "stop_sign",
+      "update" => "arrows_counterclockwise",
+      "player_join" => "wave",
+      "player_leave" => "door",
+      _ => "loudspeaker",
+    };
+    Some(json!({
+      "title": "{{server_name}}: {{title}} {{status}}",
+      "message": "{{description}}",
+      "tags": [tag]
+    }))
+  }
+
+  fn check(&self, sink: &Sink) -> Result<(), String> {
+    topic(&sink.url).map(|_| ())
+  }
+
+  fn request(&self, sink: &Sink, mut body: Value) -> Result<WebhookRequest, String> {
+    let (url, topic) = topic(&sink.url)?;
+    insert_default(&mut body, "topic", &topic);
+    let request = WebhookRequest::post(url.as_str(), body);
+    Ok(match sink.token.as_deref() {
+      Some(token) if !token.is_empty() => {
+        request.header("Authorization", format!("Bearer {}", token))
+      }
+      _ => request,
+    })
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::notifications::enums::event_status::EventStatus;
+  use crate::notifications::enums::notification_event::NotificationEvent;
+  use crate::notifications::providers::tests::send;
+  use crate::notifications::sinks::SinkKind;
+  use mockito::Matcher;
+  use serial_test::serial;
+
+  #[tokio::test]
+  #[serial]
+  async fn test_send_to_ntfy() {
+    let mut server = mockito::Server::new_async().await;
+    let mock = server
+      .mock("POST", "/")
+      .match_header("authorization", "Bearer tk_secret")
+      .match_body(Matcher::Json(json!({
+        "topic": "valheim",
+        "title": "Midgard: Stop Failed",
+        "message": "Sent by \"odin\"",
+        "tags": ["stop_sign"]
+      })))
+      .with_status(200)
+      .create_async()
+      .await;
+    let sink = Sink {
+      url: format!("{}/valheim", server.url()),
+      kind: Some(SinkKind::Ntfy),
+      token: Some(String::from("tk_secret")),
+      ..Sink::default()
+    };
+    send(&sink, NotificationEvent::Stop(EventStatus::Failed)).await;
+    mock.assert_async().await;
+
+    assert!(Ntfy.check(&Sink::from_url("https://ntfy.sh/")).is_err());
+  }
+}
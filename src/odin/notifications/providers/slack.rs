@@ -0,0 +1,62 @@
+use serde_json::{json, Value};
+
+use crate::notifications::providers::NotificationProvider;
+
+/// Slack incoming webhooks, posted as Block Kit messages.
+pub struct Slack;
+
+impl NotificationProvider for Slack {
+  fn name(&self) -> &'static str {
+    "slack"
+  }
+
+  fn default_template(&self, _event: &str) -> Option<Value> {
+    Some(json!({
+      "text": "{{server_name}}: {{title}} {{status}}",
+      "blocks": [
+        {
+          "type": "section",
+          "text": { "type": "mrkdwn", "text": "*{{title}} {{status}}*\n{{description}}" }
+        },
+        {
+          "type": "context",
+          "elements": [{ "type": "mrkdwn", "text": "{{server_name}} | {{timestamp}}" }]
+        }
+      ]
+    }))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::notifications::enums::event_status::EventStatus;
+  use crate::notifications::enums::notification_event::NotificationEvent;
+  use crate::notifications::providers::tests::send;
+  use crate::notifications::sinks::{Sink, SinkKind};
+  use mockito::Matcher;
+  use serial_test::serial;
+
+  #[tokio::test]
+  #[serial]
+  async fn test_send_to_slack() {
+    let mut server = mockito::Server::new_async().await;
+    let mock = server
+      .mock("POST", "/services/T0/B0/secret")
+      .match_body(Matcher::PartialJson(json!({
+        "text": "Midgard: Stop Failed",
+        "blocks": [{ "text": { "text": "*Stop Failed*\nSent by \"odin\"" } }]
+      })))
+      .with_status(200)
+      .with_body("ok")
+      .create_async()
+      .await;
+    let sink = Sink {
+      url: format!("{}/services/T0/B0/secret", server.url()),
+      kind: Some(SinkKind::Slack),
+      ..Sink::default()
+    };
+    send(&sink, NotificationEvent::Stop(EventStatus::Failed)).await;
+    mock.assert_async().await;
+  }
+}
@@ -0,0 +1,74 @@
+use serde_json::{json, Value};
+
+use crate::notifications::providers::{
+  insert_default, required, NotificationProvider, WebhookRequest,
+};
+use crate::notifications::sinks::Sink;
+
+/// Telegram chats, posted through the Bot API at `url`, usually https://api.telegram.org.
+pub struct Telegram;
+
+impl NotificationProvider for Telegram {
+  fn name(&self) -> &'static str {
+    "telegram"
+  }
+
+  fn default_template(&self, _event: &str) -> Option<Value> {
+    Some(json!({
+      "text": "{{server_name}}: {{title}} {{status}}\n{{description}}"
+    }))
+  }
+
+  fn check(&self, sink: &Sink) -> Result<(), String> {
+    required(&sink.token, "token, the token @BotFather gave the bot")?;
+    required(&sink.chat_id, "chat_id, the chat the bot posts in")?;
+    Ok(())
+  }
+
+  fn request(&self, sink: &Sink, mut body: Value) -> Result<WebhookRequest, String> {
+    self.check(sink)?;
+    insert_default(&mut body, "chat_id", required(&sink.chat_id, "chat_id")?);
+    let url = format!(
+      "{}/bot{}/sendMessage",
+      sink.url.trim_end_matches('/'),
+      required(&sink.token, "token")?
+    );
+    Ok(WebhookRequest::post(&url, body))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::notifications::enums::event_status::EventStatus;
+  use crate::notifications::enums::notification_event::NotificationEvent;
+  use crate::notifications::providers::tests::send;
+  use crate::notifications::sinks::SinkKind;
+  use mockito::Matcher;
+  use serial_test::serial;
+
+  #[tokio::test]
+  #[serial]
+  async fn test_send_to_telegram() {
+    let mut server = mockito::Server::new_async().await;
+    let mock = server
+      .mock("POST", "/bot123:secret/sendMessage")
+      .match_body(Matcher::Json(json!({
+        "chat_id": "-1001234",
+        "text": "Midgard: Stop Failed\nSent by \"odin\""
+      })))
+      .with_status(200)
+      .with_body(r#"{"ok": true}"#)
+      .create_async()
+      .await;
+    let sink = Sink {
+      url: server.url(),
+      kind: Some(SinkKind::Telegram),
+      token: Some(String::from("123:secret")),
+      chat_id: Some(String::from("-1001234")),
+      ..Sink::default()
+    };
+    send(&sink, NotificationEvent::Stop(EventStatus::Failed)).await;
+    mock.assert_async().await;
+  }
+}
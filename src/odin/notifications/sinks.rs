@@ -0,0 +1,432 @@
+use handlebars::Handlebars;
+use log::{debug, warn};
+use serde::{de, Deserialize, Deserializer, Serialize};
+use serde_json::Value;
+
+use crate::files::settings::Settings;
+use crate::files::templates::read_templates;
+use crate::files::{FileManager, ManagedFile};
+use crate::notifications::discord::TemplateValues;
+use crate::notifications::enums::notification_event::{
+  fetch_webhook_url, is_webhook_enabled, NotificationEvent,
+};
+use crate::notifications::providers::WebhookRequest;
+use crate::notifications::{NotificationMessage, WEBHOOK_URL};
+use crate::utils::environment::fetch_var;
+use crate::utils::is_valid_url;
+
+/// The events a sink can ask for, as used by the `events` filter and the templates.
+pub const EVENTS: [&str; 6] = [
+  "broadcast",
+  "start",
+  "stop",
+  "update",
+  "player_join",
+  "player_leave",
+];
+const STATUSES: [&str; 6] = [
+  "running",
+  "successful",
+  "failed",
+  "joined",
+  "left",
+  "triggered",
+];
+const ANY: &str = "*";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SinkKind {
+  /// A Discord webhook, gets the embeds from `discord.json`.
+  Discord,
+  Slack,
+  Matrix,
+  Telegram,
+  Ntfy,
+  Gotify,
+  /// Anything else, gets the notification as it is.
+  Json,
+}
+
+/// Somewhere notifications are posted to.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Sink {
+  /// Shown in the logs instead of the url, which usually holds a secret.
+  #[serde(default)]
+  pub name: Option<String>,
+  pub url: String,
+  /// Picked from the url when missing.
+  #[serde(rename = "type", default)]
+  pub kind: Option<SinkKind>,
+  /// `start`, `player_join`, `start:failed` or `*:failed`, every event when empty.
+  #[serde(default)]
+  pub events: Vec<String>,
+  /// A file like `discord.json`, holding a body per event to send instead of the default one.
+  #[serde(default)]
+  pub template: Option<String>,
+  /// The Matrix access token, Telegram bot token, Gotify application token or ntfy token.
+  #[serde(default)]
+  pub token: Option<String>,
+  /// The Matrix room id, like `!abc123:matrix.org`.
+  #[serde(default)]
+  pub room: Option<String>,
+  /// The Telegram chat to post in.
+  #[serde(default, deserialize_with = "string_or_number")]
+  pub chat_id: Option<String>,
+}
+
+/// Telegram chat ids are numbers, which are easy to write without quotes.
+fn string_or_number<'de, D: Deserializer<'de>>(
+  deserializer: D,
+) -> Result<Option<String>, D::Error> {
+  Ok(match Option::<Value>::deserialize(deserializer)? {
+    Some(Value::String(value)) => Some(value),
+    Some(Value::Number(value)) => Some(value.to_string()),
+    Some(_) => return Err(de::Error::custom("expected a string or a number")),
+    None => None,
+  })
+}
+
+impl Sink {
+  pub fn from_url(url: &str) -> Sink {
+    Sink {
+      url: url.to_string(),
+      ..Sink::default()
+    }
+  }
+
+  pub fn kind(&self) -> SinkKind {
+    self.kind.unwrap_or_else(|| SinkKind::detect(&self.url))
+  }
+
+  pub fn label(&self) -> &str {
+    self.name.as_deref().unwrap_or("webhook")
+  }
+
+  /// Whether the `events` filter lets `event` through.
+  pub fn accepts(&self, event: &NotificationEvent) -> bool {
+    if self.events.is_empty() {
+      return true;
+    }
+    let status = event.to_event_type().status.to_lowercase();
+    self.events.iter().any(|filter| {
+      let (name, wanted) = filter.split_once(':').unwrap_or((filter, ANY));
+      (name == ANY || name == event.key()) && (wanted == ANY || wanted == status)
+    })
+  }
+
+  fn template(&self, event: &str) -> Option<Value> {
+    let file = ManagedFile {
+      name: self.template.clone()?,
+    };
+    if !file.exists() {
+      warn!("[{}]: Template {} not found", self.label(), file.path());
+      return None;
+    }
+    match read_templates(&file) {
+      Ok(mut templates) => templates.remove(event),
+      Err(e) => {
+        warn!("[{}]: {}", self.label(), e);
+        None
+      }
+    }
+  }
+
+  /// The body to post for a notification. It comes from the sink's template, the provider's
+  /// template file or the provider's default, whichever is first to have `event` and render.
+  pub fn payload(&self, event: &str, notification: &NotificationMessage) -> Value {
+    self.payload_with(event, notification, TemplateValues::new)
+  }
+
+  /// The body to post for a notification, with the templates rendering the values `values` makes.
+  pub(crate) fn payload_with(
+    &self,
+    event: &str,
+    notification: &NotificationMessage,
+    values: fn(&NotificationMessage) -> TemplateValues,
+  ) -> Value {
+    let provider = self.kind().provider();
+    let templates = [
+      self.template(event),
+      provider.template(event),
+      provider.default_template(event),
+    ];
+    let mut templates = templates.into_iter().flatten().peekable();
+    if templates.peek().is_some() {
+      let mut values = values(notification);
+      for template in templates {
+        match render(&template, &mut values) {
+          Ok(mut payload) => {
+            provider.decorate(&mut payload, notification);
+            return payload;
+          }
+          Err(e) => warn!(
+            "[{}]: Failed to render the {} template: {}",
+            self.label(),
+            event,
+            e
+          ),
+        }
+      }
+    }
+    serde_json::to_value(notification).unwrap()
+  }
+
+  /// The request delivering a notification to the sink.
+  pub fn request(
+    &self,
+    event: &str,
+    notification: &NotificationMessage,
+  ) -> Result<WebhookRequest, String> {
+    self
+      .kind()
+      .provider()
+      .request(self, self.payload(event, notification))
+  }
+}
+
+/// Escapes a value for the inside of a JSON string, the templates are JSON.
+fn escape_json(value: &str) -> String {
+  let quoted = Value::String(value.to_string()).to_string();
+  quoted[1..quoted.len() - 1].to_string()
+}
+
+/// Renders `template` with the values of a notification.
+pub(crate) fn render(template: &Value, values: &mut TemplateValues) -> Result<Value, String> {
+  let source = template.to_string();
+  let values = values.for_template(&source);
+  let mut handlebars = Handlebars::new();
+  handlebars.register_escape_fn(escape_json);
+  handlebars
+    .register_template_string("notification", source)
+    .map_err(|e| e.to_string())?;
+  let rendered = handlebars
+    .render("notification", &values)
+    .map_err(|e| e.to_string())?;
+  debug!("Notification Parsed: \n{}", rendered);
+  serde_json::from_str(&rendered).map_err(|e| e.to_string())
+}
+
+fn check_filter(filter: &str) -> Result<(), String> {
+  let (name, status) = filter.split_once(':').unwrap_or((filter, ANY));
+  if name != ANY && !EVENTS.contains(&name) {
+    return Err(format!(
+      "unknown event {}, use one of {} or {}",
+      name,
+      EVENTS.join(", "),
+      ANY
+    ));
+  }
+  if status != ANY && !STATUSES.contains(&status) {
+    return Err(format!(
+      "unknown status {}, use one of {} or {}",
+      status,
+      STATUSES.join(", "),
+      ANY
+    ));
+  }
+  Ok(())
+}
+
+/// Reads the JSON list of sinks `NOTIFICATION_SINKS` holds.
+pub fn parse_sinks(value: &str) -> Result<Vec<Sink>, String> {
+  let sinks: Vec<Sink> = serde_json::from_str(value).map_err(|e| e.to_string())?;
+  for (index, sink) in sinks.iter().enumerate() {
+    let name = sink
+      .name
+      .clone()
+      .unwrap_or_else(|| format!("#{}", index + 1));
+    if !is_valid_url(&sink.url) {
+      return Err(format!("sink {} has no valid url", name));
+    }
+    for filter in &sink.events {
+      check_filter(filter).map_err(|e| format!("sink {}: {}", name, e))?;
+    }
+    sink
+      .kind()
+      .provider()
+      .check(sink)
+      .map_err(|e| format!("sink {} {}", name, e))?;
+  }
+  Ok(sinks)
+}
+
+/// `WEBHOOK_URL` as a sink, taking the events the `WEBHOOK_STATUS_*` variables turn on.
+fn webhook_url_sink() -> Option<Sink> {
+  if !is_webhook_enabled() {
+    return None;
+  }
+  let events: Vec<String> = STATUSES
+    .iter()
+    .filter(|status| fetch_var(&format!("WEBHOOK_STATUS_{}", status).to_uppercase(), "0") == "1")
+    .map(|status| format!("{}:{}", ANY, status))
+    .collect();
+  if events.is_empty() {
+    debug!("Skipping {}, no WEBHOOK_STATUS_* is set to 1", WEBHOOK_URL);
+    return None;
+  }
+  Some(Sink {
+    name: Some(String::from(WEBHOOK_URL)),
+    url: fetch_webhook_url(),
+    events,
+    ..Sink::default()
+  })
+}
+
+/// Every sink odin posts to, the ones from `NOTIFICATION_SINKS` and `WEBHOOK_URL`.
+pub fn sinks() -> Vec<Sink> {
+  let mut sinks = Settings::current().notifications.sinks;
+  sinks.extend(webhook_url_sink());
+  sinks
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::notifications::dispatcher::flush;
+  use crate::notifications::enums::event_status::EventStatus;
+  use crate::notifications::enums::player::PlayerStatus;
+  use crate::notifications::NOTIFICATION_SINKS;
+  use serial_test::serial;
+  use std::env;
+  use std::fs;
+  use std::time::Duration;
+
+  #[test]
+  fn test_event_filters() {
+    let sinks = parse_sinks(
+      r#"[
+        {"name": "general", "url": "https://example.com/general", "events": ["player_join", "player_leave"]},
+        {"name": "ops", "url": "https://example.com/ops", "events": ["*:failed", "update"]},
+        {"url": "https://discord.com/api/webhooks/1/token"}
+      ]"#,
+    )
+    .unwrap();
+    let wanted_by = |event: NotificationEvent| -> Vec<&str> {
+      sinks
+        .iter()
+        .filter(|sink| sink.accepts(&event))
+        .map(Sink::label)
+        .collect()
+    };
+    assert_eq!(
+      wanted_by(NotificationEvent::Player(PlayerStatus::Joined)),
+      vec!["general", "webhook"]
+    );
+    assert_eq!(
+      wanted_by(NotificationEvent::Stop(EventStatus::Failed)),
+      vec!["ops", "webhook"]
+    );
+    assert_eq!(
+      wanted_by(NotificationEvent::Update(EventStatus::Running)),
+      vec!["ops", "webhook"]
+    );
+    assert_eq!(sinks[0].kind(), SinkKind::Json);
+    assert_eq!(sinks[2].kind(), SinkKind::Discord);
+  }
+
+  #[test]
+  fn test_invalid_sinks() {
+    assert!(parse_sinks("{}").is_err());
+    assert!(parse_sinks(r#"[{"url": "not a url"}]"#)
+      .unwrap_err()
+      .contains("#1"));
+    assert!(
+      parse_sinks(r#"[{"name": "ops", "url": "https://example.com", "events": ["crash"]}]"#)
+        .unwrap_err()
+        .contains("unknown event crash")
+    );
+    assert!(
+      parse_sinks(r#"[{"url": "https://example.com", "events": ["start:broken"]}]"#)
+        .unwrap_err()
+        .contains("unknown status broken")
+    );
+  }
+
+  #[test]
+  #[serial]
+  fn test_template_payload() {
+    env::set_var("NAME", "Test Server");
+    let dir = tempfile::tempdir().unwrap();
+    let template = dir.path().join("ops.json");
+    fs::write(
+      &template,
+      r#"{"events": {"stop": {"text": "{{title}} {{status}}: {{description}}"}}}"#,
+    )
+    .unwrap();
+    let sink = Sink {
+      url: String::from("https://example.com/ops"),
+      template: Some(template.to_string_lossy().to_string()),
+      ..Sink::default()
+    };
+    let notification = NotificationMessage {
+      author: String::from("odin"),
+      event_type: NotificationEvent::Stop(EventStatus::Failed).to_event_type(),
+      event_message: String::from("Server crashed"),
+      timestamp: String::from("2024-01-01T00:00:00+00:00"),
+    };
+    assert_eq!(
+      sink.payload("stop", &notification),
+      serde_json::json!({ "text": "Stop Failed: Server crashed" })
+    );
+    let fallback = sink.payload("start", &notification);
+    env::remove_var("NAME");
+    assert_eq!(fallback["event_message"], "Server crashed");
+  }
+
+  #[test]
+  #[serial]
+  fn test_webhook_url_sink() {
+    env::set_var(WEBHOOK_URL, "https://example.com/hook");
+    env::set_var("WEBHOOK_STATUS_FAILED", "1");
+    env::set_var(
+      NOTIFICATION_SINKS,
+      r#"[{"name": "general", "url": "https://example.com/general"}]"#,
+    );
+    let found = sinks();
+    env::remove_var(WEBHOOK_URL);
+    env::remove_var("WEBHOOK_STATUS_FAILED");
+    env::remove_var(NOTIFICATION_SINKS);
+
+    assert_eq!(found.len(), 2);
+    assert_eq!(found[1].label(), WEBHOOK_URL);
+    assert_eq!(found[1].events, vec!["*:failed"]);
+    assert!(!found[1].accepts(&NotificationEvent::Start(EventStatus::Successful)));
+    assert!(found[1].accepts(&NotificationEvent::Start(EventStatus::Failed)));
+  }
+
+  #[test]
+  #[serial]
+  fn test_fan_out_to_matching_sinks() {
+    let mut server = mockito::Server::new();
+    let general = server
+      .mock("POST", "/general")
+      .match_body(mockito::Matcher::PartialJsonString(String::from(
+        r#"{"event_message": "Player Bjorn has joined the adventure!"}"#,
+      )))
+      .with_status(204)
+      .create();
+    let ops = server.mock("POST", "/ops").expect(0).create();
+    env::set_var(
+      NOTIFICATION_SINKS,
+      format!(
+        r#"[{{"url": "{0}/general", "events": ["player_join"]}}, {{"url": "{0}/ops", "events": ["*:failed"]}}]"#,
+        server.url()
+      ),
+    );
+    let event = NotificationEvent::Player(PlayerStatus::Joined);
+    let notification = NotificationMessage {
+      author: String::from("odin"),
+      event_type: event.to_event_type(),
+      event_message: String::from("Player Bjorn has joined the adventure!"),
+      timestamp: String::from("2024-01-01T00:00:00+00:00"),
+    };
+    let sent = event.send_to_sinks(&notification);
+    flush(Duration::from_secs(10));
+    env::remove_var(NOTIFICATION_SINKS);
+
+    assert_eq!(sent, 1);
+    general.assert();
+    ops.assert();
+  }
+}
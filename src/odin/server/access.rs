@@ -0,0 +1,304 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use log::{debug, info, warn};
+
+use crate::log_filters::PlayerList;
+use crate::utils::common_paths::saves_directory;
+use crate::utils::environment::fetch_var;
+
+/// The lists Valheim reads from the save directory, one player id per line.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AccessList {
+  Admin,
+  Banned,
+  Permitted,
+}
+
+impl AccessList {
+  pub const ALL: [AccessList; 3] = [AccessList::Admin, AccessList::Banned, AccessList::Permitted];
+
+  pub fn file_name(self) -> &'static str {
+    match self {
+      AccessList::Admin => "adminlist.txt",
+      AccessList::Banned => "bannedlist.txt",
+      AccessList::Permitted => "permittedlist.txt",
+    }
+  }
+
+  /// When set, the list is managed by this env variable and synced at startup.
+  pub fn env_var(self) -> &'static str {
+    match self {
+      AccessList::Admin => "ADMIN_LIST",
+      AccessList::Banned => "BANNED_LIST",
+      AccessList::Permitted => "PERMITTED_LIST",
+    }
+  }
+
+  pub fn path(self) -> PathBuf {
+    Path::new(&saves_directory()).join(self.file_name())
+  }
+}
+
+/// Steam ids are numeric, crossplay ids carry their platform like `Steam_7656...` or `PlayFab_...`.
+fn is_player_id(value: &str) -> bool {
+  match value.split_once('_') {
+    Some((platform, id)) => {
+      !platform.is_empty()
+        && platform.chars().all(|c| c.is_ascii_alphabetic())
+        && !id.is_empty()
+        && id.chars().all(|c| c.is_ascii_alphanumeric())
+    }
+    None => !value.is_empty() && value.chars().all(|c| c.is_ascii_digit()),
+  }
+}
+
+/// Turns what was passed on the command line into a player id, looking names up in `player.list`.
+pub fn resolve_player(players: &PlayerList, value: &str) -> Result<String, String> {
+  let value = value.trim();
+  if value.is_empty() {
+    return Err(String::from("a player id or name can't be empty"));
+  }
+  let ids = players.ids_by_name(value);
+  match ids.as_slice() {
+    [id] => {
+      debug!("Resolved player {} to {}", value, id);
+      Ok(id.to_string())
+    }
+    [] if is_player_id(value) => Ok(value.to_string()),
+    [] if players.has_seen(value) => Err(format!(
+      "odin hasn't seen the Steam or PlayFab id of {} connect yet, use their id instead",
+      value
+    )),
+    [] => Err(format!(
+      "{} is neither a player id nor a player odin has seen join",
+      value
+    )),
+    ids => Err(format!(
+      "{} matches several players ({}), use their id instead",
+      value,
+      ids.join(", ")
+    )),
+  }
+}
+
+fn is_entry(line: &str) -> bool {
+  let line = line.trim();
+  !line.is_empty() && !line.starts_with("//")
+}
+
+/// The ids in a list, comments and blank lines are skipped.
+pub fn read_entries(path: &Path) -> io::Result<Vec<String>> {
+  match fs::read_to_string(path) {
+    Ok(content) => Ok(
+      content
+        .lines()
+        .filter(|line| is_entry(line))
+        .map(|line| line.trim().to_string())
+        .collect(),
+    ),
+    Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(vec![]),
+    Err(e) => Err(e),
+  }
+}
+
+/// Replaces the entries of a list while keeping its comments, then moves it into place so the
+/// server never reads a half written file.
+fn write_entries(path: &Path, entries: &[String]) -> io::Result<()> {
+  let mut lines: Vec<String> = match fs::read_to_string(path) {
+    Ok(content) => content
+      .lines()
+      .filter(|line| !is_entry(line))
+      .filter(|line| !line.trim().is_empty())
+      .map(String::from)
+      .collect(),
+    Err(e) if e.kind() == io::ErrorKind::NotFound => vec![],
+    Err(e) => return Err(e),
+  };
+  lines.extend(entries.iter().cloned());
+  if let Some(parent) = path.parent() {
+    fs::create_dir_all(parent)?;
+  }
+  let temporary = path.with_extension("txt.tmp");
+  fs::write(&temporary, lines.join("\n") + "\n")?;
+  fs::rename(&temporary, path)
+}
+
+/// Adds ids to a list, returns the ones which weren't on it yet.
+pub fn add(path: &Path, ids: &[String]) -> io::Result<Vec<String>> {
+  let mut entries = read_entries(path)?;
+  let added: Vec<String> =
+    ids
+      .iter()
+      .filter(|id| !entries.contains(id))
+      .cloned()
+      .fold(vec![], |mut added, id| {
+        if !added.contains(&id) {
+          added.push(id);
+        }
+        added
+      });
+  if !added.is_empty() {
+    entries.extend(added.iter().cloned());
+    write_entries(path, &entries)?;
+  }
+  Ok(added)
+}
+
+/// Removes ids from a list, returns the ones which were on it.
+pub fn remove(path: &Path, ids: &[String]) -> io::Result<Vec<String>> {
+  let entries = read_entries(path)?;
+  let (removed, kept): (Vec<String>, Vec<String>) =
+    entries.into_iter().partition(|entry| ids.contains(entry));
+  if !removed.is_empty() {
+    write_entries(path, &kept)?;
+  }
+  Ok(removed)
+}
+
+/// Makes every list which has its env variable set match it, so they can be managed from a
+/// compose file. Ids added in game to such a list are removed on the next start.
+pub fn sync_from_env() {
+  let players = PlayerList::default();
+  for list in AccessList::ALL {
+    let value = fetch_var(list.env_var(), "");
+    if value.is_empty() {
+      continue;
+    }
+    let mut ids: Vec<String> = vec![];
+    for entry in value
+      .split([',', '\n'])
+      .filter(|entry| !entry.trim().is_empty())
+    {
+      match resolve_player(&players, entry) {
+        Ok(id) if !ids.contains(&id) => ids.push(id),
+        Ok(_) => {}
+        Err(e) => warn!("{}: {}, skipping it", list.env_var(), e),
+      }
+    }
+    let path = list.path();
+    match read_entries(&path) {
+      Ok(current) if current == ids => debug!("{} is up to date", list.file_name()),
+      _ => match write_entries(&path, &ids) {
+        Ok(()) => info!(
+          "Synced {} from {} with {} entries",
+          list.file_name(),
+          list.env_var(),
+          ids.len()
+        ),
+        Err(e) => warn!("Failed to sync {}: {}", list.file_name(), e),
+      },
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use tempfile::tempdir;
+
+  fn players() -> PlayerList {
+    PlayerList::from(String::from(
+      r#"{"players": [
+        {"id": 1234, "zdo_index": 1, "name": "Ragnar", "last_seen": 1, "platform_id": "Steam_1234"},
+        {"id": 4321, "zdo_index": 2, "name": "Ragnar", "last_seen": 2, "platform_id": "Steam_1234"},
+        {"id": 77, "zdo_index": 1, "name": "Bjorn", "last_seen": 1, "platform_id": "77"},
+        {"id": 78, "zdo_index": 1, "name": "Bjorn", "last_seen": 2, "platform_id": "78"},
+        {"id": 99, "zdo_index": 1, "name": "Sigrid", "last_seen": 1}
+      ]}"#,
+    ))
+  }
+
+  #[test]
+  fn test_resolve_player() {
+    let players = players();
+    assert_eq!(
+      resolve_player(&players, "ragnar"),
+      Ok(String::from("Steam_1234"))
+    );
+    assert_eq!(
+      resolve_player(&players, "76561198000000000"),
+      Ok(String::from("76561198000000000"))
+    );
+    assert_eq!(
+      resolve_player(&players, "Steam_76561198000000000"),
+      Ok(String::from("Steam_76561198000000000"))
+    );
+    assert!(resolve_player(&players, "Bjorn")
+      .unwrap_err()
+      .contains("77, 78"));
+    assert!(resolve_player(&players, "Some Viking").is_err());
+    // The ZDO id of a player isn't what the lists hold.
+    assert!(resolve_player(&players, "Sigrid")
+      .unwrap_err()
+      .contains("use their id instead"));
+    assert!(resolve_player(&players, " ").is_err());
+  }
+
+  #[test]
+  fn test_add_and_remove_keep_comments() {
+    let directory = tempdir().unwrap();
+    let path = directory.path().join("adminlist.txt");
+    fs::write(&path, "// List admin players ID  ONE per line\n111\n").unwrap();
+
+    let ids = vec![
+      String::from("222"),
+      String::from("111"),
+      String::from("222"),
+    ];
+    assert_eq!(add(&path, &ids).unwrap(), vec!["222"]);
+    assert_eq!(read_entries(&path).unwrap(), vec!["111", "222"]);
+
+    assert_eq!(
+      remove(&path, &[String::from("111"), String::from("333")]).unwrap(),
+      vec!["111"]
+    );
+    assert_eq!(
+      fs::read_to_string(&path).unwrap(),
+      "// List admin players ID  ONE per line\n222\n"
+    );
+    assert!(!path.with_extension("txt.tmp").exists());
+  }
+
+  #[test]
+  #[serial_test::serial]
+  fn test_sync_from_env() {
+    use crate::constants::SAVE_LOCATION;
+    use std::env;
+
+    let saves = tempdir().unwrap();
+    env::set_var(SAVE_LOCATION, saves.path());
+    fs::write(saves.path().join("adminlist.txt"), "// admins\n999\n").unwrap();
+    fs::write(saves.path().join("permittedlist.txt"), "555\n").unwrap();
+    env::set_var("ADMIN_LIST", "111,\n222, 111");
+    env::set_var("BANNED_LIST", "333,not a player");
+    sync_from_env();
+    env::remove_var("ADMIN_LIST");
+    env::remove_var("BANNED_LIST");
+    env::remove_var(SAVE_LOCATION);
+
+    assert_eq!(
+      fs::read_to_string(saves.path().join("adminlist.txt")).unwrap(),
+      "// admins\n111\n222\n"
+    );
+    assert_eq!(
+      read_entries(&saves.path().join("bannedlist.txt")).unwrap(),
+      vec!["333"]
+    );
+    // Lists without their env variable are left alone.
+    assert_eq!(
+      read_entries(&saves.path().join("permittedlist.txt")).unwrap(),
+      vec!["555"]
+    );
+  }
+
+  #[test]
+  fn test_missing_list() {
+    let directory = tempdir().unwrap();
+    let path = directory.path().join("bannedlist.txt");
+    assert!(read_entries(&path).unwrap().is_empty());
+    assert!(remove(&path, &[String::from("1")]).unwrap().is_empty());
+    assert!(!path.exists());
+  }
+}
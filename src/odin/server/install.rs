@@ -125,7 +125,7 @@ mod tests {
   #[test]
   fn test_add_beta_args() {
     let mut args = vec!["example".to_string()];
-    env::set_var("ADDITIONAL_STEAMCMD_ARGS", "".to_string());
+    env::set_var("ADDITIONAL_STEAMCMD_ARGS", "");
     env::set_var("USE_PUBLIC_BETA", "1");
     add_additional_args(&mut args);
     assert_eq!(
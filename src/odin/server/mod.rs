@@ -1,10 +1,12 @@
+pub mod access;
 mod install;
-mod process;
+pub mod process;
 mod shutdown;
 mod startup;
 mod status;
 mod update;
 mod utils;
+pub mod world_modifiers;
 
 // Reexport all public functions
 pub use crate::server::{install::*, shutdown::*, startup::*, status::*, update::*, utils::*};
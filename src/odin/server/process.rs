@@ -1,7 +1,48 @@
 use crate::constants;
-use log::{debug, error, info};
+use crate::utils::common_paths::{pid_file_path, saves_directory, saves_directory_of};
+use crate::utils::instances::current_instance;
+use log::{debug, error, info, warn};
+use std::ffi::{OsStr, OsString};
+use std::fs;
 use std::option::Option;
-use sysinfo::{Pid, Signal, System};
+use sysinfo::{Pid, ProcessRefreshKind, ProcessesToUpdate, Signal, System, UpdateKind};
+
+/// Records the PID of a launched server so later commands don't have to scan for it.
+pub fn write_pid_file(pid: u32) {
+  let path = pid_file_path();
+  debug!("Writing PID {} to {}", pid, path);
+  if let Err(e) = fs::write(&path, pid.to_string()) {
+    warn!("Failed to write the PID file {}: {}", path, e);
+  }
+}
+
+pub fn remove_pid_file() {
+  let path = pid_file_path();
+  if fs::remove_file(&path).is_ok() {
+    debug!("Removed PID file {}", path);
+  }
+}
+
+fn read_pid_file() -> Option<Pid> {
+  let content = fs::read_to_string(pid_file_path()).ok()?;
+  content.trim().parse::<usize>().ok().map(Pid::from)
+}
+
+/// The argument following `flag` on a command line.
+fn arg_after<'a>(cmd: &'a [OsString], flag: &str) -> Option<&'a OsStr> {
+  cmd
+    .iter()
+    .skip_while(|arg| arg.as_os_str() != flag)
+    .nth(1)
+    .map(OsString::as_os_str)
+}
+
+/// Whether a server launched with `cmd` runs `world` from `saves`, servers launched without
+/// `-savedir` use the default saves.
+fn runs_world(cmd: &[OsString], default_saves: &str, saves: &str, world: &str) -> bool {
+  let savedir = arg_after(cmd, "-savedir").unwrap_or(OsStr::new(default_saves));
+  savedir == saves && arg_after(cmd, "-world") == Some(OsStr::new(world))
+}
 
 pub struct ServerProcess {
   system: System,
@@ -13,53 +54,117 @@ impl Clone for ServerProcess {
   }
 }
 
+impl Default for ServerProcess {
+  fn default() -> Self {
+    Self::new()
+  }
+}
+
 impl ServerProcess {
   pub fn new() -> ServerProcess {
     ServerProcess {
-      system: System::new_all(),
+      system: System::new(),
+    }
+  }
+
+  fn refresh_kind() -> ProcessRefreshKind {
+    ProcessRefreshKind::nothing()
+      .with_exe(UpdateKind::OnlyIfNotSet)
+      .with_cmd(UpdateKind::OnlyIfNotSet)
+  }
+
+  fn is_valheim_server(process: &sysinfo::Process) -> bool {
+    process
+      .exe()
+      .and_then(|exe| exe.to_str())
+      .is_some_and(|exe| exe.contains(constants::VALHEIM_EXECUTABLE_NAME))
+  }
+
+  /// Every instance launches with its own `-savedir`, which tells their servers apart.
+  /// Servers launched without one are treated as the default server.
+  fn belongs_to_instance(process: &sysinfo::Process, saves: &str) -> bool {
+    match arg_after(process.cmd(), "-savedir") {
+      Some(savedir) => savedir == saves,
+      None => current_instance().is_none(),
+    }
+  }
+
+  /// Whether a server of any instance has `world` from `saves` loaded. This is the world it
+  /// was launched with, which is no longer the one in the config after a switch.
+  pub fn is_world_loaded(&mut self, saves: &str, world: &str) -> bool {
+    self
+      .system
+      .refresh_processes_specifics(ProcessesToUpdate::All, true, Self::refresh_kind());
+    let default_saves = saves_directory_of(None);
+    self
+      .system
+      .processes()
+      .values()
+      .filter(|process| Self::is_valheim_server(process))
+      .any(|process| runs_world(process.cmd(), &default_saves, saves, world))
+  }
+
+  /// The server recorded in the PID file, if that PID still is a server of this instance.
+  /// A stale PID file, e.g. left behind by a crash, is removed.
+  fn tracked_pid(&mut self) -> Option<Pid> {
+    let pid = read_pid_file()?;
+    self.system.refresh_processes_specifics(
+      ProcessesToUpdate::Some(&[pid]),
+      true,
+      Self::refresh_kind(),
+    );
+    match self.system.process(pid) {
+      Some(process)
+        if Self::is_valheim_server(process)
+          && Self::belongs_to_instance(process, &saves_directory()) =>
+      {
+        debug!("Found Valheim process {} from the PID file", pid);
+        Some(pid)
+      }
+      _ => {
+        debug!("PID {} from the PID file is not a running server", pid);
+        remove_pid_file();
+        None
+      }
     }
   }
 
   pub fn valheim_processes(&mut self) -> Vec<&sysinfo::Process> {
-    self.system.refresh_all();
+    if let Some(pid) = self.tracked_pid() {
+      return self.system.process(pid).into_iter().collect();
+    }
+
+    self
+      .system
+      .refresh_processes_specifics(ProcessesToUpdate::All, true, Self::refresh_kind());
     debug!(
       "Scanning for Valheim processes via system module. Number of processes: {}",
       self.system.processes().len()
     );
 
+    let saves = saves_directory();
     self
       .system
       .processes()
       .values()
-      .filter(|process| {
-        process.exe().is_some()
-          && process
-            .exe()
-            .unwrap()
-            .to_str()
-            .unwrap()
-            .contains(constants::VALHEIM_EXECUTABLE_NAME)
-      })
+      .filter(|process| Self::is_valheim_server(process))
+      .filter(|process| Self::belongs_to_instance(process, &saves))
       .collect()
   }
 
-  pub fn get_parent_process(process: &sysinfo::Process) -> Option<Pid> {
-    System::new_all()
-      .process(process.parent().unwrap())
-      .map(|parent| parent.pid())
-  }
-
   pub fn are_process_running(&mut self) -> bool {
     !self.valheim_processes().is_empty()
   }
 
   pub fn send_interrupt_to_pid(pid: u32) {
-    let s = System::new_all();
-    if let Some(process) = s.process(Pid::from(pid as usize)) {
+    let mut s = System::new();
+    let pid = Pid::from(pid as usize);
+    s.refresh_processes(ProcessesToUpdate::Some(&[pid]), true);
+    if let Some(process) = s.process(pid) {
       info!("Found process with PID: {}", pid);
       match process.kill_with(Signal::Interrupt) {
-        Some(_) => info!("Sent interrupt signal to PID: {}", pid),
-        None => error!("Failed to send interrupt signal to PID: {}.", pid),
+        Some(true) => info!("Sent interrupt signal to PID: {}", pid),
+        _ => error!("Failed to send interrupt signal to PID: {}.", pid),
       };
     } else {
       debug!("[{}]: failed to find process with PID... This can be good and means we stopped it successfully.", pid);
@@ -67,24 +172,97 @@ impl ServerProcess {
   }
 
   pub fn send_interrupt(&mut self) {
-    let processes = self.valheim_processes();
-    for process in processes {
-      if let Some(parent) = ServerProcess::get_parent_process(process) {
-        let s = System::new_all();
-        if !s
-          .process(parent)
-          .unwrap()
-          .exe()
-          .unwrap()
-          .to_str()
-          .unwrap()
-          .contains(constants::VALHEIM_EXECUTABLE_NAME)
-        {
-          let pid = process.pid();
-          info!("Found Valheim process with PID: {}", pid.as_u32());
-          ServerProcess::send_interrupt_to_pid(pid.as_u32());
-        }
-      }
+    // Threads of the server show up as processes with the server as their parent,
+    // only the top most server process needs the signal.
+    let processes: Vec<(Pid, Option<Pid>)> = self
+      .valheim_processes()
+      .iter()
+      .map(|process| (process.pid(), process.parent()))
+      .collect();
+    let pids: Vec<Pid> = processes
+      .into_iter()
+      .filter(|(_, parent)| !parent.is_some_and(|parent| self.is_valheim_pid(parent)))
+      .map(|(pid, _)| pid)
+      .collect();
+    for pid in pids {
+      info!("Found Valheim process with PID: {}", pid.as_u32());
+      ServerProcess::send_interrupt_to_pid(pid.as_u32());
     }
   }
+
+  fn is_valheim_pid(&self, pid: Pid) -> bool {
+    self
+      .system
+      .process(pid)
+      .is_some_and(Self::is_valheim_server)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::constants::GAME_LOCATION;
+  use serial_test::serial;
+  use std::env;
+  use tempfile::tempdir;
+
+  #[test]
+  #[serial]
+  fn test_stale_pid_file_is_removed() {
+    let game_dir = tempdir().unwrap();
+    env::set_var(GAME_LOCATION, game_dir.path());
+    // The test runner is running but isn't a Valheim server.
+    write_pid_file(std::process::id());
+    let path = pid_file_path();
+    assert_eq!(read_pid_file(), Some(Pid::from_u32(std::process::id())));
+
+    let tracked = ServerProcess::new().tracked_pid();
+    let removed = !std::path::Path::new(&path).exists();
+    env::remove_var(GAME_LOCATION);
+    assert_eq!(tracked, None);
+    assert!(removed);
+  }
+
+  #[test]
+  fn test_runs_world() {
+    let cmd = |args: &[&str]| args.iter().map(OsString::from).collect::<Vec<_>>();
+    let default_server = cmd(&["./valheim_server.x86_64", "-world", "Midgard"]);
+    assert!(runs_world(&default_server, "/saves", "/saves", "Midgard"));
+    assert!(!runs_world(&default_server, "/saves", "/saves", "Other"));
+    assert!(!runs_world(
+      &default_server,
+      "/saves",
+      "/saves/instances/eu",
+      "Midgard"
+    ));
+
+    let instance = cmd(&[
+      "./valheim_server.x86_64",
+      "-world",
+      "Midgard",
+      "-savedir",
+      "/saves/instances/eu",
+    ]);
+    assert!(runs_world(
+      &instance,
+      "/saves",
+      "/saves/instances/eu",
+      "Midgard"
+    ));
+    assert!(!runs_world(&instance, "/saves", "/saves", "Midgard"));
+  }
+
+  #[test]
+  #[serial]
+  fn test_missing_pid_file() {
+    let game_dir = tempdir().unwrap();
+    env::set_var(GAME_LOCATION, game_dir.path());
+    fs::write(pid_file_path(), "not a pid").unwrap();
+    let pid = read_pid_file();
+    remove_pid_file();
+    let tracked = ServerProcess::new().tracked_pid();
+    env::remove_var(GAME_LOCATION);
+    assert_eq!(pid, None);
+    assert_eq!(tracked, None);
+  }
 }
@@ -2,7 +2,7 @@ use log::debug;
 
 use std::{thread, time::Duration};
 
-use crate::server::process::ServerProcess;
+use crate::server::process::{remove_pid_file, ServerProcess};
 
 pub fn blocking_shutdown() {
   let mut server_process = ServerProcess::new();
@@ -13,6 +13,7 @@ pub fn blocking_shutdown() {
     debug!("Checking if valheim is still running.");
     if !server.are_process_running() {
       debug!("Valheim process has been stopped successfully!");
+      remove_pid_file();
       break;
     } else {
       debug!("Sleeping for 5s to wait for process to stop.");
@@ -1,15 +1,26 @@
 use std::fs::File;
-use std::process::{exit, Command};
+use std::path::{Path, PathBuf};
+use std::process::{Command, ExitStatus, Stdio};
 use std::{io, process::Child};
+use tokio::signal::unix::{signal, SignalKind};
 
 use daemonize::{Daemonize, Error};
 use log::{debug, error, info};
+use sysinfo::{get_current_pid, ProcessRefreshKind, ProcessesToUpdate, System, UpdateKind, Users};
 
+use crate::commands::logs::stream_output;
+use crate::files::config::{config_file, load_config};
+use crate::files::settings::{ServerType, Settings};
+use crate::files::FileManager;
 use crate::mods::bepinex::BepInExEnvironment;
+use crate::mods::bepinex_config::apply_env_overrides;
+use crate::notifications::dispatcher;
 use crate::notifications::enums::event_status::EventStatus;
 use crate::notifications::enums::notification_event::NotificationEvent;
-use crate::utils::common_paths::{game_directory, saves_directory};
-use crate::utils::environment::fetch_var;
+use crate::server::blocking_shutdown;
+use crate::server::process::{remove_pid_file, write_pid_file, ServerProcess};
+use crate::utils::common_paths::{game_directory, log_directory, saves_directory};
+use crate::utils::instances::current_instance;
 use crate::{
   constants,
   executable::create_execution,
@@ -20,19 +31,50 @@ use crate::{
 
 type CommandResult = io::Result<Child>;
 
+/// The user the server runs as, matching the image's steam install.
+const SERVER_USER: &str = "steam";
+
 fn create_log_files() -> Result<(File, File), Error> {
-  let game_dir = game_directory();
-  let stdout = create_file(format!("{}/logs/valheim_server.log", game_dir).as_str());
-  let stderr = create_file(format!("{}/logs/valheim_server.err", game_dir).as_str());
+  let log_dir = log_directory();
+  if let Err(e) = std::fs::create_dir_all(&log_dir) {
+    error!("Failed to create the log directory {}: {}", log_dir, e);
+  }
+  let stdout = create_file(format!("{}/valheim_server.log", log_dir).as_str());
+  let stderr = create_file(format!("{}/valheim_server.err", log_dir).as_str());
   Ok((stdout, stderr))
 }
+
+/// Settings from odin.yaml or env variables like PORT win over config.json. They apply to the
+/// whole container, so instances only use their own config.
+fn server_setting(layered: &Settings, key: &str, setting: String, configured: &str) -> String {
+  if current_instance().is_none() && layered.is_set(key) {
+    setting
+  } else {
+    configured.to_string()
+  }
+}
+/// Stops the running server and starts it again with the config, for the bot and world switches.
+pub fn restart() -> Result<(), String> {
+  // load_config exits without a config, which would take the bot down with it.
+  if !Path::new(&config_file().path()).exists() {
+    return Err(String::from("odin isn't configured, run `odin configure`"));
+  }
+  blocking_shutdown();
+  start_daemonized(load_config())
+    .map(|_| ())
+    .map_err(|e| format!("Failed to start the server: {}", e))
+}
+
 pub fn start_daemonized(config: ValheimArguments) -> Result<CommandResult, Error> {
   debug!("Starting server daemonized...");
   let (stdout, stderr) = create_log_files().unwrap();
   let command = start(config);
+  // The parent exits in `start` without returning to main, and the child can't reach the
+  // parent's dispatcher, so send what is queued now.
+  dispatcher::flush(dispatcher::FLUSH_TIMEOUT);
   Daemonize::new()
     .working_directory(game_directory())
-    .user("steam")
+    .user(SERVER_USER)
     .stdout(stdout)
     .stderr(stderr)
     .privileged_action(|| {
@@ -52,26 +94,38 @@ pub fn start_daemonized(config: ValheimArguments) -> Result<CommandResult, Error
 }
 
 fn configure_server_options(command: &mut Command, config: &ValheimArguments) {
+  let layered = Settings::load(&[]);
+  let settings = layered.lenient().server;
   debug!("Setting up base command");
   // Sets the name of the server, (Can be set with ENV variable NAME)
-  let name = fetch_var("NAME", &config.name);
+  let name = server_setting(&layered, "server.name", settings.name, &config.name);
   debug!("Setting name to: {}", &name);
   command.arg("-name");
   command.arg(&name);
 
   // Sets the port of the server, (Can be set with ENV variable PORT)
-  let port = fetch_var("PORT", &config.port);
+  let port = server_setting(
+    &layered,
+    "server.port",
+    settings.port.to_string(),
+    &config.port,
+  );
   debug!("Setting port to: {}", &port);
   command.args(["-port", &port]);
 
   // Sets the world of the server, (Can be set with ENV variable WORLD)
-  let world = fetch_var("WORLD", &config.world);
-  debug!("Setting world to: {}", &fetch_var("WORLD", &world));
+  let world = server_setting(&layered, "server.world", settings.world, &config.world);
+  debug!("Setting world to: {}", &world);
   command.arg("-world");
   command.arg(&world);
 
   // Determines if the server is public or not
-  let public = fetch_var("PUBLIC", config.public.as_str());
+  let public = server_setting(
+    &layered,
+    "server.public",
+    String::from(if settings.public { "1" } else { "0" }),
+    &config.public,
+  );
   debug!("Setting public to: {}", &public);
   command.args(["-public", &public]);
 
@@ -82,10 +136,16 @@ fn configure_server_options(command: &mut Command, config: &ValheimArguments) {
     command.args(["-saveinterval", &interval]);
   };
 
-  // Add set_key to the command
+  // Add set_key to the command, one argument per global key
   if let Some(set_key) = &config.set_key {
-    debug!("Setting set_key to: {}", &set_key);
-    command.args(["-setkey", set_key]);
+    set_key
+      .split(',')
+      .map(str::trim)
+      .filter(|key| !key.is_empty())
+      .for_each(|key| {
+        debug!("Setting set_key to: {}", key);
+        command.args(["-setkey", key]);
+      });
   };
 
   // Add preset to the command
@@ -109,15 +169,15 @@ fn configure_server_options(command: &mut Command, config: &ValheimArguments) {
   command.args({
     format!(
       "-nographics -batchmode {}",
-      fetch_var("SERVER_EXTRA_LAUNCH_ARGS", "")
+      settings.extra_launch_args.unwrap_or_default()
     )
     .trim()
     .split(' ')
     .collect::<Vec<&str>>()
   });
 
-  let is_public = config.public.eq("1");
-  let is_vanilla = fetch_var("TYPE", "vanilla").eq_ignore_ascii_case("vanilla");
+  let is_public = public.eq("1");
+  let is_vanilla = settings.server_type == ServerType::Vanilla;
   let no_password = config.password.is_empty();
 
   // If no password env variable
@@ -125,14 +185,14 @@ fn configure_server_options(command: &mut Command, config: &ValheimArguments) {
     info!("No password found, skipping password flag.")
   } else if no_password && (is_public || is_vanilla) {
     error!("Cannot run you server with no password! PUBLIC must be 0 and cannot be a Vanilla type server.");
-    exit(1)
+    dispatcher::exit(1)
   } else {
     info!("Password found, adding password flag.");
     command.arg("-password");
     command.arg(&config.password);
   }
 
-  if fetch_var("ENABLE_CROSSPLAY", "0").eq("1") {
+  if settings.crossplay {
     info!("Launching with Crossplay! <3");
     command.arg("-crossplay");
   } else {
@@ -143,13 +203,13 @@ fn configure_server_options(command: &mut Command, config: &ValheimArguments) {
   command.args(["-savedir", &saves_directory()]);
 }
 
-pub fn start(config: ValheimArguments) -> CommandResult {
+/// Builds the server command with its arguments and environment, ready to be spawned.
+fn server_command(config: &ValheimArguments) -> Command {
   let mut command = create_execution(&config.command);
   debug!("--------------------------------------------------------------------------------------------------------------");
-  let (stdout, stderr) = create_log_files().unwrap();
 
   debug!("Launching With Args: \n{:#?}", &config);
-  let base_command = command
+  command
     .env(
       "SteamAppId",
       // See https://www.reddit.com/r/valheim/comments/yvyxo8/trouble_with_the_dedicated_server/
@@ -157,10 +217,8 @@ pub fn start(config: ValheimArguments) -> CommandResult {
     )
     .current_dir(game_directory());
 
-  base_command.stdout(stdout);
-  base_command.stderr(stderr);
-  debug!("Base Command: {:#?}", base_command);
-  configure_server_options(base_command, &config);
+  debug!("Base Command: {:#?}", command);
+  configure_server_options(&mut command, config);
   debug!("Executable: {}", &config.command);
   info!("Launching Command...");
   let ld_library_path_value = environment::fetch_multiple_var(
@@ -171,12 +229,118 @@ pub fn start(config: ValheimArguments) -> CommandResult {
   let bepinex_env = BepInExEnvironment::new();
   if bepinex_env.is_installed() {
     info!("BepInEx detected! Switching to run with BepInEx...");
+    apply_env_overrides();
     info!("BepInEx Environment: \n{:#?}", bepinex_env);
-    bepinex_env.launch(command)
+    bepinex_env.apply(&mut command);
   } else {
     info!("Everything looks good! Running normally!");
-    command
-      .env(constants::LD_LIBRARY_PATH_VAR, ld_library_path_value)
-      .spawn()
+    command.env(constants::LD_LIBRARY_PATH_VAR, ld_library_path_value);
+  }
+  command
+}
+
+pub fn start(config: ValheimArguments) -> CommandResult {
+  let (stdout, stderr) = create_log_files().unwrap();
+  let mut command = server_command(&config);
+  let child = command.stdout(stdout).stderr(stderr).spawn()?;
+  write_pid_file(child.id());
+  Ok(child)
+}
+
+/// The uid and gid of the server user, when odin runs as root. The daemon drops to it through
+/// `Daemonize::user`, the foreground server needs the same so saves don't end up owned by root.
+fn server_user() -> io::Result<Option<(u32, u32)>> {
+  let pid = get_current_pid().map_err(io::Error::other)?;
+  let mut system = System::new();
+  system.refresh_processes_specifics(
+    ProcessesToUpdate::Some(&[pid]),
+    true,
+    ProcessRefreshKind::nothing().with_user(UpdateKind::Always),
+  );
+  let is_root = system
+    .process(pid)
+    .and_then(|process| process.effective_user_id())
+    .is_some_and(|uid| **uid == 0);
+  if !is_root {
+    return Ok(None);
+  }
+  Users::new_with_refreshed_list()
+    .iter()
+    .find(|user| user.name() == SERVER_USER)
+    .map(|user| Some((**user.id(), *user.group_id())))
+    .ok_or_else(|| io::Error::other(format!("No {} user to run the server as", SERVER_USER)))
+}
+
+/// Forwards SIGTERM and SIGINT to the server as an interrupt, which makes Valheim save and exit.
+async fn forward_signals(pid: u32) -> io::Result<()> {
+  let mut terminate = signal(SignalKind::terminate())?;
+  let mut interrupt = signal(SignalKind::interrupt())?;
+  loop {
+    tokio::select! {
+      _ = terminate.recv() => info!("Received SIGTERM, shutting down the server..."),
+      _ = interrupt.recv() => info!("Received SIGINT, shutting down the server..."),
+    }
+    ServerProcess::send_interrupt_to_pid(pid);
+  }
+}
+
+/// Runs the server as a child of odin instead of a daemon, for supervisors like Docker or
+/// Kubernetes which expect the main process to stay up. Output goes through the log filters
+/// live and into the usual log files, and the server's exit status is returned.
+pub async fn start_foreground(config: ValheimArguments) -> io::Result<ExitStatus> {
+  debug!("Starting server in the foreground...");
+  let log_dir = log_directory();
+  if let Err(e) = std::fs::create_dir_all(&log_dir) {
+    error!("Failed to create the log directory {}: {}", log_dir, e);
+  }
+  let mut command = tokio::process::Command::from(server_command(&config));
+  if let Some((uid, gid)) = server_user()? {
+    command.uid(uid).gid(gid);
+  }
+  let mut child = command
+    .stdout(Stdio::piped())
+    .stderr(Stdio::piped())
+    .spawn()?;
+  let pid = child
+    .id()
+    .ok_or_else(|| io::Error::other("The server exited right after launch"))?;
+  write_pid_file(pid);
+  info!(
+    "Server has been started in the foreground with PID {}!",
+    pid
+  );
+  info!("Keep an eye out for 'Game server connected' in the log!");
+  NotificationEvent::Start(EventStatus::Successful).send_notification(None);
+
+  let mut outputs = vec![];
+  if let Some(stdout) = child.stdout.take() {
+    let path = PathBuf::from(format!("{}/valheim_server.log", log_dir));
+    outputs.push(tokio::spawn(stream_output(stdout, path)));
+  }
+  if let Some(stderr) = child.stderr.take() {
+    let path = PathBuf::from(format!("{}/valheim_server.err", log_dir));
+    outputs.push(tokio::spawn(stream_output(stderr, path)));
+  }
+
+  let status = tokio::select! {
+    status = child.wait() => status,
+    result = forward_signals(pid) => match result {
+      Ok(()) => child.wait().await,
+      Err(e) => {
+        // Without signal forwarding nothing could stop the server cleanly, so don't leave it
+        // running behind odin.
+        error!("Failed to forward signals to the server, stopping it: {}", e);
+        if let Err(kill_error) = child.kill().await {
+          error!("Failed to stop the server: {}", kill_error);
+        }
+        Err(e)
+      }
+    }
+  };
+  // Wait for the last lines of output before returning.
+  for output in outputs {
+    let _ = output.await;
   }
+  remove_pid_file();
+  status
 }
@@ -4,6 +4,8 @@ use serde::{Deserialize, Serialize};
 #[derive(Debug, Deserialize, Serialize)]
 pub struct BepInExInfo {
   pub enabled: bool,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub version: Option<String>,
   pub(crate) mods: Vec<ModInfo>,
 }
 
@@ -12,12 +14,14 @@ impl BepInExInfo {
     let env = BepInExEnvironment::new();
     BepInExInfo {
       enabled: env.is_installed(),
+      version: env.version(),
       mods: env.list_mods(),
     }
   }
   pub fn disabled() -> BepInExInfo {
     BepInExInfo {
       enabled: false,
+      version: None,
       mods: vec![],
     }
   }
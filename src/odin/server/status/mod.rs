@@ -2,7 +2,9 @@ mod bepinex_info;
 mod jobs_info;
 
 use crate::constants::{AUTO_BACKUP_JOB, AUTO_UPDATE_JOB};
-use crate::utils::environment::fetch_var;
+use crate::files::settings::Settings;
+use crate::worlds::fwl::WorldMetadata;
+use crate::worlds::{active_world, world_metadata};
 use a2s::info::Info;
 use a2s::A2SClient;
 use bepinex_info::BepInExInfo;
@@ -23,6 +25,9 @@ pub struct ServerInfo {
   pub online: bool,
   pub bepinex: BepInExInfo,
   pub jobs: Vec<JobInfo>,
+  /// The active world as stored in its .fwl, missing before the world was generated.
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub world: Option<WorldMetadata>,
 }
 
 impl ServerInfo {
@@ -38,15 +43,20 @@ impl ServerInfo {
   }
   pub fn offline() -> ServerInfo {
     let unknown = String::from("Unknown");
+    let name = Settings::load(&[])
+      .set_value("server.name")
+      .map(String::from)
+      .unwrap_or_else(|| unknown.clone());
     ServerInfo {
-      name: fetch_var("NAME", &unknown),
+      name: name.clone(),
       version: unknown.clone(),
       players: 0,
       max_players: 0,
-      map: fetch_var("NAME", &unknown),
+      map: name,
       online: false,
       bepinex: BepInExInfo::disabled(),
       jobs: vec![],
+      world: world_metadata(&active_world()).ok(),
     }
   }
 }
@@ -72,6 +82,7 @@ impl From<Info> for ServerInfo {
         JobInfo::from_str(AUTO_UPDATE_JOB).unwrap(),
         JobInfo::from_str(AUTO_BACKUP_JOB).unwrap(),
       ],
+      world: world_metadata(&active_world()).ok(),
     }
   }
 }
@@ -85,6 +96,9 @@ impl Display for ServerInfo {
       format!("Map: {}", &self.map),
       format!("BepInEx Enabled: {}", bepinex.enabled),
     ];
+    if let Some(world) = &self.world {
+      server_info.push(format!("World: {} (seed {})", world.name, world.seed_name));
+    }
     if bepinex.enabled {
       let mods: Vec<String> = bepinex.mods.iter().map(|m| String::from(&m.name)).collect();
       server_info.push(format!("BepInEx Mods: {}", mods.join(", ")))
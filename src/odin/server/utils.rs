@@ -1,5 +1,11 @@
 use super::process::ServerProcess;
+use crate::utils::common_paths::saves_directory;
 
 pub fn is_running() -> bool {
   ServerProcess::new().are_process_running()
 }
+
+/// Whether a running server, of this or any other instance, has `world` of this instance loaded.
+pub fn is_world_loaded(world: &str) -> bool {
+  ServerProcess::new().is_world_loaded(&saves_directory(), world)
+}
@@ -0,0 +1,316 @@
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+use crate::commands::configure::Modifiers;
+
+/// Appended to errors about unknown values so new game versions aren't blocked on odin.
+pub const PASSTHROUGH_HINT: &str =
+  "set MODIFIER_PASSTHROUGH=1 to pass unknown values to the server as they are";
+
+/// Values the dedicated server accepts, matched without regard to case.
+pub trait WorldSetting: Copy + Sized + 'static {
+  const ALL: &'static [Self];
+  /// What the setting is called in error messages.
+  const KIND: &'static str;
+
+  fn as_str(self) -> &'static str;
+
+  fn names() -> String {
+    Self::ALL
+      .iter()
+      .map(|setting| setting.as_str())
+      .collect::<Vec<_>>()
+      .join(", ")
+  }
+
+  fn parse(value: &str) -> Result<Self, WorldSettingError> {
+    let value = value.trim();
+    if value.is_empty() {
+      return Err(WorldSettingError::Malformed(format!(
+        "{} can't be empty",
+        Self::KIND
+      )));
+    }
+    Self::ALL
+      .iter()
+      .copied()
+      .find(|setting| setting.as_str().eq_ignore_ascii_case(value))
+      .ok_or_else(|| {
+        WorldSettingError::Unknown(format!(
+          "{} is not a known {}, use one of {}",
+          value,
+          Self::KIND,
+          Self::names()
+        ))
+      })
+  }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WorldSettingError {
+  /// The server couldn't make sense of it either, e.g. a modifier without a value.
+  Malformed(String),
+  /// Well formed but not known to odin, allowed with `MODIFIER_PASSTHROUGH`.
+  Unknown(String),
+}
+
+impl Display for WorldSettingError {
+  fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    match self {
+      WorldSettingError::Malformed(message) | WorldSettingError::Unknown(message) => {
+        write!(f, "{}", message)
+      }
+    }
+  }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Preset {
+  Normal,
+  Casual,
+  Easy,
+  Hard,
+  Hardcore,
+  Immersive,
+  Hammer,
+}
+
+impl WorldSetting for Preset {
+  const ALL: &'static [Self] = &[
+    Preset::Normal,
+    Preset::Casual,
+    Preset::Easy,
+    Preset::Hard,
+    Preset::Hardcore,
+    Preset::Immersive,
+    Preset::Hammer,
+  ];
+  const KIND: &'static str = "preset";
+
+  fn as_str(self) -> &'static str {
+    match self {
+      Preset::Normal => "normal",
+      Preset::Casual => "casual",
+      Preset::Easy => "easy",
+      Preset::Hard => "hard",
+      Preset::Hardcore => "hardcore",
+      Preset::Immersive => "immersive",
+      Preset::Hammer => "hammer",
+    }
+  }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ModifierKey {
+  Combat,
+  DeathPenalty,
+  Resources,
+  Raids,
+  Portals,
+}
+
+impl ModifierKey {
+  /// The values the server accepts for this modifier.
+  pub fn values(self) -> &'static [ModifierValue] {
+    use ModifierValue as V;
+    match self {
+      ModifierKey::Combat => &[V::VeryEasy, V::Easy, V::Hard, V::VeryHard],
+      ModifierKey::DeathPenalty => &[V::Casual, V::VeryEasy, V::Easy, V::Hard, V::Hardcore],
+      ModifierKey::Resources => &[V::MuchLess, V::Less, V::More, V::MuchMore, V::Most],
+      ModifierKey::Raids => &[V::None, V::MuchLess, V::Less, V::More, V::MuchMore],
+      ModifierKey::Portals => &[V::Casual, V::Hard, V::VeryHard],
+    }
+  }
+}
+
+impl WorldSetting for ModifierKey {
+  const ALL: &'static [Self] = &[
+    ModifierKey::Combat,
+    ModifierKey::DeathPenalty,
+    ModifierKey::Resources,
+    ModifierKey::Raids,
+    ModifierKey::Portals,
+  ];
+  const KIND: &'static str = "modifier";
+
+  fn as_str(self) -> &'static str {
+    match self {
+      ModifierKey::Combat => "combat",
+      ModifierKey::DeathPenalty => "deathpenalty",
+      ModifierKey::Resources => "resources",
+      ModifierKey::Raids => "raids",
+      ModifierKey::Portals => "portals",
+    }
+  }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ModifierValue {
+  None,
+  Casual,
+  VeryEasy,
+  Easy,
+  Hard,
+  VeryHard,
+  Hardcore,
+  MuchLess,
+  Less,
+  More,
+  MuchMore,
+  Most,
+}
+
+impl WorldSetting for ModifierValue {
+  const ALL: &'static [Self] = &[
+    ModifierValue::None,
+    ModifierValue::Casual,
+    ModifierValue::VeryEasy,
+    ModifierValue::Easy,
+    ModifierValue::Hard,
+    ModifierValue::VeryHard,
+    ModifierValue::Hardcore,
+    ModifierValue::MuchLess,
+    ModifierValue::Less,
+    ModifierValue::More,
+    ModifierValue::MuchMore,
+    ModifierValue::Most,
+  ];
+  const KIND: &'static str = "modifier value";
+
+  fn as_str(self) -> &'static str {
+    match self {
+      ModifierValue::None => "none",
+      ModifierValue::Casual => "casual",
+      ModifierValue::VeryEasy => "veryeasy",
+      ModifierValue::Easy => "easy",
+      ModifierValue::Hard => "hard",
+      ModifierValue::VeryHard => "veryhard",
+      ModifierValue::Hardcore => "hardcore",
+      ModifierValue::MuchLess => "muchless",
+      ModifierValue::Less => "less",
+      ModifierValue::More => "more",
+      ModifierValue::MuchMore => "muchmore",
+      ModifierValue::Most => "most",
+    }
+  }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SetKey {
+  NoBuildCost,
+  PlayerEvents,
+  PassiveMobs,
+  NoMap,
+}
+
+impl WorldSetting for SetKey {
+  const ALL: &'static [Self] = &[
+    SetKey::NoBuildCost,
+    SetKey::PlayerEvents,
+    SetKey::PassiveMobs,
+    SetKey::NoMap,
+  ];
+  const KIND: &'static str = "global key";
+
+  fn as_str(self) -> &'static str {
+    match self {
+      SetKey::NoBuildCost => "nobuildcost",
+      SetKey::PlayerEvents => "playerevents",
+      SetKey::PassiveMobs => "passivemobs",
+      SetKey::NoMap => "nomap",
+    }
+  }
+}
+
+pub fn check_preset(value: &str) -> Result<(), WorldSettingError> {
+  Preset::parse(value).map(|_| ())
+}
+
+/// Checks a comma separated list like `combat=hard,raids=none`.
+pub fn check_modifiers(value: &str) -> Result<(), WorldSettingError> {
+  for modifier in value.split(',') {
+    let modifier = Modifiers::from_str(modifier).map_err(WorldSettingError::Malformed)?;
+    let key = ModifierKey::parse(&modifier.name)?;
+    let value = ModifierValue::parse(&modifier.value).ok();
+    if !value.is_some_and(|value| key.values().contains(&value)) {
+      return Err(WorldSettingError::Unknown(format!(
+        "{} is not a value of {}, use one of {}",
+        modifier.value,
+        key.as_str(),
+        key
+          .values()
+          .iter()
+          .map(|value| value.as_str())
+          .collect::<Vec<_>>()
+          .join(", ")
+      )));
+    }
+  }
+  Ok(())
+}
+
+/// Checks a comma separated list of global keys, each one becomes a `-setkey` argument.
+pub fn check_set_keys(value: &str) -> Result<(), WorldSettingError> {
+  for key in value.split(',') {
+    SetKey::parse(key)?;
+  }
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_parse_is_case_insensitive() {
+    assert_eq!(Preset::parse("Hardcore"), Ok(Preset::Hardcore));
+    assert_eq!(SetKey::parse(" nomap "), Ok(SetKey::NoMap));
+    assert!(matches!(
+      Preset::parse("nightmare"),
+      Err(WorldSettingError::Unknown(_))
+    ));
+    assert!(matches!(
+      Preset::parse(""),
+      Err(WorldSettingError::Malformed(_))
+    ));
+  }
+
+  #[test]
+  fn test_check_modifiers() {
+    assert_eq!(
+      check_modifiers("combat=hard,raids=none,Portals=Casual"),
+      Ok(())
+    );
+    assert!(matches!(
+      check_modifiers("combat"),
+      Err(WorldSettingError::Malformed(_))
+    ));
+    assert!(matches!(
+      check_modifiers("combat=hard,raids="),
+      Err(WorldSettingError::Malformed(_))
+    ));
+    assert_eq!(
+      check_modifiers("combat=most"),
+      Err(WorldSettingError::Unknown(String::from(
+        "most is not a value of combat, use one of veryeasy, easy, hard, veryhard"
+      )))
+    );
+    assert!(matches!(
+      check_modifiers("weather=stormy"),
+      Err(WorldSettingError::Unknown(_))
+    ));
+  }
+
+  #[test]
+  fn test_check_set_keys() {
+    assert_eq!(check_set_keys("nobuildcost,playerevents"), Ok(()));
+    assert!(matches!(
+      check_set_keys("nobuildcost,"),
+      Err(WorldSettingError::Malformed(_))
+    ));
+    assert!(matches!(
+      check_set_keys("godmode"),
+      Err(WorldSettingError::Unknown(_))
+    ));
+  }
+}
@@ -1,5 +1,6 @@
-use crate::constants::{GAME_LOCATION, MODS_LOCATION, SAVE_LOCATION};
+use crate::constants::{BACKUP_LOCATION, GAME_LOCATION, MODS_LOCATION, SAVE_LOCATION};
 use crate::utils::get_working_dir;
+use crate::utils::instances::{current_instance, instance_directory};
 use std::env;
 
 pub fn game_directory() -> String {
@@ -7,7 +8,30 @@ pub fn game_directory() -> String {
 }
 
 pub fn log_directory() -> String {
-  format!("{}/logs", game_directory())
+  log_directory_of(current_instance().as_deref())
+}
+
+pub fn log_directory_of(instance: Option<&str>) -> String {
+  match instance {
+    Some(instance) => format!("{}/logs", instance_directory(instance)),
+    None => format!("{}/logs", game_directory()),
+  }
+}
+
+/// Holds the PID of the running server, each instance has its own.
+pub fn pid_file_path() -> String {
+  match current_instance() {
+    Some(instance) => format!("{}/valheim_server.pid", instance_directory(&instance)),
+    None => format!("{}/valheim_server.pid", game_directory()),
+  }
+}
+
+/// Holds the notifications odin couldn't deliver yet, each instance has its own.
+pub fn notification_spool_path() -> String {
+  match current_instance() {
+    Some(instance) => format!("{}/notifications.spool", instance_directory(&instance)),
+    None => format!("{}/notifications.spool", game_directory()),
+  }
 }
 
 pub fn bepinex_directory() -> String {
@@ -30,13 +54,27 @@ pub fn mods_staging_directory() -> String {
   format!("{}/.staging/mods", game_directory())
 }
 
-// pub fn backup_directory() -> String {
-//   env::var(BACKUP_LOCATION).unwrap_or_else(|_| format!("{}/backups", get_working_dir()))
-// }
+pub fn cache_directory() -> String {
+  format!("{}/.cache", game_directory())
+}
+
+/// Where `odin backup` archives go, the auto backups and the bot's `/backup` land here.
+pub fn backup_directory() -> String {
+  env::var(BACKUP_LOCATION).unwrap_or_else(|_| format!("{}/backups", get_working_dir()))
+}
 
 pub fn saves_directory() -> String {
-  env::var(SAVE_LOCATION).unwrap_or_else(|_| match env::var("HOME") {
+  saves_directory_of(current_instance().as_deref())
+}
+
+pub fn saves_directory_of(instance: Option<&str>) -> String {
+  let saves = env::var(SAVE_LOCATION).unwrap_or_else(|_| match env::var("HOME") {
     Ok(dir) => format!("{}/.config/unity3d/IronGate/Valheim", dir),
     Err(_) => format!("{}/backups", get_working_dir()),
-  })
+  });
+  // Each instance keeps its worlds in a directory of its own next to the default saves.
+  match instance {
+    Some(instance) => format!("{}/instances/{}", saves, instance),
+    None => saves,
+  }
 }
@@ -0,0 +1,140 @@
+use crate::constants::{INSTANCES_LOCATION, ODIN_INSTANCE};
+use crate::files::config::ValheimArguments;
+use crate::utils::get_working_dir;
+use std::env;
+use std::fs;
+use std::path::Path;
+use std::sync::OnceLock;
+
+const INSTANCE_CONFIG_FILE: &str = "config.json";
+
+static SELECTED_INSTANCE: OnceLock<String> = OnceLock::new();
+
+/// The instance selected with `--instance`, or `ODIN_INSTANCE` for processes that don't take
+/// arguments like huginn. `None` for the default server.
+pub fn current_instance() -> Option<String> {
+  SELECTED_INSTANCE
+    .get()
+    .cloned()
+    .or_else(|| env::var(ODIN_INSTANCE).ok().filter(|name| !name.is_empty()))
+}
+
+/// Instance names end up in paths, so only letters, digits, dashes and underscores are allowed.
+pub fn is_valid_instance_name(name: &str) -> bool {
+  !name.is_empty()
+    && name
+      .chars()
+      .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+/// Makes every path and config lookup of this process resolve to the given instance, an instance
+/// is selected once when odin starts.
+pub fn select_instance(name: &str) -> bool {
+  if !is_valid_instance_name(name) {
+    return false;
+  }
+  SELECTED_INSTANCE.get_or_init(|| name.to_string()) == name
+}
+
+pub fn instances_directory() -> String {
+  env::var(INSTANCES_LOCATION).unwrap_or_else(|_| format!("{}/instances", get_working_dir()))
+}
+
+pub fn instance_directory(name: &str) -> String {
+  format!("{}/{}", instances_directory(), name)
+}
+
+pub fn instance_config_path(name: &str) -> String {
+  format!("{}/{}", instance_directory(name), INSTANCE_CONFIG_FILE)
+}
+
+/// Lists the instances which have been configured with `odin --instance <name> configure`.
+pub fn list_instances() -> Vec<String> {
+  let mut instances: Vec<String> = fs::read_dir(instances_directory())
+    .map(|entries| {
+      entries
+        .flatten()
+        .filter(|entry| entry.path().join(INSTANCE_CONFIG_FILE).is_file())
+        .filter_map(|entry| entry.file_name().to_str().map(String::from))
+        .filter(|name| is_valid_instance_name(name))
+        .collect()
+    })
+    .unwrap_or_default();
+  instances.sort();
+  instances
+}
+
+/// The game port configured for an instance.
+pub fn instance_port(name: &str) -> Option<u16> {
+  let content = fs::read_to_string(Path::new(&instance_config_path(name))).ok()?;
+  let config: ValheimArguments = serde_json::from_str(&content).ok()?;
+  config.port.parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use serial_test::serial;
+  use tempfile::tempdir;
+
+  #[test]
+  fn test_instance_names() {
+    assert!(is_valid_instance_name("survival"));
+    assert!(is_valid_instance_name("hardcore_2-eu"));
+    assert!(!is_valid_instance_name(""));
+    assert!(!is_valid_instance_name("../etc"));
+    assert!(!is_valid_instance_name("two words"));
+  }
+
+  #[test]
+  #[serial]
+  fn test_instance_paths() {
+    use crate::constants::SAVE_LOCATION;
+    use crate::files::config::config_file_of;
+    use crate::files::FileManager;
+    use crate::utils::common_paths::{log_directory_of, saves_directory_of};
+
+    env::set_var(INSTANCES_LOCATION, "/srv/instances");
+    env::set_var(SAVE_LOCATION, "/srv/saves");
+    let instance = Some("creative");
+    let paths = (
+      saves_directory_of(instance),
+      log_directory_of(instance),
+      config_file_of(instance).path(),
+    );
+    let default_saves = saves_directory_of(None);
+    env::remove_var(INSTANCES_LOCATION);
+    env::remove_var(SAVE_LOCATION);
+
+    assert_eq!(paths.0, "/srv/saves/instances/creative");
+    assert_eq!(paths.1, "/srv/instances/creative/logs");
+    assert_eq!(paths.2, "/srv/instances/creative/config.json");
+    assert_eq!(default_saves, "/srv/saves");
+    assert!(!select_instance("../creative"));
+  }
+
+  #[test]
+  #[serial]
+  fn test_list_instances() {
+    let root = tempdir().unwrap();
+    env::set_var(INSTANCES_LOCATION, root.path());
+    for (name, port) in [("survival", "2456"), ("creative", "2466")] {
+      fs::create_dir_all(root.path().join(name)).unwrap();
+      fs::write(
+        root.path().join(name).join(INSTANCE_CONFIG_FILE),
+        format!(
+          r#"{{"port": "{}", "name": "{}", "world": "{}", "public": "0", "password": "", "command": "valheim_server.x86_64"}}"#,
+          port, name, name
+        ),
+      )
+      .unwrap();
+    }
+    fs::create_dir_all(root.path().join("unconfigured")).unwrap();
+
+    let instances = list_instances();
+    let port = instance_port("creative");
+    env::remove_var(INSTANCES_LOCATION);
+    assert_eq!(instances, vec!["creative", "survival"]);
+    assert_eq!(port, Some(2466));
+  }
+}
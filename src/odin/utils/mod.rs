@@ -1,6 +1,7 @@
 pub mod common_paths;
 pub mod environment;
 pub mod fetch_public_ip_address;
+pub mod instances;
 pub mod parse_truthy;
 
 pub use fetch_public_ip_address::fetch_public_address;
@@ -18,6 +19,7 @@ use std::path::Path;
 
 use crate::constants;
 use crate::files::config::{config_file, read_config};
+use crate::files::settings::Settings;
 use crate::files::FileManager;
 use reqwest::Url;
 
@@ -30,13 +32,14 @@ pub fn get_working_dir() -> String {
 
 pub fn get_server_name() -> String {
   // Some contexts currently don't get passed in $NAME so fall back to reading from the config
-  // if it's missing or invalid UTF-8
-  match env::var("NAME") {
-    Ok(name) if !name.is_empty() => name,
+  // if neither it nor server.name in odin.yaml is set
+  match Settings::load(&[]).set_value("server.name") {
+    // Instances are named by their own config, not by the container wide $NAME.
+    Some(name) if instances::current_instance().is_none() => name.to_string(),
     _ => {
       let config_file = config_file();
       debug!(
-        "Empty or missing $NAME. Falling back to reading from {}",
+        "Empty or missing server name. Falling back to reading from {}",
         config_file.path()
       );
       let config = read_config(config_file);
@@ -59,7 +62,7 @@ pub fn parse_file_name(url: &Url, default: &str) -> String {
   String::from(
     url
       .path_segments()
-      .and_then(|segments| segments.last())
+      .and_then(|mut segments| segments.next_back())
       .and_then(|name| if name.is_empty() { None } else { Some(name) })
       .unwrap_or(default),
   )
@@ -69,6 +72,10 @@ pub fn get_md5_hash(context: &str) -> String {
   format!("{:x}", md5::compute(context.as_bytes()))
 }
 
+pub fn url_parse_file_type(url: &str) -> String {
+  url.split('.').next_back().unwrap().to_string()
+}
+
 #[cfg(test)]
 mod tests {
   use super::*;
@@ -81,7 +88,3 @@ mod tests {
     );
   }
 }
-
-pub fn url_parse_file_type(url: &str) -> String {
-  url.split('.').last().unwrap().to_string()
-}
@@ -0,0 +1,500 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::ops::Range;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::errors::WorldFileError;
+use crate::worlds::package::PackageReader;
+
+/// A day in Valheim lasts 30 minutes of net time, a new world starts in the morning of day 1.
+const DAY_LENGTH: f64 = 1800.0;
+const DAY_OFFSET: f64 = DAY_LENGTH * 0.15;
+
+/// World versions at which the layout of the .db changed.
+const OLDEST_SUPPORTED_VERSION: i32 = 17;
+pub(crate) const PACKED_ZDOS_SINCE: i32 = 31;
+const LOCATION_VERSION_SINCE: i32 = 21;
+const LOCATIONS_GENERATED_SINCE: i32 = 20;
+const LOCATION_GENERATED_FLAG_SINCE: i32 = 19;
+
+/// Flags in front of a ZDO in the packed format, which say what follows.
+const HAS_CONNECTION: u16 = 1;
+const HAS_FLOATS: u16 = 1 << 1;
+const HAS_VEC3S: u16 = 1 << 2;
+const HAS_QUATERNIONS: u16 = 1 << 3;
+const HAS_INTS: u16 = 1 << 4;
+const HAS_LONGS: u16 = 1 << 5;
+const HAS_STRINGS: u16 = 1 << 6;
+const HAS_BYTE_ARRAYS: u16 = 1 << 7;
+const HAS_ROTATION: u16 = 1 << 12;
+
+/// The global key each boss sets when it dies, and the name players know it by.
+pub const BOSSES: [(&str, &str); 7] = [
+  ("defeated_eikthyr", "Eikthyr"),
+  ("defeated_gdking", "The Elder"),
+  ("defeated_bonemass", "Bonemass"),
+  ("defeated_dragon", "Moder"),
+  ("defeated_goblinking", "Yagluth"),
+  ("defeated_queen", "The Queen"),
+  ("defeated_fader", "Fader"),
+];
+
+/// Prefabs worth a name in the stats, the .db only stores their hashes.
+pub const KNOWN_PREFABS: [&str; 40] = [
+  "wood_floor",
+  "wood_floor_1x1",
+  "wood_wall_roof",
+  "woodwall",
+  "wood_door",
+  "wood_stair",
+  "wood_roof",
+  "wood_roof_45",
+  "wood_pole",
+  "wood_pole2",
+  "wood_beam",
+  "wood_beam_26",
+  "wood_beam_45",
+  "stone_wall_1x1",
+  "stone_wall_2x1",
+  "stone_wall_4x2",
+  "stone_floor_2x2",
+  "piece_workbench",
+  "forge",
+  "piece_stonecutter",
+  "piece_artisanstation",
+  "piece_cauldron",
+  "smelter",
+  "charcoal_kiln",
+  "blastfurnace",
+  "fire_pit",
+  "hearth",
+  "bed",
+  "piece_bed02",
+  "piece_chest_wood",
+  "piece_chest",
+  "piece_chest_private",
+  "piece_chest_blackmetal",
+  "portal_wood",
+  "guard_stone",
+  "Player_tombstone",
+  "Cart",
+  "Raft",
+  "Karve",
+  "VikingShip",
+];
+
+/// Valheim's `GetStableHashCode`, which the .db uses in place of prefab names and ZDO keys.
+pub fn stable_hash(value: &str) -> i32 {
+  let chars: Vec<u16> = value.encode_utf16().collect();
+  let mut even: i32 = 5381;
+  let mut odd: i32 = 5381;
+  for pair in chars.chunks(2) {
+    even = (even << 5).wrapping_add(even) ^ pair[0] as i32;
+    if let Some(&char) = pair.get(1) {
+      odd = (odd << 5).wrapping_add(odd) ^ char as i32;
+    }
+  }
+  even.wrapping_add(odd.wrapping_mul(1566083941))
+}
+
+/// The name of a prefab from its hash, or the hash if odin doesn't know it.
+pub fn prefab_name(hash: i32) -> String {
+  KNOWN_PREFABS
+    .iter()
+    .find(|name| stable_hash(name) == hash)
+    .map_or_else(|| hash.to_string(), |name| name.to_string())
+}
+
+/// How many bytes each part of the .db takes.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub struct SectionSizes {
+  pub header: usize,
+  /// Every object in the world, built or not.
+  pub zdos: usize,
+  /// Generated zones, global keys and locations.
+  pub zones: usize,
+  /// The random event system and anything newer odin doesn't know.
+  pub events: usize,
+}
+
+/// What the .db of a world says about its progress.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct WorldStats {
+  /// The world version the file was written with.
+  pub version: i32,
+  /// Seconds the world has run for in game.
+  pub net_time: f64,
+  pub day: i64,
+  pub global_keys: Vec<String>,
+  pub defeated_bosses: Vec<String>,
+  /// The number of ZDOs, the objects the server keeps track of.
+  pub zdos: usize,
+  /// ZDOs with a creator, pieces players built. Unknown for worlds older than version 31.
+  pub player_built: Option<usize>,
+  /// ZDO counts by prefab name, or by hash for prefabs odin has no name for.
+  pub prefabs: BTreeMap<String, usize>,
+  pub sections: SectionSizes,
+}
+
+/// ZDO keys odin looks for, hashed once per file.
+struct Keys {
+  creator: i32,
+  spawn_time: i32,
+  stack: i32,
+}
+
+impl Keys {
+  fn new() -> Self {
+    Keys {
+      creator: stable_hash("creator"),
+      spawn_time: stable_hash("spawntime"),
+      stack: stable_hash("stack"),
+    }
+  }
+}
+
+/// What odin needs to know about a ZDO, `range` is where it sits in the file.
+pub(crate) struct Zdo {
+  pub(crate) prefab: i32,
+  /// The zone the ZDO is in, only stored since world version 31.
+  pub(crate) zone: Option<(i32, i32)>,
+  /// A piece a player built.
+  pub(crate) has_creator: bool,
+  /// An item lying on the ground, dropped by a player or a creature.
+  pub(crate) is_item_drop: bool,
+  pub(crate) range: Range<usize>,
+}
+
+/// A location instance such as a boss altar, placed when the world was created.
+pub(crate) struct Location {
+  pub(crate) zone: (i32, i32),
+  /// Where the flag telling whether the location was spawned into its zone sits in the file.
+  pub(crate) generated_at: Option<usize>,
+}
+
+/// The zone a position is in, zones are 64 by 64 meters centered on multiples of 64.
+fn zone_of(x: f32, z: f32) -> (i32, i32) {
+  (
+    ((x + 32.0) / 64.0).floor() as i32,
+    ((z + 32.0) / 64.0).floor() as i32,
+  )
+}
+
+/// Reads a ZDO in the format of world version 31 and later, which only stores what is set.
+fn read_packed_zdo(package: &mut PackageReader, keys: &Keys) -> Result<Zdo, WorldFileError> {
+  let start = package.position();
+  package.skip(4, "zdo id")?;
+  let flags = package.read_u16("zdo flags")?;
+  let zone = (
+    package.read_i16("zdo sector")? as i32,
+    package.read_i16("zdo sector")? as i32,
+  );
+  package.skip(12, "zdo position")?;
+  let prefab = package.read_i32("zdo prefab")?;
+  if flags & HAS_ROTATION != 0 {
+    package.skip(12, "zdo rotation")?;
+  }
+  if flags & HAS_CONNECTION != 0 {
+    package.skip(1 + 12, "zdo connection")?;
+  }
+  for (flag, size) in [(HAS_FLOATS, 4), (HAS_VEC3S, 12), (HAS_QUATERNIONS, 16)] {
+    if flags & flag != 0 {
+      let items = package.read_num_items("zdo data")?;
+      package.skip(items * (4 + size), "zdo data")?;
+    }
+  }
+  let mut has_stack = false;
+  if flags & HAS_INTS != 0 {
+    for _ in 0..package.read_num_items("zdo ints")? {
+      has_stack |= package.read_i32("zdo int key")? == keys.stack;
+      package.skip(4, "zdo int")?;
+    }
+  }
+  let mut has_creator = false;
+  let mut has_spawn_time = false;
+  if flags & HAS_LONGS != 0 {
+    for _ in 0..package.read_num_items("zdo longs")? {
+      let key = package.read_i32("zdo long key")?;
+      has_creator |= key == keys.creator;
+      has_spawn_time |= key == keys.spawn_time;
+      package.skip(8, "zdo long")?;
+    }
+  }
+  if flags & HAS_STRINGS != 0 {
+    for _ in 0..package.read_num_items("zdo strings")? {
+      package.skip(4, "zdo string key")?;
+      package.read_string("zdo string")?;
+    }
+  }
+  if flags & HAS_BYTE_ARRAYS != 0 {
+    for _ in 0..package.read_num_items("zdo byte arrays")? {
+      package.skip(4, "zdo byte array key")?;
+      let length = package.read_count("zdo byte array")?;
+      package.skip(length, "zdo byte array")?;
+    }
+  }
+  Ok(Zdo {
+    prefab,
+    zone: Some(zone),
+    has_creator,
+    is_item_drop: has_spawn_time && has_stack,
+    range: start..package.position(),
+  })
+}
+
+/// Reads a ZDO from before world version 31, each one a package of its own behind its id.
+fn read_legacy_zdo(file: &mut PackageReader, version: i32) -> Result<Zdo, WorldFileError> {
+  let start = file.position();
+  file.skip(8 + 4, "zdo id")?;
+  let length = file.read_count("zdo length")?;
+  let mut package = PackageReader::new(file.take(length, "zdo")?);
+  // Owner and data revisions, persistent, owner, creation time and generator version.
+  package.skip(4 + 4 + 1 + 8 + 8 + 4, "zdo header")?;
+  if (16..24).contains(&version) {
+    package.skip(4, "zdo header")?;
+  }
+  if version >= 23 {
+    package.skip(1, "zdo type")?;
+  }
+  if version >= 22 {
+    package.skip(1, "zdo distant")?;
+  }
+  Ok(Zdo {
+    prefab: package.read_i32("zdo prefab")?,
+    zone: None,
+    has_creator: false,
+    is_item_drop: false,
+    range: start..file.position(),
+  })
+}
+
+/// The parts of a .db odin understands, with where they sit so the file can be rewritten.
+pub(crate) struct WorldDb {
+  pub(crate) version: i32,
+  pub(crate) net_time: f64,
+  /// Where the ZDO count sits, the ZDOs follow it.
+  pub(crate) zdo_count_at: usize,
+  pub(crate) zdos: Vec<Zdo>,
+  /// Where the count of generated zones sits, after the ZDOs.
+  pub(crate) zones_at: usize,
+  pub(crate) zones: Vec<(i32, i32)>,
+  pub(crate) global_keys: Vec<String>,
+  pub(crate) locations: Vec<Location>,
+  /// Where the random event system starts, odin copies it and anything after as is.
+  pub(crate) events_at: usize,
+}
+
+impl WorldDb {
+  pub(crate) fn parse(bytes: &[u8]) -> Result<WorldDb, WorldFileError> {
+    let mut file = PackageReader::new(bytes);
+    let version = file.read_i32("world version")?;
+    if version < OLDEST_SUPPORTED_VERSION {
+      return Err(WorldFileError::Invalid(format!(
+        "world version {} is too old to inspect",
+        version
+      )));
+    }
+    let net_time = file.read_f64("net time")?;
+    // The session id and the next free ZDO id.
+    file.skip(8 + 4, "zdo header")?;
+    let zdo_count_at = file.position();
+    let count = file.read_count("zdo count")?;
+
+    let keys = Keys::new();
+    let mut zdos = Vec::with_capacity(count.min(file.remaining()));
+    for _ in 0..count {
+      zdos.push(if version >= PACKED_ZDOS_SINCE {
+        read_packed_zdo(&mut file, &keys)?
+      } else {
+        read_legacy_zdo(&mut file, version)?
+      });
+    }
+    if version < PACKED_ZDOS_SINCE {
+      // Ids and timestamps of deleted ZDOs.
+      let dead = file.read_count("dead zdos")?;
+      file.skip(dead * (8 + 4 + 8), "dead zdos")?;
+    }
+
+    let zones_at = file.position();
+    let mut zones = vec![];
+    for _ in 0..file.read_count("generated zones")? {
+      zones.push((
+        file.read_i32("generated zone")?,
+        file.read_i32("generated zone")?,
+      ));
+    }
+    file.skip(4, "generator version")?;
+    if version >= LOCATION_VERSION_SINCE {
+      file.skip(4, "location version")?;
+    }
+    let mut global_keys = vec![];
+    for _ in 0..file.read_count("global keys")? {
+      global_keys.push(file.read_string("global key")?.to_lowercase());
+    }
+    if version >= LOCATIONS_GENERATED_SINCE {
+      file.skip(1, "locations generated")?;
+    }
+    let mut locations = vec![];
+    for _ in 0..file.read_count("locations")? {
+      file.read_string("location name")?;
+      let x = file.read_f32("location position")?;
+      file.skip(4, "location position")?;
+      let z = file.read_f32("location position")?;
+      let generated_at = if version >= LOCATION_GENERATED_FLAG_SINCE {
+        let at = file.position();
+        file.skip(1, "location generated")?;
+        Some(at)
+      } else {
+        None
+      };
+      locations.push(Location {
+        zone: zone_of(x, z),
+        generated_at,
+      });
+    }
+
+    Ok(WorldDb {
+      version,
+      net_time,
+      zdo_count_at,
+      zdos,
+      zones_at,
+      zones,
+      global_keys,
+      locations,
+      events_at: file.position(),
+    })
+  }
+}
+
+impl WorldStats {
+  /// Parses the header, ZDOs and zone data of a .db, the rest of the file is only measured.
+  pub fn parse(bytes: &[u8]) -> Result<WorldStats, WorldFileError> {
+    let world = WorldDb::parse(bytes)?;
+    let mut hashes: HashMap<i32, usize> = HashMap::new();
+    for zdo in &world.zdos {
+      *hashes.entry(zdo.prefab).or_default() += 1;
+    }
+    let player_built = world.zdos.iter().filter(|zdo| zdo.has_creator).count();
+
+    // Keys can carry a value after a space, e.g. `playerdamage 85`.
+    let defeated_bosses = BOSSES
+      .iter()
+      .filter(|(key, _)| {
+        world
+          .global_keys
+          .iter()
+          .any(|global_key| global_key.split_whitespace().next() == Some(key))
+      })
+      .map(|(_, boss)| boss.to_string())
+      .collect();
+    let prefabs = hashes
+      .into_iter()
+      .map(|(hash, count)| (prefab_name(hash), count))
+      .collect();
+
+    let header = world.zdo_count_at + 4;
+    Ok(WorldStats {
+      version: world.version,
+      net_time: world.net_time,
+      day: ((world.net_time + DAY_OFFSET) / DAY_LENGTH).floor() as i64,
+      global_keys: world.global_keys,
+      defeated_bosses,
+      zdos: world.zdos.len(),
+      player_built: (world.version >= PACKED_ZDOS_SINCE).then_some(player_built),
+      prefabs,
+      sections: SectionSizes {
+        header,
+        zdos: world.zones_at - header,
+        zones: world.events_at - world.zones_at,
+        events: bytes.len() - world.events_at,
+      },
+    })
+  }
+
+  pub fn read(path: &Path) -> Result<WorldStats, WorldFileError> {
+    WorldStats::parse(&fs::read(path)?)
+  }
+
+  /// The most common prefabs, the most common first.
+  pub fn top_prefabs(&self, limit: usize) -> Vec<(&str, usize)> {
+    let mut prefabs: Vec<(&str, usize)> = self
+      .prefabs
+      .iter()
+      .map(|(name, count)| (name.as_str(), *count))
+      .collect();
+    prefabs.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+    prefabs.truncate(limit);
+    prefabs
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn fixture(name: &str) -> Result<WorldStats, WorldFileError> {
+    WorldStats::read(&Path::new("tests/assets").join(name))
+  }
+
+  #[test]
+  fn test_stable_hash() {
+    assert_eq!(
+      stable_hash(""),
+      5381i32.wrapping_add(5381i32.wrapping_mul(1566083941))
+    );
+    assert_ne!(stable_hash("wood_floor"), stable_hash("wood_floor_1x1"));
+    assert_eq!(stable_hash("creator"), stable_hash("creator"));
+  }
+
+  #[test]
+  fn test_parse_current_world() {
+    let world = fixture("example_world.db").unwrap();
+    assert_eq!(world.version, 35);
+    assert_eq!(world.day, 42);
+    assert_eq!(
+      world.global_keys,
+      vec!["defeated_eikthyr", "defeated_gdking", "nomap"]
+    );
+    assert_eq!(world.defeated_bosses, vec!["Eikthyr", "The Elder"]);
+    assert_eq!(world.zdos, 4);
+    assert_eq!(world.player_built, Some(3));
+    assert_eq!(world.prefabs["wood_floor"], 2);
+    assert_eq!(world.prefabs["piece_workbench"], 1);
+    assert_eq!(world.prefabs["12345"], 1);
+    assert_eq!(world.top_prefabs(1), vec![("wood_floor", 2)]);
+    let sections = &world.sections;
+    assert_eq!(sections.header, 28);
+    assert_eq!(
+      sections.header + sections.zdos + sections.zones + sections.events,
+      fs::metadata("tests/assets/example_world.db").unwrap().len() as usize
+    );
+    assert!(sections.events > 0);
+  }
+
+  #[test]
+  fn test_parse_older_world() {
+    let world = fixture("example_world_v29.db").unwrap();
+    assert_eq!(world.version, 29);
+    assert_eq!(world.day, 1);
+    assert_eq!(world.defeated_bosses, vec!["Bonemass"]);
+    assert_eq!(world.zdos, 2);
+    assert_eq!(world.player_built, None);
+    assert_eq!(world.prefabs["portal_wood"], 2);
+  }
+
+  #[test]
+  fn test_parse_broken_worlds() {
+    let bytes = fs::read("tests/assets/example_world.db").unwrap();
+    assert!(matches!(
+      WorldStats::parse(&bytes[..60]),
+      Err(WorldFileError::Truncated(_))
+    ));
+    assert!(matches!(
+      WorldStats::parse(&10i32.to_le_bytes()),
+      Err(WorldFileError::Invalid(_))
+    ));
+  }
+}
@@ -0,0 +1,127 @@
+use std::fs;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::errors::WorldFileError;
+use crate::worlds::package::PackageReader;
+
+/// World versions at which fields were added to the .fwl.
+const WORLD_GEN_VERSION_SINCE: i32 = 26;
+const NEEDS_DB_SINCE: i32 = 30;
+const STARTING_GLOBAL_KEYS_SINCE: i32 = 32;
+
+/// The metadata Valheim keeps next to a world's .db in its .fwl.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct WorldMetadata {
+  /// The world version the file was written with.
+  pub version: i32,
+  pub name: String,
+  /// The seed players type in to generate the same world.
+  pub seed_name: String,
+  /// The numeric seed the world is generated from, derived from the seed name.
+  pub seed: i32,
+  pub uid: i64,
+  pub world_gen_version: i32,
+  /// A world without a .db yet, generated on the first start.
+  pub needs_db: bool,
+  /// Global keys a world was created with, e.g. from a preset.
+  pub starting_global_keys: Vec<String>,
+}
+
+impl WorldMetadata {
+  /// Parses a .fwl: the length of the package followed by the package itself.
+  pub fn parse(bytes: &[u8]) -> Result<WorldMetadata, WorldFileError> {
+    let mut file = PackageReader::new(bytes);
+    let length = file.read_count("package length")?;
+    let mut package = PackageReader::new(file.take(length, "package")?);
+
+    let version = package.read_i32("world version")?;
+    if version <= 0 {
+      return Err(WorldFileError::Invalid(format!(
+        "world version {} is not a valid version",
+        version
+      )));
+    }
+    let name = package.read_string("world name")?;
+    let seed_name = package.read_string("seed name")?;
+    let seed = package.read_i32("seed")?;
+    let uid = package.read_i64("uid")?;
+    let world_gen_version = if version >= WORLD_GEN_VERSION_SINCE {
+      package.read_i32("world gen version")?
+    } else {
+      0
+    };
+    let needs_db = version >= NEEDS_DB_SINCE && package.read_bool("needs db")?;
+    let mut starting_global_keys = vec![];
+    if version >= STARTING_GLOBAL_KEYS_SINCE {
+      for _ in 0..package.read_count("starting global keys")? {
+        starting_global_keys.push(package.read_string("starting global key")?.to_lowercase());
+      }
+    }
+    Ok(WorldMetadata {
+      version,
+      name,
+      seed_name,
+      seed,
+      uid,
+      world_gen_version,
+      needs_db,
+      starting_global_keys,
+    })
+  }
+
+  pub fn read(path: &Path) -> Result<WorldMetadata, WorldFileError> {
+    WorldMetadata::parse(&fs::read(path)?)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn fixture(name: &str) -> Result<WorldMetadata, WorldFileError> {
+    WorldMetadata::read(&Path::new("tests/assets").join(name))
+  }
+
+  #[test]
+  fn test_parse_current_world() {
+    assert_eq!(
+      fixture("example_world.fwl").unwrap(),
+      WorldMetadata {
+        version: 35,
+        name: String::from("Dedicated"),
+        seed_name: String::from("HHcLC5acQt"),
+        seed: -1186235433,
+        uid: 1742419911468123,
+        world_gen_version: 2,
+        needs_db: true,
+        starting_global_keys: vec![String::from("nomap"), String::from("playerevents")],
+      }
+    );
+  }
+
+  #[test]
+  fn test_parse_older_world() {
+    let world = fixture("example_world_v26.fwl").unwrap();
+    assert_eq!(world.version, 26);
+    assert_eq!(world.name, "Midgard");
+    assert_eq!(world.seed_name, "Odin1234");
+    assert_eq!(world.world_gen_version, 1);
+    assert!(!world.needs_db);
+    assert!(world.starting_global_keys.is_empty());
+  }
+
+  #[test]
+  fn test_parse_broken_worlds() {
+    assert!(matches!(
+      fixture("example_world_truncated.fwl"),
+      Err(WorldFileError::Truncated("package"))
+    ));
+    assert!(matches!(fixture("missing.fwl"), Err(WorldFileError::Io(_))));
+    assert!(matches!(
+      WorldMetadata::parse(&[4, 0, 0, 0, 0, 0, 0, 0]),
+      Err(WorldFileError::Invalid(_))
+    ));
+  }
+}
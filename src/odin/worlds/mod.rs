@@ -0,0 +1,504 @@
+pub mod db;
+pub mod fwl;
+mod package;
+pub mod prune;
+
+use std::fs::{self, File};
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+use chrono::Local;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use log::{debug, info, warn};
+use serde::Serialize;
+
+use crate::errors::WorldFileError;
+use crate::files::config::{config_file, ValheimArguments};
+use crate::files::settings::Settings;
+use crate::files::FileManager;
+use crate::server;
+use crate::utils::common_paths::saves_directory;
+use db::WorldStats;
+use fwl::WorldMetadata;
+use package::PackageReader;
+use prune::{PruneOptions, PruneReport};
+
+const WORLDS_DIRECTORY: &str = "worlds_local";
+/// Valheim kept worlds here before it split them into local and cloud saves.
+const LEGACY_WORLDS_DIRECTORY: &str = "worlds";
+const WORLD_BACKUPS_DIRECTORY: &str = "world_backups";
+
+/// Where the server keeps its worlds, `worlds_local` or the legacy `worlds` if only that exists.
+pub fn worlds_directory() -> PathBuf {
+  let saves = PathBuf::from(saves_directory());
+  let current = saves.join(WORLDS_DIRECTORY);
+  let legacy = saves.join(LEGACY_WORLDS_DIRECTORY);
+  if !current.exists() && legacy.is_dir() {
+    legacy
+  } else {
+    current
+  }
+}
+
+/// Where `odin world delete` and `odin world import --force` keep the worlds they replace.
+pub fn world_backups_directory() -> PathBuf {
+  PathBuf::from(saves_directory()).join(WORLD_BACKUPS_DIRECTORY)
+}
+
+#[derive(Debug, Serialize)]
+pub struct World {
+  pub name: String,
+  /// The seed players type in to generate the same world.
+  pub seed: Option<String>,
+  /// Size of the .db and .fwl in bytes.
+  pub size: u64,
+  /// Unix timestamp of the last save.
+  pub modified: Option<i64>,
+  /// A world which has never been saved only has its .fwl.
+  pub has_db: bool,
+}
+
+impl World {
+  fn load(directory: &Path, name: &str) -> Option<World> {
+    let fwl = directory.join(format!("{}.fwl", name));
+    let db = directory.join(format!("{}.db", name));
+    let fwl_metadata = fs::metadata(&fwl).ok()?;
+    let db_metadata = fs::metadata(&db).ok();
+    let modified = [Some(&fwl_metadata), db_metadata.as_ref()]
+      .into_iter()
+      .flatten()
+      .filter_map(|metadata| metadata.modified().ok())
+      .max()
+      .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+      .map(|duration| duration.as_secs() as i64);
+    Some(World {
+      name: name.to_string(),
+      seed: WorldMetadata::read(&fwl).ok().map(|world| world.seed_name),
+      size: fwl_metadata.len() + db_metadata.as_ref().map_or(0, |metadata| metadata.len()),
+      modified,
+      has_db: db_metadata.is_some(),
+    })
+  }
+}
+
+pub fn is_world_file_name(name: &str) -> bool {
+  !name.is_empty() && !name.contains(['/', '\\']) && name != "." && name != ".."
+}
+
+/// Worlds in the worlds directory, sorted by name. Valheim's own backups are skipped.
+pub fn list_worlds() -> io::Result<Vec<World>> {
+  let directory = worlds_directory();
+  let entries = match fs::read_dir(&directory) {
+    Ok(entries) => entries,
+    Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(vec![]),
+    Err(e) => return Err(e),
+  };
+  let mut worlds: Vec<World> = entries
+    .flatten()
+    .filter_map(|entry| {
+      let path = entry.path();
+      if path.extension()? != "fwl" {
+        return None;
+      }
+      let name = path.file_stem()?.to_str()?.to_string();
+      if name.contains("_backup_") {
+        return None;
+      }
+      World::load(&directory, &name)
+    })
+    .collect();
+  worlds.sort_by(|a, b| a.name.cmp(&b.name));
+  Ok(worlds)
+}
+
+pub fn find_world(name: &str) -> Option<World> {
+  if !is_world_file_name(name) {
+    return None;
+  }
+  World::load(&worlds_directory(), name)
+}
+
+/// The world the server loads: the one in config.json, or `server.world` before `odin configure`
+/// ran.
+pub fn active_world() -> String {
+  let config = config_file();
+  fs::read_to_string(config.path())
+    .ok()
+    .and_then(|content| serde_json::from_str::<ValheimArguments>(&content).ok())
+    .map(|config| config.world)
+    .unwrap_or_else(|| Settings::current().server.world)
+}
+
+/// The metadata of a world from its .fwl.
+pub fn world_metadata(name: &str) -> Result<WorldMetadata, WorldFileError> {
+  if !is_world_file_name(name) {
+    return Err(WorldFileError::Invalid(format!(
+      "{} is not a valid world name",
+      name
+    )));
+  }
+  WorldMetadata::read(&worlds_directory().join(format!("{}.fwl", name)))
+}
+
+/// The progress of a world from its .db.
+pub fn world_stats(name: &str) -> Result<WorldStats, WorldFileError> {
+  if !is_world_file_name(name) {
+    return Err(WorldFileError::Invalid(format!(
+      "{} is not a valid world name",
+      name
+    )));
+  }
+  WorldStats::read(&worlds_directory().join(format!("{}.db", name)))
+}
+
+/// Prunes the .db of a world after backing the world up, with `report_only` it only reports
+/// what would be removed.
+pub fn prune_world(
+  name: &str,
+  options: &PruneOptions,
+  report_only: bool,
+) -> Result<PruneReport, String> {
+  let path = worlds_directory().join(format!("{}.db", name));
+  let bytes = fs::read(&path).map_err(|e| format!("Failed to read {:?}: {}", path, e))?;
+  let (pruned, report) = prune::prune(&bytes, options).map_err(|e| format!("{}: {}", name, e))?;
+  if report_only || pruned == bytes {
+    return Ok(report);
+  }
+  let backup = backup_world(name)?;
+  info!("Backed up {} to {:?}", name, backup);
+  let temporary = path.with_extension("db.prune");
+  fs::write(&temporary, pruned)
+    .and_then(|_| fs::rename(&temporary, &path))
+    .map_err(|e| format!("Failed to write {:?}: {}", path, e))?;
+  Ok(report)
+}
+
+/// Every file of a world: its .db and .fwl, the .old copies and Valheim's auto backups.
+fn world_files(directory: &Path, name: &str) -> Vec<PathBuf> {
+  let backup_prefix = format!("{}_backup_", name);
+  let mut files: Vec<PathBuf> = fs::read_dir(directory)
+    .map(|entries| {
+      entries
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| {
+          let Some(file_name) = path.file_name().and_then(|name| name.to_str()) else {
+            return false;
+          };
+          let is_world_file = [".db", ".fwl", ".db.old", ".fwl.old"]
+            .iter()
+            .any(|extension| file_name.strip_suffix(extension) == Some(name));
+          let is_auto_backup = file_name.starts_with(&backup_prefix)
+            && (file_name.ends_with(".db") || file_name.ends_with(".fwl"));
+          is_world_file || is_auto_backup
+        })
+        .collect()
+    })
+    .unwrap_or_default();
+  files.sort();
+  files
+}
+
+/// Archives every file of a world into the world backups directory.
+pub fn backup_world(name: &str) -> Result<PathBuf, String> {
+  let files = world_files(&worlds_directory(), name);
+  if files.is_empty() {
+    return Err(format!("There is no world named {}", name));
+  }
+  let backups = world_backups_directory();
+  fs::create_dir_all(&backups).map_err(|e| format!("Failed to create {:?}: {}", backups, e))?;
+  let output = backups.join(format!(
+    "{}-{}.tar.gz",
+    name,
+    Local::now().format("%Y%m%d-%H%M%S")
+  ));
+  let archive =
+    File::create(&output).map_err(|e| format!("Failed to create {:?}: {}", output, e))?;
+  let mut tar = tar::Builder::new(GzEncoder::new(archive, Compression::default()));
+  for file in &files {
+    debug!("Adding {:?} to {:?}", file, output);
+    tar
+      .append_path_with_name(file, file.file_name().unwrap())
+      .map_err(|e| format!("Failed to back up {:?}: {}", file, e))?;
+  }
+  tar
+    .into_inner()
+    .and_then(|encoder| encoder.finish())
+    .map_err(|e| format!("Failed to write {:?}: {}", output, e))?;
+  Ok(output)
+}
+
+/// Backs the world up and then removes all of its files, returns the backup.
+pub fn delete_world(name: &str) -> Result<PathBuf, String> {
+  if !is_world_file_name(name) {
+    return Err(format!("{} is not a valid world name", name));
+  }
+  let backup = backup_world(name)?;
+  info!("Backed up {} to {:?}", name, backup);
+  for file in world_files(&worlds_directory(), name) {
+    fs::remove_file(&file).map_err(|e| format!("Failed to remove {:?}: {}", file, e))?;
+  }
+  Ok(backup)
+}
+
+/// The .fwl and .db of a world about to be imported.
+struct WorldPair {
+  name: String,
+  fwl: PathBuf,
+  db: PathBuf,
+}
+
+fn pair_from_file(path: &Path) -> Result<WorldPair, String> {
+  let name = path
+    .file_stem()
+    .and_then(|name| name.to_str())
+    .ok_or_else(|| format!("{:?} has no world name", path))?
+    .to_string();
+  Ok(WorldPair {
+    fwl: path.with_file_name(format!("{}.fwl", name)),
+    db: path.with_file_name(format!("{}.db", name)),
+    name,
+  })
+}
+
+fn pair_from_directory(directory: &Path) -> Result<WorldPair, String> {
+  let fwls: Vec<PathBuf> = walkdir::WalkDir::new(directory)
+    .into_iter()
+    .flatten()
+    .map(|entry| entry.into_path())
+    .filter(|path| path.extension().is_some_and(|extension| extension == "fwl"))
+    .filter(|path| {
+      !path
+        .file_name()
+        .and_then(|name| name.to_str())
+        .is_some_and(|name| name.contains("_backup_"))
+    })
+    .collect();
+  match fwls.as_slice() {
+    [fwl] => pair_from_file(fwl),
+    [] => Err(format!("No .fwl world file found in {:?}", directory)),
+    _ => Err(format!(
+      "{:?} holds several worlds, import them one at a time",
+      directory
+    )),
+  }
+}
+
+fn extract_zip(archive: &Path, destination: &Path) -> Result<(), String> {
+  let file = File::open(archive).map_err(|e| format!("Failed to open {:?}: {}", archive, e))?;
+  let mut zip =
+    zip::ZipArchive::new(file).map_err(|e| format!("{:?} is not a zip archive: {}", archive, e))?;
+  zip
+    .extract(destination)
+    .map_err(|e| format!("Failed to extract {:?}: {}", archive, e))
+}
+
+/// Checks the pair is something the server can load: both files, a readable header and a
+/// .db which at least starts with a world version.
+fn validate_pair(pair: &WorldPair) -> Result<(), String> {
+  if !is_world_file_name(&pair.name) || pair.name.contains("_backup_") {
+    return Err(format!("{} is not a valid world name", pair.name));
+  }
+  for path in [&pair.fwl, &pair.db] {
+    if !path.is_file() {
+      return Err(format!(
+        "{:?} is missing, a world needs its .db and .fwl",
+        path
+      ));
+    }
+  }
+  let metadata = WorldMetadata::read(&pair.fwl)
+    .map_err(|e| format!("{:?} is not a Valheim world file: {}", pair.fwl, e))?;
+  if metadata.name != pair.name {
+    warn!(
+      "{:?} belongs to a world named {}, the server loads it as {}",
+      pair.fwl, metadata.name, pair.name
+    );
+  }
+  let mut header = [0; 4];
+  let version = File::open(&pair.db)
+    .and_then(|mut file| file.read_exact(&mut header))
+    .map_err(WorldFileError::from)
+    .and_then(|_| PackageReader::new(&header).read_i32("world version"))
+    .map_err(|e| format!("{:?} is not a Valheim world database: {}", pair.db, e))?;
+  if version <= 0 {
+    return Err(format!(
+      "{:?} is not a Valheim world database, it has version {}",
+      pair.db, version
+    ));
+  }
+  Ok(())
+}
+
+/// Copies a world into place through a temporary file, so the server never sees half a world.
+fn copy_into(source: &Path, destination: &Path) -> Result<(), String> {
+  let temporary = destination.with_extension("import");
+  fs::copy(source, &temporary)
+    .and_then(|_| fs::rename(&temporary, destination))
+    .map_err(|e| {
+      let _ = fs::remove_file(&temporary);
+      format!("Failed to copy {:?}: {}", source, e)
+    })
+}
+
+/// Imports a world from a .fwl or .db file with its sibling, a directory or a zip archive.
+/// An existing world with the same name is only replaced with `force`, after backing it up.
+pub fn import_world(source: &Path, force: bool) -> Result<World, String> {
+  let extracted = tempfile::tempdir().map_err(|e| e.to_string())?;
+  let pair = if source.is_dir() {
+    pair_from_directory(source)?
+  } else if source
+    .extension()
+    .is_some_and(|extension| extension == "zip")
+  {
+    extract_zip(source, extracted.path())?;
+    pair_from_directory(extracted.path())?
+  } else if source
+    .extension()
+    .is_some_and(|extension| extension == "fwl" || extension == "db")
+  {
+    pair_from_file(source)?
+  } else {
+    return Err(format!(
+      "{:?} is not a world, import a .fwl, .db, directory or zip",
+      source
+    ));
+  };
+  validate_pair(&pair)?;
+
+  let directory = worlds_directory();
+  if World::load(&directory, &pair.name).is_some() {
+    if !force {
+      return Err(format!(
+        "A world named {} already exists, use --force to replace it",
+        pair.name
+      ));
+    }
+    if server::is_world_loaded(&pair.name) {
+      return Err(format!(
+        "{} is in use by the running server, stop it first",
+        pair.name
+      ));
+    }
+    let backup = backup_world(&pair.name)?;
+    warn!("Replacing {}, the old world is in {:?}", pair.name, backup);
+  }
+  fs::create_dir_all(&directory).map_err(|e| format!("Failed to create {:?}: {}", directory, e))?;
+  copy_into(&pair.db, &directory.join(format!("{}.db", pair.name)))?;
+  copy_into(&pair.fwl, &directory.join(format!("{}.fwl", pair.name)))?;
+  World::load(&directory, &pair.name).ok_or_else(|| format!("Failed to import {}", pair.name))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::constants::SAVE_LOCATION;
+  use serial_test::serial;
+  use std::env;
+  use std::io::Write;
+  use tempfile::tempdir;
+
+  fn write_string(buffer: &mut Vec<u8>, value: &str) {
+    buffer.push(value.len() as u8);
+    buffer.extend_from_slice(value.as_bytes());
+  }
+
+  fn write_world(directory: &Path, name: &str, seed: &str) {
+    let mut package = vec![];
+    package.extend_from_slice(&35i32.to_le_bytes());
+    write_string(&mut package, name);
+    write_string(&mut package, seed);
+    package.extend_from_slice(&42i32.to_le_bytes());
+    package.extend_from_slice(&7i64.to_le_bytes());
+    package.extend_from_slice(&2i32.to_le_bytes());
+    package.push(0);
+    package.extend_from_slice(&0i32.to_le_bytes());
+    let mut fwl = (package.len() as i32).to_le_bytes().to_vec();
+    fwl.extend(package);
+    fs::create_dir_all(directory).unwrap();
+    fs::write(directory.join(format!("{}.fwl", name)), fwl).unwrap();
+    fs::write(directory.join(format!("{}.db", name)), 35i32.to_le_bytes()).unwrap();
+  }
+
+  #[test]
+  #[serial]
+  fn test_list_and_delete_worlds() {
+    let saves = tempdir().unwrap();
+    env::set_var(SAVE_LOCATION, saves.path());
+    let directory = saves.path().join(WORLDS_DIRECTORY);
+    write_world(&directory, "Summer", "abcdefghij");
+    write_world(&directory, "Autumn", "HHcLC5acQt");
+    fs::write(directory.join("Summer_backup_auto-20250101.db"), "old").unwrap();
+    fs::write(directory.join("Summer.db.old"), "old").unwrap();
+
+    let worlds = list_worlds().unwrap();
+    let names: Vec<&str> = worlds.iter().map(|world| world.name.as_str()).collect();
+    assert_eq!(names, vec!["Autumn", "Summer"]);
+    assert_eq!(worlds[0].seed.as_deref(), Some("HHcLC5acQt"));
+    assert!(worlds[0].has_db);
+
+    let backup = delete_world("Summer");
+    let remaining = fs::read_dir(&directory).unwrap().count();
+    env::remove_var(SAVE_LOCATION);
+    let backup = backup.unwrap();
+    assert!(backup.starts_with(saves.path().join(WORLD_BACKUPS_DIRECTORY)));
+    let archived: Vec<String> =
+      tar::Archive::new(flate2::read::GzDecoder::new(File::open(&backup).unwrap()))
+        .entries()
+        .unwrap()
+        .map(|entry| entry.unwrap().path().unwrap().display().to_string())
+        .collect();
+    assert_eq!(archived.len(), 4);
+    assert_eq!(remaining, 2);
+  }
+
+  #[test]
+  #[serial]
+  fn test_import_world() {
+    let saves = tempdir().unwrap();
+    let source = tempdir().unwrap();
+    env::set_var(SAVE_LOCATION, saves.path());
+    write_world(&source.path().join("nested"), "Winter", "snowsnow");
+    let archive = source.path().join("winter.zip");
+    let mut zip = zip::ZipWriter::new(File::create(&archive).unwrap());
+    for file in ["Winter.fwl", "Winter.db"] {
+      zip
+        .start_file(
+          format!("nested/{}", file),
+          zip::write::SimpleFileOptions::default(),
+        )
+        .unwrap();
+      zip
+        .write_all(&fs::read(source.path().join("nested").join(file)).unwrap())
+        .unwrap();
+    }
+    zip.finish().unwrap();
+
+    let imported = import_world(&archive, false);
+    let again = import_world(&source.path().join("nested/Winter.fwl"), false);
+    let forced = import_world(&source.path().join("nested/Winter.db"), true);
+    fs::remove_file(source.path().join("nested/Winter.db")).unwrap();
+    let incomplete = import_world(&source.path().join("nested"), true);
+    let backups = fs::read_dir(world_backups_directory()).unwrap().count();
+    env::remove_var(SAVE_LOCATION);
+
+    assert_eq!(imported.unwrap().seed.as_deref(), Some("snowsnow"));
+    assert!(again.unwrap_err().contains("--force"));
+    assert!(forced.is_ok());
+    assert!(incomplete.unwrap_err().contains("missing"));
+    assert_eq!(backups, 1);
+  }
+
+  #[test]
+  fn test_invalid_world_files() {
+    let source = tempdir().unwrap();
+    fs::write(source.path().join("Broken.fwl"), "nope").unwrap();
+    fs::write(source.path().join("Broken.db"), "nope").unwrap();
+    assert!(import_world(&source.path().join("Broken.fwl"), false)
+      .unwrap_err()
+      .contains("not a Valheim world file"));
+    assert!(import_world(&source.path().join("Broken.txt"), false).is_err());
+  }
+}
@@ -0,0 +1,136 @@
+use crate::errors::WorldFileError;
+
+/// Reads the little endian values Valheim's `ZPackage` writes through a .NET `BinaryWriter`.
+pub(crate) struct PackageReader<'a> {
+  bytes: &'a [u8],
+  position: usize,
+}
+
+impl<'a> PackageReader<'a> {
+  pub(crate) fn new(bytes: &'a [u8]) -> Self {
+    PackageReader { bytes, position: 0 }
+  }
+
+  pub(crate) fn position(&self) -> usize {
+    self.position
+  }
+
+  pub(crate) fn remaining(&self) -> usize {
+    self.bytes.len() - self.position
+  }
+
+  /// `field` names what was being read when the data ran out, for the error message.
+  pub(crate) fn take(
+    &mut self,
+    length: usize,
+    field: &'static str,
+  ) -> Result<&'a [u8], WorldFileError> {
+    if self.remaining() < length {
+      return Err(WorldFileError::Truncated(field));
+    }
+    let bytes = &self.bytes[self.position..self.position + length];
+    self.position += length;
+    Ok(bytes)
+  }
+
+  pub(crate) fn skip(&mut self, length: usize, field: &'static str) -> Result<(), WorldFileError> {
+    self.take(length, field).map(|_| ())
+  }
+
+  pub(crate) fn read_u8(&mut self, field: &'static str) -> Result<u8, WorldFileError> {
+    Ok(self.take(1, field)?[0])
+  }
+
+  pub(crate) fn read_u16(&mut self, field: &'static str) -> Result<u16, WorldFileError> {
+    Ok(u16::from_le_bytes(self.take(2, field)?.try_into().unwrap()))
+  }
+
+  pub(crate) fn read_i16(&mut self, field: &'static str) -> Result<i16, WorldFileError> {
+    Ok(i16::from_le_bytes(self.take(2, field)?.try_into().unwrap()))
+  }
+
+  pub(crate) fn read_bool(&mut self, field: &'static str) -> Result<bool, WorldFileError> {
+    Ok(self.take(1, field)?[0] != 0)
+  }
+
+  pub(crate) fn read_i32(&mut self, field: &'static str) -> Result<i32, WorldFileError> {
+    Ok(i32::from_le_bytes(self.take(4, field)?.try_into().unwrap()))
+  }
+
+  pub(crate) fn read_i64(&mut self, field: &'static str) -> Result<i64, WorldFileError> {
+    Ok(i64::from_le_bytes(self.take(8, field)?.try_into().unwrap()))
+  }
+
+  pub(crate) fn read_f32(&mut self, field: &'static str) -> Result<f32, WorldFileError> {
+    Ok(f32::from_le_bytes(self.take(4, field)?.try_into().unwrap()))
+  }
+
+  pub(crate) fn read_f64(&mut self, field: &'static str) -> Result<f64, WorldFileError> {
+    Ok(f64::from_le_bytes(self.take(8, field)?.try_into().unwrap()))
+  }
+
+  /// A count in front of a list, negative counts only show up in corrupt files.
+  pub(crate) fn read_count(&mut self, field: &'static str) -> Result<usize, WorldFileError> {
+    let count = self.read_i32(field)?;
+    usize::try_from(count)
+      .map_err(|_| WorldFileError::Invalid(format!("{} has a negative count of {}", field, count)))
+  }
+
+  /// The item count in front of ZDO data, one byte or two when the high bit of the first is set.
+  pub(crate) fn read_num_items(&mut self, field: &'static str) -> Result<usize, WorldFileError> {
+    let first = self.read_u8(field)? as usize;
+    if first & 0x80 == 0 {
+      return Ok(first);
+    }
+    Ok((first & 0x7f) | (self.read_u8(field)? as usize) << 7)
+  }
+
+  /// A string prefixed with its 7 bit encoded length, followed by UTF-8.
+  pub(crate) fn read_string(&mut self, field: &'static str) -> Result<String, WorldFileError> {
+    let mut length = 0usize;
+    for shift in (0..35).step_by(7) {
+      let byte = self.take(1, field)?[0];
+      length |= ((byte & 0x7f) as usize) << shift;
+      if byte & 0x80 == 0 {
+        let bytes = self.take(length, field)?;
+        return String::from_utf8(bytes.to_vec())
+          .map_err(|_| WorldFileError::Invalid(format!("{} is not valid UTF-8", field)));
+      }
+    }
+    Err(WorldFileError::Invalid(format!(
+      "{} has an invalid length",
+      field
+    )))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_read_values() {
+    let mut bytes = vec![];
+    bytes.extend_from_slice(&(-5i32).to_le_bytes());
+    bytes.extend_from_slice(&(1i64 << 40).to_le_bytes());
+    bytes.push(1);
+    // A 130 byte string needs two bytes for its length.
+    bytes.extend_from_slice(&[0x82, 0x01]);
+    bytes.extend(std::iter::repeat_n(b'a', 130));
+    bytes.extend_from_slice(&[0x85, 0x02]);
+    bytes.extend_from_slice(&1.5f64.to_le_bytes());
+
+    let mut reader = PackageReader::new(&bytes);
+    assert_eq!(reader.read_i32("int").unwrap(), -5);
+    assert_eq!(reader.read_i64("long").unwrap(), 1 << 40);
+    assert!(reader.read_bool("bool").unwrap());
+    assert_eq!(reader.read_string("string").unwrap().len(), 130);
+    assert_eq!(reader.read_num_items("items").unwrap(), 261);
+    assert_eq!(reader.read_f64("double").unwrap(), 1.5);
+    assert_eq!(reader.remaining(), 0);
+    assert!(matches!(
+      reader.read_i32("missing"),
+      Err(WorldFileError::Truncated("missing"))
+    ));
+  }
+}
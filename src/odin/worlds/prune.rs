@@ -0,0 +1,200 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use serde::Serialize;
+
+use crate::errors::WorldFileError;
+use crate::worlds::db::{stable_hash, WorldDb, Zdo, PACKED_ZDOS_SINCE};
+
+/// Prefabs which mean a player was in a zone without building there.
+const VISIT_MARKERS: [&str; 3] = ["_TerrainCompiler", "TerrainModifier", "Player_tombstone"];
+
+/// What `odin world prune` removes.
+#[derive(Debug, Clone, Default)]
+pub struct PruneOptions {
+  /// Items lying on the ground.
+  pub item_drops: bool,
+  /// Prefab names or hashes to remove wherever they are.
+  pub prefabs: Vec<String>,
+  /// Reset zones nobody has left a mark in, the server generates them again on the next visit.
+  pub unvisited_zones: bool,
+}
+
+impl PruneOptions {
+  pub fn is_empty(&self) -> bool {
+    !self.item_drops && self.prefabs.is_empty() && !self.unvisited_zones
+  }
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct PruneReport {
+  pub zdos_before: usize,
+  pub item_drops: usize,
+  /// Removed ZDOs by the prefab names or hashes that were asked for.
+  pub prefabs: BTreeMap<String, usize>,
+  pub reset_zones: usize,
+  /// ZDOs removed together with the zones they were in.
+  pub zone_zdos: usize,
+  pub size_before: usize,
+  pub size_after: usize,
+}
+
+impl PruneReport {
+  pub fn removed(&self) -> usize {
+    self.item_drops + self.prefabs.values().sum::<usize>() + self.zone_zdos
+  }
+}
+
+/// A prefab given by name or by the hash `odin world stats` shows for unknown prefabs.
+fn prefab_hash(prefab: &str) -> i32 {
+  prefab.parse().unwrap_or_else(|_| stable_hash(prefab))
+}
+
+/// Zones players have built in, died in or changed the terrain of, and the zones around them.
+fn visited_zones(zdos: &[Zdo]) -> HashSet<(i32, i32)> {
+  let markers: Vec<i32> = VISIT_MARKERS.iter().map(|name| stable_hash(name)).collect();
+  let mut visited = HashSet::new();
+  for zdo in zdos {
+    let Some((x, z)) = zdo.zone else { continue };
+    if zdo.has_creator || markers.contains(&zdo.prefab) {
+      for dx in -1..=1 {
+        for dz in -1..=1 {
+          visited.insert((x + dx, z + dz));
+        }
+      }
+    }
+  }
+  visited
+}
+
+/// Returns the .db without the ZDOs the options select, and what was removed.
+pub fn prune(
+  bytes: &[u8],
+  options: &PruneOptions,
+) -> Result<(Vec<u8>, PruneReport), WorldFileError> {
+  let world = WorldDb::parse(bytes)?;
+  if world.version < PACKED_ZDOS_SINCE {
+    return Err(WorldFileError::Invalid(format!(
+      "world version {} is too old to prune, start a current server with it once to upgrade it",
+      world.version
+    )));
+  }
+  let prefabs: HashMap<i32, &String> = options
+    .prefabs
+    .iter()
+    .map(|prefab| (prefab_hash(prefab), prefab))
+    .collect();
+  let reset: HashSet<(i32, i32)> = if options.unvisited_zones {
+    let visited = visited_zones(&world.zdos);
+    world
+      .zones
+      .iter()
+      .filter(|zone| !visited.contains(zone))
+      .copied()
+      .collect()
+  } else {
+    HashSet::new()
+  };
+
+  let mut report = PruneReport {
+    zdos_before: world.zdos.len(),
+    reset_zones: reset.len(),
+    size_before: bytes.len(),
+    ..PruneReport::default()
+  };
+  let mut kept = vec![];
+  for zdo in &world.zdos {
+    if zdo.zone.is_some_and(|zone| reset.contains(&zone)) {
+      report.zone_zdos += 1;
+    } else if options.item_drops && zdo.is_item_drop {
+      report.item_drops += 1;
+    } else if let Some(prefab) = prefabs.get(&zdo.prefab) {
+      *report.prefabs.entry(prefab.to_string()).or_default() += 1;
+    } else {
+      kept.push(&bytes[zdo.range.clone()]);
+    }
+  }
+
+  let mut pruned = Vec::with_capacity(bytes.len());
+  pruned.extend_from_slice(&bytes[..world.zdo_count_at]);
+  pruned.extend_from_slice(&(kept.len() as i32).to_le_bytes());
+  for zdo in kept {
+    pruned.extend_from_slice(zdo);
+  }
+  let zones: Vec<&(i32, i32)> = world
+    .zones
+    .iter()
+    .filter(|zone| !reset.contains(zone))
+    .collect();
+  pruned.extend_from_slice(&(zones.len() as i32).to_le_bytes());
+  for (x, z) in zones {
+    pruned.extend_from_slice(&x.to_le_bytes());
+    pruned.extend_from_slice(&z.to_le_bytes());
+  }
+  // Locations in reset zones are placed again when their zone is generated.
+  let rest_at = world.zones_at + 4 + world.zones.len() * 8;
+  let mut rest = bytes[rest_at..].to_vec();
+  for location in &world.locations {
+    if let (true, Some(at)) = (reset.contains(&location.zone), location.generated_at) {
+      rest[at - rest_at] = 0;
+    }
+  }
+  pruned.extend(rest);
+  report.size_after = pruned.len();
+  Ok((pruned, report))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::worlds::db::WorldStats;
+  use std::fs;
+
+  #[test]
+  fn test_prune_world() {
+    let bytes = fs::read("tests/assets/example_world_prune.db").unwrap();
+    let options = PruneOptions {
+      item_drops: true,
+      prefabs: vec![String::from("12345"), String::from("Rock_4")],
+      unvisited_zones: true,
+    };
+    let (pruned, report) = prune(&bytes, &options).unwrap();
+    assert_eq!(report.zdos_before, 6);
+    assert_eq!(report.item_drops, 1);
+    assert_eq!(report.prefabs, BTreeMap::from([(String::from("12345"), 1)]));
+    assert_eq!(report.reset_zones, 2);
+    assert_eq!(report.zone_zdos, 1);
+    assert_eq!(report.removed(), 3);
+    assert_eq!(report.size_after, pruned.len());
+
+    let world = WorldDb::parse(&pruned).unwrap();
+    assert_eq!(world.zones, vec![(1, -2)]);
+    let generated: Vec<u8> = world
+      .locations
+      .iter()
+      .map(|location| pruned[location.generated_at.unwrap()])
+      .collect();
+    assert_eq!(generated, vec![0, 1]);
+    let stats = WorldStats::parse(&pruned).unwrap();
+    assert_eq!(stats.zdos, 3);
+    assert_eq!(stats.player_built, Some(3));
+    assert_eq!(stats.global_keys.len(), 3);
+    assert_eq!(
+      stats.sections.events,
+      WorldStats::parse(&bytes).unwrap().sections.events
+    );
+  }
+
+  #[test]
+  fn test_prune_nothing() {
+    let bytes = fs::read("tests/assets/example_world_prune.db").unwrap();
+    let (pruned, report) = prune(&bytes, &PruneOptions::default()).unwrap();
+    assert_eq!(report.removed(), 0);
+    assert_eq!(pruned, bytes);
+
+    let legacy = fs::read("tests/assets/example_world_v29.db").unwrap();
+    assert!(matches!(
+      prune(&legacy, &PruneOptions::default()),
+      Err(WorldFileError::Invalid(_))
+    ));
+  }
+}
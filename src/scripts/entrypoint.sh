@@ -119,6 +119,9 @@ setup_cron_env() {
     [[ -n "$value" ]] && echo "export ${var}=\"$value\"" | sudo tee -a /env.sh
   done
 
+  # NOTIFICATION_SINKS holds JSON, so its quotes have to survive.
+  [[ -n "${NOTIFICATION_SINKS:-}" ]] && printf 'export NOTIFICATION_SINKS=%q\n' "$NOTIFICATION_SINKS" | sudo tee -a /env.sh
+
   log "Preset Env Configured"
 }
 
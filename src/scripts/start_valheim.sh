@@ -34,18 +34,19 @@ cleanup() {
   fi
   [[ -n $TAIL_PID ]] && kill "$TAIL_PID"
   [[ -n $ODIN_HTTP_SERVER_PID ]] && kill "$ODIN_HTTP_SERVER_PID"
+  [[ -n $ODIN_BOT_PID ]] && kill "$ODIN_BOT_PID"
 }
 
 # Function to handle BepInEx installation
 install_bepinex() {
   log "Installing BepInEx"
-  if [ -z "${BEPINEX_DOWNLOAD_URL}" ]; then
-    log "Fetching BepInEx download URL..."
-    log "BepInEx Releases URL: ${BEPINEX_RELEASES_URL}"
-    BEPINEX_DOWNLOAD_URL="$(curl -L "${BEPINEX_RELEASES_URL}" | jq -r '.latest.download_url')"
+  if [ "${FORCE_INSTALL:-0}" -eq 1 ]; then
+    odin bepinex install --force
+  elif [ "${UPDATE_ON_STARTUP:-0}" -eq 1 ] && [ -z "${BEPINEX_DOWNLOAD_URL}" ]; then
+    odin bepinex upgrade || odin bepinex install
+  else
+    odin bepinex install
   fi
-  log "Pulling BepInEx from ${BEPINEX_DOWNLOAD_URL}"
-  odin mod:install "${BEPINEX_DOWNLOAD_URL}"
 }
 
 # Navigate to the Valheim directory or exit if it fails
@@ -185,6 +186,13 @@ if [ -n "${HTTP_PORT}" ]; then
   export ODIN_HTTP_SERVER_PID=$!
 fi
 
+# Start the Discord bot if a bot token is set, in the env or as discord.bot_token in odin.yaml
+if [ -n "${DISCORD_BOT_TOKEN}" ] ||
+  odin config show --effective --json 2>/dev/null | jq -e '."discord.bot_token".value // "" | length > 0' >/dev/null; then
+  odin bot &
+  export ODIN_BOT_PID=$!
+fi
+
 # Set up traps for cleaning up on exit
 trap cleanup INT TERM
 